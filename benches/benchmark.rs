@@ -1,13 +1,41 @@
 //! # Benchmarks
 //!
-//! Benchmark the two versions of the algorithm.
+//! Benchmark the two versions of the algorithm,
+//! and the parameterized suite comparing the backends on different shapes of input:
+//!
+//! - the length of the input, from `6` to `12`.
+//! - the ratio of duplicated values.
+//! - integer and string payloads.
+//! - the size of the values, from `8` to `64` bytes: `Backend::select` prefers the optimized iterator
+//!   for the values wider than `WIDE_VALUE_BYTES`.
+//! - the chunk size, from `1` to `10^6`.
+//!
+//! It also compares the formatting of the chunks through their `Display` with `to_bytes`,
+//! and the breadth-first job queue with the one stored in an arena (`IntoChunks::with_arena`).
+//! With the `fxhash` feature, it compares the hashers of the values (`Permutations::with_hasher`).
+//!
+//! The throughput is reported in permutations per second (`elem/s`).
+//! The spaces with more than `MAX_PERMUTATIONS` permutations are only generated up to it.
 
+use std::fmt::Display;
+use std::hash::Hash;
 use std::thread;
 use std::thread::JoinHandle;
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput,
+};
+
+use perm::{
+    Chunk, ChunkView, ExpansionStrategy, OptimizedChunk, OutputFormat, Permutations, ValueHasher,
+};
 
-use perm::Permutations;
+/// The maximum number of permutations generated in a single iteration of the suite.
+const MAX_PERMUTATIONS: usize = 1_000_000;
+
+/// The chunk size of the suite, unless it is the parameter.
+const CHUNK_SIZE: usize = 10_000;
 
 fn generate_string_new_thread<T: 'static + ToString + Send + Sync>(chunk: T) -> JoinHandle<String> {
     thread::spawn(move || chunk.to_string())
@@ -43,10 +71,254 @@ fn permutations_into_optimized_chunks(c: &mut Criterion) {
     });
 }
 
+/// Count the permutations of the `chunks`, without formatting them.
+fn count<C: ChunkView>(chunks: impl Iterator<Item = C>) -> usize {
+    chunks.map(|chunk| chunk.permutations_count()).sum()
+}
+
+/// Benchmark the three backends generating at most `MAX_PERMUTATIONS` permutations of `values`.
+fn bench_backends<T: Copy + Eq + Hash>(
+    group: &mut BenchmarkGroup<WallTime>,
+    parameter: impl Display,
+    values: Vec<T>,
+    chunk_size: usize,
+) {
+    let permutations = Permutations::new(values);
+    let generated = permutations
+        .checked_permutations_number()
+        .map_or(MAX_PERMUTATIONS, |n| n.min(MAX_PERMUTATIONS));
+    group.throughput(Throughput::Elements(generated as u64));
+    group.bench_with_input(
+        BenchmarkId::new("normal", &parameter),
+        &permutations,
+        |b, permutations| {
+            b.iter(|| {
+                count(
+                    permutations
+                        .clone()
+                        .into_chunks(chunk_size)
+                        .with_limit(MAX_PERMUTATIONS),
+                )
+            })
+        },
+    );
+    group.bench_with_input(
+        BenchmarkId::new("optimized", &parameter),
+        &permutations,
+        |b, permutations| {
+            b.iter(|| {
+                count(
+                    permutations
+                        .clone()
+                        .into_optimized_chunks(chunk_size)
+                        .with_limit(MAX_PERMUTATIONS),
+                )
+            })
+        },
+    );
+    group.bench_with_input(
+        BenchmarkId::new("ranked", &parameter),
+        &permutations,
+        |b, permutations| {
+            b.iter(|| {
+                count(
+                    permutations
+                        .clone()
+                        .into_ranked_chunks(chunk_size)
+                        .with_limit(MAX_PERMUTATIONS),
+                )
+            })
+        },
+    );
+}
+
+fn generation_by_length(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation by length");
+    for length in [6, 8, 10, 12] {
+        bench_backends(&mut group, length, (0..length).collect(), CHUNK_SIZE);
+    }
+    group.finish();
+}
+
+fn generation_by_duplicates(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation by distinct values");
+    // 10 values with 10, 5, 3 and 2 distinct values.
+    for distinct in [10, 5, 3, 2] {
+        let values = (0..10).map(|value| value % distinct).collect();
+        bench_backends(&mut group, distinct, values, CHUNK_SIZE);
+    }
+    group.finish();
+}
+
+fn generation_by_value_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation by value size");
+    bench_backends(&mut group, 8, (0..9u64).collect(), CHUNK_SIZE);
+    bench_backends(
+        &mut group,
+        24,
+        (0..9u64).map(|i| [i; 3]).collect(),
+        CHUNK_SIZE,
+    );
+    bench_backends(
+        &mut group,
+        64,
+        (0..9u64).map(|i| [i; 8]).collect(),
+        CHUNK_SIZE,
+    );
+    group.finish();
+}
+
+fn generation_by_chunk_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation by chunk size");
+    for chunk_size in [1, 100, 10_000, 1_000_000] {
+        bench_backends(&mut group, chunk_size, (0..10).collect(), chunk_size);
+    }
+    group.finish();
+}
+
+fn formatting_by_payload(c: &mut Criterion) {
+    let mut group = c.benchmark_group("formatting by payload");
+    let integers = Permutations::new((0..9).collect::<Vec<i64>>());
+    let words = [
+        "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota",
+    ];
+    let strings = Permutations::new(words.to_vec());
+    group.throughput(Throughput::Elements(integers.permutations_number() as u64));
+    group.bench_function("integers", |b| {
+        b.iter(|| {
+            integers
+                .clone()
+                .into_optimized_chunks(CHUNK_SIZE)
+                .map(|chunk| black_box(chunk.to_string()).len())
+                .sum::<usize>()
+        })
+    });
+    // the chunks holding the values, whose indices are looked up in the table of the iterator.
+    group.bench_function("integers by value", |b| {
+        b.iter(|| {
+            integers
+                .clone()
+                .into_chunks(CHUNK_SIZE)
+                .map(|chunk| black_box(chunk.to_string()).len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("strings", |b| {
+        b.iter(|| {
+            strings
+                .clone()
+                .into_optimized_chunks(CHUNK_SIZE)
+                .map(|chunk| black_box(chunk.to_string()).len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+fn serialization_by_chunk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization by chunk");
+    let permutations = Permutations::new((0..9).collect::<Vec<i64>>());
+    let format = OutputFormat::default();
+    let chunks = permutations
+        .clone()
+        .into_chunks(CHUNK_SIZE)
+        .collect::<Vec<Chunk<i64>>>();
+    let optimized_chunks = permutations
+        .into_optimized_chunks(CHUNK_SIZE)
+        .collect::<Vec<OptimizedChunk<i64>>>();
+    group.throughput(Throughput::Elements(
+        chunks.iter().map(Chunk::len).sum::<usize>() as u64,
+    ));
+    group.bench_function("display", |b| {
+        b.iter(|| {
+            chunks
+                .iter()
+                .map(|chunk| black_box(chunk.formatted(format).to_string()).len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("to_bytes", |b| {
+        b.iter(|| {
+            chunks
+                .iter()
+                .map(|chunk| black_box(chunk.to_bytes(format)).len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("optimized display", |b| {
+        b.iter(|| {
+            optimized_chunks
+                .iter()
+                .map(|chunk| black_box(chunk.formatted(format).to_string()).len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("optimized to_bytes", |b| {
+        b.iter(|| {
+            optimized_chunks
+                .iter()
+                .map(|chunk| black_box(chunk.to_bytes(format)).len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+fn breadth_first_jobs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("breadth-first jobs");
+    let permutations = Permutations::new((0..9).collect::<Vec<i32>>())
+        .with_strategy(ExpansionStrategy::BreadthFirst);
+    group.throughput(Throughput::Elements(
+        permutations.permutations_number() as u64
+    ));
+    group.bench_function("queue", |b| {
+        b.iter(|| count(permutations.clone().into_chunks(CHUNK_SIZE)))
+    });
+    group.bench_function("arena", |b| {
+        b.iter(|| count(permutations.clone().into_chunks(CHUNK_SIZE).with_arena()))
+    });
+    group.finish();
+}
+
+fn hashing_by_hasher(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hashing by hasher");
+    // the constraint counts the values of each partial permutation in a map.
+    let permutations = Permutations::new((0..9).collect::<Vec<i64>>());
+    let hashers = [
+        ("siphash", ValueHasher::SipHash),
+        #[cfg(feature = "fxhash")]
+        ("fxhash", ValueHasher::FxHash),
+    ];
+    group.throughput(Throughput::Elements(
+        permutations.permutations_number() as u64
+    ));
+    for (name, hasher) in hashers {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                permutations
+                    .clone()
+                    .with_hasher(hasher)
+                    .into_pruned_chunks(CHUNK_SIZE, |_| false)
+                    .with_fixed_points(2)
+                    .map(|chunk| black_box(chunk.len()))
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
 criterion_group! {
     name = benchmark;
     config = Criterion::default().sample_size(10);
     targets = permutations_into_chunks, permutations_into_optimized_chunks
 }
 
-criterion_main!(benchmark);
+criterion_group! {
+    name = suite;
+    config = Criterion::default().sample_size(10);
+    targets = generation_by_length, generation_by_duplicates, generation_by_value_size, generation_by_chunk_size,
+        formatting_by_payload, serialization_by_chunk, breadth_first_jobs, hashing_by_hasher
+}
+
+criterion_main!(benchmark, suite);
@@ -0,0 +1,231 @@
+//! # Comparison
+//!
+//! Benchmark the backends against the permutations of other crates, on the same inputs:
+//!
+//! - `itertools`: `Itertools::permutations` generates the permutations of the positions,
+//!   so with duplicated values it is followed by `Itertools::unique` to generate each permutation once.
+//! - `permutohedron`: `Heap` for the distinct values, and `LexicalPermutation::next_permutation`
+//!   on the sorted values with duplicates, since it skips the equal permutations.
+//!   Both permute a single array in place, so they do not allocate the permutations.
+//!
+//! The backends of this crate allocate the permutations in chunks of `CHUNK_SIZE`,
+//! and the permutations are only counted, without formatting them.
+//!
+//! After the benchmarks, the mean time of each one is read back from the `estimates.json` of Criterion,
+//! and summarized in `comparison.json` in the Criterion directory (`target/criterion` by default),
+//! with the throughput and the speedup relative to `itertools` for each input.
+//! So the claims on the performance can be checked by a script, e.g. in the CI.
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+use itertools::Itertools;
+use permutohedron::{Heap, LexicalPermutation};
+use serde_json::{json, Value};
+
+use perm::{ChunkView, Permutations};
+
+/// The chunk size of the backends of this crate.
+const CHUNK_SIZE: usize = 10_000;
+
+/// The name of the Criterion group of the comparison.
+const GROUP: &str = "comparison";
+
+/// The implementations compared, in order of the report.
+const IMPLEMENTATIONS: [&str; 5] = [
+    "itertools",
+    "permutohedron",
+    "normal",
+    "optimized",
+    "ranked",
+];
+
+/// The inputs of the comparison: their name, and the values.
+fn inputs() -> Vec<(&'static str, Vec<u32>)> {
+    vec![
+        ("distinct-8", (0..8).collect()),
+        ("distinct-9", (0..9).collect()),
+        // 10 values, each one twice: 113400 permutations out of 3628800 orderings.
+        ("pairs-10", (0..10).map(|value| value / 2).collect()),
+    ]
+}
+
+/// Count the permutations of the `chunks`, without formatting them.
+fn count<C: ChunkView>(chunks: impl Iterator<Item = C>) -> usize {
+    chunks.map(|chunk| chunk.permutations_count()).sum()
+}
+
+/// Generate the permutations of `values` with `itertools`, removing the duplicated ones if any.
+/// `Permutations::count` computes the number without generating them, so they are consumed one by one.
+fn itertools_count(values: &[u32], has_duplicates: bool) -> usize {
+    let permutations = values.iter().copied().permutations(values.len());
+    if has_duplicates {
+        consume(permutations.unique())
+    } else {
+        consume(permutations)
+    }
+}
+
+/// Count the permutations of an iterator, consuming each one.
+fn consume(permutations: impl Iterator<Item = Vec<u32>>) -> usize {
+    let mut count = 0;
+    for permutation in permutations {
+        black_box(permutation);
+        count += 1;
+    }
+    count
+}
+
+/// Generate the permutations of `values` with `permutohedron`, in place.
+fn permutohedron_count(values: &[u32], has_duplicates: bool) -> usize {
+    let mut values = values.to_vec();
+    if has_duplicates {
+        values.sort_unstable();
+        let mut count = 1;
+        while values.next_permutation() {
+            black_box(&values);
+            count += 1;
+        }
+        count
+    } else {
+        let mut heap = Heap::new(&mut values);
+        let mut count = 0;
+        while let Some(permutation) = heap.next_permutation() {
+            black_box(permutation);
+            count += 1;
+        }
+        count
+    }
+}
+
+fn comparison(c: &mut Criterion) {
+    let mut group = c.benchmark_group(GROUP);
+    for (name, values) in inputs() {
+        let permutations = Permutations::new(values.clone());
+        let has_duplicates = values.iter().collect::<HashSet<_>>().len() < values.len();
+        // the implementations must agree on the permutations generated, to be compared.
+        assert_eq!(
+            itertools_count(&values, has_duplicates),
+            permutations.permutations_number()
+        );
+        assert_eq!(
+            permutohedron_count(&values, has_duplicates),
+            permutations.permutations_number()
+        );
+        group.throughput(Throughput::Elements(
+            permutations.permutations_number() as u64
+        ));
+        group.bench_with_input(BenchmarkId::new("itertools", name), &values, |b, values| {
+            b.iter(|| itertools_count(values, has_duplicates))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("permutohedron", name),
+            &values,
+            |b, values| b.iter(|| permutohedron_count(values, has_duplicates)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("normal", name),
+            &permutations,
+            |b, permutations| b.iter(|| count(permutations.clone().into_chunks(CHUNK_SIZE))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("optimized", name),
+            &permutations,
+            |b, permutations| {
+                b.iter(|| count(permutations.clone().into_optimized_chunks(CHUNK_SIZE)))
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("ranked", name),
+            &permutations,
+            |b, permutations| b.iter(|| count(permutations.clone().into_ranked_chunks(CHUNK_SIZE))),
+        );
+    }
+    group.finish();
+}
+
+/// The directory where Criterion saves the results, as Criterion itself computes it.
+fn criterion_directory() -> PathBuf {
+    if let Some(home) = env::var_os("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    env::var_os("CARGO_TARGET_DIR")
+        .map_or_else(|| PathBuf::from("target"), PathBuf::from)
+        .join("criterion")
+}
+
+/// Read the mean time in nanoseconds of a benchmark, if it has been run.
+fn mean_nanoseconds(directory: &Path, implementation: &str, input: &str) -> Option<f64> {
+    let estimates = directory
+        .join(GROUP)
+        .join(implementation)
+        .join(input)
+        .join("new")
+        .join("estimates.json");
+    let estimates: Value = serde_json::from_str(&fs::read_to_string(estimates).ok()?).ok()?;
+    estimates["mean"]["point_estimate"].as_f64()
+}
+
+/// Summarize the results of the benchmarks that have been run in `comparison.json`.
+fn write_report() {
+    let directory = criterion_directory();
+    let mut report = BTreeMap::new();
+    for (name, values) in inputs() {
+        let permutations = Permutations::new(values).permutations_number();
+        let means = IMPLEMENTATIONS
+            .iter()
+            .filter_map(|implementation| {
+                mean_nanoseconds(&directory, implementation, name)
+                    .map(|mean| (*implementation, mean))
+            })
+            .collect::<Vec<_>>();
+        let baseline = means
+            .iter()
+            .find(|(implementation, _)| *implementation == "itertools")
+            .map(|(_, mean)| *mean);
+        let results = means
+            .iter()
+            .map(|(implementation, mean)| {
+                json!({
+                    "implementation": implementation,
+                    "mean_ns": mean,
+                    "permutations_per_second": permutations as f64 / mean * 1e9,
+                    "speedup_over_itertools": baseline.map(|baseline| baseline / mean),
+                })
+            })
+            .collect::<Vec<_>>();
+        if !results.is_empty() {
+            report.insert(
+                name,
+                json!({ "permutations": permutations, "results": results }),
+            );
+        }
+    }
+    // the benchmarks are not run in test mode.
+    if report.is_empty() {
+        return;
+    }
+    let path = directory.join("comparison.json");
+    let written = serde_json::to_string_pretty(&report)
+        .map_err(|error| error.to_string())
+        .and_then(|report| fs::write(&path, report).map_err(|error| error.to_string()));
+    match written {
+        Ok(()) => println!("Comparison report written to {}", path.display()),
+        Err(error) => eprintln!("Cannot write {}: {}", path.display(), error),
+    }
+}
+
+criterion_group! {
+    name = benchmark;
+    config = Criterion::default().sample_size(10);
+    targets = comparison
+}
+
+fn main() {
+    benchmark();
+    Criterion::default().configure_from_args().final_summary();
+    write_report();
+}
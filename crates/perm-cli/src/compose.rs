@@ -0,0 +1,85 @@
+//! # Compose
+//!
+//! `perm compose --perms <FILE>`: write the composition of the permutations of the positions in a file,
+//! one for each line as written by `--indices`, with its inverse and its order.
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use tracing::error;
+
+use perm::PermutationMap;
+
+use crate::output::write_error_exit_code;
+use crate::ExitCode;
+
+/// Write the composition of the permutations in the file at `path`, in file order,
+/// with its inverse and its order.
+pub(crate) fn compose(path: &Path) -> ExitCode {
+    let lines = match File::open(path) {
+        Ok(file) => io::BufReader::new(file).lines(),
+        Err(e) => {
+            error!(error = %e, "error reading permutations");
+            return ExitCode::IoError;
+        }
+    };
+    let mut composition: Option<PermutationMap> = None;
+    for (index, line) in lines.enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                error!(error = %e, "error reading permutations");
+                return ExitCode::IoError;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let permutation = match parse_permutation_map(&line) {
+            Some(permutation) => permutation,
+            None => {
+                error!(line = index + 1, "not a permutation of the positions");
+                return ExitCode::InvalidInput;
+            }
+        };
+        composition = match composition {
+            None => Some(permutation),
+            Some(composition) if composition.len() == permutation.len() => {
+                Some(composition.compose(&permutation))
+            }
+            Some(_) => {
+                error!(line = index + 1, "the permutations have different lengths");
+                return ExitCode::InvalidInput;
+            }
+        };
+    }
+    let composition = match composition {
+        Some(composition) => composition,
+        None => {
+            error!("no permutations");
+            return ExitCode::InvalidInput;
+        }
+    };
+    let order = composition.order().map_or_else(
+        || format!("more than {}", usize::MAX),
+        |order| order.to_string(),
+    );
+    match writeln!(
+        io::stdout(),
+        "composition: {}\ninverse: {}\norder: {}",
+        composition,
+        composition.inverse(),
+        order
+    ) {
+        Ok(()) => ExitCode::Success,
+        Err(e) => write_error_exit_code(&e),
+    }
+}
+
+/// Parse the comma separated positions of a `PermutationMap`.
+fn parse_permutation_map(line: &str) -> Option<PermutationMap> {
+    line.split(',')
+        .map(|position| position.trim().parse::<usize>().ok())
+        .collect::<Option<Vec<usize>>>()
+        .and_then(PermutationMap::new)
+}
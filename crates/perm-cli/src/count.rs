@@ -0,0 +1,35 @@
+//! # Count
+//!
+//! `perm count`: write the number of permutations of the input read from `stdin`.
+use std::io::{self, Write};
+
+use tracing::error;
+
+use crate::input::{parse_input, read_input};
+use crate::output::write_error_exit_code;
+use crate::{ExitCode, InputOptions};
+
+/// Write the number of permutations of the input read from `stdin`.
+pub(crate) fn count(options: &InputOptions) -> ExitCode {
+    let text = match read_input() {
+        Ok(text) => text,
+        Err(exit_code) => return exit_code,
+    };
+    let permutations = match parse_input(&text, options) {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    match permutations.checked_permutations_number() {
+        Some(count) => match writeln!(io::stdout(), "{}", count) {
+            Ok(()) => ExitCode::Success,
+            Err(e) => write_error_exit_code(&e),
+        },
+        None => {
+            error!(
+                "the number of permutations does not fit in {} bits",
+                usize::BITS
+            );
+            ExitCode::InvalidInput
+        }
+    }
+}
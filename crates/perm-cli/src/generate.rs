@@ -0,0 +1,839 @@
+//! # Generate
+//!
+//! `perm generate` (and `perm` without a subcommand): generate the permutations of the input read from `stdin`
+//! with the iterator chosen by `--algorithm`, and write them with the `Pipeline`
+//! to `stdout` or to the output chosen by the options.
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{debug, error, info, info_span, warn};
+
+#[cfg(feature = "compression")]
+use perm::compression::{Codec, Compress};
+use perm::format::Formatted;
+use perm::framing::{EncodeFrame, FrameSink};
+#[cfg(feature = "numa")]
+use perm::pipeline::PinnedPipeline;
+#[cfg(feature = "compression")]
+use perm::pipeline::Transform;
+use perm::pipeline::{
+    ContentHasher, Encode, EncodeBytes, FlushPolicy, Manifest, ManifestSink, Metrics,
+    MetricsSnapshot, Pipeline, ShardedSink, SplitBy, WriterSink, Written,
+};
+#[cfg(feature = "nats")]
+use perm::pipeline::{NatsSink, PublishEach};
+use perm::raw::EncodeRaw;
+use perm::{Backend, ChunkView, LineEnding, OutputFormat, PermValue, Permutations, ToBytes};
+
+use crate::input::{parse_bytes, parse_chars, parse_input, read_bytes_input, read_input};
+use crate::interrupt::{handle_sigint, is_running, INTERRUPTED};
+use crate::output::{output_format, write_error_exit_code};
+#[cfg(feature = "compression")]
+use crate::CompressOption;
+#[cfg(feature = "nats")]
+use crate::PublishEachOption;
+use crate::{
+    value_name, AlgorithmOption, ExitCode, FlushOption, FormatOption, GenerateOptions,
+    LineEndingOption, SplitByOption, OPTIMAL_CHUNKS_NUMBER,
+};
+
+/// The metadata of a run, written with `--summary`.
+#[derive(Serialize)]
+struct Summary {
+    input_length: usize,
+    permutations_emitted: usize,
+    bytes_written: usize,
+    wall_time_seconds: f64,
+    algorithm: &'static str,
+    exit_code: i32,
+}
+
+/// What has been written by the generation.
+#[derive(Default)]
+struct Emitted {
+    permutations: usize,
+    bytes: usize,
+}
+
+impl GenerateOptions {
+    /// The hash of the input `values` and of the options changing the content of the output,
+    /// recorded in the `--manifest` to recognize a repeated run with `--skip-if-exists`.
+    fn content_hash<V: AsRef<str>>(&self, values: &[V]) -> String {
+        let mut hasher = ContentHasher::new();
+        hasher.write_values(values);
+        hasher.write_option("normalize", &self.input.normalize.to_string());
+        hasher.write_option("quote", &self.quote.to_string());
+        hasher.write_option("line-ending", &value_name(self.line_ending));
+        hasher.write_option("format", &value_name(self.format));
+        hasher.write_option("indices", &self.indices.to_string());
+        hasher.write_option("join", &self.join.to_string());
+        #[cfg(feature = "compression")]
+        if let Some(compress) = self.compress {
+            hasher.write_option("compress", &value_name(compress));
+        }
+        let chunk_bytes = self.chunk_bytes.map(|bytes| bytes.to_string());
+        hasher.write_option("chunk-bytes", chunk_bytes.as_deref().unwrap_or(""));
+        hasher.write_option(
+            "output-pattern",
+            self.output_pattern.as_deref().unwrap_or(""),
+        );
+        hasher.write_option("split-by", &value_name(self.split_by));
+        hasher.write_option("shards", &self.shards.to_string());
+        hasher.write_option("max-file-size", &self.max_file_size.to_string());
+        hasher.finish()
+    }
+    /// Refuse a generation of more than `--max-permutations` permutations, unless `--force` is given,
+    /// logging the number of `permutations` and the size of the output, each `permutation_len` bytes long.
+    fn check_output_size(
+        &self,
+        permutations: Option<usize>,
+        permutation_len: usize,
+    ) -> Result<(), ExitCode> {
+        if self.force {
+            return Ok(());
+        }
+        let estimate = match permutations {
+            Some(permutations) if permutations <= self.max_permutations => return Ok(()),
+            Some(permutations) => {
+                let size = match permutations.checked_mul(permutation_len) {
+                    Some(bytes) => format!("about {}", human_bytes(bytes)),
+                    None => format!("more than {}", human_bytes(usize::MAX)),
+                };
+                format!("the output has {} permutations, {}", permutations, size)
+            }
+            None => format!("the output has more than {} permutations", usize::MAX),
+        };
+        let message = format!(
+            "{}: more than --max-permutations {}, use --force to generate it anyway",
+            estimate, self.max_permutations
+        );
+        error!(%message, "output too large");
+        Err(ExitCode::InvalidInput)
+    }
+    /// The sink writing the text and raw output to `stdout`, with `--buffer-size` and `--flush`.
+    fn stdout_sink(&self) -> WriterSink<io::Stdout> {
+        let flush_policy = match self.flush {
+            FlushOption::End => FlushPolicy::AtEnd,
+            FlushOption::Chunk => FlushPolicy::PerChunk,
+            FlushOption::Line => FlushPolicy::PerLine,
+        };
+        let buffer_size = self.buffer_size.map_or(0, |buffer_size| {
+            usize::try_from(buffer_size).unwrap_or(usize::MAX)
+        });
+        WriterSink::new(io::stdout())
+            .with_buffer_size(buffer_size)
+            .with_flush_policy(flush_policy)
+    }
+    fn split_by(&self) -> SplitBy {
+        match self.split_by {
+            SplitByOption::Chunk => SplitBy::Chunk {
+                shards: self.shards.into(),
+            },
+            SplitByOption::Prefix => SplitBy::Prefix,
+            SplitByOption::Size => SplitBy::Size {
+                max_bytes: usize::try_from(self.max_file_size).unwrap_or(usize::MAX),
+            },
+        }
+    }
+}
+
+/// Write a size in bytes with the largest binary unit it reaches, e.g. `1.5 GiB`.
+fn human_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Generate the permutations of the input read from `stdin`.
+pub(crate) fn generate(options: &GenerateOptions) -> ExitCode {
+    if options.format == FormatOption::Raw {
+        return generate_raw(options);
+    }
+    // the pinned threads cannot be stopped: `SIGINT` terminates the process.
+    #[cfg(feature = "numa")]
+    let numa_mode = options.numa_mode;
+    #[cfg(not(feature = "numa"))]
+    let numa_mode = false;
+    if !numa_mode {
+        handle_sigint();
+    }
+    let line_ending = match options.line_ending {
+        LineEndingOption::Lf => LineEnding::Lf,
+        LineEndingOption::Crlf => LineEnding::CrLf,
+    };
+    let format = output_format(options.quote)
+        .with_line_ending(line_ending)
+        .with_joined(options.join);
+    let start = Instant::now();
+
+    let parse_span = info_span!("parse").entered();
+    let text = match read_input() {
+        Ok(text) => text,
+        Err(exit_code) => return exit_code,
+    };
+    let parsed = if options.join {
+        parse_chars(&text, &options.input)
+    } else {
+        parse_input(&text, &options.input)
+    };
+    let permutations = match parsed {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    let input_length = permutations.length();
+    info!(length = input_length, "input parsed");
+    parse_span.exit();
+    let input_hash = options.content_hash(permutations.values());
+    if options.skip_if_exists {
+        if let Some(manifest_path) = &options.manifest {
+            if is_already_generated(manifest_path, &input_hash) {
+                info!(input_hash = %input_hash, "the output already exists, skipping");
+                return ExitCode::Success;
+            }
+        }
+    }
+    let permutation_len = if options.indices {
+        format.permutation_len((0..input_length).collect::<Vec<_>>().iter())
+    } else {
+        format.permutation_len(permutations.values().iter())
+    };
+    if let Err(exit_code) =
+        options.check_output_size(permutations.checked_permutations_number(), permutation_len)
+    {
+        return exit_code;
+    }
+
+    let chunk_bytes = options
+        .chunk_bytes
+        .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX));
+    let chunk_size = match chunk_bytes {
+        Some(bytes) if options.indices => {
+            Permutations::new((0..input_length).collect()).chunk_size_for_bytes(bytes, format)
+        }
+        Some(bytes) => permutations.chunk_size_for_bytes(bytes, format),
+        None => permutations.suggested_chunk_size(OPTIMAL_CHUNKS_NUMBER),
+    };
+    #[cfg(feature = "numa")]
+    if options.numa_mode {
+        return generate_pinned(options, &permutations, format, chunk_size, start);
+    }
+    let pipeline = Pipeline::new().with_workers(
+        options
+            .threads
+            .map_or(Pipeline::new().workers(), usize::from),
+    );
+    let (pipeline, metrics_reporter) = match options.metrics_interval {
+        Some(interval) => {
+            let metrics = Metrics::new();
+            let reporter = MetricsReporter::start(metrics.clone(), interval);
+            (pipeline.with_metrics(metrics), Some(reporter))
+        }
+        None => (pipeline, None),
+    };
+    let (backend, reason) = match select_backend(options.algorithm, &permutations) {
+        Ok(selection) => selection,
+        Err(exit_code) => return exit_code,
+    };
+    // the positions are generated by the normal iterator.
+    let algorithm = if options.indices {
+        "normal"
+    } else {
+        backend.name()
+    };
+    let generate_span = info_span!(
+        "generate",
+        algorithm,
+        chunk_size,
+        workers = pipeline.workers()
+    )
+    .entered();
+    let mut permutations_emitted = 0;
+    let written = if options.indices {
+        info!("using index iterator");
+        let chunks = permutations
+            .into_index_chunks(chunk_size)
+            .take_while(|_| is_running())
+            .enumerate()
+            .map(|(index, chunk)| {
+                log_chunk(&mut permutations_emitted, index, chunk.len());
+                chunk
+            });
+        write_chunks(&pipeline, chunks, format, options, &input_hash)
+    } else {
+        info!(reason, "using {} iterator", backend);
+        let mut writer = TextWriter {
+            pipeline: &pipeline,
+            format,
+            options,
+            input_hash: &input_hash,
+        };
+        generate_chunks(
+            backend,
+            permutations,
+            chunk_size,
+            &mut permutations_emitted,
+            &mut writer,
+        )
+    };
+    generate_span.exit();
+    if let Some(reporter) = metrics_reporter {
+        reporter.stop();
+    }
+    let generated = written.map(|written| Emitted {
+        permutations: permutations_emitted,
+        bytes: written.bytes,
+    });
+
+    finish_generation(options, generated, input_length, algorithm, start)
+}
+
+/// Generate the permutations with `--numa-mode`: one thread pinned to each core writes its range of ranks
+/// to its own file, concatenated to `stdout` at the end with `--numa-concatenate`.
+#[cfg(feature = "numa")]
+fn generate_pinned(
+    options: &GenerateOptions,
+    permutations: &Permutations<&str>,
+    format: OutputFormat,
+    chunk_size: usize,
+    start: Instant,
+) -> ExitCode {
+    let pattern = options
+        .output_pattern
+        .as_deref()
+        .expect("--numa-mode requires --output-pattern");
+    let pipeline = PinnedPipeline::new().with_chunk_size(chunk_size);
+    let pipeline = match options.threads {
+        Some(threads) => pipeline.with_threads(threads.into()),
+        None => pipeline,
+    };
+    let generate_span = info_span!(
+        "generate",
+        algorithm = Backend::Ranked.name(),
+        chunk_size,
+        threads = pipeline.threads()
+    )
+    .entered();
+    let permutations_number = permutations.checked_permutations_number().unwrap_or(0);
+    let generated = if options.numa_concatenate {
+        pipeline
+            .write(permutations, format, pattern, io::stdout())
+            .map(|written| written.bytes)
+    } else {
+        pipeline
+            .write_files(permutations, format, pattern)
+            .map(|files| {
+                for file in &files {
+                    info!(path = %file.path.display(), bytes = file.bytes, "file written");
+                }
+                files.iter().map(|file| file.bytes).sum()
+            })
+    };
+    generate_span.exit();
+    let generated = generated.map(|bytes| Emitted {
+        permutations: permutations_number,
+        bytes,
+    });
+
+    finish_generation(
+        options,
+        generated,
+        permutations.length(),
+        Backend::Ranked.name(),
+        start,
+    )
+}
+
+/// Generate the permutations of the bytes of the first line of `stdin`, written as raw byte strings.
+fn generate_raw(options: &GenerateOptions) -> ExitCode {
+    handle_sigint();
+    let start = Instant::now();
+    if options.indices {
+        error!("--indices cannot be used with --format raw");
+        return ExitCode::InvalidInput;
+    }
+
+    let parse_span = info_span!("parse").entered();
+    let bytes = match read_bytes_input() {
+        Ok(bytes) => bytes,
+        Err(exit_code) => return exit_code,
+    };
+    let permutations = match parse_bytes(&bytes, &options.input) {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    let input_length = permutations.length();
+    info!(length = input_length, "input parsed");
+    parse_span.exit();
+
+    let encode = EncodeRaw::new().with_terminator(b'\n');
+    if let Err(exit_code) = options.check_output_size(
+        permutations.checked_permutations_number(),
+        encode.record_len(input_length),
+    ) {
+        return exit_code;
+    }
+    let chunk_bytes = options
+        .chunk_bytes
+        .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX));
+    let chunk_size = match chunk_bytes {
+        Some(bytes) => (bytes / encode.record_len(input_length)).max(1),
+        None => permutations.suggested_chunk_size(OPTIMAL_CHUNKS_NUMBER),
+    };
+    let pipeline = Pipeline::new().with_workers(
+        options
+            .threads
+            .map_or(Pipeline::new().workers(), usize::from),
+    );
+    let (pipeline, metrics_reporter) = match options.metrics_interval {
+        Some(interval) => {
+            let metrics = Metrics::new();
+            let reporter = MetricsReporter::start(metrics.clone(), interval);
+            (pipeline.with_metrics(metrics), Some(reporter))
+        }
+        None => (pipeline, None),
+    };
+    let (backend, reason) = match select_backend(options.algorithm, &permutations) {
+        Ok(selection) => selection,
+        Err(exit_code) => return exit_code,
+    };
+    let generate_span = info_span!(
+        "generate",
+        algorithm = backend.name(),
+        chunk_size,
+        workers = pipeline.workers()
+    )
+    .entered();
+    info!(reason, "using {} iterator", backend);
+    let mut permutations_emitted = 0;
+    let mut writer = RawWriter {
+        pipeline: &pipeline,
+        encode,
+        sink: options.stdout_sink(),
+    };
+    let written = generate_chunks(
+        backend,
+        permutations,
+        chunk_size,
+        &mut permutations_emitted,
+        &mut writer,
+    );
+    generate_span.exit();
+    if let Some(reporter) = metrics_reporter {
+        reporter.stop();
+    }
+    let generated = written.map(|written| Emitted {
+        permutations: permutations_emitted,
+        bytes: written.bytes,
+    });
+
+    finish_generation(options, generated, input_length, backend.name(), start)
+}
+
+/// Write the chunks of a generation, whichever iterator generates them.
+trait WriteChunks<T> {
+    fn write<C>(&mut self, chunks: impl Iterator<Item = C>) -> io::Result<Written>
+    where
+        C: Send + ChunkView<Value = T> + ToBytes,
+        for<'a> Formatted<'a, C>: Display;
+}
+
+/// Write the chunks of values with `write_chunks`.
+struct TextWriter<'a> {
+    pipeline: &'a Pipeline,
+    format: OutputFormat,
+    options: &'a GenerateOptions,
+    input_hash: &'a str,
+}
+
+impl<'v> WriteChunks<&'v str> for TextWriter<'_> {
+    fn write<C>(&mut self, chunks: impl Iterator<Item = C>) -> io::Result<Written>
+    where
+        C: Send + ChunkView<Value = &'v str> + ToBytes,
+        for<'a> Formatted<'a, C>: Display,
+    {
+        write_chunks(
+            self.pipeline,
+            chunks,
+            self.format,
+            self.options,
+            self.input_hash,
+        )
+    }
+}
+
+/// Write the chunks of bytes to `stdout` as raw records.
+struct RawWriter<'a> {
+    pipeline: &'a Pipeline,
+    encode: EncodeRaw,
+    sink: WriterSink<io::Stdout>,
+}
+
+impl WriteChunks<u8> for RawWriter<'_> {
+    fn write<C>(&mut self, chunks: impl Iterator<Item = C>) -> io::Result<Written>
+    where
+        C: Send + ChunkView<Value = u8> + ToBytes,
+        for<'a> Formatted<'a, C>: Display,
+    {
+        let chunks = count_permutations(self.pipeline, chunks);
+        let chunks = self.pipeline.run(chunks, self.encode, &mut self.sink)?;
+        Ok(Written {
+            chunks,
+            bytes: self.sink.bytes_written(),
+        })
+    }
+}
+
+/// Generate the chunks of the `permutations` with the `backend`, until `SIGINT`,
+/// counting them in `permutations_emitted`, and write them with the `writer`.
+fn generate_chunks<T, W>(
+    backend: Backend,
+    permutations: Permutations<T>,
+    chunk_size: usize,
+    permutations_emitted: &mut usize,
+    writer: &mut W,
+) -> io::Result<Written>
+where
+    T: PermValue + Copy + Eq + Hash + Send + Sync,
+    W: WriteChunks<T>,
+{
+    match backend {
+        Backend::Optimized => writer.write(
+            permutations
+                .into_optimized_chunks(chunk_size)
+                .take_while(|_| is_running())
+                .enumerate()
+                .map(|(index, chunk)| {
+                    log_chunk(permutations_emitted, index, chunk.len());
+                    chunk
+                }),
+        ),
+        Backend::Ranked => writer.write(
+            permutations
+                .into_ranked_chunks(chunk_size)
+                .take_while(|_| is_running())
+                .enumerate()
+                .map(|(index, chunk)| {
+                    log_chunk(permutations_emitted, index, chunk.len());
+                    chunk
+                }),
+        ),
+        Backend::Normal => writer.write(
+            permutations
+                .into_chunks(chunk_size)
+                .take_while(|_| is_running())
+                .enumerate()
+                .map(|(index, chunk)| {
+                    log_chunk(permutations_emitted, index, chunk.len());
+                    chunk
+                }),
+        ),
+    }
+}
+
+/// Choose the iterator with `--algorithm`, checking that it can generate the `permutations`.
+fn select_backend<T: Copy + Eq + Hash>(
+    algorithm: AlgorithmOption,
+    permutations: &Permutations<T>,
+) -> Result<(Backend, &'static str), ExitCode> {
+    let (backend, reason) = match algorithm {
+        AlgorithmOption::Auto => {
+            let selection = Backend::select(permutations);
+            (selection.backend, selection.reason)
+        }
+        AlgorithmOption::Normal => (Backend::Normal, "selected with --algorithm"),
+        AlgorithmOption::Optimized => (Backend::Optimized, "selected with --algorithm"),
+        AlgorithmOption::Ranked => (Backend::Ranked, "selected with --algorithm"),
+    };
+    if let Err(e) = backend.check(permutations) {
+        error!(error = %e, "the {} iterator cannot generate the input", backend);
+        return Err(ExitCode::InvalidInput);
+    }
+    Ok((backend, reason))
+}
+
+/// Log how the generation ended and write the `--summary`.
+fn finish_generation(
+    options: &GenerateOptions,
+    generated: io::Result<Emitted>,
+    input_length: usize,
+    algorithm: &'static str,
+    start: Instant,
+) -> ExitCode {
+    let (exit_code, emitted) = match generated {
+        Ok(emitted) if INTERRUPTED.load(Ordering::SeqCst) => {
+            // every dispatched chunk has been written in generation order,
+            // so the emitted permutations are the first ones.
+            warn!(
+                emitted = emitted.permutations,
+                resume_rank = emitted.permutations,
+                "interrupted"
+            );
+            (ExitCode::Interrupted, emitted)
+        }
+        Ok(emitted) => {
+            info!(emitted = emitted.permutations, "done");
+            (ExitCode::Success, emitted)
+        }
+        Err(e) => (write_error_exit_code(&e), Emitted::default()),
+    };
+
+    if options.summary.is_some() {
+        let summary = Summary {
+            input_length,
+            permutations_emitted: emitted.permutations,
+            bytes_written: emitted.bytes,
+            wall_time_seconds: start.elapsed().as_secs_f64(),
+            algorithm,
+            exit_code: exit_code as i32,
+        };
+        if let Err(e) = write_summary(&summary, options.summary_file.as_deref()) {
+            error!(error = %e, "error writing summary");
+            return ExitCode::IoError;
+        }
+    }
+    exit_code
+}
+
+/// Write the summary as a single line of JSON to `path` or to `stderr`.
+fn write_summary(summary: &Summary, path: Option<&Path>) -> io::Result<()> {
+    let json = serde_json::to_string(summary)?;
+    match path {
+        Some(path) => writeln!(File::create(path)?, "{}", json),
+        None => writeln!(io::stderr(), "{}", json),
+    }
+}
+
+/// Write the chunks to `stdout` (compressed with `--compress`), to the files of `--output-pattern`,
+/// to the `--parquet-file` or to the `--nats-subject`.
+fn write_chunks<C>(
+    pipeline: &Pipeline,
+    chunks: impl Iterator<Item = C>,
+    format: OutputFormat,
+    options: &GenerateOptions,
+    input_hash: &str,
+) -> io::Result<Written>
+where
+    C: Send + ChunkView + ToBytes,
+    C::Value: PermValue + Serialize,
+    for<'a> Formatted<'a, C>: Display,
+{
+    let chunks = count_permutations(pipeline, chunks);
+    #[cfg(feature = "arrow")]
+    if let Some(path) = &options.parquet_file {
+        let mut sink = perm::arrow::ParquetSink::new(File::create(path)?);
+        let chunks = pipeline.run(chunks, perm::arrow::ToRecordBatch, &mut sink)?;
+        return Ok(Written {
+            chunks,
+            bytes: sink.bytes_written(),
+        });
+    }
+    #[cfg(feature = "nats")]
+    if let (Some(address), Some(subject)) = (&options.nats_address, &options.nats_subject) {
+        let publish_each = match options.nats_publish {
+            PublishEachOption::Chunk => PublishEach::Chunk,
+            PublishEachOption::Permutation => PublishEach::Permutation,
+        };
+        let mut sink = NatsSink::connect(address, subject.as_str(), publish_each)?;
+        let chunks = pipeline.run(chunks, Encode::new(format), &mut sink)?;
+        return Ok(Written {
+            chunks,
+            bytes: sink.bytes_published(),
+        });
+    }
+    #[cfg(feature = "compression")]
+    if let Some(compress) = options.compress {
+        let codec = match compress {
+            CompressOption::Gzip => Codec::Gzip,
+            CompressOption::Zstd => Codec::Zstd,
+        };
+        let transform = Transform::<C>::then(Encode::new(format), Compress::new(codec));
+        let mut sink = ManifestSink::new(options.stdout_sink());
+        let chunks = pipeline.run(chunks, transform, &mut sink)?;
+        let (sink, mut manifest) = sink.into_parts();
+        if let Some(manifest_path) = &options.manifest {
+            manifest.input_hash = Some(input_hash.to_string());
+            write_manifest(&manifest, manifest_path)?;
+        }
+        return Ok(Written {
+            chunks,
+            bytes: sink.bytes_written(),
+        });
+    }
+    if options.format == FormatOption::Framed {
+        let mut sink = FrameSink::new(io::stdout());
+        let chunks = pipeline.run(chunks, EncodeFrame, &mut sink)?;
+        return Ok(Written {
+            chunks,
+            bytes: sink.bytes_written(),
+        });
+    }
+    match (&options.output_pattern, &options.manifest) {
+        (None, None) => {
+            // `stdout` takes bytes: the chunks are formatted without an intermediate `String`.
+            let mut sink = options.stdout_sink();
+            let chunks = pipeline.run(chunks, EncodeBytes::new(format), &mut sink)?;
+            Ok(Written {
+                chunks,
+                bytes: sink.bytes_written(),
+            })
+        }
+        (None, Some(manifest_path)) => {
+            let mut sink = ManifestSink::new(options.stdout_sink());
+            let chunks = pipeline.run(chunks, Encode::new(format), &mut sink)?;
+            let (sink, mut manifest) = sink.into_parts();
+            manifest.input_hash = Some(input_hash.to_string());
+            write_manifest(&manifest, manifest_path)?;
+            Ok(Written {
+                chunks,
+                bytes: sink.bytes_written(),
+            })
+        }
+        (Some(pattern), manifest_path) => {
+            let mut sink =
+                ManifestSink::new(ShardedSink::new(pattern.as_str(), options.split_by())?);
+            let chunks = pipeline.run(chunks, Encode::new(format), &mut sink)?;
+            let (sink, mut manifest) = sink.into_parts();
+            if let Some(manifest_path) = manifest_path {
+                manifest.files = sink.file_entries();
+                manifest.input_hash = Some(input_hash.to_string());
+                write_manifest(&manifest, manifest_path)?;
+            }
+            Ok(Written {
+                chunks,
+                bytes: sink.bytes_written(),
+            })
+        }
+    }
+}
+
+/// Count the permutations of the chunks in the `Metrics` of the `pipeline`, if any.
+fn count_permutations<C: ChunkView>(
+    pipeline: &Pipeline,
+    chunks: impl Iterator<Item = C>,
+) -> impl Iterator<Item = C> {
+    let metrics = pipeline.metrics().cloned();
+    chunks.inspect(move |chunk| {
+        if let Some(metrics) = &metrics {
+            metrics.record_permutations(chunk.permutations_count());
+        }
+    })
+}
+
+/// Log the `Metrics` of a pipeline periodically, on its own thread.
+struct MetricsReporter {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl MetricsReporter {
+    /// Start logging the `metrics` every `interval`.
+    fn start(metrics: Metrics, interval: Duration) -> Self {
+        let (stop, stop_signal) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let start = (Instant::now(), MetricsSnapshot::default());
+            let mut previous = start;
+            loop {
+                // the sender is dropped to stop the reporter.
+                let stopped = !matches!(
+                    stop_signal.recv_timeout(interval),
+                    Err(RecvTimeoutError::Timeout)
+                );
+                let current = (Instant::now(), metrics.snapshot());
+                if stopped {
+                    // the final rate is the one of the whole generation.
+                    log_metrics(&start, &current, true);
+                    break;
+                }
+                log_metrics(&previous, &current, false);
+                previous = current;
+            }
+        });
+        Self { stop, thread }
+    }
+
+    /// Stop logging the metrics, after logging them one last time.
+    fn stop(self) {
+        drop(self.stop);
+        self.thread
+            .join()
+            .expect("Error waiting the metrics reporter to terminate");
+    }
+}
+
+/// Log the `current` metrics, with the rate of the permutations since the `previous` ones.
+fn log_metrics(
+    previous: &(Instant, MetricsSnapshot),
+    current: &(Instant, MetricsSnapshot),
+    is_final: bool,
+) {
+    let (previous_time, previous) = previous;
+    let (current_time, current) = current;
+    let elapsed = current_time.duration_since(*previous_time).as_secs_f64();
+    let rate = (current.permutations_generated - previous.permutations_generated) as f64
+        / elapsed.max(f64::EPSILON);
+    info!(
+        permutations = current.permutations_generated,
+        chunks_generated = current.chunks_generated,
+        chunks_emitted = current.chunks_emitted,
+        bytes = current.bytes_written,
+        queue_depth = current.queue_depth,
+        permutations_per_second = rate.round() as u64,
+        generation_ms = current.generation.as_millis() as u64,
+        transform_ms = current.transform.as_millis() as u64,
+        write_ms = current.write.as_millis() as u64,
+        "{}",
+        if is_final { "final metrics" } else { "metrics" }
+    );
+}
+
+/// Check if the manifest at `manifest_path` was written by a run with the same `input_hash`,
+/// and the files it lists are intact.
+fn is_already_generated(manifest_path: &Path, input_hash: &str) -> bool {
+    let manifest = match File::open(manifest_path).and_then(Manifest::from_reader) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            debug!(error = %e, "no previous manifest");
+            return false;
+        }
+    };
+    if manifest.input_hash.as_deref() != Some(input_hash) {
+        debug!("the previous manifest has a different input hash");
+        return false;
+    }
+    match manifest.verify_files() {
+        Ok(true) => true,
+        Ok(false) | Err(_) => {
+            warn!("the output of the previous run does not match its manifest");
+            false
+        }
+    }
+}
+
+fn write_manifest(manifest: &Manifest, path: &Path) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    manifest.to_writer(&mut file)?;
+    writeln!(file)
+}
+
+/// Log each generated chunk and count its permutations.
+pub(crate) fn log_chunk(emitted: &mut usize, index: usize, size: usize) {
+    debug!(chunk = index, size, "chunk generated");
+    *emitted += size;
+}
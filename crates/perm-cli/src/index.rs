@@ -0,0 +1,31 @@
+//! # Index
+//!
+//! `perm index <OUTPUT>`: write the index of a previously generated output,
+//! to fetch its permutations by rank with `perm::index::IndexedReader`.
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use tracing::{error, info};
+
+use perm::index::build_index;
+
+use crate::ExitCode;
+
+/// Write the index of the `output` file to the `index` file.
+pub(crate) fn write_index(output: &Path, index: &Path) -> ExitCode {
+    let indexed = File::open(output).and_then(|output| {
+        let mut index = io::BufWriter::new(File::create(index)?);
+        build_index(io::BufReader::new(output), &mut index)
+    });
+    match indexed {
+        Ok(permutations) => {
+            info!(permutations, index = %index.display(), "index written");
+            ExitCode::Success
+        }
+        Err(e) => {
+            error!(error = %e, "error writing index");
+            ExitCode::IoError
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! # Input
+//!
+//! Read the first line of `stdin` and parse it with the input options,
+//! logging why it is not valid.
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+
+use tracing::error;
+
+use perm::Permutations;
+
+use crate::{DuplicatesOption, ExitCode, InputOptions};
+
+/// Read the first line of `stdin`.
+pub(crate) fn read_input() -> Result<String, ExitCode> {
+    match io::stdin().lock().lines().next() {
+        Some(Ok(text)) => Ok(text),
+        Some(Err(e)) => {
+            error!(error = %e, "error reading input");
+            Err(ExitCode::IoError)
+        }
+        None => {
+            error!("empty input");
+            Err(ExitCode::InvalidInput)
+        }
+    }
+}
+
+/// Read the bytes of the first line of `stdin`, without the line break.
+pub(crate) fn read_bytes_input() -> Result<Vec<u8>, ExitCode> {
+    let mut bytes = vec![];
+    match io::stdin().lock().read_until(b'\n', &mut bytes) {
+        Ok(0) => {
+            error!("empty input");
+            Err(ExitCode::InvalidInput)
+        }
+        Ok(_) => {
+            if bytes.last() == Some(&b'\n') {
+                bytes.pop();
+                if bytes.last() == Some(&b'\r') {
+                    bytes.pop();
+                }
+            }
+            Ok(bytes)
+        }
+        Err(e) => {
+            error!(error = %e, "error reading input");
+            Err(ExitCode::IoError)
+        }
+    }
+}
+
+/// Check the input `bytes` against `--max-length` and `--duplicates`, logging why they are not valid.
+pub(crate) fn parse_bytes(
+    bytes: &[u8],
+    options: &InputOptions,
+) -> Result<Permutations<u8>, ExitCode> {
+    if bytes.len() > options.max_length {
+        error!(
+            length = bytes.len(),
+            max_length = options.max_length,
+            "invalid input: too many bytes"
+        );
+        return Err(ExitCode::InvalidInput);
+    }
+    let mut seen = [false; 256];
+    let mut distinct = Vec::with_capacity(bytes.len());
+    for (index, byte) in bytes.iter().enumerate() {
+        if !seen[usize::from(*byte)] {
+            seen[usize::from(*byte)] = true;
+            distinct.push(*byte);
+        } else if let DuplicatesOption::Reject = options.duplicates {
+            error!(index, byte, "invalid input: duplicate byte");
+            return Err(ExitCode::InvalidInput);
+        }
+    }
+    Ok(match options.duplicates {
+        DuplicatesOption::Dedup => Permutations::from_bytes(&distinct),
+        DuplicatesOption::Keep | DuplicatesOption::Reject => Permutations::from_bytes(bytes),
+    })
+}
+
+/// Split the input `text` in its characters, checked against `--max-length`, `--max-distinct` and `--duplicates`,
+/// logging why they are not valid.
+/// Each character is a slice of the input, so they are generated and written as any other value.
+pub(crate) fn parse_chars<'a>(
+    text: &'a str,
+    options: &InputOptions,
+) -> Result<Permutations<&'a str>, ExitCode> {
+    let characters = text
+        .char_indices()
+        .map(|(offset, character)| &text[offset..offset + character.len_utf8()])
+        .collect::<Vec<_>>();
+    if characters.len() > options.max_length {
+        error!(
+            length = characters.len(),
+            max_length = options.max_length,
+            "invalid input: too many characters"
+        );
+        return Err(ExitCode::InvalidInput);
+    }
+    let mut seen = HashSet::new();
+    let mut distinct = Vec::with_capacity(characters.len());
+    for (index, character) in characters.iter().enumerate() {
+        if seen.insert(*character) {
+            distinct.push(*character);
+        } else if let DuplicatesOption::Reject = options.duplicates {
+            error!(index, character, "invalid input: duplicate character");
+            return Err(ExitCode::InvalidInput);
+        }
+    }
+    if let Some(max_distinct) = options.max_distinct.filter(|max| distinct.len() > *max) {
+        error!(
+            distinct = distinct.len(),
+            max_distinct, "invalid input: too many distinct characters"
+        );
+        return Err(ExitCode::InvalidInput);
+    }
+    Ok(match options.duplicates {
+        DuplicatesOption::Dedup => Permutations::new(distinct),
+        DuplicatesOption::Keep | DuplicatesOption::Reject => Permutations::new(characters),
+    })
+}
+
+/// Parse the input `text`, logging why it is not valid.
+pub(crate) fn parse_input<'a>(
+    text: &'a str,
+    options: &InputOptions,
+) -> Result<Permutations<&'a str>, ExitCode> {
+    Permutations::parse(text, &options.parse_options()).map_err(|message| {
+        error!(%message, "invalid input");
+        ExitCode::InvalidInput
+    })
+}
@@ -0,0 +1,18 @@
+//! # Interrupt
+//!
+//! On `SIGINT` the generation stops dispatching new chunks instead of terminating the process,
+//! so that the chunks already dispatched are written and the output ends with a complete line.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the `SIGINT` handler to stop dispatching new chunks.
+pub(crate) static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Stop dispatching new chunks on `SIGINT`, instead of terminating the process.
+pub(crate) fn handle_sigint() {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+        .expect("Error setting the SIGINT handler");
+}
+
+pub(crate) fn is_running() -> bool {
+    !INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,693 @@
+//! # The `perm` entry point.
+//!
+//! Read the input from `stdin` and generate the permutations to `stdout`.
+//!
+//! A valid input is expected to contain only comma separated numbers.
+//! An empty line is the empty input: its only permutation, the empty one, is written as an empty line.
+//! The permutations are computed sequentially in chunks of a given size,
+//! formatted by a pool of worker threads and written to `stdout` in generation order by the `Pipeline`.
+//! In this way the blocking I/O operations do not block the computations of the next chunk.
+//! The number of workers is the number of available CPUs, or the value of `--threads`.
+//!
+//! The chunk size is such that to have `OPTIMAL_CHUNKS_NUMBER` total chunks,
+//! considering the duplicated values in the input.
+//! This value has been found empirically after some benchmarks on my pc.
+//!
+//! The iterator generating the permutations is chosen with `--algorithm`: `normal`, `optimized`
+//! (only if the input is short enough, `limits::OPTIMIZED_MAX_LEN=128` elements) or `ranked`.
+//! With `auto`, the default, `Backend::select` chooses the fastest for the input, and the choice is logged with its reason.
+//!
+//! Diagnostics are logged to `stderr` with `tracing`:
+//! `--log-level` sets the verbosity and `--log-format json` emits one JSON object per event,
+//! to run the tool inside orchestrated pipelines.
+//!
+//! On `SIGINT` (Ctrl-C) no new chunk is dispatched, the chunks already dispatched are written
+//! and `stdout` is flushed, so that no truncated line is left.
+//! Then, the number of emitted permutations and the rank to resume from are logged,
+//! and the process exits with the status code: `ExitCode::Interrupted`.
+//!
+//! `perm resume --output <OUTPUT>` continues a generation to `stdout` that was saved to a file and cut short,
+//! by an interruption or a power loss: the permutation cut in the middle is removed from the end of the file,
+//! the rank of the last complete permutation is computed, and the following permutations are appended.
+//! The output must have been written with the same input options, without `--indices` and `--quote`.
+//!
+//! With `--indices` each permutation is written as the ordering of the input positions (`0..n-1`)
+//! instead of the values: the equal values are given their positions in input order.
+//!
+//! The input is rejected if it has more than `--max-length` values (10000 by default),
+//! or more than `--max-distinct` distinct values if given,
+//! and the first invalid value is logged with its position and its byte offset.
+//! The generation is refused if it has more than `--max-permutations` permutations (a billion by default),
+//! logging their number and the estimated size of the output, unless `--force` is given:
+//! e.g. 30 distinct values would never finish, and would fill the disk.
+//! `--validate integer` only accepts integers, `--validate any` accepts any value,
+//! and `--normalize` writes the equal numbers the same way (e.g. `+1.0` as `1`).
+//! With `--counts` a value can be followed by its number of occurrences: `1x3,2x2,5` is `1,1,1,2,2,5`.
+//! `--duplicates reject` rejects an input with equal values, and `--duplicates dedup` removes them.
+//!
+//! With `--quote` the values that would make the output ambiguous are quoted RFC 4180-style.
+//! With `--join` the characters of the input line are permuted, and each permutation is written
+//! as a string without separators: `echo abc | perm --join` writes the anagrams of `abc`.
+//! With `--line-ending crlf` each permutation ends with `\r\n` instead of `\n`, for the Windows consumers.
+//!
+//! When the consumer closes `stdout` early (e.g. `perm | head`), the writes fail with a broken pipe:
+//! the generation stops and the process exits successfully, without an error.
+//!
+//! With `--format framed` the chunks are written to `stdout` as length-prefixed `bincode` frames,
+//! to be read by another Rust process with `perm::framing::ChunkReader` without parsing the text.
+//! With `--format raw` the bytes of the input line are permuted, and each permutation is written
+//! as its raw bytes followed by a line break, for the tools reading byte-exact candidates.
+//! `perm mask <MASK>` writes the password candidates of a mask like `?u?l?l?d?d` the same way,
+//! with a different alphabet for each position (see `perm::mask`).
+//!
+//! With `--output-pattern` the permutations are written to many files instead of `stdout`,
+//! split by chunk (round-robin), by first value or by size with `--split-by`.
+//!
+//! With `--parquet-file` (requires the `arrow` feature) the permutations are written to a Parquet file
+//! with one column for each position, instead of `stdout`.
+//!
+//! With `--compress gzip` or `--compress zstd` (requires the `compression` feature) each chunk is compressed
+//! on its worker thread, and the compressed chunks are written to `stdout` as a single gzip (zstd) stream.
+//! With `--manifest` the byte range of each compressed chunk is recorded, to decompress them in parallel.
+//!
+//! With `--numa-mode` (requires the `numa` feature) the permutations are generated by one thread pinned to each core
+//! (or `--threads` threads), each writing its own range of ranks to its own file of `--output-pattern`,
+//! where `{}` is the thread number: there is no shared writer, for the machines with many cores.
+//! The permutations are generated by the `ranked` iterator, and `--split-by` is ignored.
+//! With `--numa-concatenate` the files are concatenated to `stdout` in generation order at the end, and removed.
+//! A run with `--numa-mode` cannot be resumed: `SIGINT` terminates it at once, leaving the files incomplete.
+//!
+//! With `--nats-address` and `--nats-subject` (requires the `nats` feature) the permutations are published
+//! to a NATS subject instead of `stdout`: one message for each chunk, or for each permutation with
+//! `--nats-publish permutation`.
+//!
+//! With `--manifest` a JSON `Manifest` is written with the rank range, the byte range and the checksum
+//! of each chunk, and the size and the checksum of each file of `--output-pattern`,
+//! with the files and the byte ranges in them each chunk has been written to.
+//! It also records a hash of the input values (in input order, which decides the ranks) and of the options changing the output:
+//! with `--skip-if-exists` a repeated run whose manifest has the same hash, and whose files are intact, is skipped.
+//!
+//! The other subcommands are:
+//!
+//! - `perm generate`: the same as `perm` without a subcommand.
+//! - `perm count`: write the number of permutations of the input.
+//! - `perm sample <N>`: write `N` permutations of the input, evenly spaced by rank,
+//!   or drawn uniformly at random with `--random`.
+//! - `perm rank --perm <PERMUTATION>` and `perm unrank --rank <RANK>`: convert between a permutation
+//!   of the input (or of `--values`) and its rank, in lexicographic order of the first occurrences of the values.
+//! - `perm compose --perms <FILE>`: write the composition of the permutations of the positions in a file
+//!   (one for each line, as written by `--indices`), its inverse and its order.
+//! - `perm verify [OUTPUT] --manifest <MANIFEST>`: check an output, or the files of a sharded output, against its manifest.
+//! - `perm completions <SHELL>`: write the completion script of a shell.
+//! - `perm serve` (requires the `serve` feature): answer the HTTP requests for pages of permutations,
+//!   see the `serve` module.
+//!
+//! `perm index <OUTPUT>` writes the index of a previously generated output,
+//! to fetch its permutations by rank with `perm::index::IndexedReader`.
+//!
+//! `perm repl` reads the inputs line by line and writes their permutations as soon as each line is entered,
+//! with the commands `:count`, `:sample <n>`, `:format text|json`, `:help` and `:quit`.
+//! There `SIGINT` only stops writing the permutations of the current line.
+//!
+//! Each subcommand is implemented in its own module (e.g. `generate`, `resume`, `serve`):
+//! this one only parses the command line and dispatches it.
+//!
+//! With `--metrics-interval` (e.g. `5s` or `500ms`) the metrics of the pipeline are logged periodically
+//! and on completion: the permutations and the chunks generated, the chunks and the bytes written,
+//! the chunks waiting for a worker, the rate and the time spent in each stage.
+//!
+//! The text and raw output to `stdout` is unbuffered and flushed at the end by default:
+//! `--buffer-size` accumulates it in a buffer of that size, and `--flush chunk|line` flushes it
+//! after each chunk, or only the complete lines, e.g. when piping to an interactive consumer.
+//!
+//! The random choices are seeded by `--seed`, to reproduce a run in the CI or in an experiment:
+//! without it a random seed is chosen, and logged.
+//!
+//! With `--summary json` a JSON object describing the run is written on completion
+//! to `stderr`, or to the file given with `--summary-file`.
+//!
+//! # Exit codes
+//!
+//! - `0`: all the permutations have been written.
+//! - `2`: the command line options are not valid.
+//! - `65`: there is no input line, or it does not contain comma separated numbers,
+//!   or the output has more than `--max-permutations` permutations without `--force`,
+//!   or the output does not match its manifest.
+//! - `74`: the input cannot be read or the output cannot be written.
+//! - `130`: the generation has been interrupted by `SIGINT`.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{generate as generate_completions, Shell};
+use tracing::Level;
+
+use perm::parse::DEFAULT_MAX_LENGTH;
+use perm::pipeline::SHARD_PLACEHOLDER;
+use perm::{DuplicatePolicy, ParseOptions, Validation};
+
+use interrupt::handle_sigint;
+use output::{output_format, write_error_exit_code};
+
+mod compose;
+mod count;
+mod generate;
+mod index;
+mod input;
+mod interrupt;
+mod mask;
+mod output;
+mod rank;
+mod repl;
+mod resume;
+mod sample;
+#[cfg(feature = "serve")]
+mod serve;
+mod verify;
+
+const OPTIMAL_CHUNKS_NUMBER: usize = 256;
+
+/// The default of `--max-permutations`: 12 distinct values have about half a billion permutations,
+/// 13 distinct values six billion.
+const DEFAULT_MAX_PERMUTATIONS: usize = 1_000_000_000;
+
+/// The status codes of the process.
+/// They follow the `sysexits.h` convention where possible.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ExitCode {
+    Success = 0,
+    InvalidInput = 65,
+    IoError = 74,
+    /// The conventional status code of a process terminated by `SIGINT`.
+    Interrupted = 130,
+}
+
+/// Generate all the distinct permutations of the comma separated numbers read from `stdin`.
+///
+/// Without a subcommand, the permutations are generated as with `perm generate`.
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Options {
+    /// The maximum level of the logs written to `stderr`.
+    #[arg(long, default_value = "info", global = true)]
+    log_level: Level,
+    /// The format of the logs written to `stderr`.
+    #[arg(long, value_enum, default_value = "text", global = true)]
+    log_format: LogFormat,
+    /// The seed of the random choices, to reproduce a run [default: a random seed, logged].
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+    #[command(flatten)]
+    generate: GenerateOptions,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// How the input values are parsed.
+#[derive(Args)]
+struct InputOptions {
+    /// The maximum number of input values: a longer input is rejected before generating anything.
+    #[arg(long, default_value_t = DEFAULT_MAX_LENGTH)]
+    max_length: usize,
+    /// The maximum number of distinct input values: a more diverse input is rejected before generating anything.
+    #[arg(long)]
+    max_distinct: Option<usize>,
+    /// Which input values are valid.
+    #[arg(long, value_enum, default_value = "number")]
+    validate: ValidationOption,
+    /// Write the equal numbers the same way: `+1.0`, `01` and `1` are all written as `1`.
+    #[arg(long)]
+    normalize: bool,
+    /// Read `VALUExCOUNT` as `COUNT` occurrences of `VALUE`, e.g. `1x3,2x2,5`.
+    #[arg(long)]
+    counts: bool,
+    /// What to do with the equal input values.
+    #[arg(long, value_enum, default_value = "keep")]
+    duplicates: DuplicatesOption,
+}
+
+/// How the permutations are generated and where they are written.
+#[derive(Args)]
+struct GenerateOptions {
+    #[command(flatten)]
+    input: InputOptions,
+    /// The maximum number of permutations: a larger generation is refused before writing anything.
+    #[arg(long, default_value_t = DEFAULT_MAX_PERMUTATIONS)]
+    max_permutations: usize,
+    /// Generate the permutations even if there are more than `--max-permutations`.
+    #[arg(long)]
+    force: bool,
+    /// Quote the values containing commas, double quotes or line breaks (RFC 4180).
+    #[arg(long)]
+    quote: bool,
+    /// Permute the characters of the input line, and write each permutation as a string without separators.
+    #[arg(long, conflicts_with_all = ["quote", "indices", "format"])]
+    join: bool,
+    /// The line break written after each permutation of the text output.
+    #[arg(long, value_enum, default_value = "lf")]
+    line_ending: LineEndingOption,
+    /// How the chunks are written to `stdout`.
+    #[arg(long, value_enum, default_value = "text", conflicts_with_all = ["quote", "output_pattern", "manifest"])]
+    format: FormatOption,
+    /// Buffer the text and raw output to `stdout` in a buffer of this size in bytes [default: unbuffered].
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    buffer_size: Option<u64>,
+    /// When the text and raw output to `stdout` is flushed.
+    #[arg(long, value_enum, default_value = "end")]
+    flush: FlushOption,
+    /// Write the permutations of the input positions (`0..n-1`) instead of the values.
+    #[arg(long)]
+    indices: bool,
+    /// The iterator generating the permutations: `auto` chooses the fastest for the input.
+    #[arg(long, value_enum, default_value = "auto")]
+    algorithm: AlgorithmOption,
+    /// The number of worker threads formatting the chunks [default: the number of CPUs].
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    threads: Option<u16>,
+    /// The size in bytes of the formatted chunks [default: the input is split in a fixed number of chunks].
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    chunk_bytes: Option<u64>,
+    /// Write the permutations to many files instead of `stdout`:
+    /// `{}` in the pattern is replaced by the shard key, e.g. `out-{}.txt`.
+    #[arg(long, value_parser = parse_output_pattern)]
+    output_pattern: Option<String>,
+    /// How the permutations are split across the files of `--output-pattern`.
+    #[arg(long, value_enum, default_value = "chunk", requires = "output_pattern")]
+    split_by: SplitByOption,
+    /// The number of files the chunks are written to round-robin, with `--split-by chunk`.
+    #[arg(long, default_value = "16", value_parser = clap::value_parser!(u16).range(1..))]
+    shards: u16,
+    /// The maximum size in bytes of each file, with `--split-by size`.
+    #[arg(long, default_value = "1073741824", value_parser = clap::value_parser!(u64).range(1..))]
+    max_file_size: u64,
+    /// Write the permutations to a Parquet file, with one column for each position.
+    #[cfg(feature = "arrow")]
+    #[arg(long, conflicts_with_all = ["output_pattern", "manifest"])]
+    parquet_file: Option<PathBuf>,
+    /// Compress each chunk on its worker thread, as a gzip member or a zstd frame, before writing it to `stdout`.
+    #[cfg(feature = "compression")]
+    #[arg(long, value_enum, conflicts_with_all = ["output_pattern", "format"])]
+    compress: Option<CompressOption>,
+    /// Generate the permutations on one thread pinned to each core, each writing its own range of ranks
+    /// to its own file of `--output-pattern` (`{}` is the thread number).
+    #[cfg(feature = "numa")]
+    #[arg(long, requires = "output_pattern", conflicts_with_all = ["format", "indices", "manifest"])]
+    numa_mode: bool,
+    /// Concatenate the files of `--numa-mode` to `stdout` in generation order at the end, and remove them.
+    #[cfg(feature = "numa")]
+    #[arg(long, requires = "numa_mode")]
+    numa_concatenate: bool,
+    /// Publish the permutations to the NATS server at this address (`host:port`).
+    #[cfg(feature = "nats")]
+    #[arg(
+        long,
+        requires = "nats_subject",
+        conflicts_with_all = ["output_pattern", "manifest"]
+    )]
+    nats_address: Option<String>,
+    /// The NATS subject the permutations are published to.
+    #[cfg(feature = "nats")]
+    #[arg(long, requires = "nats_address")]
+    nats_subject: Option<String>,
+    /// What is published as a single NATS message.
+    #[cfg(feature = "nats")]
+    #[arg(long, value_enum, default_value = "chunk", requires = "nats_address")]
+    nats_publish: PublishEachOption,
+    /// Write a JSON manifest with the rank range, the byte range and the checksum of each chunk.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// Skip the generation if the `--manifest` of a previous run has the same input values and options,
+    /// and the files of `--output-pattern` it lists are intact.
+    #[arg(long, requires_all = ["manifest", "output_pattern"])]
+    skip_if_exists: bool,
+    /// Log the metrics of the pipeline at this interval, e.g. `5s` or `500ms`, and on completion.
+    #[arg(long, value_parser = parse_interval)]
+    metrics_interval: Option<Duration>,
+    /// Write a machine-readable summary of the run on completion.
+    #[arg(long, value_enum)]
+    summary: Option<SummaryFormat>,
+    /// The file to write the summary to, instead of `stderr`.
+    #[arg(long, requires = "summary")]
+    summary_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate the permutations of the input read from `stdin` (the default command).
+    Generate(GenerateOptions),
+    /// Write the number of permutations of the input read from `stdin`.
+    Count {
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Write some permutations of the input read from `stdin`, evenly spaced by rank.
+    Sample {
+        /// The number of permutations.
+        samples: usize,
+        /// Draw the permutations uniformly at random, seeded by `--seed`, instead of evenly spaced.
+        #[arg(long)]
+        random: bool,
+        /// Quote the values containing commas, double quotes or line breaks (RFC 4180).
+        #[arg(long)]
+        quote: bool,
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Write the rank of a permutation of the input, in lexicographic order of the first occurrences.
+    Rank {
+        /// The comma separated values to permute [default: read from `stdin`].
+        #[arg(long)]
+        values: Option<String>,
+        /// The comma separated permutation.
+        #[arg(long = "perm")]
+        permutation: String,
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Write the permutation of the input with a given rank, in lexicographic order of the first occurrences.
+    Unrank {
+        /// The comma separated values to permute [default: read from `stdin`].
+        #[arg(long)]
+        values: Option<String>,
+        /// The rank of the permutation.
+        #[arg(long)]
+        rank: usize,
+        /// Quote the values containing commas, double quotes or line breaks (RFC 4180).
+        #[arg(long)]
+        quote: bool,
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Write the composition of the permutations of the positions in a file, its inverse and its order.
+    Compose {
+        /// The file with one permutation of the positions `0..n-1` for each line, as written by `--indices`.
+        #[arg(long)]
+        perms: PathBuf,
+    },
+    /// Check the size and the checksums of a generated output against its manifest.
+    Verify {
+        /// The generated output, unless it is split across the files listed in the manifest.
+        output: Option<PathBuf>,
+        /// The manifest written with `--manifest`.
+        #[arg(long)]
+        manifest: PathBuf,
+    },
+    /// Write the index mapping the rank of each permutation of a generated output to its byte offset.
+    Index {
+        /// The generated output.
+        output: PathBuf,
+        /// The index file to write [default: the output path with the `.idx` extension].
+        #[arg(long)]
+        index: Option<PathBuf>,
+    },
+    /// Read the inputs line by line and write their permutations as soon as each line is entered.
+    Repl {
+        /// Quote the values containing commas, double quotes or line breaks (RFC 4180).
+        #[arg(long)]
+        quote: bool,
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Append the permutations following the last complete one of a truncated output.
+    Resume {
+        /// The truncated output.
+        #[arg(long)]
+        output: PathBuf,
+        /// The comma separated values permuted in the output [default: read from `stdin`].
+        #[arg(long = "input")]
+        values: Option<String>,
+        /// The number of worker threads formatting the chunks [default: the number of CPUs].
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+        threads: Option<u16>,
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Write the password candidates of a mask like `?u?l?l?d?d`, one for each line, as raw bytes.
+    Mask {
+        /// The mask: `?l`, `?u`, `?d`, `?s` and `?a` are charsets, `??` is `?`, any other character is itself.
+        mask: String,
+        /// The rank of the first candidate, to resume an interrupted generation.
+        #[arg(long, default_value_t = 0)]
+        start_rank: usize,
+        /// The number of worker threads formatting the chunks [default: the number of CPUs].
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+        threads: Option<u16>,
+    },
+    /// Answer `GET /permutations?values=<VALUES>&cursor=<RANK>&limit=<N>` with a JSON page of permutations,
+    /// and `GET /metrics` with the Prometheus metrics of the server.
+    #[cfg(feature = "serve")]
+    Serve {
+        /// The address to listen on (`host:port`).
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+        #[command(flatten)]
+        input: InputOptions,
+    },
+    /// Write the completion script of a shell to `stdout`.
+    Completions {
+        /// The shell.
+        shell: Shell,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ValidationOption {
+    /// Any number that can be parsed as a floating point.
+    Number,
+    /// Only the integers.
+    Integer,
+    /// Any value.
+    Any,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum DuplicatesOption {
+    /// Generate the distinct permutations of the values, with their repetitions.
+    Keep,
+    /// Reject the input.
+    Reject,
+    /// Keep only the first occurrence of each value.
+    Dedup,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum AlgorithmOption {
+    /// Choose the iterator from the length and the size of the input values.
+    Auto,
+    /// The job tree, without limits on the length.
+    Normal,
+    /// The job tree of fixed arrays, up to 128 values.
+    Optimized,
+    /// The in-place successor of each permutation, with the lowest memory.
+    Ranked,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum FormatOption {
+    /// One permutation for each line, with the values separated by commas.
+    Text,
+    /// Length-prefixed `bincode` frames, one for each chunk, read by `perm::framing::ChunkReader`.
+    Framed,
+    /// The permutations of the bytes of the input line, each one written as raw bytes and a line break.
+    Raw,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum LineEndingOption {
+    /// `\n`.
+    Lf,
+    /// `\r\n`, for the Windows consumers.
+    Crlf,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum FlushOption {
+    /// Flush once all the permutations are written.
+    End,
+    /// Flush after each chunk.
+    Chunk,
+    /// Flush the complete lines after each chunk, for interactive piping.
+    Line,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum SplitByOption {
+    /// Write the chunks round-robin to `--shards` files.
+    Chunk,
+    /// Write each permutation to the file of its first value.
+    Prefix,
+    /// Start a new file when the current one reaches `--max-file-size` bytes.
+    Size,
+}
+
+#[cfg(feature = "compression")]
+#[derive(Copy, Clone, ValueEnum)]
+enum CompressOption {
+    Gzip,
+    Zstd,
+}
+
+#[cfg(feature = "nats")]
+#[derive(Copy, Clone, ValueEnum)]
+enum PublishEachOption {
+    /// Publish each chunk of permutations.
+    Chunk,
+    /// Publish each permutation.
+    Permutation,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum SummaryFormat {
+    Json,
+}
+
+impl InputOptions {
+    fn parse_options(&self) -> ParseOptions {
+        let options = match self.max_distinct {
+            Some(max_distinct) => ParseOptions::new().with_max_distinct_values(max_distinct),
+            None => ParseOptions::new(),
+        };
+        options
+            .with_max_length(self.max_length)
+            .with_validation(match self.validate {
+                ValidationOption::Number => Validation::Number,
+                ValidationOption::Integer => Validation::Integer,
+                ValidationOption::Any => Validation::Any,
+            })
+            .with_normalization(self.normalize)
+            .with_counts(self.counts)
+            .with_duplicates(match self.duplicates {
+                DuplicatesOption::Keep => DuplicatePolicy::Keep,
+                DuplicatesOption::Reject => DuplicatePolicy::Reject,
+                DuplicatesOption::Dedup => DuplicatePolicy::Dedup,
+            })
+    }
+}
+
+fn parse_output_pattern(pattern: &str) -> Result<String, String> {
+    if pattern.contains(SHARD_PLACEHOLDER) {
+        Ok(pattern.to_string())
+    } else {
+        Err(format!("the pattern must contain `{}`", SHARD_PLACEHOLDER))
+    }
+}
+
+/// Parse a positive interval: a number followed by `ms`, `s`, `m` or `h`.
+fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or("the interval needs a unit: `ms`, `s`, `m` or `h`")?;
+    let (value, unit) = interval.split_at(split);
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    let interval = match unit {
+        "ms" => Duration::from_millis(value),
+        "s" => Duration::from_secs(value),
+        "m" => Duration::from_secs(value.saturating_mul(60)),
+        "h" => Duration::from_secs(value.saturating_mul(3600)),
+        _ => {
+            return Err(format!(
+                "`{}` is not a unit: use `ms`, `s`, `m` or `h`",
+                unit
+            ))
+        }
+    };
+    if interval.is_zero() {
+        return Err("the interval must be positive".to_string());
+    }
+    Ok(interval)
+}
+
+fn main() {
+    let options = Options::parse();
+    init_logs(options.log_level, options.log_format);
+    let exit_code = match &options.command {
+        None => generate::generate(&options.generate),
+        Some(Command::Generate(generate_options)) => generate::generate(generate_options),
+        Some(Command::Count { input }) => count::count(input),
+        Some(Command::Sample {
+            samples,
+            random,
+            quote,
+            input,
+        }) => {
+            let seed = random.then(|| sample::random_seed(options.seed));
+            sample::sample(*samples, seed, output_format(*quote), input)
+        }
+        Some(Command::Rank {
+            values,
+            permutation,
+            input,
+        }) => rank::rank(values.as_deref(), permutation, input),
+        Some(Command::Unrank {
+            values,
+            rank,
+            quote,
+            input,
+        }) => rank::unrank(values.as_deref(), *rank, output_format(*quote), input),
+        Some(Command::Compose { perms }) => compose::compose(perms),
+        Some(Command::Verify { output, manifest }) => verify::verify(output.as_deref(), manifest),
+        Some(Command::Index { output, index }) => {
+            let index = index
+                .clone()
+                .unwrap_or_else(|| output.with_extension("idx"));
+            index::write_index(output, &index)
+        }
+        Some(Command::Resume {
+            output,
+            values,
+            threads,
+            input,
+        }) => resume::resume(output, values.as_deref(), *threads, input),
+        Some(Command::Mask {
+            mask,
+            start_rank,
+            threads,
+        }) => mask::generate_mask(mask, *start_rank, *threads),
+        Some(Command::Repl { quote, input }) => {
+            handle_sigint();
+            repl::repl(&input.parse_options(), output_format(*quote))
+        }
+        #[cfg(feature = "serve")]
+        Some(Command::Serve { address, input }) => serve::serve(address, &input.parse_options()),
+        Some(Command::Completions { shell }) => {
+            // `generate_completions` panics on a write error, so the script is written at once.
+            let mut script = vec![];
+            generate_completions(*shell, &mut Options::command(), "perm", &mut script);
+            match io::stdout().write_all(&script) {
+                Ok(()) => ExitCode::Success,
+                Err(e) => write_error_exit_code(&e),
+            }
+        }
+    };
+    process::exit(exit_code as i32)
+}
+
+/// The name of an option value on the command line.
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+fn init_logs(level: Level, format: LogFormat) {
+    // the colors are only written to a terminal, not to a redirected `stderr`.
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_max_level(level);
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
@@ -0,0 +1,64 @@
+//! # Mask
+//!
+//! `perm mask <MASK>`: write the password candidates of a mask like `?u?l?l?d?d` as raw byte strings,
+//! with a different alphabet for each position (see `perm::mask`).
+use std::io;
+use std::sync::atomic::Ordering;
+
+use tracing::{error, info, warn};
+
+use perm::pipeline::{Pipeline, WriterSink};
+use perm::raw::EncodeRaw;
+use perm::Mask;
+
+use crate::generate::log_chunk;
+use crate::interrupt::{handle_sigint, is_running, INTERRUPTED};
+use crate::output::write_error_exit_code;
+use crate::{ExitCode, OPTIMAL_CHUNKS_NUMBER};
+
+/// Write the candidates of the `mask` to `stdout`, starting from the rank `start_rank`.
+pub(crate) fn generate_mask(mask: &str, start_rank: usize, threads: Option<u16>) -> ExitCode {
+    handle_sigint();
+    let mask = match Mask::parse(mask) {
+        Ok(mask) => mask,
+        Err(e) => {
+            error!(message = %e, "invalid mask");
+            return ExitCode::InvalidInput;
+        }
+    };
+    info!(
+        length = mask.length(),
+        candidates = mask.checked_candidates_number(),
+        "mask parsed"
+    );
+
+    let chunk_size = mask.suggested_chunk_size(OPTIMAL_CHUNKS_NUMBER);
+    let pipeline =
+        Pipeline::new().with_workers(threads.map_or(Pipeline::new().workers(), usize::from));
+    let mut candidates_emitted = 0;
+    let chunks = mask
+        .into_chunks(chunk_size)
+        .starting_at(start_rank)
+        .take_while(|_| is_running())
+        .enumerate()
+        .map(|(index, chunk)| {
+            log_chunk(&mut candidates_emitted, index, chunk.len());
+            chunk
+        });
+    let mut sink = WriterSink::new(io::stdout());
+    match pipeline.run(chunks, EncodeRaw::new().with_terminator(b'\n'), &mut sink) {
+        Ok(_) if INTERRUPTED.load(Ordering::SeqCst) => {
+            warn!(
+                emitted = candidates_emitted,
+                resume_rank = start_rank + candidates_emitted,
+                "interrupted"
+            );
+            ExitCode::Interrupted
+        }
+        Ok(_) => {
+            info!(emitted = candidates_emitted, "done");
+            ExitCode::Success
+        }
+        Err(e) => write_error_exit_code(&e),
+    }
+}
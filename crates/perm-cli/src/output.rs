@@ -0,0 +1,57 @@
+//! # Output
+//!
+//! Write the permutations of the subcommands answering with a few records,
+//! and turn the errors writing the output into the exit code.
+use std::io::{self, Write};
+
+use tracing::{error, info};
+
+use perm::{Chunk, ChunkView, OutputFormat, Quoting};
+
+use crate::ExitCode;
+
+/// Log an error writing the output, and get the exit code.
+/// The output closed by the consumer (e.g. `perm | head`) is not a failure:
+/// the process stops writing and exits successfully.
+pub(crate) fn write_error_exit_code(error: &io::Error) -> ExitCode {
+    if error.kind() == io::ErrorKind::BrokenPipe {
+        info!("the output has been closed");
+        ExitCode::Success
+    } else {
+        error!(error = %error, "error writing data");
+        ExitCode::IoError
+    }
+}
+
+pub(crate) fn output_format(quote: bool) -> OutputFormat {
+    if quote {
+        OutputFormat::new().with_quoting(Quoting::Necessary)
+    } else {
+        OutputFormat::new()
+    }
+}
+
+/// The format of the permutations written by the REPL and by `perm sample`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum RecordFormat {
+    Text,
+    Json,
+}
+
+/// Write the permutations of the `chunk` as text, or one JSON array of strings for each permutation.
+pub(crate) fn write_records(
+    output: &mut impl Write,
+    chunk: &Chunk<&str>,
+    format: OutputFormat,
+    record_format: RecordFormat,
+) -> io::Result<()> {
+    match record_format {
+        RecordFormat::Text => output.write_all(&chunk.to_bytes(format)),
+        RecordFormat::Json => (0..chunk.permutations_count()).try_for_each(|index| {
+            let permutation: Vec<&str> = (0..chunk.permutation_length())
+                .map(|position| *chunk.value(index, position))
+                .collect();
+            writeln!(output, "{}", serde_json::to_string(&permutation)?)
+        }),
+    }
+}
@@ -0,0 +1,77 @@
+//! # Rank
+//!
+//! `perm rank --perm <PERMUTATION>` and `perm unrank --rank <RANK>`: convert between a permutation
+//! of the input (or of `--values`) and its rank, in lexicographic order of the first occurrences of the values.
+use std::io::{self, Write};
+
+use tracing::error;
+
+use perm::{DuplicatePolicy, OutputFormat, Permutations};
+
+use crate::input::{parse_input, read_input};
+use crate::output::{write_error_exit_code, write_records, RecordFormat};
+use crate::{ExitCode, InputOptions};
+
+/// Write the rank of the `permutation` of the `values`, or of the input read from `stdin`.
+pub(crate) fn rank(values: Option<&str>, permutation: &str, options: &InputOptions) -> ExitCode {
+    let text = match values.map_or_else(read_input, |values| Ok(values.to_string())) {
+        Ok(text) => text,
+        Err(exit_code) => return exit_code,
+    };
+    let permutations = match parse_input(&text, options) {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    // the permutation keeps its equal values, to be compared with the input.
+    let permutation_options = options
+        .parse_options()
+        .with_duplicates(DuplicatePolicy::Keep);
+    let permutation = match Permutations::parse(permutation, &permutation_options) {
+        Ok(permutation) => permutation,
+        Err(message) => {
+            error!(%message, "invalid permutation");
+            return ExitCode::InvalidInput;
+        }
+    };
+    match permutations.rank_of(permutation.values()) {
+        Some(rank) => match writeln!(io::stdout(), "{}", rank) {
+            Ok(()) => ExitCode::Success,
+            Err(e) => write_error_exit_code(&e),
+        },
+        None => {
+            error!("the permutation is not a permutation of the values");
+            ExitCode::InvalidInput
+        }
+    }
+}
+
+/// Write the permutation of rank `rank` of the `values`, or of the input read from `stdin`.
+pub(crate) fn unrank(
+    values: Option<&str>,
+    rank: usize,
+    format: OutputFormat,
+    options: &InputOptions,
+) -> ExitCode {
+    let text = match values.map_or_else(read_input, |values| Ok(values.to_string())) {
+        Ok(text) => text,
+        Err(exit_code) => return exit_code,
+    };
+    let permutations = match parse_input(&text, options) {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    let chunk = match permutations.into_ranked_chunks(1).starting_at(rank).next() {
+        Some(chunk) => chunk,
+        None => {
+            error!(
+                rank,
+                "the rank is not lower than the number of permutations"
+            );
+            return ExitCode::InvalidInput;
+        }
+    };
+    match write_records(&mut io::stdout(), &chunk, format, RecordFormat::Text) {
+        Ok(()) => ExitCode::Success,
+        Err(e) => write_error_exit_code(&e),
+    }
+}
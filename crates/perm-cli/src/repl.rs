@@ -0,0 +1,121 @@
+//! # REPL
+//!
+//! `perm repl`: read the inputs line by line and write their permutations as soon as each line is entered,
+//! with the commands `:count`, `:sample <n>`, `:format text|json`, `:help` and `:quit`.
+//! There `SIGINT` only stops writing the permutations of the current line.
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tracing::{error, warn};
+
+use perm::{OutputFormat, ParseOptions, Permutations, MIN_SUGGESTED_CHUNK_SIZE};
+
+use crate::interrupt::{is_running, INTERRUPTED};
+use crate::output::{write_error_exit_code, write_records, RecordFormat};
+use crate::sample::write_samples;
+use crate::ExitCode;
+
+/// The maximum time between two writes of the permutations in the REPL.
+const REPL_TIME_SLICE: Duration = Duration::from_millis(50);
+
+const REPL_HELP: &str = "\
+<values>        write the permutations of the comma separated values
+:count          write the number of permutations of the last values
+:sample <n>     write <n> permutations of the last values, evenly spaced by rank
+:format <name>  write the permutations as `text` or as `json` arrays
+:help           write this help
+:quit           exit";
+
+/// Run the REPL on `stdin` until its end or `:quit`.
+/// `SIGINT` stops writing the permutations of the current line, without exiting.
+pub(crate) fn repl(parse_options: &ParseOptions, format: OutputFormat) -> ExitCode {
+    let stdout = io::stdout();
+    let mut output = io::BufWriter::new(stdout.lock());
+    let mut record_format = RecordFormat::Text;
+    // the last valid values entered.
+    let mut last_input: Option<String> = None;
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                error!(error = %e, "error reading input");
+                return ExitCode::IoError;
+            }
+        };
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        let mut words = line.split_whitespace();
+        let last_permutations = || {
+            let permutations = last_input
+                .as_deref()
+                .map(|text| Permutations::parse(text, parse_options).expect("The input is valid"));
+            if permutations.is_none() {
+                warn!("no values entered yet");
+            }
+            permutations
+        };
+        let written = match words.next() {
+            None => Ok(()),
+            Some(":quit") => break,
+            Some(":help") => writeln!(output, "{}", REPL_HELP),
+            Some(":format") => {
+                match words.next() {
+                    Some("text") => record_format = RecordFormat::Text,
+                    Some("json") => record_format = RecordFormat::Json,
+                    _ => warn!("the format must be `text` or `json`"),
+                }
+                Ok(())
+            }
+            Some(":count") => match last_permutations() {
+                Some(permutations) => match permutations.checked_permutations_number() {
+                    Some(count) => writeln!(output, "{}", count),
+                    None => writeln!(output, "more than {}", usize::MAX),
+                },
+                None => Ok(()),
+            },
+            Some(":sample") => match words.next().map(str::parse::<usize>) {
+                Some(Ok(samples)) => match last_permutations() {
+                    Some(permutations) => write_samples(
+                        &mut output,
+                        permutations,
+                        samples,
+                        None,
+                        format,
+                        record_format,
+                    ),
+                    None => Ok(()),
+                },
+                _ => {
+                    warn!("the number of samples must be a non negative integer");
+                    Ok(())
+                }
+            },
+            Some(command) if command.starts_with(':') => {
+                warn!(command, "unknown command, see `:help`");
+                Ok(())
+            }
+            Some(_) => match Permutations::parse(&line, parse_options) {
+                Ok(permutations) => {
+                    let written = permutations
+                        .into_chunks(MIN_SUGGESTED_CHUNK_SIZE)
+                        .with_time_slice(REPL_TIME_SLICE)
+                        .take_while(|_| is_running())
+                        .try_for_each(|chunk| {
+                            write_records(&mut output, &chunk, format, record_format)
+                                .and_then(|_| output.flush())
+                        });
+                    last_input = Some(line.clone());
+                    written
+                }
+                Err(message) => {
+                    error!(%message, "invalid input");
+                    Ok(())
+                }
+            },
+        };
+        if let Err(e) = written.and_then(|_| output.flush()) {
+            return write_error_exit_code(&e);
+        }
+    }
+    ExitCode::Success
+}
@@ -0,0 +1,155 @@
+//! # Resume
+//!
+//! `perm resume --output <OUTPUT>`: continue a generation saved to a file and cut short.
+//! The permutation cut in the middle is removed from the end of the file,
+//! and the permutations following the last complete one are appended.
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use tracing::{error, info, warn};
+
+use perm::pipeline::Pipeline;
+use perm::{DuplicatePolicy, LineEnding, OutputFormat, Permutations};
+
+use crate::generate::log_chunk;
+use crate::input::{parse_input, read_input};
+use crate::interrupt::{handle_sigint, is_running, INTERRUPTED};
+use crate::output::write_error_exit_code;
+use crate::{ExitCode, InputOptions, OPTIMAL_CHUNKS_NUMBER};
+
+/// Append to the `output` the permutations of the `values`, or of the input read from `stdin`,
+/// following its last complete permutation.
+pub(crate) fn resume(
+    output: &Path,
+    values: Option<&str>,
+    threads: Option<u16>,
+    options: &InputOptions,
+) -> ExitCode {
+    handle_sigint();
+    let text = match values.map_or_else(read_input, |values| Ok(values.to_string())) {
+        Ok(text) => text,
+        Err(exit_code) => return exit_code,
+    };
+    let permutations = match parse_input(&text, options) {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    let mut output = match fs::OpenOptions::new().read(true).write(true).open(output) {
+        Ok(output) => output,
+        Err(e) => {
+            error!(error = %e, "error reading output");
+            return ExitCode::IoError;
+        }
+    };
+    let (complete_bytes, last_line) = match last_complete_line(&mut output) {
+        Ok(last) => last,
+        Err(e) => {
+            error!(error = %e, "error reading output");
+            return ExitCode::IoError;
+        }
+    };
+    // the last permutation keeps its equal values, to be compared with the input.
+    let permutation_options = options
+        .parse_options()
+        .with_duplicates(DuplicatePolicy::Keep);
+    // the output written with `--line-ending crlf` is resumed with the same line break.
+    let line_ending = match last_line.as_deref() {
+        Some(line) if line.ends_with('\r') => LineEnding::CrLf,
+        _ => LineEnding::Lf,
+    };
+    let last_rank = match last_line.as_deref() {
+        None => None,
+        Some(line) => match Permutations::parse(line.trim_end_matches('\r'), &permutation_options)
+            .ok()
+            .and_then(|permutation| permutations.rank_of(permutation.values()))
+        {
+            Some(rank) => Some(rank),
+            None => {
+                error!("the last line of the output is not a permutation of the input");
+                return ExitCode::InvalidInput;
+            }
+        },
+    };
+    let next_rank = last_rank.map_or(0, |rank| rank + 1);
+    // the permutation cut short is removed, and the next ones are appended after the last complete one.
+    if let Err(e) = output
+        .set_len(complete_bytes)
+        .and_then(|_| output.seek(SeekFrom::End(0)))
+    {
+        error!(error = %e, "error truncating output");
+        return ExitCode::IoError;
+    }
+    info!(
+        output_bytes = complete_bytes,
+        resume_rank = next_rank,
+        "resuming"
+    );
+
+    let chunk_size = permutations.suggested_chunk_size(OPTIMAL_CHUNKS_NUMBER);
+    let pipeline =
+        Pipeline::new().with_workers(threads.map_or(Pipeline::new().workers(), usize::from));
+    let mut permutations_emitted = 0;
+    let chunks = permutations
+        .into_ranked_chunks(chunk_size)
+        .starting_at(next_rank)
+        .take_while(|_| is_running())
+        .enumerate()
+        .map(|(index, chunk)| {
+            log_chunk(&mut permutations_emitted, index, chunk.len());
+            chunk
+        });
+    let format = OutputFormat::new().with_line_ending(line_ending);
+    match pipeline.write(chunks, format, output) {
+        Ok(_) if INTERRUPTED.load(Ordering::SeqCst) => {
+            warn!(
+                emitted = permutations_emitted,
+                resume_rank = next_rank + permutations_emitted,
+                "interrupted"
+            );
+            ExitCode::Interrupted
+        }
+        Ok(_) => {
+            info!(emitted = permutations_emitted, "done");
+            ExitCode::Success
+        }
+        Err(e) => write_error_exit_code(&e),
+    }
+}
+
+/// The number of bytes read at a time from the end of an output, looking for its last complete line.
+const RESUME_TAIL_BYTES: u64 = 64 * 1024;
+
+/// Find the last complete line of the `output`, without its line break,
+/// and the number of bytes up to its end.
+/// Only the tail of the `output` is read, growing it until the line fits.
+fn last_complete_line(output: &mut File) -> io::Result<(u64, Option<String>)> {
+    let length = output.metadata()?.len();
+    let mut tail_bytes = RESUME_TAIL_BYTES;
+    loop {
+        let start = length.saturating_sub(tail_bytes);
+        output.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        output.read_to_end(&mut tail)?;
+        let line_end = match tail.iter().rposition(|byte| *byte == b'\n') {
+            Some(line_end) => line_end,
+            None if start == 0 => return Ok((0, None)),
+            None => {
+                tail_bytes = tail_bytes.saturating_mul(2);
+                continue;
+            }
+        };
+        let line_start = match tail[..line_end].iter().rposition(|byte| *byte == b'\n') {
+            Some(previous_end) => previous_end + 1,
+            None if start == 0 => 0,
+            None => {
+                tail_bytes = tail_bytes.saturating_mul(2);
+                continue;
+            }
+        };
+        let line = String::from_utf8(tail[line_start..line_end].to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return Ok((start + line_end as u64 + 1, Some(line)));
+    }
+}
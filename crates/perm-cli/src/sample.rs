@@ -0,0 +1,86 @@
+//! # Sample
+//!
+//! `perm sample <N>`: write `N` permutations of the input read from `stdin`,
+//! evenly spaced by rank or drawn uniformly at random with `--random`.
+use std::io::{self, Write};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tracing::info;
+
+use perm::{OutputFormat, Permutations};
+
+use crate::input::{parse_input, read_input};
+use crate::output::{write_error_exit_code, write_records, RecordFormat};
+use crate::{ExitCode, InputOptions};
+
+/// Write `samples` permutations of the input read from `stdin`, evenly spaced by rank.
+pub(crate) fn sample(
+    samples: usize,
+    seed: Option<u64>,
+    format: OutputFormat,
+    options: &InputOptions,
+) -> ExitCode {
+    let text = match read_input() {
+        Ok(text) => text,
+        Err(exit_code) => return exit_code,
+    };
+    let permutations = match parse_input(&text, options) {
+        Ok(permutations) => permutations,
+        Err(exit_code) => return exit_code,
+    };
+    let stdout = io::stdout();
+    let mut output = io::BufWriter::new(stdout.lock());
+    match write_samples(
+        &mut output,
+        permutations,
+        samples,
+        seed,
+        format,
+        RecordFormat::Text,
+    )
+    .and_then(|_| output.flush())
+    {
+        Ok(()) => ExitCode::Success,
+        Err(e) => write_error_exit_code(&e),
+    }
+}
+
+/// Write `samples` permutations evenly spaced by rank, starting from the first one.
+pub(crate) fn write_samples(
+    output: &mut impl Write,
+    permutations: Permutations<&str>,
+    samples: usize,
+    seed: Option<u64>,
+    format: OutputFormat,
+    record_format: RecordFormat,
+) -> io::Result<()> {
+    // the tables are built once for all the samples.
+    let compiled = permutations.compile();
+    let count = compiled.permutations_number().unwrap_or(usize::MAX);
+    let ranks: Vec<usize> = match seed {
+        Some(seed) => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..samples).map(|_| rng.gen_range(0..count)).collect()
+        }
+        None => {
+            let samples = samples.min(count);
+            (0..samples)
+                .map(|sample| (sample as u128 * count as u128 / samples as u128) as usize)
+                .collect()
+        }
+    };
+    for rank in ranks {
+        if let Some(chunk) = compiled.range(rank..rank + 1, 1).next() {
+            write_records(output, &chunk, format, record_format)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the `--seed`, or a random one: it is logged, so that the run can be reproduced.
+pub(crate) fn random_seed(seed: Option<u64>) -> u64 {
+    let seed = seed.unwrap_or_else(rand::random);
+    info!(seed, "random seed");
+    seed
+}
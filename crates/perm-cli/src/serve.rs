@@ -0,0 +1,389 @@
+//! # Serve
+//!
+//! `perm serve` answers `GET /permutations?values=<VALUES>&cursor=<RANK>&limit=<N>` with a page of permutations,
+//! as a JSON object: `{"permutations": [["1", "2", "3"], ...], "next_cursor": 6}`.
+//!
+//! The server keeps no session: the cursor is the rank of the first permutation of the page (`0` by default),
+//! and the page is generated starting from it with `into_ranked_chunks`.
+//! A client pages through the permutations passing back `next_cursor`, until it is `null`.
+//!
+//! The values are parsed with the input options of `perm serve`.
+//! An invalid request is answered with the status code `400` and a JSON object with the `error`.
+//!
+//! `GET /metrics` answers with the metrics of the server in the Prometheus text format:
+//! the requests by status code, the permutations written in the pages, the pages being generated
+//! and the histogram of the time to generate a page.
+//! The requests are answered concurrently, by one worker thread for each available core,
+//! so the metrics can be scraped while pages are being generated.
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info, warn};
+
+use perm::{ChunkView, ParseOptions, Permutations};
+
+use crate::ExitCode;
+
+/// The number of permutations of a page, without `limit`.
+const DEFAULT_PAGE_LIMIT: usize = 1000;
+/// The maximum number of permutations of a page.
+const MAX_PAGE_LIMIT: usize = 10_000;
+/// The upper bounds in seconds of the buckets of the histogram of the generation time.
+const LATENCY_BUCKETS: [f64; 8] = [0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// A page of permutations, with the cursor of the next one.
+#[derive(Serialize)]
+struct Page<'a> {
+    permutations: Vec<Vec<&'a str>>,
+    next_cursor: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+/// The metrics of the server, exposed at `/metrics`.
+#[derive(Default)]
+struct ServerMetrics {
+    /// the number of requests answered with each status code, sorted by status code.
+    requests: Vec<(u16, u64)>,
+    permutations_streamed: u64,
+    active_streams: u64,
+    /// the number of pages generated within each bucket of `LATENCY_BUCKETS`, not cumulative.
+    latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_count: u64,
+    latency_sum: Duration,
+}
+
+impl ServerMetrics {
+    fn record_request(&mut self, status: u16) {
+        match self
+            .requests
+            .binary_search_by_key(&status, |(status, _)| *status)
+        {
+            Ok(index) => self.requests[index].1 += 1,
+            Err(index) => self.requests.insert(index, (status, 1)),
+        }
+    }
+
+    fn start_page(&mut self) {
+        self.active_streams += 1;
+    }
+
+    /// Record a page generated after `start_page`.
+    fn record_page(&mut self, permutations: usize, latency: Duration) {
+        self.active_streams -= 1;
+        self.permutations_streamed += permutations as u64;
+        let seconds = latency.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.latency_buckets[bucket] += 1;
+        }
+        self.latency_count += 1;
+        self.latency_sum += latency;
+    }
+
+    /// Write the metrics in the Prometheus text format.
+    fn render(&self) -> String {
+        let mut text = String::new();
+        self.render_to(&mut text)
+            .expect("Writing to a String does not fail");
+        text
+    }
+
+    fn render_to(&self, text: &mut String) -> std::fmt::Result {
+        writeln!(
+            text,
+            "# HELP perm_requests_total The requests answered, by status code."
+        )?;
+        writeln!(text, "# TYPE perm_requests_total counter")?;
+        for (status, count) in &self.requests {
+            writeln!(
+                text,
+                "perm_requests_total{{status=\"{}\"}} {}",
+                status, count
+            )?;
+        }
+        writeln!(
+            text,
+            "# HELP perm_permutations_streamed_total The permutations written in the pages."
+        )?;
+        writeln!(text, "# TYPE perm_permutations_streamed_total counter")?;
+        writeln!(
+            text,
+            "perm_permutations_streamed_total {}",
+            self.permutations_streamed
+        )?;
+        writeln!(
+            text,
+            "# HELP perm_active_streams The pages being generated."
+        )?;
+        writeln!(text, "# TYPE perm_active_streams gauge")?;
+        writeln!(text, "perm_active_streams {}", self.active_streams)?;
+        writeln!(
+            text,
+            "# HELP perm_generation_seconds The time to generate a page."
+        )?;
+        writeln!(text, "# TYPE perm_generation_seconds histogram")?;
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            cumulative += count;
+            writeln!(
+                text,
+                "perm_generation_seconds_bucket{{le=\"{}\"}} {}",
+                bound, cumulative
+            )?;
+        }
+        writeln!(
+            text,
+            "perm_generation_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.latency_count
+        )?;
+        writeln!(
+            text,
+            "perm_generation_seconds_sum {}",
+            self.latency_sum.as_secs_f64()
+        )?;
+        writeln!(text, "perm_generation_seconds_count {}", self.latency_count)
+    }
+}
+
+/// Answer the requests received at `address`, until the process is terminated.
+pub(crate) fn serve(address: &str, parse_options: &ParseOptions) -> ExitCode {
+    let server = match Server::http(address) {
+        Ok(server) => server,
+        Err(e) => {
+            error!(error = %e, address, "error listening");
+            return ExitCode::IoError;
+        }
+    };
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    info!(address, workers, "listening");
+    let metrics = Mutex::new(ServerMetrics::default());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    answer(request, parse_options, &metrics);
+                }
+            });
+        }
+    });
+    ExitCode::Success
+}
+
+/// Answer the `request`, recording it in the `metrics`.
+fn answer(request: Request, parse_options: &ParseOptions, metrics: &Mutex<ServerMetrics>) {
+    let (status, body, content_type) = respond_to(&request, parse_options, metrics);
+    lock(metrics).record_request(status);
+    info!(method = %request.method(), url = request.url(), status, "request");
+    let content_type =
+        Header::from_bytes("Content-Type", content_type).expect("The header is valid");
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        warn!(error = %e, "error writing response");
+    }
+}
+
+fn lock(metrics: &Mutex<ServerMetrics>) -> MutexGuard<'_, ServerMetrics> {
+    metrics.lock().expect("The metrics are not poisoned")
+}
+
+/// Compute the status code, the body and its content type of the response to the `request`.
+fn respond_to(
+    request: &Request,
+    parse_options: &ParseOptions,
+    metrics: &Mutex<ServerMetrics>,
+) -> (u16, String, &'static str) {
+    if *request.method() != Method::Get {
+        return (
+            405,
+            error_body("only GET requests are supported"),
+            JSON_CONTENT_TYPE,
+        );
+    }
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    match path {
+        "/permutations" => match page(query, parse_options, metrics) {
+            Ok(body) => (200, body, JSON_CONTENT_TYPE),
+            Err(message) => (400, error_body(&message), JSON_CONTENT_TYPE),
+        },
+        "/metrics" => (200, lock(metrics).render(), PROMETHEUS_CONTENT_TYPE),
+        _ => (404, error_body("not found"), JSON_CONTENT_TYPE),
+    }
+}
+
+fn error_body(error: &str) -> String {
+    serde_json::to_string(&ErrorBody { error }).expect("The error is serializable")
+}
+
+/// Compute the JSON page of the permutations requested by the `query`, recording it in the `metrics`.
+/// The `metrics` are not locked during the generation, so that the other requests are answered meanwhile.
+fn page(
+    query: &str,
+    parse_options: &ParseOptions,
+    metrics: &Mutex<ServerMetrics>,
+) -> Result<String, String> {
+    let mut values = None;
+    let mut cursor = 0;
+    let mut limit = DEFAULT_PAGE_LIMIT;
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = percent_decode(value)
+            .ok_or_else(|| format!("the parameter `{}` is not URL-encoded", name))?;
+        match name {
+            "values" => values = Some(value),
+            "cursor" => {
+                cursor = value
+                    .parse()
+                    .map_err(|_| "the cursor must be a rank".to_string())?
+            }
+            "limit" => {
+                limit = value
+                    .parse()
+                    .ok()
+                    .filter(|limit| (1..=MAX_PAGE_LIMIT).contains(limit))
+                    .ok_or_else(|| format!("the limit must be between 1 and {}", MAX_PAGE_LIMIT))?
+            }
+            _ => return Err(format!("unknown parameter `{}`", name)),
+        }
+    }
+    let values = values.ok_or_else(|| "the values are required".to_string())?;
+    let permutations =
+        Permutations::parse(&values, parse_options).map_err(|message| message.to_string())?;
+    lock(metrics).start_page();
+    let start = Instant::now();
+    let chunk = permutations
+        .into_ranked_chunks(limit)
+        .starting_at(cursor)
+        .next();
+    let latency = start.elapsed();
+    lock(metrics).record_page(chunk.as_ref().map_or(0, |chunk| chunk.len()), latency);
+    let page = match chunk {
+        Some(chunk) => Page {
+            permutations: (0..chunk.permutations_count())
+                .map(|index| {
+                    (0..chunk.permutation_length())
+                        .map(|position| *chunk.value(index, position))
+                        .collect()
+                })
+                .collect(),
+            // there is no next cursor past `usize::MAX`.
+            next_cursor: if chunk.is_last() {
+                None
+            } else {
+                cursor.checked_add(chunk.len())
+            },
+        },
+        // the cursor is past the last permutation.
+        None => Page {
+            permutations: vec![],
+            next_cursor: None,
+        },
+    };
+    Ok(serde_json::to_string(&page).expect("The page is serializable"))
+}
+
+/// Decode a URL-encoded query value: `+` is a space and `%XX` is the byte `XX`.
+/// Return `None` if an escape is malformed or the bytes are not UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+    while let Some(byte) = input.next() {
+        bytes.push(match byte {
+            b'+' => b' ',
+            b'%' => {
+                let digits = [input.next()?, input.next()?];
+                u8::from_str_radix(std::str::from_utf8(&digits).ok()?, 16).ok()?
+            }
+            byte => byte,
+        });
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_follow_the_cursor() {
+        let options = ParseOptions::new();
+        assert_eq!(
+            page("values=1%2C2%2C3&limit=4", &options, &Mutex::default()).unwrap(),
+            r#"{"permutations":[["1","2","3"],["1","3","2"],["2","1","3"],["2","3","1"]],"next_cursor":4}"#
+        );
+        assert_eq!(
+            page("values=1,2,3&cursor=4&limit=4", &options, &Mutex::default()).unwrap(),
+            r#"{"permutations":[["3","1","2"],["3","2","1"]],"next_cursor":null}"#
+        );
+        assert_eq!(
+            page("values=1,2,3&cursor=6", &options, &Mutex::default()).unwrap(),
+            r#"{"permutations":[],"next_cursor":null}"#
+        );
+    }
+
+    #[test]
+    fn pages_end_at_the_last_usize_cursor() {
+        // 25! does not fit in a usize: the page at `usize::MAX` is the last one.
+        let values = (1..=25).map(|value| value.to_string()).collect::<Vec<_>>();
+        let query = format!("values={}&cursor={}&limit=3", values.join(","), usize::MAX);
+        let page = page(&query, &ParseOptions::new(), &Mutex::default()).unwrap();
+        assert!(
+            page.starts_with(r#"{"permutations":[["1","2","3","4","#),
+            "{}",
+            page
+        );
+        assert_eq!(page.matches('[').count(), 2, "{}", page);
+        assert!(page.ends_with(r#""next_cursor":null}"#), "{}", page);
+    }
+
+    #[test]
+    fn metrics_are_rendered() {
+        let options = ParseOptions::new();
+        let metrics = Mutex::default();
+        page("values=1,2,3&limit=4", &options, &metrics).unwrap();
+        page("values=1,2,3&cursor=4", &options, &metrics).unwrap();
+        let mut metrics = metrics.into_inner().unwrap();
+        metrics.record_request(200);
+        metrics.record_request(400);
+        metrics.record_request(200);
+
+        let text = metrics.render();
+        assert!(text.contains("perm_requests_total{status=\"200\"} 2\n"));
+        assert!(text.contains("perm_requests_total{status=\"400\"} 1\n"));
+        assert!(text.contains("perm_permutations_streamed_total 6\n"));
+        assert!(text.contains("perm_active_streams 0\n"));
+        assert!(text.contains("perm_generation_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("perm_generation_seconds_count 2\n"));
+    }
+
+    #[test]
+    fn active_streams_are_the_pages_being_generated() {
+        let mut metrics = ServerMetrics::default();
+        metrics.start_page();
+        metrics.start_page();
+        assert!(metrics.render().contains("perm_active_streams 2\n"));
+        metrics.record_page(3, Duration::from_millis(1));
+        assert!(metrics.render().contains("perm_active_streams 1\n"));
+    }
+
+    #[test]
+    fn invalid_queries_are_rejected() {
+        let options = ParseOptions::new();
+        assert!(page("cursor=1", &options, &Mutex::default()).is_err());
+        assert!(page("values=1,2&limit=0", &options, &Mutex::default()).is_err());
+        assert!(page("values=1,a", &options, &Mutex::default()).is_err());
+        assert!(page("values=1,2&order=desc", &options, &Mutex::default()).is_err());
+        assert!(page("values=1%2", &options, &Mutex::default()).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! # Verify
+//!
+//! `perm verify [OUTPUT] --manifest <MANIFEST>`: check an output, or the files of a sharded output,
+//! against the manifest written with `--manifest`.
+use std::fs::{self, File};
+use std::path::Path;
+
+use tracing::{error, info};
+
+use perm::pipeline::Manifest;
+
+use crate::ExitCode;
+
+/// Check the `output`, or the files listed in the `manifest`, against the `manifest`.
+pub(crate) fn verify(output: Option<&Path>, manifest: &Path) -> ExitCode {
+    let manifest = match File::open(manifest).and_then(Manifest::from_reader) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            error!(error = %e, "error reading manifest");
+            return ExitCode::IoError;
+        }
+    };
+    let verified = match output {
+        Some(output) => fs::read(output).map(|output| manifest.verify(&output)),
+        None if !manifest.files.is_empty() => manifest.verify_files(),
+        None => {
+            error!("the output is required, unless the manifest lists its files");
+            return ExitCode::InvalidInput;
+        }
+    };
+    match verified {
+        Ok(true) => {
+            info!(permutations = manifest.permutations(), "output verified");
+            ExitCode::Success
+        }
+        Ok(false) => {
+            error!("the output does not match the manifest");
+            ExitCode::InvalidInput
+        }
+        Err(e) => {
+            error!(error = %e, "error reading output");
+            ExitCode::IoError
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! # CLI
+//!
+//! Run the compiled binary against the inputs in `tests/fixtures`.
+//!
+//! - `<name>.input` is written to `stdin`.
+//! - `<name>.golden` has the expected permutations, sorted:
+//!   the output is sorted before the comparison, so that it does not depend on the generation order.
+//! - `<name>.stderr` has the expected error message, written to `stderr` with a failure.
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// The exit code of an invalid input.
+const INVALID_INPUT: i32 = 65;
+
+fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {:?}: {}", path, e))
+}
+
+/// Run the binary with `args`, writing the fixture `<name>.input` to `stdin`.
+fn run(name: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_perm"))
+        .args(args)
+        .args(["--log-format", "json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Error running the binary");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(fixture(&format!("{}.input", name)).as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Check that the sorted output of the binary is the golden file `<name>.golden`.
+fn assert_golden(name: &str, args: &[&str]) -> Output {
+    let output = run(name, args);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    let mut lines = stdout.lines().collect::<Vec<&str>>();
+    lines.sort_unstable();
+    let golden = fixture(&format!("{}.golden", name));
+    assert_eq!(lines, golden.lines().collect::<Vec<&str>>());
+    output
+}
+
+/// Check that the binary fails with the message of the golden file `<name>.stderr`.
+fn assert_error(name: &str, args: &[&str]) {
+    let output = run(name, args);
+    assert_eq!(output.status.code(), Some(INVALID_INPUT));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let message = fixture(&format!("{}.stderr", name));
+    assert!(
+        stderr.contains(message.trim_end()),
+        "`{}` not found in: {}",
+        message.trim_end(),
+        stderr
+    );
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn distinct_values_are_permuted() {
+    assert_golden("distinct", &[]);
+}
+
+#[test]
+fn duplicated_permutations_are_written_once() {
+    assert_golden("duplicates", &[]);
+}
+
+#[test]
+fn empty_input_has_the_empty_permutation() {
+    let output = assert_golden("empty", &[]);
+    assert_eq!(output.stdout, b"\n");
+}
+
+#[test]
+fn values_are_quoted() {
+    assert_golden("quoted", &["--validate", "any", "--quote"]);
+}
+
+#[test]
+fn algorithm_is_selected_by_the_input() {
+    let auto = assert_golden("distinct", &[]);
+    assert!(stderr(&auto).contains("using ranked iterator"));
+    let optimized = assert_golden("optimized_128", &["--algorithm", "optimized"]);
+    assert!(stderr(&optimized).contains("using optimized iterator"));
+    let normal = assert_golden("normal_129", &["--algorithm", "normal"]);
+    assert!(stderr(&normal).contains("using normal iterator"));
+    let too_long = run("normal_129", &["--algorithm", "optimized"]);
+    assert_eq!(too_long.status.code(), Some(INVALID_INPUT));
+}
+
+#[test]
+fn metrics_are_reported() {
+    let output = assert_golden("distinct", &["--metrics-interval", "1h"]);
+    let final_metrics = stderr(&output)
+        .lines()
+        .find(|line| line.contains("final metrics"))
+        .map(str::to_string)
+        .unwrap();
+    assert!(final_metrics.contains(r#""permutations":6"#));
+    assert!(final_metrics.contains(r#""bytes":36"#));
+}
+
+#[test]
+fn buffered_output_is_complete() {
+    assert_golden("distinct", &["--buffer-size", "4", "--flush", "line"]);
+    assert_golden("distinct", &["--buffer-size", "65536", "--flush", "chunk"]);
+    assert_golden("bytes", &["--format", "raw", "--buffer-size", "16"]);
+}
+
+#[test]
+fn lines_end_with_crlf() {
+    let output = assert_golden("distinct", &["--line-ending", "crlf"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("\r\n").count(), stdout.matches('\n').count());
+}
+
+#[test]
+fn characters_are_joined_as_anagrams() {
+    assert_golden("anagrams", &["--join"]);
+}
+
+#[test]
+fn values_are_repeated_by_their_count() {
+    assert_golden("counts", &["--counts"]);
+}
+
+#[test]
+fn random_samples_are_reproducible() {
+    let sample = |seed: &str| run("distinct", &["sample", "4", "--random", "--seed", seed]);
+    let first = sample("5");
+    assert!(first.status.success(), "{:?}", first);
+    assert_eq!(
+        String::from_utf8(first.stdout.clone())
+            .unwrap()
+            .lines()
+            .count(),
+        4
+    );
+    assert_eq!(first.stdout, sample("5").stdout);
+}
+
+#[test]
+fn closed_output_is_not_an_error() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_perm"))
+        .args(["--log-format", "json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Error running the binary");
+    // 10! permutations, much more than the pipe holds.
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"1,2,3,4,5,6,7,8,9,10\n")
+        .unwrap();
+    let mut first_line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first_line)
+        .unwrap();
+    assert_eq!(first_line, "1,2,3,4,5,6,7,8,9,10\n");
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stderr(&output).contains("the output has been closed"));
+}
+
+#[test]
+fn redirected_logs_are_not_colored() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_perm"))
+        .args(["--max-length", "0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Error running the binary");
+    child.stdin.take().unwrap().write_all(b"1,2,3\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(INVALID_INPUT));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("ERROR"), "{}", stderr);
+    assert!(!stderr.contains('\x1b'), "{:?}", stderr);
+}
+
+#[test]
+fn invalid_input_is_reported() {
+    assert_error("invalid", &[]);
+    assert_error("too_long", &["--max-length", "2"]);
+    assert_error("too_diverse", &["--max-distinct", "2"]);
+}
+
+#[test]
+fn large_output_is_refused() {
+    assert_error("too_many", &[]);
+    assert_error("too_big", &["--max-permutations", "5"]);
+    assert_error("too_many", &["--format", "raw"]);
+    assert_golden("distinct", &["--max-permutations", "5", "--force"]);
+}
+
+#[test]
+fn truncated_output_is_resumed() {
+    let output = run("duplicates", &[]);
+    let complete = String::from_utf8(output.stdout).unwrap();
+    // two complete permutations, and the third one cut short.
+    let cut = complete.match_indices('\n').nth(1).unwrap().0 + 4;
+    let path = env::temp_dir().join(format!("perm-resume-{}.txt", std::process::id()));
+    fs::write(&path, &complete[..cut]).unwrap();
+
+    let resumed = run(
+        "duplicates",
+        &["resume", "--output", path.to_str().unwrap()],
+    );
+    assert!(resumed.status.success(), "{:?}", resumed);
+    assert!(stderr(&resumed).contains("resuming"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), complete);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn repeated_run_is_skipped() {
+    let directory = env::temp_dir().join(format!("perm-skip-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let pattern = directory.join("out-{}.txt");
+    let manifest = directory.join("manifest.json");
+    let args = [
+        "--output-pattern",
+        pattern.to_str().unwrap(),
+        "--shards",
+        "2",
+        "--manifest",
+        manifest.to_str().unwrap(),
+        "--skip-if-exists",
+    ];
+    let first = run("duplicates", &args);
+    assert!(first.status.success(), "{:?}", first);
+    assert!(!stderr(&first).contains("skipping"));
+    let repeated = run("duplicates", &args);
+    assert!(repeated.status.success(), "{:?}", repeated);
+    assert!(stderr(&repeated).contains("skipping"));
+    // a damaged output is generated again.
+    fs::write(directory.join("out-0.txt"), "").unwrap();
+    let damaged = run("duplicates", &args);
+    assert!(!stderr(&damaged).contains("skipping"));
+    let quoted = run("duplicates", &[&args[..], &["--quote"]].concat());
+    assert!(!stderr(&quoted).contains("skipping"));
+    // the same values in another order are permuted in another order.
+    let ordered = run("distinct", &args);
+    assert!(!stderr(&ordered).contains("skipping"));
+    let reordered = run("sorted", &args);
+    assert!(reordered.status.success(), "{:?}", reordered);
+    assert!(!stderr(&reordered).contains("skipping"));
+    let output = fs::read_to_string(directory.join("out-0.txt")).unwrap();
+    assert!(output.starts_with("1,2,3\n"), "{}", output);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn bytes_are_permuted_raw() {
+    // the comma is a byte like the others, not a separator.
+    assert_golden("bytes", &["--format", "raw"]);
+    let output = run("bytes", &["--format", "raw", "--duplicates", "reject"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = run("bytes", &["--format", "raw", "--indices"]);
+    assert_eq!(output.status.code(), Some(INVALID_INPUT));
+}
+
+#[test]
+fn mask_candidates_are_generated() {
+    let output = run("empty", &["mask", "?d-?l", "--start-rank", "1"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let candidates = stdout.lines().collect::<Vec<&str>>();
+    assert_eq!(candidates.len(), 259);
+    assert_eq!(candidates[..2], ["0-b", "0-c"]);
+    assert_eq!(candidates.last(), Some(&"9-z"));
+
+    let output = run("empty", &["mask", "?d?x"]);
+    assert_eq!(output.status.code(), Some(INVALID_INPUT));
+    assert!(stderr(&output).contains("`?x` is not a charset"));
+}
+
+#[test]
+fn framed_chunks_are_read_back() {
+    let output = run("distinct", &["--format", "framed"]);
+    assert!(output.status.success(), "{:?}", output);
+    let text = perm::framing::ChunkReader::<_, String>::new(output.stdout.as_slice())
+        .map(|chunk| chunk.unwrap().to_string())
+        .collect::<String>();
+    let mut lines = text.lines().collect::<Vec<&str>>();
+    lines.sort_unstable();
+    assert_eq!(
+        lines,
+        fixture("distinct.golden").lines().collect::<Vec<&str>>()
+    );
+}
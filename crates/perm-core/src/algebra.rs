@@ -0,0 +1,238 @@
+//! # Algebra
+//!
+//! `PermutationMap` is a permutation of the positions `0..n`, as written by `--indices`:
+//! rearranging a sequence by it moves the value at position `map[i]` to position `i`.
+//!
+//! The permutation maps form a group: they can be composed and inverted,
+//! and each one has an order, the number of times it must be composed with itself
+//! to get back the identity.
+//!
+//! Besides the generated permutations, the common ones can be constructed directly:
+//! the identity, the reversal, the rotations, the disjoint cycles,
+//! and with the `rand` feature a uniformly random permutation.
+use std::fmt;
+
+/// A permutation of the positions `0..n`, in one-line notation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PermutationMap {
+    images: Vec<usize>,
+}
+
+impl PermutationMap {
+    /// Initialize a `PermutationMap` with the image of each position.
+    /// Return `None` if the `images` are not a permutation of `0..images.len()`.
+    pub fn new(images: Vec<usize>) -> Option<Self> {
+        let mut seen = vec![false; images.len()];
+        for image in &images {
+            if *seen.get(*image)? {
+                return None;
+            }
+            seen[*image] = true;
+        }
+        Some(Self { images })
+    }
+    /// Initialize the identity `PermutationMap` of `length` positions, leaving the values in place.
+    pub fn identity(length: usize) -> Self {
+        Self {
+            images: (0..length).collect(),
+        }
+    }
+    /// Initialize the `PermutationMap` of `length` positions reversing the values.
+    pub fn reversal(length: usize) -> Self {
+        Self {
+            images: (0..length).rev().collect(),
+        }
+    }
+    /// Initialize the `PermutationMap` of `length` positions rotating the values to the left by `shift` positions:
+    /// the value at position `(i + shift) % length` moves to position `i`.
+    pub fn rotation(length: usize, shift: usize) -> Self {
+        if length == 0 {
+            return Self::identity(0);
+        }
+        Self {
+            images: (0..length).map(|i| (i + shift) % length).collect(),
+        }
+    }
+    /// Initialize a uniformly random `PermutationMap` of `length` positions.
+    #[cfg(feature = "rand")]
+    pub fn random<R: rand::Rng + ?Sized>(length: usize, rng: &mut R) -> Self {
+        use rand::seq::SliceRandom;
+
+        let mut images = (0..length).collect::<Vec<usize>>();
+        images.shuffle(rng);
+        Self { images }
+    }
+    /// Initialize the `PermutationMap` of `length` positions from its disjoint `cycles`, as returned by `cycles`:
+    /// each position of a cycle maps to the next one, and the last one to the first.
+    /// The positions in no cycle are fixed points.
+    /// Return `None` if a position is not lower than `length`, or if it is in more than one cycle.
+    pub fn from_cycles(length: usize, cycles: &[&[usize]]) -> Option<Self> {
+        let mut images = (0..length).collect::<Vec<usize>>();
+        let mut seen = vec![false; length];
+        for cycle in cycles {
+            for (index, position) in cycle.iter().enumerate() {
+                if std::mem::replace(seen.get_mut(*position)?, true) {
+                    return None;
+                }
+                images[*position] = cycle[(index + 1) % cycle.len()];
+            }
+        }
+        Some(Self { images })
+    }
+    /// Get the number of positions.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+    /// Check if there are no positions.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+    /// Get the image of each position.
+    pub fn as_slice(&self) -> &[usize] {
+        &self.images
+    }
+    /// Compose `self` with `other`: the result maps `i` to `self[other[i]]`.
+    /// Rearranging a sequence by `self` and then by `other` is the same as rearranging it by the result.
+    /// Panics if the lengths are different.
+    pub fn compose(&self, other: &PermutationMap) -> PermutationMap {
+        if self.len() != other.len() {
+            panic!("Only permutations of the same length can be composed")
+        }
+        PermutationMap {
+            images: other.images.iter().map(|i| self.images[*i]).collect(),
+        }
+    }
+    /// Compute the inverse permutation: composed with `self` it gives the identity.
+    pub fn inverse(&self) -> PermutationMap {
+        let mut images = vec![0; self.len()];
+        for (position, image) in self.images.iter().enumerate() {
+            images[*image] = position;
+        }
+        PermutationMap { images }
+    }
+    /// Compute the disjoint cycles, each starting from its lowest position,
+    /// without the fixed points.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut visited = vec![false; self.len()];
+        let mut cycles = vec![];
+        for start in 0..self.len() {
+            if visited[start] || self.images[start] == start {
+                continue;
+            }
+            let mut cycle = vec![];
+            let mut position = start;
+            while !visited[position] {
+                visited[position] = true;
+                cycle.push(position);
+                position = self.images[position];
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+    /// Compute the order: the least common multiple of the lengths of the cycles.
+    /// Return `None` if it does not fit in a `usize`.
+    pub fn order(&self) -> Option<usize> {
+        self.cycles().iter().try_fold(1usize, |order, cycle| {
+            let length = cycle.len();
+            (order / gcd(order, length)).checked_mul(length)
+        })
+    }
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+impl fmt::Display for PermutationMap {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (position, image) in self.images.iter().enumerate() {
+            if position > 0 {
+                write!(fmt, ",")?;
+            }
+            write!(fmt, "{}", image)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(images: &[usize]) -> PermutationMap {
+        PermutationMap::new(images.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        assert_eq!(PermutationMap::new(vec![0, 0]), None);
+        assert_eq!(PermutationMap::new(vec![1, 2]), None);
+    }
+
+    #[test]
+    fn composition_rearranges_in_order() {
+        let values = ["a", "b", "c", "d"];
+        let rearrange = |map: &PermutationMap, values: &[&'static str]| {
+            map.as_slice()
+                .iter()
+                .map(|i| values[*i])
+                .collect::<Vec<_>>()
+        };
+        let (first, second) = (map(&[1, 2, 3, 0]), map(&[3, 1, 0, 2]));
+        assert_eq!(
+            rearrange(&second, &rearrange(&first, &values)),
+            rearrange(&first.compose(&second), &values)
+        );
+        assert_eq!(first.compose(&first.inverse()), map(&[0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn order_is_the_lcm_of_the_cycles() {
+        let permutation = map(&[1, 0, 3, 4, 2, 5]);
+        assert_eq!(permutation.cycles(), vec![vec![0, 1], vec![2, 3, 4]]);
+        assert_eq!(permutation.order(), Some(6));
+        assert_eq!(map(&[0, 1]).order(), Some(1));
+        assert_eq!(permutation.to_string(), "1,0,3,4,2,5");
+    }
+
+    #[test]
+    fn common_permutations_are_constructed() {
+        assert_eq!(PermutationMap::identity(3), map(&[0, 1, 2]));
+        assert_eq!(PermutationMap::reversal(3), map(&[2, 1, 0]));
+        assert_eq!(PermutationMap::rotation(4, 1), map(&[1, 2, 3, 0]));
+        assert_eq!(PermutationMap::rotation(4, 6), map(&[2, 3, 0, 1]));
+        assert!(PermutationMap::rotation(0, 1).is_empty());
+        assert_eq!(PermutationMap::rotation(4, 1).order(), Some(4));
+        let reversal = PermutationMap::reversal(5);
+        assert_eq!(reversal.compose(&reversal), PermutationMap::identity(5));
+    }
+
+    #[test]
+    fn cycles_are_converted_back() {
+        let permutation = map(&[1, 0, 3, 4, 2, 5]);
+        let cycles = permutation.cycles();
+        let cycles = cycles.iter().map(Vec::as_slice).collect::<Vec<&[usize]>>();
+        assert_eq!(PermutationMap::from_cycles(6, &cycles), Some(permutation));
+        assert_eq!(PermutationMap::from_cycles(3, &[&[0, 1], &[1, 2]]), None);
+        assert_eq!(PermutationMap::from_cycles(2, &[&[0, 2]]), None);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random_permutations_are_valid() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let permutation = PermutationMap::random(100, &mut StdRng::seed_from_u64(7));
+        assert_eq!(
+            PermutationMap::new(permutation.as_slice().to_vec()),
+            Some(permutation)
+        );
+    }
+}
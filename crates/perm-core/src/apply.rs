@@ -0,0 +1,120 @@
+//! # Apply
+//!
+//! Rearrange a slice in place by a permutation of its positions, as written by `--indices`
+//! and held by `PermutationMap`.
+//!
+//! `apply_permutation` moves the value at position `permutation[i]` to position `i`,
+//! and `apply_inverse` undoes it, moving the value at position `i` to position `permutation[i]`.
+//!
+//! Both follow the cycles of the permutation swapping the values along each cycle,
+//! so they do not copy the values and need only one flag for each position.
+//! The permutation is validated first: an invalid one would make a cycle never return to its start.
+
+/// Rearrange the `data` by the `permutation`: the value at position `permutation[i]` moves to position `i`.
+/// Panics if the `permutation` is not a permutation of `0..data.len()`.
+pub fn apply_permutation<T>(permutation: &[usize], data: &mut [T]) {
+    let mut visited = visited_flags(permutation, data.len());
+    for start in 0..data.len() {
+        if visited[start] {
+            continue;
+        }
+        let mut position = start;
+        loop {
+            visited[position] = true;
+            let source = permutation[position];
+            if source == start {
+                break;
+            }
+            data.swap(position, source);
+            position = source;
+        }
+    }
+}
+
+/// Rearrange the `data` by the inverse of the `permutation`: the value at position `i` moves to position `permutation[i]`.
+/// Panics if the `permutation` is not a permutation of `0..data.len()`.
+pub fn apply_inverse<T>(permutation: &[usize], data: &mut [T]) {
+    let mut visited = visited_flags(permutation, data.len());
+    for start in 0..data.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut target = permutation[start];
+        while target != start {
+            visited[target] = true;
+            data.swap(start, target);
+            target = permutation[target];
+        }
+    }
+}
+
+/// Check that the `permutation` is a permutation of `0..length`, and initialize the visited flags of its positions.
+fn visited_flags(permutation: &[usize], length: usize) -> Vec<bool> {
+    if permutation.len() != length {
+        panic!("Only permutations of the same length as the data can be applied")
+    }
+    let mut seen = vec![false; length];
+    for image in permutation {
+        match seen.get_mut(*image) {
+            Some(seen) if !*seen => *seen = true,
+            _ => panic!("The positions are not a permutation"),
+        }
+    }
+    seen.fill(false);
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn random_permutation(length: usize, seed: u64) -> Vec<usize> {
+        let mut permutation = (0..length).collect::<Vec<usize>>();
+        permutation.shuffle(&mut StdRng::seed_from_u64(seed));
+        permutation
+    }
+
+    #[test]
+    fn permutation_moves_the_values_like_indices() {
+        let mut data = ["a", "b", "c", "d"];
+        apply_permutation(&[2, 0, 3, 1], &mut data);
+        assert_eq!(data, ["c", "a", "d", "b"]);
+        apply_inverse(&[2, 0, 3, 1], &mut data);
+        assert_eq!(data, ["a", "b", "c", "d"]);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn in_place_application_matches_copying(data: Vec<String>, seed: u64) -> bool {
+        let permutation = random_permutation(data.len(), seed);
+        let mut applied = data.clone();
+        apply_permutation(&permutation, &mut applied);
+        let mut inverted = data.clone();
+        apply_inverse(&permutation, &mut inverted);
+        applied
+            == permutation
+                .iter()
+                .map(|i| data[*i].clone())
+                .collect::<Vec<_>>()
+            && permutation
+                .iter()
+                .zip(&data)
+                .all(|(i, value)| inverted[*i] == *value)
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn repeated_positions_are_rejected() {
+        apply_permutation(&[0, 0, 1], &mut [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "same length")]
+    fn different_lengths_are_rejected() {
+        apply_inverse(&[0, 1], &mut [1, 2, 3]);
+    }
+}
@@ -0,0 +1,227 @@
+//! # Backend
+//!
+//! `Backend` names the iterators generating the chunks of permutations,
+//! and `Backend::select` chooses one for a `Permutations` from the shape of its input.
+//!
+//! The heuristic follows the `suite` benchmarks, generating the chunks without formatting them:
+//!
+//! - the ranked iterator computes each permutation from the previous one in place, so it is the fastest
+//!   for the values up to `WIDE_VALUE_BYTES` wide (integers, string slices), 2 to 15 times faster than the job trees,
+//!   whatever the length. The gap grows with the equal values: with two distinct values the job tree
+//!   expands a job for each of them at each level, and most jobs lead to few permutations.
+//! - the wider values are copied into every permutation by the ranked iterator, and into every job by the normal one,
+//!   while the optimized jobs copy the labels of the values: for the values of 64 bytes it is twice as fast as the ranked iterator.
+//!   It only holds up to `limits::OPTIMIZED_MAX_LEN` values.
+//! - the ranked iterator ignores the `ExpansionStrategy`, so `ExpansionStrategy::BreadthFirst` needs a job tree.
+//!
+//! All the backends generate the same permutations in the same order.
+//!
+//! `Backend::check` tells with a `BackendError` why a backend cannot generate the permutations of an input,
+//! so that a forced backend is rejected before the generation starts instead of panicking.
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::mem::size_of;
+
+use crate::freq::values_with_frequency;
+use crate::limits::{max_distinct_values, max_length};
+use crate::{ExpansionStrategy, Permutations, Uniqueness};
+
+/// The size in bytes above which a value is cheaper to copy as a label, in the optimized jobs.
+pub const WIDE_VALUE_BYTES: usize = 32;
+
+/// The iterators generating the chunks of permutations.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// The job tree of `Permutations::into_chunks`.
+    Normal,
+    /// The job tree of fixed arrays of `Permutations::into_optimized_chunks`.
+    Optimized,
+    /// The in-place successor of `Permutations::into_ranked_chunks`.
+    Ranked,
+}
+
+/// The backend chosen by `Backend::select`, with the reason of the choice.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Selection {
+    /// the backend to use.
+    pub backend: Backend,
+    /// why it is the fastest for the input.
+    pub reason: &'static str,
+}
+
+/// Why a backend cannot generate the permutations of an input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BackendError {
+    /// The input has more values than the backend holds.
+    TooLong {
+        /// the number of values.
+        length: usize,
+        /// the maximum number of values of the backend.
+        max_length: usize,
+    },
+    /// The input has more distinct values than the backend can label.
+    TooManyDistinctValues {
+        /// the number of distinct values, or of values if they are distinguishable.
+        distinct: usize,
+        /// the maximum number of distinct values of the backend.
+        max_distinct_values: usize,
+    },
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendError::TooLong { length, max_length } => write!(
+                fmt,
+                "the input has {} values, but the backend holds at most {}",
+                length, max_length
+            ),
+            BackendError::TooManyDistinctValues {
+                distinct,
+                max_distinct_values,
+            } => write!(
+                fmt,
+                "the input has {} distinct values, but the backend labels at most {}",
+                distinct, max_distinct_values
+            ),
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+impl Backend {
+    /// Check if the backend can generate the `permutations`:
+    /// the length and the distinct values of the input must be within its limits (see `limits`).
+    pub fn check<T: Copy + Eq + Hash>(
+        &self,
+        permutations: &Permutations<T>,
+    ) -> Result<(), BackendError> {
+        let length = permutations.length();
+        if let Some(max_length) = max_length(*self).filter(|max_length| length > *max_length) {
+            return Err(BackendError::TooLong { length, max_length });
+        }
+        if let Some(max_distinct_values) = max_distinct_values(*self) {
+            let distinct = match permutations.uniqueness() {
+                Uniqueness::Distinct => {
+                    values_with_frequency(permutations.values(), permutations.hasher()).len()
+                }
+                Uniqueness::WithDuplicates => length,
+            };
+            if distinct > max_distinct_values {
+                return Err(BackendError::TooManyDistinctValues {
+                    distinct,
+                    max_distinct_values,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose the fastest backend for the `permutations`,
+    /// considering the length of the input, the size of the values and the `ExpansionStrategy`.
+    /// The equal values only widen the lead of the ranked iterator, so they are not looked for.
+    pub fn select<T: Copy + Eq + Hash>(permutations: &Permutations<T>) -> Selection {
+        let fits_optimized = permutations.can_be_optimized();
+        let is_wide = size_of::<T>() > WIDE_VALUE_BYTES;
+        let (backend, reason) = if permutations.strategy() == ExpansionStrategy::BreadthFirst {
+            if fits_optimized && is_wide {
+                (
+                    Backend::Optimized,
+                    "the breadth-first strategy needs a job tree, and the wide values are copied as labels",
+                )
+            } else {
+                (
+                    Backend::Normal,
+                    "the breadth-first strategy needs a job tree",
+                )
+            }
+        } else if is_wide && fits_optimized {
+            (
+                Backend::Optimized,
+                "the wide values are copied as labels by the optimized jobs",
+            )
+        } else if is_wide {
+            (
+                Backend::Ranked,
+                "the input is too long for the optimized jobs, and the job tree copies the wide values",
+            )
+        } else {
+            (
+                Backend::Ranked,
+                "computing each permutation from the previous one is the fastest for narrow values",
+            )
+        };
+        Selection { backend, reason }
+    }
+
+    /// The name of the backend, as written by `--algorithm`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Normal => "normal",
+            Backend::Optimized => "optimized",
+            Backend::Ranked => "ranked",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected<T: Copy + Eq + Hash>(permutations: &Permutations<T>) -> Backend {
+        Backend::select(permutations).backend
+    }
+
+    #[test]
+    fn narrow_values_use_the_ranked_iterator() {
+        assert_eq!(selected(&Permutations::new(vec![1, 2, 3])), Backend::Ranked);
+        assert_eq!(
+            selected(&Permutations::new(vec!["a", "b", "a", "b"])),
+            Backend::Ranked
+        );
+        assert_eq!(
+            selected(&Permutations::new((0..200).collect::<Vec<u8>>())),
+            Backend::Ranked
+        );
+    }
+
+    #[test]
+    fn wide_values_use_the_optimized_iterator_if_they_fit() {
+        let wide = |length: u64| (0..length).map(|i| [i; 8]).collect::<Vec<[u64; 8]>>();
+        assert_eq!(selected(&Permutations::new(wide(9))), Backend::Optimized);
+        assert_eq!(selected(&Permutations::new(wide(200))), Backend::Ranked);
+    }
+
+    #[test]
+    fn limits_are_reported_as_errors() {
+        let long = Permutations::new((0..200).collect::<Vec<u32>>());
+        assert_eq!(
+            Backend::Optimized.check(&long),
+            Err(BackendError::TooLong {
+                length: 200,
+                max_length: 128
+            })
+        );
+        assert_eq!(Backend::Ranked.check(&long), Ok(()));
+        assert!(long.try_into_optimized_chunks(10).is_err());
+        assert_eq!(
+            Backend::Optimized.check(&Permutations::new(vec![1, 2])),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn breadth_first_uses_a_job_tree() {
+        let permutations =
+            Permutations::new(vec![1, 2, 3]).with_strategy(ExpansionStrategy::BreadthFirst);
+        assert_eq!(selected(&permutations), Backend::Normal);
+    }
+}
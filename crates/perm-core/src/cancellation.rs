@@ -0,0 +1,46 @@
+//! # Cancellation
+//!
+//! `CancellationToken` aborts a long-running generation from another thread,
+//! for example when a service embedding the generator receives a shutdown request.
+//!
+//! The chunk iterators check the token before expanding each job:
+//! once it is cancelled they return `None` without completing the current chunk,
+//! and their `next_rank` is the rank to resume the generation from.
+//! The `Pipeline` stops taking chunks from its source and writes the chunks already transformed.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag shared by the clones of the token, to cancel a generation.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Initialize a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Cancel the generations checking this token or any of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    /// Check if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Share an existing flag: the generation is cancelled when it is set to `true`.
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(cancelled: Arc<AtomicBool>) -> Self {
+        Self { cancelled }
+    }
+}
+
+/// Check if the optional `cancellation` token has been cancelled.
+pub(crate) fn is_cancelled(cancellation: &Option<CancellationToken>) -> bool {
+    cancellation
+        .as_ref()
+        .is_some_and(CancellationToken::is_cancelled)
+}
@@ -0,0 +1,172 @@
+//! # Codes
+//!
+//! Conversions between the permutations and their encodings as sequences of small integers.
+//!
+//! - The Lehmer code of a permutation `p` has, for each position `i`,
+//!   the number of values after `i` lower than `p[i]`.
+//!   If the values are distinct, read as a number in the factorial number system
+//!   it is the rank of `p` in lexicographic order of the values.
+//! - The inversion table of `p` has, for each value in increasing order,
+//!   the number of greater values before it.
+//!
+//! Both encodings are defined for the permutations of a multiset, where the equal values
+//! are ordered by position.
+//! Decoding them needs the values, and fails with `None` if the code is not the encoding
+//! of any of their permutations.
+use std::cmp::Ordering;
+
+/// Compute the Lehmer code of the `permutation`.
+pub fn lehmer_code<T: Ord>(permutation: &[T]) -> Vec<usize> {
+    (0..permutation.len())
+        .map(|i| {
+            permutation[i + 1..]
+                .iter()
+                .filter(|value| **value < permutation[i])
+                .count()
+        })
+        .collect()
+}
+
+/// Compute the permutation of the `values` with the Lehmer `code`.
+/// Return `None` if the `code` is not the Lehmer code of a permutation of the `values`.
+pub fn from_lehmer_code<T: Ord + Copy>(code: &[usize], values: &[T]) -> Option<Vec<T>> {
+    if code.len() != values.len() {
+        return None;
+    }
+    let mut remaining = values.to_vec();
+    remaining.sort();
+    code.iter()
+        .map(|&lower| {
+            // the remaining value with exactly `lower` lower values.
+            if lower >= remaining.len() || (lower > 0 && remaining[lower - 1] == remaining[lower]) {
+                return None;
+            }
+            Some(remaining.remove(lower))
+        })
+        .collect()
+}
+
+/// Compute the inversion table of the `permutation`.
+pub fn inversion_table<T: Ord>(permutation: &[T]) -> Vec<usize> {
+    let mut positions = (0..permutation.len()).collect::<Vec<usize>>();
+    // the sort is stable: the equal values are ordered by position.
+    positions.sort_by(|a, b| permutation[*a].cmp(&permutation[*b]));
+    positions
+        .into_iter()
+        .map(|position| {
+            permutation[..position]
+                .iter()
+                .filter(|value| **value > permutation[position])
+                .count()
+        })
+        .collect()
+}
+
+/// Compute the permutation of the `values` with the inversion `table`.
+/// Return `None` if the `table` is not the inversion table of a permutation of the `values`.
+pub fn from_inversion_table<T: Ord + Copy>(table: &[usize], values: &[T]) -> Option<Vec<T>> {
+    if table.len() != values.len() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let mut permutation = Vec::with_capacity(values.len());
+    // insert the values from the greatest one:
+    // the values already inserted are greater, or equal and after it.
+    for (value, &greater) in sorted.iter().zip(table).rev() {
+        let mut position = 0;
+        while position < greater {
+            match permutation.get(position).map(|other: &T| other.cmp(value)) {
+                Some(Ordering::Greater) => position += 1,
+                // too few greater values, or an equal value before it.
+                _ => return None,
+            }
+        }
+        permutation.insert(position, *value);
+    }
+    Some(permutation)
+}
+
+/// Read the Lehmer `code` as a number in the factorial number system:
+/// the rank of the permutation in lexicographic order of the values, if they are distinct.
+/// Return `None` if a digit is out of range or if the rank does not fit in a `usize`.
+pub fn lehmer_code_to_rank(code: &[usize]) -> Option<usize> {
+    let mut rank: usize = 0;
+    for (i, &digit) in code.iter().enumerate() {
+        let base = code.len() - i;
+        if digit >= base {
+            return None;
+        }
+        rank = rank.checked_mul(base)?.checked_add(digit)?;
+    }
+    Some(rank)
+}
+
+/// Write the `rank` in the factorial number system with `length` digits:
+/// the Lehmer code of the permutation of rank `rank` of `length` distinct values.
+/// Return `None` if `rank` is not lower than `length!`.
+pub fn rank_to_lehmer_code(mut rank: usize, length: usize) -> Option<Vec<usize>> {
+    let mut code = vec![0; length];
+    for i in (0..length).rev() {
+        let base = length - i;
+        code[i] = rank % base;
+        rank /= base;
+    }
+    if rank == 0 {
+        Some(code)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Permutations;
+
+    use super::*;
+
+    #[test]
+    fn permutation_is_encoded() {
+        let permutation = [3, 1, 4, 2];
+        assert_eq!(lehmer_code(&permutation), vec![2, 0, 1, 0]);
+        assert_eq!(inversion_table(&permutation), vec![1, 2, 0, 0]);
+        assert_eq!(lehmer_code_to_rank(&[2, 0, 1, 0]), Some(13));
+        assert_eq!(rank_to_lehmer_code(13, 4), Some(vec![2, 0, 1, 0]));
+        assert_eq!(rank_to_lehmer_code(24, 4), None);
+        // the ranks of the sorted distinct values are the ranks of `Permutations`.
+        assert_eq!(
+            Permutations::new(vec![1, 2, 3, 4]).nth_permutation(13),
+            from_lehmer_code(&[2, 0, 1, 0], &[1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn invalid_codes_are_rejected() {
+        assert_eq!(from_lehmer_code(&[3, 0, 0], &[1, 2, 3]), None);
+        // there is no value with exactly one lower value.
+        assert_eq!(from_lehmer_code(&[1, 0, 0], &[1, 1, 2]), None);
+        assert_eq!(from_inversion_table(&[0, 2, 0], &[1, 2, 3]), None);
+        assert_eq!(lehmer_code_to_rank(&[0, 2, 0]), None);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn codes_are_decoded(values: Vec<u8>) {
+        // a small multiset.
+        let permutation = values.iter().take(8).map(|v| v % 4).collect::<Vec<u8>>();
+        assert_eq!(
+            from_lehmer_code(&lehmer_code(&permutation), &permutation),
+            Some(permutation.clone())
+        );
+        assert_eq!(
+            from_inversion_table(&inversion_table(&permutation), &permutation),
+            Some(permutation.clone())
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn ranks_are_decoded(rank: u16) {
+        let rank = rank as usize % 5040;
+        let code = rank_to_lehmer_code(rank, 7).unwrap();
+        assert_eq!(lehmer_code_to_rank(&code), Some(rank));
+    }
+}
@@ -0,0 +1,132 @@
+//! # Encoding
+//!
+//! The compact form of the values used by the optimized and the ranked iterators,
+//! and by the ranks of the chunks written to the output (e.g. the first rank in the header of a framed chunk).
+//!
+//! The layout is stable:
+//!
+//! - each distinct value has an index: the order of its first occurrence in the input, from `0`.
+//!   With `compress_distinguishable` every value has its own index, its position, even if it is equal to another.
+//! - `Encoding::values[i]` is the value of index `i`: the decode table.
+//! - `Encoding::frequencies[i]` is the number of occurrences of the value of index `i`:
+//!   their sum is the length of the permutations.
+//! - a permutation is encoded as the indices of its values, in order.
+//!   The permutations are ranked in lexicographic order of their indices,
+//!   so the permutation of a given rank can be computed from the frequencies alone.
+//!
+//! For example the values `b,a,b,c` are encoded with the values `[b, a, c]` and the frequencies `[2, 1, 1]`:
+//! the permutation `a,b,c,b` is encoded as `[1, 0, 2, 0]`, and `b,b,a,c`, the first one, as `[0, 0, 1, 2]`.
+//!
+//! The optimized iterator keeps the frequencies in a fixed array of `limits::OPTIMIZED_MAX_LEN` counters,
+//! the unused ones set to zero.
+use std::hash::Hash;
+
+use crate::freq::values_with_frequency_in_order;
+use crate::hasher::ValueHasher;
+use crate::permutations::Uniqueness;
+
+/// The values of the permutations, compressed to the frequency of each index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Encoding<T> {
+    /// the value of each index.
+    pub values: Vec<T>,
+    /// the number of occurrences of each index.
+    pub frequencies: Vec<usize>,
+}
+
+impl<T> Encoding<T> {
+    /// Get the length of the permutations: the sum of the frequencies.
+    pub fn length(&self) -> usize {
+        self.frequencies.iter().sum()
+    }
+}
+
+impl<T: Eq> Encoding<T> {
+    /// Encode a permutation of the values as the indices of its values: the inverse of `decode`.
+    /// The equal values are given the lowest indices first.
+    /// Return `None` if it is not a permutation of the values.
+    pub fn encode(&self, permutation: &[T]) -> Option<Vec<usize>> {
+        if permutation.len() != self.length() {
+            return None;
+        }
+        let mut frequencies = self.frequencies.clone();
+        permutation
+            .iter()
+            .map(|value| {
+                let index = (0..self.values.len())
+                    .find(|index| frequencies[*index] > 0 && self.values[*index] == *value)?;
+                frequencies[index] -= 1;
+                Some(index)
+            })
+            .collect()
+    }
+}
+
+/// Compress the `values`, giving each distinct value the order of its first occurrence as index.
+pub fn compress<T: Copy + Eq + Hash>(values: &[T]) -> Encoding<T> {
+    let (values, frequencies) = values_with_frequency_in_order(values, ValueHasher::default())
+        .into_iter()
+        .unzip();
+    Encoding {
+        values,
+        frequencies,
+    }
+}
+
+/// Compress the `values` as if they were all distinct:
+/// the `i`-th value has index `i` and frequency one.
+pub fn compress_distinguishable<T: Copy>(values: &[T]) -> Encoding<T> {
+    Encoding {
+        values: values.to_vec(),
+        frequencies: vec![1; values.len()],
+    }
+}
+
+/// Decode the `indices` of a permutation with the decode table of the `encoding`.
+/// Return `None` if an index has no value.
+pub fn decode<T: Copy>(encoding: &Encoding<T>, indices: &[usize]) -> Option<Vec<T>> {
+    indices
+        .iter()
+        .map(|index| encoding.values.get(*index).copied())
+        .collect()
+}
+
+/// Compress the `values` as the permutations with the given `uniqueness` see them.
+pub(crate) fn compress_as<T: Copy + Eq + Hash>(
+    values: &[T],
+    uniqueness: Uniqueness,
+) -> Encoding<T> {
+    match uniqueness {
+        Uniqueness::Distinct => compress(values),
+        Uniqueness::WithDuplicates => compress_distinguishable(values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_indexed_by_first_occurrence() {
+        let encoding = compress(&["b", "a", "b", "c"]);
+        assert_eq!(encoding.values, vec!["b", "a", "c"]);
+        assert_eq!(encoding.frequencies, vec![2, 1, 1]);
+        assert_eq!(encoding.length(), 4);
+        assert_eq!(
+            encoding.encode(&["a", "b", "c", "b"]),
+            Some(vec![1, 0, 2, 0])
+        );
+        assert_eq!(
+            decode(&encoding, &[1, 0, 2, 0]),
+            Some(vec!["a", "b", "c", "b"])
+        );
+        assert_eq!(encoding.encode(&["a", "a", "c", "b"]), None);
+        assert_eq!(decode(&encoding, &[3]), None);
+        let distinguishable = compress_distinguishable(&["b", "a", "b"]);
+        assert_eq!(distinguishable.frequencies, vec![1, 1, 1]);
+        assert_eq!(
+            distinguishable.encode(&["b", "b", "a"]),
+            Some(vec![0, 2, 1])
+        );
+    }
+}
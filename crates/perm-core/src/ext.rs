@@ -0,0 +1,74 @@
+//! # Extension traits
+//!
+//! Build the `Permutations` right where the values are, in the style of `itertools`:
+//! `vec![1, 2, 2].permutations_chunks(100)` or `"a,b,c".parse_permutations()`.
+//!
+//! `PermutationsExt` is implemented for every `IntoIterator` of `Copy + Eq + Hash` values,
+//! and `ParsePermutations` for the strings of comma-separated values.
+//! Unlike `Permutations::try_from`, `parse_permutations` accepts any value, not only the numbers.
+//! The permutations are the distinct ones of the multiset, as with `Permutations::new`.
+use std::hash::Hash;
+
+use crate::parse::{ParseError, ParseOptions, Validation};
+use crate::{IntoChunks, Permutations};
+
+/// Generate the permutations of the values of an iterator.
+pub trait PermutationsExt: IntoIterator + Sized
+where
+    Self::Item: Copy + Eq + Hash,
+{
+    /// Collect the values into a `Permutations`.
+    fn into_permutations(self) -> Permutations<Self::Item> {
+        Permutations::new(self.into_iter().collect())
+    }
+    /// Create the iterator over chunks of the permutations of the values: see `Permutations::into_chunks`.
+    /// Panics if the chunk size is zero.
+    fn permutations_chunks(self, size: usize) -> IntoChunks<Self::Item> {
+        self.into_permutations().into_chunks(size)
+    }
+}
+
+impl<I: IntoIterator> PermutationsExt for I where I::Item: Copy + Eq + Hash {}
+
+/// Parse a string of comma-separated values into the `Permutations` of its values.
+pub trait ParsePermutations {
+    /// Parse the values with the default `ParseOptions`, but accepting any value (`Validation::Any`).
+    fn parse_permutations(&self) -> Result<Permutations<&str>, ParseError> {
+        self.parse_permutations_with(&ParseOptions::new().with_validation(Validation::Any))
+    }
+    /// Parse the values with the given `options`.
+    fn parse_permutations_with(
+        &self,
+        options: &ParseOptions,
+    ) -> Result<Permutations<&str>, ParseError>;
+}
+
+impl ParsePermutations for str {
+    fn parse_permutations_with(
+        &self,
+        options: &ParseOptions,
+    ) -> Result<Permutations<&str>, ParseError> {
+        Permutations::parse(self, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutations_are_built_from_iterators_and_strings() {
+        let chunks = vec![1, 2, 2].permutations_chunks(100).collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].to_string(), "1,2,2\n2,1,2\n2,2,1\n");
+        assert_eq!((0..4).into_permutations().permutations_number(), 24);
+        assert_eq!(
+            ["a", "b"].iter().into_permutations().values(),
+            &[&"a", &"b"]
+        );
+        let permutations = "a,b,a".parse_permutations().unwrap();
+        assert_eq!(permutations.values(), &["a", "b", "a"]);
+        assert_eq!(permutations.permutations_number(), 3);
+        assert!("1,x".parse_permutations_with(&ParseOptions::new()).is_err());
+    }
+}
@@ -0,0 +1,525 @@
+//! # Format
+//!
+//! Control how the chunks of permutations are written to output.
+//!
+//! Each permutation is written on its own line, with its values separated by commas.
+//! When a value contains a comma, a double quote or a line break the output becomes ambiguous,
+//! so `Quoting` allows to quote the values RFC 4180-style:
+//! the value is wrapped in double quotes, and every double quote inside the value is doubled.
+//!
+//! The values are written with the `PermValue` trait rather than `ToString`,
+//! so that a custom element type only has to tell how it is formatted
+//! (hex numbers, fixed-precision floats, debug structs) without a newtype wrapper.
+//!
+//! A `Labelled` value separates the identity of a value from the label it is written as:
+//! the item ids are permuted, compared and deduplicated, and the item names are written.
+//!
+//! The permutations end with a line feed by default, or with a carriage return and a line feed
+//! (`LineEnding::CrLf`) for the Windows consumers expecting them.
+//!
+//! A joined format (`OutputFormat::with_joined`) writes the values of each permutation one after the other,
+//! without separators: the permutations of the characters of a word are written as its anagrams.
+//!
+//! `Formatted` is a `Display` wrapper around a chunk with a given `OutputFormat`.
+//!
+//! The iterators share a table of the strings of their distinct values with all their chunks:
+//! each value is formatted (and quoted) once for each `OutputFormat`, not once for each permutation,
+//! and each permutation is written by copying the strings of its values.
+use std::fmt::{self, Display, Write};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::hasher::{ValueHasher, ValueMap};
+
+const SEPARATOR: char = ',';
+const QUOTE: char = '"';
+/// Up to this many values, the index of a value is found by comparing it with each one, faster than hashing it.
+const LINEAR_LOOKUP_MAX: usize = 16;
+
+/// A value that can be written inside a permutation.
+///
+/// It is implemented for the primitive types, the strings and the references to a `PermValue`.
+/// The chunks can hold values of any type, but they can be displayed
+/// only if the values are `PermValue`: other payloads need a different sink.
+pub trait PermValue {
+    /// Write the value to the formatter.
+    fn fmt_value(&self, fmt: &mut fmt::Formatter) -> fmt::Result;
+}
+
+macro_rules! impl_perm_value_with_display {
+    ($($value_type:ty),*) => {
+        $(
+            impl PermValue for $value_type {
+                fn fmt_value(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                    Display::fmt(self, fmt)
+                }
+            }
+        )*
+    };
+}
+
+impl_perm_value_with_display!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool, char, str,
+    String
+);
+
+impl<T: PermValue + ?Sized> PermValue for &T {
+    fn fmt_value(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt_value(fmt)
+    }
+}
+
+/// `Display` adapter for a `PermValue`.
+pub struct DisplayValue<'a, T: ?Sized>(pub &'a T);
+
+impl<T: PermValue + ?Sized> Display for DisplayValue<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_value(fmt)
+    }
+}
+
+/// A value written as its `label`: the equality and the hash only consider the `value`,
+/// so the permutations are the ones of the values, whatever the labels.
+/// Two labelled values with equal values are the same value: only one of the labels is written for both.
+#[derive(Copy, Clone, Debug)]
+pub struct Labelled<T, L> {
+    /// the identity of the value.
+    pub value: T,
+    /// what is written for the value.
+    pub label: L,
+}
+
+impl<T: PartialEq, L> PartialEq for Labelled<T, L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq, L> Eq for Labelled<T, L> {}
+
+impl<T: Hash, L> Hash for Labelled<T, L> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+impl<T, L: PermValue> PermValue for Labelled<T, L> {
+    fn fmt_value(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.label.fmt_value(fmt)
+    }
+}
+
+/// When a value must be quoted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quoting {
+    /// The values are written as they are.
+    Never,
+    /// Only the values that would make the output ambiguous are quoted.
+    Necessary,
+    /// Every value is quoted.
+    Always,
+}
+
+/// The line break written after each permutation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`.
+    Lf,
+    /// `\r\n`.
+    CrLf,
+}
+
+impl LineEnding {
+    /// Get the characters of the line break.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// The options to write the chunks of permutations.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutputFormat {
+    quoting: Quoting,
+    line_ending: LineEnding,
+    joined: bool,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            quoting: Quoting::Never,
+            line_ending: LineEnding::Lf,
+            joined: false,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Initialize the default `OutputFormat`: values are not quoted, and lines end with `\n`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set when the values must be quoted.
+    pub fn with_quoting(mut self, quoting: Quoting) -> Self {
+        self.quoting = quoting;
+        self
+    }
+    /// Get when the values are quoted.
+    pub fn quoting(&self) -> Quoting {
+        self.quoting
+    }
+    /// Set the line break written after each permutation.
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+    /// Get the line break written after each permutation.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Set whether the values are written without separators between them, e.g. the characters of an anagram.
+    pub fn with_joined(mut self, joined: bool) -> Self {
+        self.joined = joined;
+        self
+    }
+    /// Get whether the values are written without separators between them.
+    pub fn is_joined(&self) -> bool {
+        self.joined
+    }
+
+    /// Write a single permutation terminated by a line break.
+    pub(crate) fn write_permutation<'a, T: PermValue + 'a>(
+        &self,
+        fmt: &mut fmt::Formatter,
+        permutation: impl Iterator<Item = &'a T>,
+    ) -> fmt::Result {
+        for (position, value) in permutation.enumerate() {
+            if position > 0 && !self.joined {
+                fmt.write_char(SEPARATOR)?;
+            }
+            self.write_value(fmt, value)?;
+        }
+        fmt.write_str(self.line_ending.as_str())
+    }
+
+    /// Compute the length in bytes of a single permutation written with this format,
+    /// including the line break.
+    pub fn permutation_len<'a, T: PermValue + 'a>(
+        &self,
+        permutation: impl Iterator<Item = &'a T>,
+    ) -> usize {
+        let (values, len) = permutation.fold((0_usize, 0), |(values, len), value| {
+            (values + 1, len + self.value_len(value))
+        });
+        let separators = if self.joined {
+            0
+        } else {
+            values.saturating_sub(1)
+        };
+        len + separators + self.line_ending.as_str().len()
+    }
+
+    /// Write a single value with this format, quoting it if required.
+    pub(crate) fn value_to_string<T: PermValue>(&self, value: &T) -> String {
+        /// A single value to be displayed with a given `OutputFormat`.
+        struct FormattedValue<'a, T>(OutputFormat, &'a T);
+        impl<T: PermValue> Display for FormattedValue<'_, T> {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                self.0.write_value(fmt, self.1)
+            }
+        }
+        FormattedValue(*self, value).to_string()
+    }
+
+    /// Get the separator written between the values of a permutation.
+    pub(crate) fn separator(&self) -> &'static str {
+        if self.joined {
+            ""
+        } else {
+            ","
+        }
+    }
+
+    /// Compute the length in bytes of a single value written with this format.
+    fn value_len<T: PermValue>(&self, value: &T) -> usize {
+        let value = DisplayValue(value).to_string();
+        if self.quoting == Quoting::Never
+            || (self.quoting == Quoting::Necessary && !needs_quotes(&value))
+        {
+            return value.len();
+        }
+        value.len() + value.matches(QUOTE).count() + 2 * QUOTE.len_utf8()
+    }
+
+    /// Write a single value, quoting it if required.
+    /// The value is formatted to an intermediate string only when quoting is enabled.
+    fn write_value<T: PermValue>(&self, fmt: &mut fmt::Formatter, value: &T) -> fmt::Result {
+        if self.quoting == Quoting::Never {
+            return value.fmt_value(fmt);
+        }
+        let value = DisplayValue(value).to_string();
+        if self.quoting == Quoting::Necessary && !needs_quotes(&value) {
+            return fmt.write_str(&value);
+        }
+        fmt.write_char(QUOTE)?;
+        for character in value.chars() {
+            if character == QUOTE {
+                fmt.write_char(QUOTE)?;
+            }
+            fmt.write_char(character)?;
+        }
+        fmt.write_char(QUOTE)
+    }
+}
+
+/// Check if a value would be ambiguous without quotes.
+/// Leading and trailing whitespaces are quoted because they are trimmed when the input is parsed.
+fn needs_quotes(value: &str) -> bool {
+    value
+        .chars()
+        .any(|c| c == SEPARATOR || c == QUOTE || c == '\n' || c == '\r')
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+}
+
+/// The strings of the values, with the format they were formatted with.
+type FormattedStrings = (OutputFormat, Arc<[Box<str>]>);
+
+/// The distinct values of an iterator, with their strings formatted once and shared by all its chunks.
+pub(crate) struct ValueStrings<T> {
+    /// the values, by index.
+    values: Vec<T>,
+    /// the index of each value.
+    indices: ValueMap<T, usize>,
+    /// get the index of a value, trying the given index first:
+    /// it is set where the values can be compared and hashed.
+    index_of: fn(&Self, &T, Option<usize>) -> Option<usize>,
+    /// the strings of the values with the last format used.
+    strings: Mutex<Option<FormattedStrings>>,
+}
+
+impl<T: Copy + Eq + Hash> ValueStrings<T> {
+    /// Initialize the table of the `values`, by index, hashed with `hasher`.
+    /// The equal values (e.g. with `Uniqueness::WithDuplicates`) are found at the first of their indices.
+    pub(crate) fn new(values: Vec<T>, hasher: ValueHasher) -> Self {
+        let mut indices = hasher.map_with_capacity(values.len());
+        for (index, value) in values.iter().enumerate() {
+            indices.entry(*value).or_insert(index);
+        }
+        Self {
+            values,
+            indices,
+            index_of: |strings, value, hint| match hint {
+                Some(index) if strings.values[index] == *value => Some(index),
+                _ if strings.values.len() <= LINEAR_LOOKUP_MAX => {
+                    strings.values.iter().position(|other| other == value)
+                }
+                _ => strings.indices.get(value).copied(),
+            },
+            strings: Mutex::new(None),
+        }
+    }
+}
+
+impl<T> ValueStrings<T> {
+    /// Get the values, by index.
+    pub(crate) fn values(&self) -> &[T] {
+        &self.values
+    }
+    /// Get the index of a `value`, if it is one of the values, trying the index `hint` first:
+    /// e.g. the index of the value at the same position of the previous permutation.
+    pub(crate) fn index_of(&self, value: &T, hint: Option<usize>) -> Option<usize> {
+        (self.index_of)(self, value, hint)
+    }
+}
+
+impl<T: PermValue> ValueStrings<T> {
+    /// Get the strings of the values with `format`, formatting them only if the format has changed.
+    pub(crate) fn strings(&self, format: OutputFormat) -> Arc<[Box<str>]> {
+        let mut strings = self
+            .strings
+            .lock()
+            .expect("The value strings are not poisoned");
+        match &*strings {
+            Some((last_format, last_strings)) if *last_format == format => last_strings.clone(),
+            _ => {
+                let formatted = self
+                    .values
+                    .iter()
+                    .map(|value| format.value_to_string(value).into_boxed_str())
+                    .collect::<Arc<[Box<str>]>>();
+                *strings = Some((format, formatted.clone()));
+                formatted
+            }
+        }
+    }
+
+    /// Write the `permutations`, each one given by the indices of its values, with `format`,
+    /// appending them to `output`.
+    /// Every permutation has the same values, so the output is allocated once with its exact length.
+    pub(crate) fn write_permutations<I: Iterator<Item = usize>>(
+        &self,
+        format: OutputFormat,
+        permutations: impl ExactSizeIterator<Item = I> + Clone,
+        output: &mut String,
+    ) {
+        let strings = self.strings(format);
+        let separator = format.separator();
+        let line_ending = format.line_ending().as_str();
+        let permutation_len = permutations.clone().next().map_or(0, |permutation| {
+            let (values, len) = permutation.fold((0_usize, 0), |(values, len), index| {
+                (values + 1, len + strings[index].len())
+            });
+            len + separator.len() * values.saturating_sub(1) + line_ending.len()
+        });
+        output.reserve(permutation_len * permutations.len());
+        for permutation in permutations {
+            for (position, index) in permutation.enumerate() {
+                if position > 0 {
+                    output.push_str(separator);
+                }
+                output.push_str(&strings[index]);
+            }
+            output.push_str(line_ending);
+        }
+    }
+}
+
+/// A chunk of permutations written as bytes with a given `OutputFormat`,
+/// e.g. by a writer of bytes: the bytes are the text of its `Formatted`, without copying it from a `String`.
+pub trait ToBytes {
+    /// Format the whole chunk with `format` into bytes.
+    fn to_bytes(&self, format: OutputFormat) -> Vec<u8>;
+}
+
+/// A chunk of permutations to be displayed with a given `OutputFormat`.
+pub struct Formatted<'a, C> {
+    pub(crate) chunk: &'a C,
+    pub(crate) format: OutputFormat,
+}
+
+impl<'a, C> Formatted<'a, C> {
+    /// Display the `chunk` with the given `format`.
+    pub fn new(chunk: &'a C, format: OutputFormat) -> Self {
+        Self { chunk, format }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Permutations;
+
+    use super::*;
+
+    fn format_single_permutation(values: Vec<&str>, quoting: Quoting) -> String {
+        Permutations::new(values)
+            .into_chunks(1)
+            .next()
+            .unwrap()
+            .formatted(OutputFormat::new().with_quoting(quoting))
+            .to_string()
+    }
+
+    #[test]
+    fn values_are_not_quoted_by_default() {
+        assert_eq!(
+            format_single_permutation(vec!["a,b"], Quoting::Never),
+            "a,b\n"
+        );
+    }
+
+    #[test]
+    fn ambiguous_values_are_quoted_when_necessary() {
+        assert_eq!(
+            format_single_permutation(vec!["a,b"], Quoting::Necessary),
+            "\"a,b\"\n"
+        );
+        assert_eq!(
+            format_single_permutation(vec!["say \"hi\""], Quoting::Necessary),
+            "\"say \"\"hi\"\"\"\n"
+        );
+        assert_eq!(
+            format_single_permutation(vec!["a\nb"], Quoting::Necessary),
+            "\"a\nb\"\n"
+        );
+        assert_eq!(
+            format_single_permutation(vec![" 1"], Quoting::Necessary),
+            "\" 1\"\n"
+        );
+    }
+
+    #[test]
+    fn unambiguous_values_are_not_quoted_when_necessary() {
+        assert_eq!(
+            format_single_permutation(vec!["ü-ñ"], Quoting::Necessary),
+            "ü-ñ\n"
+        );
+    }
+
+    #[derive(PartialEq, Eq, Hash)]
+    struct Hex(u32);
+
+    impl PermValue for Hex {
+        fn fmt_value(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            write!(fmt, "{:#x}", self.0)
+        }
+    }
+
+    #[test]
+    fn custom_values_are_formatted_with_perm_value() {
+        let values = [Hex(10), Hex(255)];
+        let mut permutations = Permutations::new(values.iter().collect::<Vec<&Hex>>())
+            .into_chunks(2)
+            .map(|c| c.to_string())
+            .collect::<String>()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        permutations.sort();
+        assert_eq!(permutations, vec!["0xa,0xff", "0xff,0xa"]);
+    }
+
+    #[test]
+    fn lines_end_with_the_line_ending() {
+        let format = OutputFormat::new().with_line_ending(LineEnding::CrLf);
+        let chunk = Permutations::new(vec![1, 2]).into_chunks(2).next().unwrap();
+        let formatted = chunk.formatted(format).to_string();
+        assert_eq!(formatted, "1,2\r\n2,1\r\n");
+        let permutation = [1, 2];
+        assert_eq!(format.permutation_len(permutation.iter()), 5);
+    }
+
+    #[test]
+    fn joined_values_have_no_separators() {
+        let format = OutputFormat::new().with_joined(true);
+        let chunk = Permutations::from_chars("aéb")
+            .into_chunks(2)
+            .next()
+            .unwrap();
+        let formatted = chunk.formatted(format).to_string();
+        assert_eq!(formatted, "aéb\nabé\n");
+        assert_eq!(
+            format.permutation_len("aéb".chars().collect::<Vec<_>>().iter()),
+            5
+        );
+    }
+
+    #[test]
+    fn all_values_are_quoted_always() {
+        let mut permutations = Permutations::new(vec!["1", "é"])
+            .into_optimized_chunks(2)
+            .next()
+            .unwrap()
+            .formatted(OutputFormat::new().with_quoting(Quoting::Always))
+            .to_string()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        permutations.sort();
+        assert_eq!(permutations, vec!["\"1\",\"é\"", "\"é\",\"1\""]);
+    }
+}
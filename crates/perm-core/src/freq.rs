@@ -0,0 +1,185 @@
+//! # Frequencies
+//!
+//! The multiset of the input values, as each distinct value with its frequency (the number of its occurrences),
+//! shared by all the backends: the ranks, the counting and the generation follow the same frequencies.
+//!
+//! - `values_with_frequency` counts the values, when their order does not matter (e.g. to count the permutations).
+//! - `values_with_frequency_in_order` lists them in order of first occurrence, the order of the generation.
+//!
+//! While a permutation is built, each backend labels the distinct values (see `encoding`) and keeps
+//! the remaining frequency of each label in a `FreqTable`: a value is taken for the next position
+//! and put back when the generation backtracks. The storage is chosen by the backend:
+//! the optimized iterator keeps the frequencies in its fixed arrays, so that its jobs are `Copy`,
+//! and the normal iterator in `Counts`, stored inline for up to `INLINE_LENGTH` labels with the `smallvec` feature.
+use std::collections::hash_map::Entry;
+use std::hash::Hash;
+use std::ops::Range;
+
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
+
+use crate::hasher::{ValueHasher, ValueMap};
+#[cfg(feature = "smallvec")]
+use crate::permutations::into_chunks::INLINE_LENGTH;
+
+/// The frequency of each label, stored inline for at most `INLINE_LENGTH` labels.
+#[cfg(feature = "smallvec")]
+pub(crate) type Counts = SmallVec<[usize; INLINE_LENGTH]>;
+
+/// The frequency of each label.
+#[cfg(not(feature = "smallvec"))]
+pub(crate) type Counts = Vec<usize>;
+
+/// The remaining frequency of each label of a multiset, stored in `C` (e.g. `Counts` or a fixed array).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FreqTable<C> {
+    frequencies: C,
+}
+
+impl<C: AsRef<[usize]>> FreqTable<C> {
+    /// Initialize the table with the `frequencies` of the labels.
+    pub(crate) fn new(frequencies: C) -> Self {
+        Self { frequencies }
+    }
+    /// Get the remaining frequency of `label`.
+    pub(crate) fn frequency(&self, label: usize) -> usize {
+        self.frequencies.as_ref()[label]
+    }
+    /// Get the remaining frequency of each label, in order.
+    pub(crate) fn frequencies(&self) -> &[usize] {
+        self.frequencies.as_ref()
+    }
+    /// Check if every occurrence has been taken, that is the permutation is complete.
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.frequencies
+            .as_ref()
+            .iter()
+            .all(|frequency| *frequency == 0)
+    }
+    /// Find the first of the `labels` with occurrences left.
+    pub(crate) fn next_available(&self, labels: Range<usize>) -> Option<usize> {
+        labels
+            .into_iter()
+            .find(|label| self.frequencies.as_ref()[*label] > 0)
+    }
+    /// Iterate over the labels with occurrences left, in order.
+    pub(crate) fn available(&self) -> impl Iterator<Item = usize> + '_ {
+        self.frequencies
+            .as_ref()
+            .iter()
+            .enumerate()
+            .filter(|(_, frequency)| **frequency > 0)
+            .map(|(label, _)| label)
+    }
+}
+
+impl<C: AsRef<[usize]> + AsMut<[usize]>> FreqTable<C> {
+    /// Take one occurrence of `label`, for the next position of the permutation.
+    /// The label must have occurrences left: it is checked only in the debug builds.
+    pub(crate) fn take(&mut self, label: usize) {
+        let frequency = &mut self.frequencies.as_mut()[label];
+        debug_assert!(
+            *frequency > 0,
+            "The label: {} has no occurrences left",
+            label
+        );
+        *frequency -= 1;
+    }
+    /// Put back one occurrence of `label`, removed from the permutation.
+    pub(crate) fn put_back(&mut self, label: usize) {
+        self.frequencies.as_mut()[label] += 1;
+    }
+    /// Relabel the frequencies: the new label `i` is the old label `order[i]`.
+    pub(crate) fn reorder(&mut self, order: &[usize]) {
+        let reordered = order
+            .iter()
+            .map(|label| self.frequency(*label))
+            .collect::<Vec<usize>>();
+        self.frequencies.as_mut().copy_from_slice(&reordered);
+    }
+    /// Get a copy of the table with one occurrence of `label` taken, for a child job.
+    pub(crate) fn taken(&self, label: usize) -> Self
+    where
+        C: Clone,
+    {
+        let mut table = self.clone();
+        table.take(label);
+        table
+    }
+}
+
+/// Compute the hashmap with the frequency for each value, hashed with `hasher`.
+pub(crate) fn values_with_frequency<T: Copy + Eq + Hash>(
+    values: &[T],
+    hasher: ValueHasher,
+) -> ValueMap<T, usize> {
+    let mut values_with_frequency = hasher.map();
+    for value in values {
+        *values_with_frequency.entry(*value).or_insert(0) += 1;
+    }
+    values_with_frequency
+}
+
+/// Compute the distinct values with their frequency, in order of first occurrence, hashed with `hasher`.
+pub(crate) fn values_with_frequency_in_order<T: Copy + Eq + Hash>(
+    values: &[T],
+    hasher: ValueHasher,
+) -> Vec<(T, usize)> {
+    let mut value_to_index: ValueMap<T, usize> = hasher.map();
+    let mut values_with_frequency: Vec<(T, usize)> = vec![];
+    for value in values {
+        match value_to_index.entry(*value) {
+            Entry::Occupied(index) => values_with_frequency[*index.get()].1 += 1,
+            Entry::Vacant(index) => {
+                index.insert(values_with_frequency.len());
+                values_with_frequency.push((*value, 1));
+            }
+        }
+    }
+    values_with_frequency
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_counted() {
+        let values = ["b", "a", "b", "c", "b"];
+        let counted = values_with_frequency(&values, ValueHasher::default());
+        assert_eq!(counted.len(), 3);
+        assert_eq!(counted["b"], 3);
+        assert_eq!(counted["a"], 1);
+        assert_eq!(
+            values_with_frequency_in_order(&values, ValueHasher::SipHash),
+            vec![("b", 3), ("a", 1), ("c", 1)]
+        );
+        assert!(values_with_frequency_in_order::<i32>(&[], ValueHasher::default()).is_empty());
+    }
+
+    #[test]
+    fn labels_are_taken_and_put_back() {
+        let mut table = FreqTable::new(vec![1, 2, 0, 1]);
+        assert_eq!(table.available().collect::<Vec<usize>>(), vec![0, 1, 3]);
+        table.take(1);
+        table.take(1);
+        assert_eq!(table.frequency(1), 0);
+        assert_eq!(table.next_available(1..4), Some(3));
+        assert_eq!(table.next_available(1..3), None);
+        let child = table.taken(3);
+        assert_eq!(child.available().collect::<Vec<usize>>(), vec![0]);
+        assert_eq!(table.frequency(3), 1);
+        table.put_back(1);
+        assert_eq!(table.available().collect::<Vec<usize>>(), vec![0, 1, 3]);
+        table.reorder(&[3, 0, 2, 1]);
+        assert_eq!(table, FreqTable::new(vec![1, 1, 0, 1]));
+        assert!(!table.is_exhausted());
+        assert!(FreqTable::new([0; 4]).is_exhausted());
+    }
+
+    #[test]
+    #[should_panic(expected = "The label: 2 has no occurrences left")]
+    fn labels_without_occurrences_are_not_taken() {
+        FreqTable::new(vec![1, 2, 0]).take(2);
+    }
+}
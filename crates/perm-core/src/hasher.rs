@@ -0,0 +1,146 @@
+//! # Hasher
+//!
+//! The hash maps keyed by the values to permute (their labels, frequencies and formatted strings)
+//! are looked up in the hot paths: the formatting of the chunks with many distinct values,
+//! the constraints of `IntoPrunedChunks` and the queries of `CompiledPermutations`.
+//! The std SipHash resists hash flooding, but it is slow for the small keys (e.g. integers and `&str`).
+//!
+//! `ValueHasher` chooses the hash function of these maps:
+//!
+//! - `ValueHasher::SipHash`: the std `RandomState`, randomly seeded.
+//! - `ValueHasher::FxHash` (with the `fxhash` feature): the fast non-cryptographic hash of `rustc-hash`.
+//!
+//! The default is `FxHash` with the `fxhash` feature, `SipHash` otherwise,
+//! and `Permutations::with_hasher` overrides it for the iterators and the tables built from them.
+//! The hasher never changes the permutations nor their order: only the speed of the lookups.
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+
+#[cfg(feature = "fxhash")]
+use rustc_hash::{FxBuildHasher, FxHasher};
+
+/// A hash map keyed by the values to permute, hashed with a `ValueHasher`.
+pub(crate) type ValueMap<K, V> = HashMap<K, V, ValueHashBuilder>;
+
+/// The hash function of the maps keyed by the values to permute.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValueHasher {
+    /// The std SipHash, randomly seeded: resistant to hash flooding.
+    SipHash,
+    /// The FxHash of `rustc-hash`: fast for the small keys, but not resistant to hash flooding.
+    #[cfg(feature = "fxhash")]
+    FxHash,
+}
+
+/// The default hasher is `ValueHasher::FxHash` with the `fxhash` feature, else `ValueHasher::SipHash`.
+impl Default for ValueHasher {
+    fn default() -> Self {
+        #[cfg(feature = "fxhash")]
+        return ValueHasher::FxHash;
+        #[cfg(not(feature = "fxhash"))]
+        return ValueHasher::SipHash;
+    }
+}
+
+impl ValueHasher {
+    /// Create an empty `ValueMap` hashed with this hasher.
+    pub(crate) fn map<K, V>(self) -> ValueMap<K, V> {
+        self.map_with_capacity(0)
+    }
+    /// Create an empty `ValueMap` hashed with this hasher, with room for `capacity` values.
+    pub(crate) fn map_with_capacity<K, V>(self, capacity: usize) -> ValueMap<K, V> {
+        let builder = match self {
+            ValueHasher::SipHash => ValueHashBuilder::SipHash(RandomState::new()),
+            #[cfg(feature = "fxhash")]
+            ValueHasher::FxHash => ValueHashBuilder::FxHash(FxBuildHasher),
+        };
+        HashMap::with_capacity_and_hasher(capacity, builder)
+    }
+}
+
+/// The state of a `ValueHasher`, building the hasher of each key.
+#[derive(Clone)]
+pub(crate) enum ValueHashBuilder {
+    SipHash(RandomState),
+    #[cfg(feature = "fxhash")]
+    FxHash(FxBuildHasher),
+}
+
+impl BuildHasher for ValueHashBuilder {
+    type Hasher = ValueHashState;
+    fn build_hasher(&self) -> ValueHashState {
+        match self {
+            ValueHashBuilder::SipHash(state) => ValueHashState::SipHash(state.build_hasher()),
+            #[cfg(feature = "fxhash")]
+            ValueHashBuilder::FxHash(state) => ValueHashState::FxHash(state.build_hasher()),
+        }
+    }
+}
+
+/// The hasher of a key: the integer writes are forwarded, so that FxHash hashes them in a single step.
+pub(crate) enum ValueHashState {
+    SipHash(DefaultHasher),
+    #[cfg(feature = "fxhash")]
+    FxHash(FxHasher),
+}
+
+macro_rules! forward {
+    ($self:ident, $hasher:ident => $call:expr) => {
+        match $self {
+            ValueHashState::SipHash($hasher) => $call,
+            #[cfg(feature = "fxhash")]
+            ValueHashState::FxHash($hasher) => $call,
+        }
+    };
+}
+
+impl Hasher for ValueHashState {
+    fn finish(&self) -> u64 {
+        forward!(self, hasher => hasher.finish())
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        forward!(self, hasher => hasher.write(bytes))
+    }
+    fn write_u8(&mut self, i: u8) {
+        forward!(self, hasher => hasher.write_u8(i))
+    }
+    fn write_u16(&mut self, i: u16) {
+        forward!(self, hasher => hasher.write_u16(i))
+    }
+    fn write_u32(&mut self, i: u32) {
+        forward!(self, hasher => hasher.write_u32(i))
+    }
+    fn write_u64(&mut self, i: u64) {
+        forward!(self, hasher => hasher.write_u64(i))
+    }
+    fn write_usize(&mut self, i: usize) {
+        forward!(self, hasher => hasher.write_usize(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_hasher_finds_the_values() {
+        let hashers = [
+            ValueHasher::SipHash,
+            #[cfg(feature = "fxhash")]
+            ValueHasher::FxHash,
+        ];
+        for hasher in hashers {
+            let mut map = hasher.map_with_capacity(3);
+            for (index, value) in ["a", "b", "c", "b"].iter().enumerate() {
+                map.entry(*value).or_insert(index);
+            }
+            assert_eq!(map.len(), 3);
+            assert_eq!(map["b"], 1);
+            assert_eq!(map.get("d"), None);
+            let mut map = hasher.map();
+            map.insert(7_i32, 'x');
+            assert_eq!(map[&7], 'x');
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! # Index
+//!
+//! Random access to the permutations of a previously generated output.
+//!
+//! The index file maps the rank of each permutation to its byte offset in the output:
+//! it starts with `INDEX_MAGIC`, followed by one little-endian `u64` offset for each permutation
+//! and by the size of the output, so that the `i`-th permutation is between the offsets `i` and `i + 1`.
+//!
+//! `build_index` scans the output once to write the index,
+//! then `IndexedReader` fetches any permutation with two seeks, in O(1).
+//! The permutations are records as written by `OutputFormat`:
+//! the line breaks inside quoted values do not terminate a permutation.
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+/// The first bytes of an index file.
+pub const INDEX_MAGIC: &[u8; 8] = b"PERMIDX1";
+
+const OFFSET_SIZE: u64 = 8;
+
+/// Scan the `output` and write the offset of each permutation to `index`.
+/// Return the number of permutations.
+pub fn build_index(mut output: impl BufRead, mut index: impl Write) -> io::Result<usize> {
+    index.write_all(INDEX_MAGIC)?;
+    let mut offset = 0u64;
+    let mut permutations = 0;
+    let mut quoted = false;
+    let mut line = vec![];
+    loop {
+        line.clear();
+        let read = output.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        if !quoted {
+            // a new permutation starts here.
+            index.write_all(&offset.to_le_bytes())?;
+            permutations += 1;
+        }
+        if line.iter().filter(|b| **b == b'"').count() % 2 == 1 {
+            quoted = !quoted;
+        }
+        offset += read as u64;
+    }
+    index.write_all(&offset.to_le_bytes())?;
+    index.flush()?;
+    Ok(permutations)
+}
+
+/// Fetch the permutations of an output by rank, with its index.
+pub struct IndexedReader<R, I> {
+    output: R,
+    index: I,
+    len: usize,
+    /// the size of the output, bounding the offsets read from the index.
+    output_size: u64,
+}
+
+impl<R: Read + Seek, I: Read + Seek> IndexedReader<R, I> {
+    /// Initialize the `IndexedReader` with the `output` and its `index`, written by `build_index`.
+    /// Fails if the `index` is not valid.
+    pub fn new(mut output: R, mut index: I) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        index.seek(SeekFrom::Start(0))?;
+        index.read_exact(&mut magic)?;
+        let index_size = index.seek(SeekFrom::End(0))?;
+        if &magic != INDEX_MAGIC
+            || index_size < INDEX_MAGIC.len() as u64 + OFFSET_SIZE
+            || index_size % OFFSET_SIZE != 0
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The file is not a valid permutations index",
+            ));
+        }
+        let len = ((index_size - INDEX_MAGIC.len() as u64) / OFFSET_SIZE - 1) as usize;
+        let output_size = output.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            output,
+            index,
+            len,
+            output_size,
+        })
+    }
+    /// Get the number of permutations of the output.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Check if the output has no permutations.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Get the formatted permutation of rank `rank`, without the line break.
+    /// Return `None` if `rank` is out of bounds.
+    pub fn get(&mut self, rank: usize) -> io::Result<Option<String>> {
+        if rank >= self.len {
+            return Ok(None);
+        }
+        self.index.seek(SeekFrom::Start(
+            INDEX_MAGIC.len() as u64 + rank as u64 * OFFSET_SIZE,
+        ))?;
+        let start = read_offset(&mut self.index)?;
+        let end = read_offset(&mut self.index)?;
+        if end < start || end > self.output_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The index has an invalid range for the permutation {}",
+                    rank
+                ),
+            ));
+        }
+
+        let mut permutation = vec![0; (end - start) as usize];
+        self.output.seek(SeekFrom::Start(start))?;
+        self.output.read_exact(&mut permutation)?;
+        let permutation = String::from_utf8(permutation)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(permutation.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
+
+fn read_offset(index: &mut impl Read) -> io::Result<u64> {
+    let mut offset = [0; OFFSET_SIZE as usize];
+    index.read_exact(&mut offset)?;
+    Ok(u64::from_le_bytes(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn indexed(output: &str) -> IndexedReader<Cursor<&[u8]>, Cursor<Vec<u8>>> {
+        let mut index = vec![];
+        build_index(output.as_bytes(), &mut index).unwrap();
+        IndexedReader::new(Cursor::new(output.as_bytes()), Cursor::new(index)).unwrap()
+    }
+
+    #[test]
+    fn permutations_are_fetched_by_rank() {
+        let mut reader = indexed("1,2,3\n1,3,2\n2,1,3\n");
+        assert_eq!(reader.len(), 3);
+        assert_eq!(reader.get(1).unwrap().unwrap(), "1,3,2");
+        assert_eq!(reader.get(2).unwrap().unwrap(), "2,1,3");
+        assert_eq!(reader.get(3).unwrap(), None);
+    }
+
+    #[test]
+    fn quoted_line_breaks_do_not_split_permutations() {
+        let mut reader = indexed("\"a\nb\",c\nc,\"a\nb\"\n");
+        assert_eq!(reader.len(), 2);
+        assert_eq!(reader.get(1).unwrap().unwrap(), "c,\"a\nb\"");
+    }
+
+    #[test]
+    fn invalid_index_is_rejected() {
+        assert!(IndexedReader::new(Cursor::new(vec![]), Cursor::new(vec![0; 16])).is_err());
+        let output = b"1,2\n2,1\n";
+        let corrupt = |offsets: [u64; 3]| {
+            let mut index = INDEX_MAGIC.to_vec();
+            for offset in offsets {
+                index.extend_from_slice(&offset.to_le_bytes());
+            }
+            IndexedReader::new(Cursor::new(&output[..]), Cursor::new(index)).unwrap()
+        };
+        // the offsets go backwards.
+        let error = corrupt([4, 0, 8]).get(0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // the offsets go past the end of the output.
+        let error = corrupt([0, 4, u64::MAX]).get(1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(corrupt([0, 4, 8]).get(1).unwrap().unwrap(), "2,1");
+    }
+}
@@ -0,0 +1,51 @@
+//! # Perm core
+//!
+//! Generate the permutations of a sequence in chunks.
+pub use algebra::PermutationMap;
+pub use backend::{Backend, BackendError, Selection};
+pub use cancellation::CancellationToken;
+pub use ext::{ParsePermutations, PermutationsExt};
+pub use format::{Labelled, LineEnding, OutputFormat, PermValue, Quoting, ToBytes};
+pub use hasher::ValueHasher;
+pub use mask::Mask;
+pub use parse::{DuplicatePolicy, ParseError, ParseOptions, Validation};
+pub use permutations::compiled::CompiledPermutations;
+pub use permutations::into_beam_chunks::IntoBeamChunks;
+pub use permutations::into_best_first_chunks::IntoBestFirstChunks;
+pub use permutations::into_chunks::IntoChunks;
+pub use permutations::into_chunks::{Chunk, ChunkBuilder};
+pub use permutations::into_chunks::{Permutation, INLINE_LENGTH};
+pub use permutations::into_mapped_chunks::IntoMappedChunks;
+pub use permutations::into_mapped_chunks::MappedChunk;
+pub use permutations::into_optimized_chunks::IntoOptimizedChunks;
+pub use permutations::into_optimized_chunks::OptimizedChunk;
+pub use permutations::into_pruned_chunks::{IntoPrunedChunks, PruneFn};
+pub use permutations::into_ranked_chunks::IntoRankedChunks;
+pub use permutations::latin_rectangles::LatinRectangles;
+pub use permutations::ChunkView;
+pub use permutations::Coverage;
+pub use permutations::ExpansionStrategy;
+pub use permutations::Permutations;
+pub use permutations::Uniqueness;
+pub use permutations::MIN_SUGGESTED_CHUNK_SIZE;
+
+pub mod algebra;
+pub mod apply;
+pub mod backend;
+pub mod cancellation;
+pub mod codes;
+pub mod encoding;
+pub mod ext;
+pub mod format;
+mod freq;
+pub mod hasher;
+pub mod index;
+pub mod limits;
+pub mod mask;
+pub mod math;
+pub mod parse;
+mod permutations;
+pub mod search;
+pub mod table;
+#[cfg(feature = "test-util")]
+pub mod test_util;
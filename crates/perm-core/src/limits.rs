@@ -0,0 +1,58 @@
+//! # Limits
+//!
+//! The limits of the backends, to choose a backend without hard-coding them.
+//!
+//! Only the optimized iterator has a hard limit: its jobs are fixed arrays of `OPTIMIZED_MAX_LEN` positions,
+//! indexed by the label of each distinct value, so both the length and the distinct values are capped.
+//! The normal and the ranked iterators accept any length,
+//! but the ranks (and so `Permutations::nth_permutation` and `Permutations::rank_of`)
+//! are only defined while the number of permutations fits in a `usize`:
+//! see `Permutations::checked_permutations_number`.
+//!
+//! The soft limits of the other modules are re-exported here.
+use crate::Backend;
+
+pub use crate::parse::DEFAULT_MAX_LENGTH;
+pub use crate::permutations::into_chunks::INLINE_LENGTH;
+pub use crate::table::MAX_TABLE_LENGTH;
+
+/// The maximum length of the permutations of the optimized iterator.
+pub const OPTIMIZED_MAX_LEN: usize = 128;
+
+/// The maximum number of distinct values of the optimized iterator: one label for each position of its jobs.
+pub const OPTIMIZED_MAX_DISTINCT: usize = OPTIMIZED_MAX_LEN;
+
+/// Get the maximum length of the permutations generated by `backend`, or `None` if it has no limit.
+pub fn max_length(backend: Backend) -> Option<usize> {
+    match backend {
+        Backend::Optimized => Some(OPTIMIZED_MAX_LEN),
+        Backend::Normal | Backend::Ranked => None,
+    }
+}
+
+/// Get the maximum number of distinct values permuted by `backend`, or `None` if it has no limit.
+pub fn max_distinct_values(backend: Backend) -> Option<usize> {
+    match backend {
+        Backend::Optimized => Some(OPTIMIZED_MAX_DISTINCT),
+        Backend::Normal | Backend::Ranked => None,
+    }
+}
+
+/// Check if `backend` can generate the permutations of `length` values, with `distinct` distinct values.
+pub fn supports(backend: Backend, length: usize, distinct: usize) -> bool {
+    max_length(backend).is_none_or(|max| length <= max)
+        && max_distinct_values(backend).is_none_or(|max| distinct <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_optimized_backend_is_limited() {
+        assert!(supports(Backend::Optimized, OPTIMIZED_MAX_LEN, 2));
+        assert!(!supports(Backend::Optimized, OPTIMIZED_MAX_LEN + 1, 2));
+        assert!(supports(Backend::Normal, usize::MAX, usize::MAX));
+        assert!(supports(Backend::Ranked, usize::MAX, usize::MAX));
+    }
+}
@@ -0,0 +1,345 @@
+//! # Mask
+//!
+//! Generate the password candidates described by a mask, with a different alphabet for each position.
+//!
+//! A mask is parsed like the masks of the password crackers: each `?x` is a built-in charset
+//! and any other character is a literal byte.
+//!
+//! - `?l` is `abcdefghijklmnopqrstuvwxyz`, `?u` is `ABCDEFGHIJKLMNOPQRSTUVWXYZ` and `?d` is `0123456789`.
+//! - `?s` is the printable ASCII symbols, space included: `` !"#$%&'()*+,-./:;<=>?@[\]^_`{|}~``.
+//! - `?a` is `?l?u?d?s` and `??` is a literal `?`.
+//!
+//! For example `?u?l?l?d?d` generates `Aaa00`, `Aaa01`, ..., `Zzz99`.
+//!
+//! The candidates are the words with repetition over the alphabets of the positions,
+//! generated by counting in the mixed radix of the alphabet lengths: the last position changes first,
+//! and the values of each position follow the order of its alphabet.
+//! So the candidate of a given rank is computed directly, and the generation can start from any rank.
+//!
+//! `IntoMaskChunks` yields `Chunk<u8>`s like the iterators over permutations,
+//! so the candidates are written by the same `Pipeline`, e.g. as raw bytes with `perm::raw::EncodeRaw`.
+use std::cmp::max;
+use std::error::Error;
+use std::fmt;
+
+use crate::cancellation::{is_cancelled, CancellationToken};
+use crate::permutations::into_chunks::Chunk;
+use crate::MIN_SUGGESTED_CHUNK_SIZE;
+
+const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &[u8] = b"0123456789";
+const SYMBOLS: &[u8] = b" !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// Why a mask is not valid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MaskError {
+    /// `?` is followed by a character that is not a built-in charset.
+    UnknownCharset {
+        /// the character after `?`.
+        charset: char,
+        /// the offset in bytes of the `?` in the mask.
+        offset: usize,
+    },
+    /// The mask ends with a single `?`.
+    TrailingQuestionMark,
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MaskError::UnknownCharset { charset, offset } => {
+                write!(fmt, "`?{}` is not a charset (at byte {})", charset, offset)
+            }
+            MaskError::TrailingQuestionMark => {
+                write!(fmt, "the mask ends with `?`: use `??` for a literal `?`")
+            }
+        }
+    }
+}
+
+impl Error for MaskError {}
+
+/// The alphabets of the positions of the candidates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    alphabets: Vec<Vec<u8>>,
+}
+
+impl Mask {
+    /// Initialize the `Mask` with the alphabet of each position.
+    /// An empty alphabet has no values, so the mask has no candidates.
+    pub fn new(alphabets: Vec<Vec<u8>>) -> Self {
+        Self { alphabets }
+    }
+    /// Parse a mask like `?u?l?l?d?d`: see the module documentation for the charsets.
+    pub fn parse(mask: &str) -> Result<Self, MaskError> {
+        let mut alphabets = vec![];
+        let mut characters = mask.char_indices();
+        while let Some((offset, character)) = characters.next() {
+            if character != '?' {
+                let mut literal = [0; 4];
+                alphabets.extend(
+                    character
+                        .encode_utf8(&mut literal)
+                        .bytes()
+                        .map(|byte| vec![byte]),
+                );
+                continue;
+            }
+            let alphabet = match characters.next() {
+                Some((_, 'l')) => LOWER.to_vec(),
+                Some((_, 'u')) => UPPER.to_vec(),
+                Some((_, 'd')) => DIGITS.to_vec(),
+                Some((_, 's')) => SYMBOLS.to_vec(),
+                Some((_, 'a')) => [LOWER, UPPER, DIGITS, SYMBOLS].concat(),
+                Some((_, '?')) => vec![b'?'],
+                Some((_, charset)) => return Err(MaskError::UnknownCharset { charset, offset }),
+                None => return Err(MaskError::TrailingQuestionMark),
+            };
+            alphabets.push(alphabet);
+        }
+        Ok(Self { alphabets })
+    }
+    /// Get the alphabet of each position.
+    pub fn alphabets(&self) -> &[Vec<u8>] {
+        &self.alphabets
+    }
+    /// Compute the length of each candidate.
+    pub fn length(&self) -> usize {
+        self.alphabets.len()
+    }
+    /// Compute the total number of candidates: the product of the lengths of the alphabets.
+    /// Return `None` if it does not fit in a `usize`.
+    pub fn checked_candidates_number(&self) -> Option<usize> {
+        self.alphabets.iter().try_fold(1usize, |product, alphabet| {
+            product.checked_mul(alphabet.len())
+        })
+    }
+    /// Suggest a chunk size to split the candidates across `threads` chunks,
+    /// as `Permutations::suggested_chunk_size`.
+    pub fn suggested_chunk_size(&self, threads: usize) -> usize {
+        let candidates_number = self.checked_candidates_number().unwrap_or(usize::MAX);
+        max(
+            MIN_SUGGESTED_CHUNK_SIZE,
+            candidates_number / max(threads, 1),
+        )
+    }
+    /// Create the iterator over chunks of candidates.
+    /// Panics if the chunk size is zero.
+    pub fn into_chunks(self, size: usize) -> IntoMaskChunks {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        IntoMaskChunks::new(self.alphabets, size)
+    }
+}
+
+/// Iterator over `Chunk`s of the candidates of a `Mask`.
+pub struct IntoMaskChunks {
+    alphabets: Vec<Vec<u8>>,
+    /// the position in its alphabet of each value of the next candidate, if any.
+    current: Option<Vec<usize>>,
+    size: usize,
+    next_rank: usize,
+    cancellation: Option<CancellationToken>,
+    chunks_generated: usize,
+}
+
+impl IntoMaskChunks {
+    fn new(alphabets: Vec<Vec<u8>>, size: usize) -> Self {
+        let current = unrank(&alphabets, 0);
+        Self {
+            alphabets,
+            current,
+            size,
+            next_rank: 0,
+            cancellation: None,
+            chunks_generated: 0,
+        }
+    }
+
+    /// Start the generation from the candidate of rank `rank`.
+    /// The iterator is empty if `rank` is not lower than the number of candidates.
+    pub fn starting_at(mut self, rank: usize) -> Self {
+        self.current = unrank(&self.alphabets, rank);
+        self.next_rank = rank;
+        self
+    }
+
+    /// Stop the generation when `cancellation` is cancelled, as `IntoChunks::with_cancellation`.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Get the rank of the first candidate of the next chunk,
+    /// to resume the generation after a cancellation.
+    pub fn next_rank(&self) -> usize {
+        self.next_rank
+    }
+}
+
+/// The iterator implementation to generate a single chunk of candidates.
+/// It terminates when the chunk is full or the last candidate has been generated.
+/// It returns `None` if the generation has been cancelled.
+impl Iterator for IntoMaskChunks {
+    type Item = Chunk<u8>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Chunk::new(self.size);
+        let first_rank = self.next_rank;
+
+        while !chunk.is_full() {
+            if is_cancelled(&self.cancellation) {
+                // the current chunk is discarded.
+                self.next_rank = first_rank;
+                self.current = None;
+                return None;
+            }
+            let mut digits = match self.current.take() {
+                Some(digits) => digits,
+                None => break,
+            };
+            chunk.as_mut().push(
+                digits
+                    .iter()
+                    .zip(&self.alphabets)
+                    .map(|(digit, alphabet)| alphabet[*digit])
+                    .collect(),
+            );
+            if increment(&mut digits, &self.alphabets) {
+                self.current = Some(digits);
+            }
+            match self.next_rank.checked_add(1) {
+                Some(rank) => self.next_rank = rank,
+                // the following ranks do not fit in a usize: the generation ends here.
+                None => self.current = None,
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            let index = self.chunks_generated;
+            self.chunks_generated += 1;
+            Some(chunk.with_position(index, first_rank, self.current.is_none()))
+        }
+    }
+}
+
+/// Compute the positions in the alphabets of the candidate of rank `rank`.
+/// Return `None` if `rank` is not lower than the number of candidates.
+fn unrank(alphabets: &[Vec<u8>], mut rank: usize) -> Option<Vec<usize>> {
+    let mut digits = vec![0; alphabets.len()];
+    for (digit, alphabet) in digits.iter_mut().zip(alphabets).rev() {
+        if alphabet.is_empty() {
+            return None;
+        }
+        *digit = rank % alphabet.len();
+        rank /= alphabet.len();
+    }
+    if rank == 0 {
+        Some(digits)
+    } else {
+        None
+    }
+}
+
+/// Advance the `digits` to the next candidate.
+/// Return `false` if they were the last one.
+fn increment(digits: &mut [usize], alphabets: &[Vec<u8>]) -> bool {
+    for (digit, alphabet) in digits.iter_mut().zip(alphabets).rev() {
+        *digit += 1;
+        if *digit < alphabet.len() {
+            return true;
+        }
+        *digit = 0;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChunkView;
+
+    use super::*;
+
+    fn candidates(chunks: impl Iterator<Item = Chunk<u8>>) -> Vec<String> {
+        chunks
+            .flat_map(|chunk| {
+                (0..chunk.permutations_count())
+                    .map(|index| {
+                        (0..chunk.permutation_length())
+                            .map(|position| char::from(*chunk.value(index, position)))
+                            .collect()
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn masks_are_parsed() {
+        let mask = Mask::parse("a?d??é").unwrap();
+        assert_eq!(mask.length(), 5);
+        assert_eq!(mask.alphabets()[1], DIGITS);
+        assert_eq!(mask.alphabets()[2], b"?");
+        assert_eq!(
+            Mask::parse("?a").unwrap().checked_candidates_number(),
+            Some(95)
+        );
+        assert_eq!(
+            Mask::parse("?d?x"),
+            Err(MaskError::UnknownCharset {
+                charset: 'x',
+                offset: 2
+            })
+        );
+        assert_eq!(Mask::parse("?d?"), Err(MaskError::TrailingQuestionMark));
+    }
+
+    #[test]
+    fn candidates_are_counted_in_mixed_radix() {
+        let mask = Mask::new(vec![b"ab".to_vec(), b"-".to_vec(), b"123".to_vec()]);
+        assert_eq!(mask.checked_candidates_number(), Some(6));
+        assert_eq!(
+            candidates(mask.clone().into_chunks(4)),
+            vec!["a-1", "a-2", "a-3", "b-1", "b-2", "b-3"]
+        );
+        let chunks = mask.clone().into_chunks(4).collect::<Vec<_>>();
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.index(), chunk.first_rank(), chunk.is_last()))
+                .collect::<Vec<_>>(),
+            vec![(0, 0, false), (1, 4, true)]
+        );
+        assert_eq!(
+            candidates(mask.clone().into_chunks(4).starting_at(4)),
+            vec!["b-2", "b-3"]
+        );
+        assert!(mask.into_chunks(4).starting_at(6).next().is_none());
+    }
+
+    #[test]
+    fn generation_ends_at_the_last_usize_rank() {
+        // 10^21 candidates do not fit in a usize: the ranks after `usize::MAX` are not generated.
+        let mask = Mask::parse(&"?d".repeat(21)).unwrap();
+        let chunks = mask
+            .into_chunks(4)
+            .starting_at(usize::MAX)
+            .map(|chunk| (chunk.len(), chunk.first_rank(), chunk.is_last()))
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, vec![(1, usize::MAX, true)]);
+    }
+
+    #[test]
+    fn degenerate_masks_are_generated() {
+        assert_eq!(
+            candidates(Mask::parse("").unwrap().into_chunks(2)),
+            vec![""]
+        );
+        let empty_alphabet = Mask::new(vec![b"ab".to_vec(), vec![]]);
+        assert_eq!(empty_alphabet.checked_candidates_number(), Some(0));
+        assert!(empty_alphabet.into_chunks(2).next().is_none());
+    }
+}
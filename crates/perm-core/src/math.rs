@@ -0,0 +1,169 @@
+//! # Math
+//!
+//! The counting functions behind the ranks and the numbers of permutations,
+//! for the planning of a generation (e.g. how many permutations a prefix has) and for the downstream users.
+//!
+//! - `factorial` and `falling_factorial`: the permutations of `n` distinct values, of all of them or of `k`.
+//! - `binomial` and `multinomial`: the permutations of a multiset, with two or more distinct values.
+//! - `stirling_first` and `stirling_second`: the permutations of `n` values with `k` cycles,
+//!   and the partitions of `n` values into `k` non-empty groups.
+//!
+//! Every function is overflow-safe: it returns `None` if the result does not fit in a `usize`,
+//! and the intermediate results are computed so that they never overflow while the result fits.
+
+/// Compute `n!`, the number of permutations of `n` distinct values.
+/// Return `None` if the result does not fit in a `usize`.
+/// It is a `const fn`, for the lengths of the tables computed at compile time (see `table`).
+pub const fn factorial(n: usize) -> Option<usize> {
+    let mut result: usize = 1;
+    let mut factor = 2;
+    while factor <= n {
+        result = match result.checked_mul(factor) {
+            Some(result) => result,
+            None => return None,
+        };
+        factor += 1;
+    }
+    Some(result)
+}
+
+/// Compute the falling factorial `n * (n - 1) * ... * (n - k + 1)`,
+/// the number of permutations of `k` out of `n` distinct values: zero if `k` is greater than `n`.
+/// Return `None` if the result does not fit in a `usize`.
+pub fn falling_factorial(n: usize, k: usize) -> Option<usize> {
+    if k > n {
+        return Some(0);
+    }
+    ((n - k + 1)..=n).try_fold(1_usize, |result, factor| result.checked_mul(factor))
+}
+
+/// Compute the binomial coefficient `n! / (k! * (n - k)!)`,
+/// the number of permutations of `k` equal values and `n - k` other equal values: zero if `k` is greater than `n`.
+/// Return `None` if the result does not fit in a `usize`.
+pub fn binomial(n: usize, k: usize) -> Option<usize> {
+    if k > n {
+        return Some(0);
+    }
+    // C(n, i + 1) = C(n, i) * (n - i) / (i + 1), up to the smaller of `k` and `n - k`:
+    // each C(n, i) is not greater than the result, and its product with `n - i` fits in a `u128`.
+    let mut result: u128 = 1;
+    for i in 0..k.min(n - k) as u128 {
+        result = result * (n as u128 - i) / (i + 1);
+        if result > usize::MAX as u128 {
+            return None;
+        }
+    }
+    Some(result as usize)
+}
+
+/// Compute the multinomial coefficient of the `frequencies`:
+/// `(f_1+f_2+...+f_k)! / (f_1!*f_2!*...*f_k!)`, the number of permutations of a multiset.
+/// It is computed as a product of binomial coefficients,
+/// so that the intermediate results are never greater than the final one.
+/// Return `None` if the result does not fit in a `usize`.
+pub fn multinomial(frequencies: impl IntoIterator<Item = usize>) -> Option<usize> {
+    let mut result: u128 = 1;
+    let mut total: u128 = 0;
+    for frequency in frequencies {
+        for i in 1..=frequency as u128 {
+            total += 1;
+            result = result.checked_mul(total)? / i;
+            if result > usize::MAX as u128 {
+                return None;
+            }
+        }
+    }
+    Some(result as usize)
+}
+
+/// Compute the unsigned Stirling number of the first kind `[n k]`,
+/// the number of permutations of `n` distinct values with exactly `k` cycles.
+/// Return `None` if the result does not fit in a `usize`.
+pub fn stirling_first(n: usize, k: usize) -> Option<usize> {
+    // [m j] = (m - 1) * [m-1 j] + [m-1 j-1]
+    stirling(n, k, |m, _| m - 1)
+}
+
+/// Compute the Stirling number of the second kind `{n k}`,
+/// the number of partitions of `n` distinct values into exactly `k` non-empty groups.
+/// Return `None` if the result does not fit in a `usize`.
+pub fn stirling_second(n: usize, k: usize) -> Option<usize> {
+    // {m j} = j * {m-1 j} + {m-1 j-1}
+    stirling(n, k, |_, j| j)
+}
+
+/// Compute the Stirling numbers of the recurrence `S(m, j) = weight(m, j) * S(m-1, j) + S(m-1, j-1)`,
+/// with `S(0, 0) = 1`.
+/// Only the numbers `S(m, j)` that `S(n, k)` depends on are computed, the ones with `k - j <= n - m`:
+/// each one is added to the result at least once, so none of them overflows while the result fits.
+fn stirling(n: usize, k: usize, weight: fn(usize, usize) -> usize) -> Option<usize> {
+    if k > n {
+        return Some(0);
+    }
+    // the numbers of the current row, by `j`: the ones below the band are never read again.
+    let mut row: Vec<u128> = vec![0; k + 1];
+    row[0] = 1;
+    for m in 1..=n {
+        let first = k.saturating_sub(n - m);
+        for j in (first..=m.min(k)).rev() {
+            let previous = if j > 0 { row[j - 1] } else { 0 };
+            row[j] = (weight(m, j) as u128)
+                .checked_mul(row[j])?
+                .checked_add(previous)?;
+            if row[j] > usize::MAX as u128 {
+                return None;
+            }
+        }
+    }
+    Some(row[k] as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factorials_are_checked() {
+        assert_eq!(factorial(0), Some(1));
+        assert_eq!(factorial(5), Some(120));
+        assert_eq!(factorial(20), Some(2_432_902_008_176_640_000));
+        assert_eq!(factorial(21), None);
+        assert_eq!(falling_factorial(5, 0), Some(1));
+        assert_eq!(falling_factorial(5, 2), Some(20));
+        assert_eq!(falling_factorial(2, 5), Some(0));
+        assert_eq!(falling_factorial(usize::MAX, 1), Some(usize::MAX));
+        assert_eq!(falling_factorial(usize::MAX, 2), None);
+    }
+
+    #[test]
+    fn binomials_and_multinomials_are_checked() {
+        assert_eq!(binomial(5, 2), Some(10));
+        assert_eq!(binomial(5, 0), Some(1));
+        assert_eq!(binomial(2, 5), Some(0));
+        assert_eq!(binomial(67, 33), Some(14_226_520_737_620_288_370));
+        assert_eq!(binomial(68, 34), None);
+        assert_eq!(binomial(usize::MAX, 1), Some(usize::MAX));
+        assert_eq!(multinomial(vec![]), Some(1));
+        assert_eq!(multinomial(vec![1, 2, 2]), Some(30));
+        assert_eq!(multinomial(vec![1; 21]), None);
+    }
+
+    #[test]
+    fn stirling_numbers_are_checked() {
+        let first = (0..=5).map(|k| stirling_first(5, k).unwrap());
+        assert_eq!(first.collect::<Vec<usize>>(), vec![0, 24, 50, 35, 10, 1]);
+        let second = (0..=5).map(|k| stirling_second(5, k).unwrap());
+        assert_eq!(second.collect::<Vec<usize>>(), vec![0, 1, 15, 25, 10, 1]);
+        assert_eq!(stirling_first(0, 0), Some(1));
+        assert_eq!(stirling_second(3, 4), Some(0));
+        // the permutations with any number of cycles are all the permutations.
+        let cycles = (0..=10)
+            .map(|k| stirling_first(10, k).unwrap())
+            .sum::<usize>();
+        assert_eq!(Some(cycles), factorial(10));
+        assert_eq!(stirling_first(21, 1), factorial(20));
+        assert_eq!(stirling_first(22, 1), None);
+        assert_eq!(stirling_second(1000, 999), Some(binomial(1000, 2).unwrap()));
+        assert_eq!(stirling_second(1000, 2), None);
+    }
+}
@@ -0,0 +1,565 @@
+//! # Parse
+//!
+//! Parse the input text into the values to permute.
+//!
+//! A valid input is a list of comma separated numbers.
+//! The whitespaces around each number are ignored, and so are a trailing line break and trailing whitespaces.
+//! A blank input has no values: its only permutation is the empty one, written as an empty line.
+//!
+//! `Validation` chooses which values are valid: any number that can be parsed as a `f64` (the default),
+//! only integers, or any value at all.
+//! With `ParseOptions::with_normalization` the equal numbers written differently are written the same way:
+//! the leading `+`, the leading zeros, the trailing zeros of the fractional part and the sign of zero are removed,
+//! so that `+1.0`, `1` and `01.` are all written as `1`.
+//! The normalized number is always a slice of the input, so the forms that would need other characters,
+//! like `1e3`, `.5` or `-01`, are only normalized as far as possible.
+//!
+//! With `ParseOptions::with_counts` a value can be followed by its number of occurrences:
+//! `1x3,2x2,5` is read as `1,1,1,2,2,5`, so the inputs dominated by the equal values stay short.
+//! The count is the decimal number after the last `x` of the token, and it must be positive.
+//!
+//! `DuplicatePolicy` chooses what to do with the equal values (after the normalization):
+//! keep them and generate the permutations of the multiset (the default),
+//! reject the input, or keep only the first occurrence of each value.
+//!
+//! The parser never panics: any malformed input is reported as a `ParseError`,
+//! with the index and the byte offset of the first invalid token,
+//! and the inputs with more values than `ParseOptions::max_length`,
+//! or more distinct values than `ParseOptions::max_distinct_values`, are rejected
+//! before any permutation is generated.
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+/// The maximum number of values accepted by default.
+pub const DEFAULT_MAX_LENGTH: usize = 10_000;
+
+const SEPARATOR: char = ',';
+
+/// Which values are valid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Validation {
+    /// Any number that can be parsed as a `f64`.
+    Number,
+    /// Only the integers: an optional sign followed by decimal digits.
+    Integer,
+    /// Any value, without validation.
+    Any,
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Validation::Number => write!(fmt, "number"),
+            Validation::Integer => write!(fmt, "integer"),
+            Validation::Any => write!(fmt, "value"),
+        }
+    }
+}
+
+/// What to do with the equal values of the input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the equal values: the permutations are those of the multiset.
+    Keep,
+    /// Reject the input.
+    Reject,
+    /// Keep only the first occurrence of each value.
+    Dedup,
+}
+
+/// The options to parse the input text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    max_length: usize,
+    max_distinct_values: Option<usize>,
+    validation: Validation,
+    normalization: bool,
+    counts: bool,
+    duplicates: DuplicatePolicy,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_length: DEFAULT_MAX_LENGTH,
+            max_distinct_values: None,
+            validation: Validation::Number,
+            normalization: false,
+            counts: false,
+            duplicates: DuplicatePolicy::Keep,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Initialize the default `ParseOptions`: at most `DEFAULT_MAX_LENGTH` numbers, written as in the input.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the maximum number of values.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+    /// Get the maximum number of values.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+    /// Set the maximum number of distinct values, after the normalization.
+    /// There is no maximum by default.
+    pub fn with_max_distinct_values(mut self, max_distinct_values: usize) -> Self {
+        self.max_distinct_values = Some(max_distinct_values);
+        self
+    }
+    /// Get the maximum number of distinct values, if any.
+    pub fn max_distinct_values(&self) -> Option<usize> {
+        self.max_distinct_values
+    }
+    /// Set which values are valid.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+    /// Get which values are valid.
+    pub fn validation(&self) -> Validation {
+        self.validation
+    }
+    /// Set whether the numbers are normalized.
+    pub fn with_normalization(mut self, normalization: bool) -> Self {
+        self.normalization = normalization;
+        self
+    }
+    /// Check if the numbers are normalized.
+    pub fn normalization(&self) -> bool {
+        self.normalization
+    }
+    /// Set whether a value can be followed by its number of occurrences, as `1x3`.
+    pub fn with_counts(mut self, counts: bool) -> Self {
+        self.counts = counts;
+        self
+    }
+    /// Check if a value can be followed by its number of occurrences.
+    pub fn counts(&self) -> bool {
+        self.counts
+    }
+    /// Set what to do with the equal values.
+    pub fn with_duplicates(mut self, duplicates: DuplicatePolicy) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+    /// Get what to do with the equal values.
+    pub fn duplicates(&self) -> DuplicatePolicy {
+        self.duplicates
+    }
+}
+
+/// Why the input text is not valid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A token is not valid.
+    InvalidToken {
+        /// the token, without the surrounding whitespaces.
+        token: String,
+        /// the position of the token in the list of values.
+        index: usize,
+        /// the offset in bytes of the token in the input text.
+        offset: usize,
+        /// which values are valid.
+        validation: Validation,
+    },
+    /// A value is equal to a previous one, with `DuplicatePolicy::Reject`.
+    Duplicate {
+        /// the value, after the normalization.
+        token: String,
+        /// the position of the duplicate in the list of values.
+        index: usize,
+        /// the offset in bytes of the duplicate in the input text.
+        offset: usize,
+        /// the position of the first occurrence in the list of values.
+        first_index: usize,
+    },
+    /// The count of a value is not a positive number, with `ParseOptions::with_counts`.
+    InvalidCount {
+        /// the token, without the surrounding whitespaces.
+        token: String,
+        /// the position of the token in the list of values.
+        index: usize,
+        /// the offset in bytes of the token in the input text.
+        offset: usize,
+    },
+    /// The input has more values than the maximum length.
+    TooLong {
+        /// the maximum number of values.
+        max_length: usize,
+    },
+    /// The input has more distinct values than the maximum.
+    TooManyDistinctValues {
+        /// the maximum number of distinct values.
+        max_distinct_values: usize,
+        /// the first value over the maximum.
+        token: String,
+        /// the position of the value in the list of values.
+        index: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidToken {
+                token,
+                index,
+                offset,
+                validation,
+            } => write!(
+                fmt,
+                "`{}` is not a valid {} (value {} at byte {})",
+                token, validation, index, offset
+            ),
+            ParseError::Duplicate {
+                token,
+                index,
+                offset,
+                first_index,
+            } => write!(
+                fmt,
+                "`{}` is duplicated (value {} at byte {}, first seen as value {})",
+                token, index, offset, first_index
+            ),
+            ParseError::InvalidCount {
+                token,
+                index,
+                offset,
+            } => write!(
+                fmt,
+                "`{}` does not have a positive count (value {} at byte {})",
+                token, index, offset
+            ),
+            ParseError::TooLong { max_length } => {
+                write!(fmt, "the input has more than {} values", max_length)
+            }
+            ParseError::TooManyDistinctValues {
+                max_distinct_values,
+                token,
+                index,
+            } => write!(
+                fmt,
+                "the input has more than {} distinct values (`{}` is value {})",
+                max_distinct_values, token, index
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Split the `text` into the values, checking that each one is valid.
+pub(crate) fn parse_values<'a>(
+    text: &'a str,
+    options: &ParseOptions,
+) -> Result<Vec<&'a str>, ParseError> {
+    let text = text.trim_end();
+    let mut values = vec![];
+    if text.trim_start().is_empty() {
+        // the blank input has no values: its only permutation is the empty one.
+        return Ok(values);
+    }
+    let mut first_indexes = HashMap::new();
+    let mut distinct_values = HashSet::new();
+    let mut offset = 0;
+    // the number of values read, with their counts.
+    let mut length: usize = 0;
+    for (index, raw_token) in text.split(SEPARATOR).enumerate() {
+        let token = raw_token.trim();
+        let token_offset = offset + (raw_token.len() - raw_token.trim_start().len());
+        let (token, count) = if options.counts {
+            split_count(token).ok_or_else(|| ParseError::InvalidCount {
+                token: token.to_string(),
+                index,
+                offset: token_offset,
+            })?
+        } else {
+            (token, 1)
+        };
+        length = match length.checked_add(count) {
+            Some(length) if length <= options.max_length => length,
+            _ => {
+                return Err(ParseError::TooLong {
+                    max_length: options.max_length,
+                })
+            }
+        };
+        if !is_valid(token, options.validation) {
+            return Err(ParseError::InvalidToken {
+                token: token.to_string(),
+                index,
+                offset: token_offset,
+                validation: options.validation,
+            });
+        }
+        let value = if options.normalization {
+            normalize(token)
+        } else {
+            token
+        };
+        if let Some(max_distinct_values) = options.max_distinct_values {
+            // the distinct values are counted only when they are limited.
+            let distinct = distinct_values.len();
+            if distinct_values.insert(value) && distinct == max_distinct_values {
+                return Err(ParseError::TooManyDistinctValues {
+                    max_distinct_values,
+                    token: value.to_string(),
+                    index,
+                });
+            }
+        }
+        for _ in 0..count {
+            match options.duplicates {
+                DuplicatePolicy::Keep => values.push(value),
+                DuplicatePolicy::Reject => {
+                    if let Some(first_index) = first_indexes.insert(value, index) {
+                        return Err(ParseError::Duplicate {
+                            token: value.to_string(),
+                            index,
+                            offset: token_offset,
+                            first_index,
+                        });
+                    }
+                    values.push(value);
+                }
+                DuplicatePolicy::Dedup => {
+                    if let Entry::Vacant(entry) = first_indexes.entry(value) {
+                        entry.insert(index);
+                        values.push(value);
+                    }
+                }
+            }
+        }
+        offset += raw_token.len() + SEPARATOR.len_utf8();
+    }
+    Ok(values)
+}
+
+/// Split the count after the last `x` of the `token`, if it is followed by digits:
+/// the value and the count, or `None` if the count is not positive.
+/// The token without a count is a single value.
+fn split_count(token: &str) -> Option<(&str, usize)> {
+    match token.rsplit_once('x') {
+        Some((value, count)) if is_digits(count) => match count.parse::<usize>() {
+            Ok(count) if count > 0 => Some((value.trim_end(), count)),
+            _ => None,
+        },
+        _ => Some((token, 1)),
+    }
+}
+
+fn is_valid(token: &str, validation: Validation) -> bool {
+    match validation {
+        Validation::Number => token.parse::<f64>().is_ok(),
+        Validation::Integer => is_digits(token.strip_prefix(['+', '-']).unwrap_or(token)),
+        Validation::Any => true,
+    }
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Remove the characters that do not change the value of the number `token`,
+/// returning a slice of it.
+fn normalize(token: &str) -> &str {
+    let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+    let negative = unsigned.len() < token.len() && token.starts_with('-');
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(dot) => (&unsigned[..dot], &unsigned[dot + 1..]),
+        None => (unsigned, ""),
+    };
+    if !is_digits(integer) || !(fraction.is_empty() || is_digits(fraction)) {
+        // not a plain decimal number: only the `+` can be removed.
+        return if negative || unsigned.is_empty() {
+            token
+        } else {
+            unsigned
+        };
+    }
+    let sign_length = token.len() - unsigned.len();
+    let fraction = fraction.trim_end_matches('0');
+    let end = if fraction.is_empty() {
+        sign_length + integer.len()
+    } else {
+        sign_length + integer.len() + 1 + fraction.len()
+    };
+    let is_zero = fraction.is_empty() && integer.bytes().all(|b| b == b'0');
+    let start = if negative && !is_zero {
+        // the sign must be followed by the digits, so the leading zeros are kept.
+        0
+    } else {
+        // at least one digit is kept before the dot.
+        sign_length + integer.find(|c| c != '0').unwrap_or(integer.len() - 1)
+    };
+    &token[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_line_break_is_accepted() {
+        assert_eq!(
+            parse_values("1, 2 ,3 \r\n", &ParseOptions::new()),
+            Ok(vec!["1", "2", "3"])
+        );
+    }
+
+    #[test]
+    fn first_invalid_token_is_located() {
+        assert_eq!(
+            parse_values("1,2, x,y", &ParseOptions::new()),
+            Err(ParseError::InvalidToken {
+                token: "x".to_string(),
+                index: 2,
+                offset: 5,
+                validation: Validation::Number
+            })
+        );
+        assert_eq!(
+            parse_values("1,,2", &ParseOptions::new()),
+            Err(ParseError::InvalidToken {
+                token: "".to_string(),
+                index: 1,
+                offset: 2,
+                validation: Validation::Number
+            })
+        );
+    }
+
+    #[test]
+    fn blank_input_has_no_values() {
+        assert_eq!(parse_values("", &ParseOptions::new()), Ok(vec![]));
+        let any = ParseOptions::new().with_validation(Validation::Any);
+        assert_eq!(parse_values(" \r\n", &any), Ok(vec![]));
+    }
+
+    #[test]
+    fn long_input_is_rejected() {
+        let options = ParseOptions::new().with_max_length(3);
+        assert!(parse_values("1,2,3", &options).is_ok());
+        assert_eq!(
+            parse_values("1,2,3,4", &options),
+            Err(ParseError::TooLong { max_length: 3 })
+        );
+    }
+
+    #[test]
+    fn diverse_input_is_rejected() {
+        let options = ParseOptions::new().with_max_distinct_values(2);
+        assert!(parse_values("1,2,1,2,2", &options).is_ok());
+        assert_eq!(
+            parse_values("1,2,1,3", &options),
+            Err(ParseError::TooManyDistinctValues {
+                max_distinct_values: 2,
+                token: "3".to_string(),
+                index: 3
+            })
+        );
+        let normalized = options.with_normalization(true);
+        assert!(parse_values("1,2,+1.0", &normalized).is_ok());
+    }
+
+    #[test]
+    fn values_are_repeated_by_their_count() {
+        let options = ParseOptions::new().with_counts(true);
+        assert_eq!(
+            parse_values("1x3, 2 x2,5", &options),
+            Ok(vec!["1", "1", "1", "2", "2", "5"])
+        );
+        assert_eq!(
+            parse_values("1,2x0", &options),
+            Err(ParseError::InvalidCount {
+                token: "2x0".to_string(),
+                index: 1,
+                offset: 2
+            })
+        );
+        assert_eq!(
+            parse_values("1x5", &options.with_max_length(4)),
+            Err(ParseError::TooLong { max_length: 4 })
+        );
+        let unlimited = options.with_max_length(usize::MAX);
+        assert_eq!(
+            parse_values(&format!("1,1x{}", usize::MAX), &unlimited),
+            Err(ParseError::TooLong {
+                max_length: usize::MAX
+            })
+        );
+        assert_eq!(
+            parse_values("1x2,2", &options.with_duplicates(DuplicatePolicy::Dedup)),
+            Ok(vec!["1", "2"])
+        );
+        assert!(parse_values("1x2", &options.with_duplicates(DuplicatePolicy::Reject)).is_err());
+        // without counts, `x` is not a number.
+        assert!(parse_values("1x3", &ParseOptions::new()).is_err());
+    }
+
+    #[test]
+    fn validation_is_configurable() {
+        let integers = ParseOptions::new().with_validation(Validation::Integer);
+        assert_eq!(
+            parse_values("+1,-2,03", &integers),
+            Ok(vec!["+1", "-2", "03"])
+        );
+        assert!(parse_values("1,2.5", &integers).is_err());
+        let any = ParseOptions::new().with_validation(Validation::Any);
+        assert_eq!(parse_values("a, b,", &any), Ok(vec!["a", "b", ""]));
+    }
+
+    #[test]
+    fn numbers_are_normalized() {
+        let options = ParseOptions::new().with_normalization(true);
+        assert_eq!(
+            parse_values("+1.0,1,01.,1.50,-0.0,000,-1.20,-0.5", &options),
+            Ok(vec!["1", "1", "1", "1.5", "0", "0", "-1.2", "-0.5"])
+        );
+        assert_eq!(
+            parse_values("+1e3,.50,-01", &options),
+            Ok(vec!["1e3", ".50", "-01"])
+        );
+    }
+
+    #[test]
+    fn duplicates_are_rejected_or_removed() {
+        let options = ParseOptions::new().with_normalization(true);
+        assert_eq!(parse_values("1,2,1.0", &options), Ok(vec!["1", "2", "1"]));
+        assert_eq!(
+            parse_values(
+                "1,2, 1.0",
+                &options.with_duplicates(DuplicatePolicy::Reject)
+            ),
+            Err(ParseError::Duplicate {
+                token: "1".to_string(),
+                index: 2,
+                offset: 5,
+                first_index: 0
+            })
+        );
+        assert_eq!(
+            parse_values(
+                "2,1,2,3,1",
+                &options.with_duplicates(DuplicatePolicy::Dedup)
+            ),
+            Ok(vec!["2", "1", "3"])
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn arbitrary_input_never_panics(text: String) {
+        let options = ParseOptions::new()
+            .with_max_length(8)
+            .with_normalization(true);
+        let _ = parse_values(&text, &options);
+        let _ = parse_values(&text, &options.with_validation(Validation::Any));
+    }
+}
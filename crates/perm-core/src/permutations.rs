@@ -0,0 +1,1827 @@
+//! # Permutation
+//!
+//! Parse the input string and generate the iterator over chunks of permutations.
+
+use std::cmp::max;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::ops::{Range, RangeInclusive};
+
+pub use budget::Coverage;
+use compiled::CompiledPermutations;
+use into_beam_chunks::IntoBeamChunks;
+use into_best_first_chunks::IntoBestFirstChunks;
+use into_chunks::IntoChunks;
+use into_mapped_chunks::IntoMappedChunks;
+use into_optimized_chunks::IntoOptimizedChunks;
+use into_pruned_chunks::{IntoPrunedChunks, PruneFn};
+use into_ranked_chunks::IntoRankedChunks;
+use latin_rectangles::LatinRectangles;
+
+use crate::backend::{Backend, BackendError};
+use crate::format::{Labelled, OutputFormat, PermValue};
+use crate::freq::values_with_frequency;
+use crate::hasher::ValueHasher;
+use crate::math::multinomial;
+use crate::parse::{parse_values, ParseError, ParseOptions};
+
+mod arena;
+mod budget;
+pub mod compiled;
+mod constraints;
+pub mod into_beam_chunks;
+pub mod into_best_first_chunks;
+pub mod into_chunks;
+pub mod into_mapped_chunks;
+pub mod into_optimized_chunks;
+pub mod into_pruned_chunks;
+pub mod into_ranked_chunks;
+pub mod latin_rectangles;
+mod paranoid;
+mod patterns;
+mod top_k;
+pub(crate) mod utils;
+
+/// Random access to the values of the permutations in a chunk,
+/// independently of how the chunk stores them.
+/// It is implemented by `Chunk` and `OptimizedChunk`, and it is used by the output formats.
+pub trait ChunkView {
+    /// The type of the permuted values.
+    type Value;
+    /// The number of permutations in the chunk.
+    fn permutations_count(&self) -> usize;
+    /// The length of each permutation.
+    fn permutation_length(&self) -> usize;
+    /// The value at `position` of the permutation at `index`.
+    /// Panics if `index` or `position` are out of bounds.
+    fn value(&self, index: usize, position: usize) -> &Self::Value;
+}
+
+/// The minimum chunk size suggested by `Permutations::suggested_chunk_size`.
+/// Smaller chunks spend more time in the bookkeeping than in the generation.
+pub const MIN_SUGGESTED_CHUNK_SIZE: usize = 16;
+
+/// Which permutations are generated when the input has equal values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Uniqueness {
+    /// Each distinct permutation of the multiset is generated exactly once:
+    /// swapping two equal values does not give a new permutation.
+    Distinct,
+    /// The equal values are distinguishable, as if they were different items:
+    /// all the `n!` orderings are generated, so equal permutations are repeated.
+    WithDuplicates,
+}
+
+/// The order in which the job tree is explored.
+///
+/// Both strategies generate the same permutations in the same order,
+/// but with a different memory footprint.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExpansionStrategy {
+    /// The job queue is a stack: the last job added is expanded first.
+    /// The queue holds at most `O(n^2)` jobs, and the permutations sharing a prefix are contiguous.
+    DepthFirst,
+    /// The job queue is a FIFO queue: the jobs are expanded level by level.
+    /// The queue holds a whole level of the tree, up to the number of permutations,
+    /// and no permutation is generated until the last level is reached.
+    BreadthFirst,
+}
+
+/// Permutations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permutations<T: Copy> {
+    values: Vec<T>,
+    uniqueness: Uniqueness,
+    strategy: ExpansionStrategy,
+    hasher: ValueHasher,
+}
+
+impl<T: Copy + Eq + Hash> Permutations<T> {
+    /// Initialize a new `Permutations` with the values to permute.
+    /// The permutations are distinct: see `Permutations::distinct`.
+    pub fn new(values: Vec<T>) -> Self {
+        Self::distinct(values)
+    }
+    /// Initialize a new `Permutations` generating each distinct permutation exactly once.
+    pub fn distinct(values: Vec<T>) -> Self {
+        Self {
+            values,
+            uniqueness: Uniqueness::Distinct,
+            strategy: ExpansionStrategy::DepthFirst,
+            hasher: ValueHasher::default(),
+        }
+    }
+    /// Initialize a new `Permutations` generating all the `n!` orderings of the values,
+    /// treating the equal values as distinguishable.
+    pub fn with_duplicates(values: Vec<T>) -> Self {
+        Self {
+            values,
+            uniqueness: Uniqueness::WithDuplicates,
+            strategy: ExpansionStrategy::DepthFirst,
+            hasher: ValueHasher::default(),
+        }
+    }
+    /// Initialize a new `Permutations` of a multiset given as each value with its number of occurrences:
+    /// `[(1, 3), (2, 2)]` is the same as the values `[1, 1, 1, 2, 2]`.
+    pub fn from_counts(counts: &[(T, usize)]) -> Self {
+        Self::new(
+            counts
+                .iter()
+                .flat_map(|(value, count)| std::iter::repeat_n(*value, *count))
+                .collect(),
+        )
+    }
+    /// Get which permutations are generated when the input has equal values.
+    pub fn uniqueness(&self) -> Uniqueness {
+        self.uniqueness
+    }
+    /// Set the order in which the job tree is explored by the chunk iterators.
+    /// The default is `ExpansionStrategy::DepthFirst`.
+    /// The top-k search is always depth-first, to reach the leaves and prune early.
+    pub fn with_strategy(mut self, strategy: ExpansionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    /// Get the order in which the job tree is explored.
+    pub fn strategy(&self) -> ExpansionStrategy {
+        self.strategy
+    }
+    /// Set the hash function of the maps keyed by the values, built by the iterators and the tables
+    /// (e.g. to format the chunks, to check the constraints of the pruned iterator, or to answer the queries).
+    /// The default is `ValueHasher::default()`: `FxHash` with the `fxhash` feature, `SipHash` otherwise.
+    /// The permutations and their order do not depend on it.
+    pub fn with_hasher(mut self, hasher: ValueHasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+    /// Get the hash function of the maps keyed by the values.
+    pub fn hasher(&self) -> ValueHasher {
+        self.hasher
+    }
+    /// Get the values to permute.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+    /// Compute the length of each permutation.
+    pub fn length(&self) -> usize {
+        self.values.len()
+    }
+    /// Compute the total number of permutations.
+    /// Panics if it does not fit in a `usize`.
+    pub fn permutations_number(&self) -> usize {
+        self.checked_permutations_number()
+            .expect("The number of permutations does not fit in a usize")
+    }
+    /// Compute the total number of permutations:
+    /// the multinomial coefficient of the frequencies of the values if they are `Distinct`,
+    /// the factorial of the length otherwise.
+    /// Return `None` if it does not fit in a `usize`.
+    pub fn checked_permutations_number(&self) -> Option<usize> {
+        match self.uniqueness {
+            Uniqueness::Distinct => multinomial(
+                values_with_frequency(&self.values, self.hasher)
+                    .values()
+                    .copied(),
+            ),
+            Uniqueness::WithDuplicates => multinomial(self.values.iter().map(|_| 1)),
+        }
+    }
+    /// Suggest a chunk size to split the permutations across `threads` chunks.
+    /// The number of permutations saturates to `usize::MAX` when it is too big,
+    /// and the chunk size is never smaller than `MIN_SUGGESTED_CHUNK_SIZE`.
+    pub fn suggested_chunk_size(&self, threads: usize) -> usize {
+        let permutations_number = self.checked_permutations_number().unwrap_or(usize::MAX);
+        max(
+            MIN_SUGGESTED_CHUNK_SIZE,
+            permutations_number / max(threads, 1),
+        )
+    }
+    /// Check if the input values is short enough to use the optimized version of the algorithm:
+    /// at most `limits::OPTIMIZED_MAX_LEN` values and `limits::OPTIMIZED_MAX_DISTINCT` distinct values.
+    pub fn can_be_optimized(&self) -> bool {
+        Backend::Optimized.check(self).is_ok()
+    }
+    /// Create the optimized iterator over chunks of permutations.
+    /// The permutations are generated in the order of `Permutations::into_ranked_chunks`,
+    /// so the chunk `i` has the ranks `[i * size, (i + 1) * size)` whatever the iterator.
+    /// Panics if the chunk size is zero
+    /// or the input values are not short enough to use the optimized version of the algorithm:
+    /// see `Permutations::try_into_optimized_chunks`.
+    pub fn into_optimized_chunks(self, size: usize) -> IntoOptimizedChunks<T> {
+        match self.try_into_optimized_chunks(size) {
+            Ok(chunks) => chunks,
+            Err(error) => panic!("Cannot use optimized_chunks because {}", error),
+        }
+    }
+    /// Create the optimized iterator over chunks of permutations,
+    /// or return the `BackendError` telling why the input does not fit in its jobs.
+    /// Panics if the chunk size is zero.
+    pub fn try_into_optimized_chunks(
+        self,
+        size: usize,
+    ) -> Result<IntoOptimizedChunks<T>, BackendError> {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        Backend::Optimized.check(&self)?;
+        Ok(IntoOptimizedChunks::new(
+            self.values,
+            size,
+            self.uniqueness,
+            self.strategy,
+            self.hasher,
+        ))
+    }
+    /// Create the slower iterator over chunks of permutations
+    /// with no limitations of permutation length.
+    /// The permutations are generated in the order of `Permutations::into_ranked_chunks`,
+    /// so the chunk `i` has the ranks `[i * size, (i + 1) * size)` whatever the iterator.
+    /// Panics if the chunk size is zero.
+    pub fn into_chunks(self, size: usize) -> IntoChunks<T> {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        IntoChunks::new(
+            self.values,
+            size,
+            self.uniqueness,
+            self.strategy,
+            self.hasher,
+        )
+    }
+    /// Compute the chunk size so that each chunk written with `format` is at most `byte_budget` bytes long.
+    /// All the permutations have the same values, so they are written with the same length:
+    /// the chunks have a predictable size, whatever the length of the input.
+    /// The chunk size is at least one, even if a single permutation is longer than `byte_budget`.
+    pub fn chunk_size_for_bytes(&self, byte_budget: usize, format: OutputFormat) -> usize
+    where
+        T: PermValue,
+    {
+        max(1, byte_budget / format.permutation_len(self.values.iter()))
+    }
+    /// Create the slower iterator over chunks of permutations,
+    /// filling each chunk until its text with the default `OutputFormat` reaches `byte_budget` bytes.
+    pub fn into_chunks_by_bytes(self, byte_budget: usize) -> IntoChunks<T>
+    where
+        T: PermValue,
+    {
+        let size = self.chunk_size_for_bytes(byte_budget, OutputFormat::default());
+        self.into_chunks(size)
+    }
+    /// Create the low-memory iterator over chunks of permutations, without a job tree.
+    /// The permutations are generated in lexicographic order of the first occurrences of the values,
+    /// computing each one from the previous one,
+    /// and the generation can start from any rank with `IntoRankedChunks::starting_at`.
+    /// The `ExpansionStrategy` is ignored.
+    /// Panics if the chunk size is zero.
+    pub fn into_ranked_chunks(self, size: usize) -> IntoRankedChunks<T> {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        IntoRankedChunks::new(self.values, size, self.uniqueness, self.hasher)
+    }
+    /// Compute the permutation of rank `rank` in the order of `Permutations::into_ranked_chunks`,
+    /// in `O(n)` memory.
+    /// Return `None` if `rank` is not lower than the number of permutations.
+    pub fn nth_permutation(&self, rank: usize) -> Option<Vec<T>> {
+        IntoRankedChunks::new(self.values.clone(), 1, self.uniqueness, self.hasher)
+            .nth_permutation(rank)
+    }
+    /// Compute the rank of `permutation` in the order of `Permutations::into_ranked_chunks`:
+    /// the inverse of `Permutations::nth_permutation`.
+    /// Return `None` if it is not a permutation of the values, or if its rank does not fit in a `usize`.
+    pub fn rank_of(&self, permutation: &[T]) -> Option<usize> {
+        IntoRankedChunks::new(self.values.clone(), 1, self.uniqueness, self.hasher)
+            .rank_of(permutation)
+    }
+    /// Build the tables of `CompiledPermutations`, to answer many rank, unrank, sample and range queries
+    /// without building them again for each one.
+    pub fn compile(&self) -> CompiledPermutations<T> {
+        CompiledPermutations::new(self.values.clone(), self.uniqueness, self.hasher)
+    }
+    /// Create the iterator over chunks of permutations transformed by `map`.
+    /// Each permutation is transformed as soon as it is generated,
+    /// so that the chunks carry the transformed values.
+    /// Panics if the chunk size is zero.
+    pub fn into_chunks_mapped<U, F: FnMut(Vec<T>) -> U>(
+        self,
+        size: usize,
+        map: F,
+    ) -> IntoMappedChunks<T, F> {
+        IntoMappedChunks::new(self.into_chunks(size), map)
+    }
+    /// Create the iterator over chunks of index permutations:
+    /// each permutation is an ordering of `0..n`, where `i` stands for the `i`-th input value.
+    /// With `Uniqueness::Distinct` the equal values are given their indices in input order,
+    /// so that there is exactly one ordering for each distinct permutation.
+    /// Panics if the chunk size is zero.
+    pub fn into_index_chunks(
+        self,
+        size: usize,
+    ) -> IntoMappedChunks<usize, impl FnMut(Vec<usize>) -> Vec<usize>> {
+        // the label of a value is the index of its first occurrence,
+        // unless the equal values are distinguishable.
+        let mut first_occurrences = self.hasher.map();
+        let labels = self
+            .values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| match self.uniqueness {
+                Uniqueness::Distinct => *first_occurrences.entry(*value).or_insert(index),
+                Uniqueness::WithDuplicates => index,
+            })
+            .collect::<Vec<usize>>();
+        let mut indices_of_label = vec![vec![]; labels.len()];
+        for (index, label) in labels.iter().enumerate() {
+            indices_of_label[*label].push(index);
+        }
+        Permutations::distinct(labels)
+            .with_strategy(self.strategy)
+            .with_hasher(self.hasher)
+            .into_chunks_mapped(size, move |permutation| {
+                let mut occurrences = vec![0; permutation.len()];
+                permutation
+                    .into_iter()
+                    .map(|label| {
+                        occurrences[label] += 1;
+                        indices_of_label[label][occurrences[label] - 1]
+                    })
+                    .collect()
+            })
+    }
+    /// Create the iterator over chunks of permutations,
+    /// skipping all the permutations starting with a partial permutation rejected by `prune`.
+    /// `prune` is invoked on every partial permutation, and it must return `true` to cut its subtree.
+    /// Panics if the chunk size is zero.
+    pub fn into_pruned_chunks<P: PruneFn<T>>(
+        self,
+        size: usize,
+        prune: P,
+    ) -> IntoPrunedChunks<T, P> {
+        IntoPrunedChunks::new(self.values.clone(), self.into_chunks(size), prune)
+    }
+    /// Create the iterator over chunks of permutations explored best-first:
+    /// the partial permutation with the highest `score` is extended first, from a priority queue.
+    /// When `score` is not lower on a partial permutation than on any of its completions,
+    /// the permutations are generated by descending score, so the best ones come first in the stream.
+    /// The queue can grow up to the number of permutations, and the `ExpansionStrategy` is ignored.
+    /// Panics if the chunk size is zero.
+    pub fn into_best_first_chunks<S: FnMut(&[T]) -> f64>(
+        self,
+        size: usize,
+        score: S,
+    ) -> IntoBestFirstChunks<T, S> {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        IntoBestFirstChunks::new(self.values, size, self.uniqueness, score)
+    }
+    /// Create the iterator over chunks of the permutations found by a beam search:
+    /// the job tree is explored level by level, keeping only the `beam_width` partial permutations
+    /// with the highest `score`, so that the inputs too long to be enumerated still get good permutations.
+    /// At most `beam_width` permutations are generated, by descending score, once the search is over.
+    /// The `ExpansionStrategy` is ignored.
+    /// Panics if the chunk size or the beam width is zero.
+    pub fn into_beam_chunks<S: FnMut(&[T]) -> f64>(
+        self,
+        beam_width: usize,
+        size: usize,
+        score: S,
+    ) -> IntoBeamChunks<T, S> {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        if beam_width == 0 {
+            panic!("Beam width must be at least one")
+        }
+        IntoBeamChunks::new(self.values, beam_width, size, self.uniqueness, score)
+    }
+    /// Create the iterator over the Latin rectangles of `rows` permutations of the values:
+    /// each permutation disagrees with all the previous ones in every position.
+    /// With as many rows as values, it enumerates the Latin squares.
+    /// The rows are built on the pruning of the job tree, so they are generated one at a time.
+    pub fn latin_rectangles(self, rows: usize) -> LatinRectangles<T> {
+        LatinRectangles::new(self.values, self.uniqueness, self.hasher, rows)
+    }
+    /// Find the `k` permutations with the highest `score`, sorted by descending score.
+    /// All the permutations are scored, but only the best `k` are kept in memory.
+    pub fn top_k(self, k: usize, score: impl Fn(&[T]) -> f64) -> Vec<(f64, Vec<T>)> {
+        top_k::top_k(
+            IntoChunks::new(
+                self.values,
+                1,
+                self.uniqueness,
+                ExpansionStrategy::DepthFirst,
+                self.hasher,
+            ),
+            k,
+            score,
+            |_| f64::INFINITY,
+        )
+    }
+    /// Find the `k` permutations with the highest `score`, sorted by descending score,
+    /// with branch-and-bound pruning.
+    /// `upper_bound` is called on the partial permutations, and it must return a value
+    /// not lower than the score of any permutation starting with the partial one:
+    /// the permutations starting with a partial permutation are not generated
+    /// when its upper bound is not greater than the `k`-th best score found so far.
+    pub fn top_k_with_bound(
+        self,
+        k: usize,
+        score: impl Fn(&[T]) -> f64,
+        upper_bound: impl Fn(&[T]) -> f64,
+    ) -> Vec<(f64, Vec<T>)> {
+        top_k::top_k(
+            IntoChunks::new(
+                self.values,
+                1,
+                self.uniqueness,
+                ExpansionStrategy::DepthFirst,
+                self.hasher,
+            ),
+            k,
+            score,
+            upper_bound,
+        )
+    }
+}
+
+impl<'a> Permutations<&'a str> {
+    /// Initialize the `Permutations` from a string of comma separated numbers,
+    /// with the given `ParseOptions`.
+    pub fn parse(text: &'a str, options: &ParseOptions) -> Result<Self, ParseError> {
+        parse_values(text, options).map(Permutations::new)
+    }
+}
+
+impl Permutations<u8> {
+    /// Initialize the `Permutations` of the bytes of a byte string, e.g. a character set:
+    /// each permutation is a byte string of the same length, written as it is by `perm::raw::EncodeRaw`.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Permutations::new(bytes.to_vec())
+    }
+}
+
+impl Permutations<char> {
+    /// Initialize the `Permutations` of the characters of `text`:
+    /// with a joined `OutputFormat` each permutation is written as a string, e.g. the anagrams of a word.
+    pub fn from_chars(text: &str) -> Self {
+        Permutations::new(text.chars().collect())
+    }
+}
+
+impl<T: Copy + Eq + Hash, L: Copy> Permutations<Labelled<T, L>> {
+    /// Initialize a new `Permutations` of the `values`, written as the `labels` at the same positions:
+    /// the equal values are found by value, and each permutation is written with the labels.
+    /// The equal values are written as the label of the first one.
+    /// Panics if there are not as many labels as values.
+    pub fn with_labels(values: Vec<T>, labels: Vec<L>) -> Self {
+        assert_eq!(values.len(), labels.len(), "Each value must have a label");
+        Self::new(
+            values
+                .into_iter()
+                .zip(labels)
+                .map(|(value, label)| Labelled { value, label })
+                .collect(),
+        )
+    }
+}
+
+impl Permutations<usize> {
+    /// Initialize the `Permutations` of the integers of `range`, e.g. `0..n` to permute the indices of a slice.
+    /// A shorthand for `Permutations::with_duplicates(range.collect())`: the integers are collected
+    /// as the values, and since they are all different they are labelled by their position,
+    /// so no value is hashed and the permutations are the same as with `Permutations::new`.
+    /// There is no lazy path: every backend writes the permutations through a table of the values,
+    /// so the `n` integers are stored once, which is negligible next to the `n!` permutations.
+    pub fn from_range(range: Range<usize>) -> Self {
+        Permutations::with_duplicates(range.collect())
+    }
+    /// Initialize the `Permutations` of the integers of `range`, with its end included,
+    /// as `Permutations::from_range`.
+    pub fn from_range_inclusive(range: RangeInclusive<usize>) -> Self {
+        Permutations::with_duplicates(range.collect())
+    }
+}
+
+/// Initialize the `Permutations` from a given string, with the default `ParseOptions`.
+/// It fails if the input is not a string of comma separated numbers.
+impl<'a> TryFrom<&'a str> for Permutations<&'a str> {
+    type Error = ParseError;
+
+    fn try_from(text: &'a str) -> Result<Self, Self::Error> {
+        Permutations::parse(text, &ParseOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fake::Fake;
+    use fake::Faker;
+    use itertools::Itertools;
+    use quickcheck::{Arbitrary, Gen};
+    use rand::Rng;
+
+    use super::*;
+    use crate::{CancellationToken, Chunk, ChunkBuilder};
+
+    #[derive(Clone, Debug)]
+    struct RandomIntegersWithDuplicates(Vec<i32>);
+
+    impl Arbitrary for RandomIntegersWithDuplicates {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let random_values: [i32; 5] = Faker.fake_with_rng(g);
+            let mut values = random_values.to_vec();
+            (0..2).for_each(|_| values.push(random_values[rand::thread_rng().gen_range(0..4)]));
+            Self(random_values.to_vec())
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct RandomStringsWithDuplicates(Vec<String>);
+
+    impl Arbitrary for RandomStringsWithDuplicates {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let random_values: [String; 5] = Faker.fake_with_rng(g);
+            let mut values = random_values.to_vec();
+            (0..2).for_each(|_| {
+                values.push(random_values[rand::thread_rng().gen_range(0..4)].clone())
+            });
+            Self(random_values.to_vec())
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct InvalidInput(String);
+
+    impl Arbitrary for InvalidInput {
+        fn arbitrary<G: Gen>(_g: &mut G) -> Self {
+            let invalid_inputs = [",", "c", "+123,", "123,345,,45"];
+            Self(invalid_inputs[rand::thread_rng().gen_range(0..invalid_inputs.len())].to_string())
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct ValidInput(String);
+
+    impl Arbitrary for ValidInput {
+        fn arbitrary<G: Gen>(_g: &mut G) -> Self {
+            let valid_inputs = [
+                "",
+                "1",
+                "1\n",
+                "1,2",
+                "1,2\n",
+                "-1",
+                "+1,-1",
+                "1.12",
+                "-1.23,+12",
+            ];
+            Self(valid_inputs[rand::thread_rng().gen_range(0..valid_inputs.len())].to_string())
+        }
+    }
+
+    /// Collect the permutations of the `chunks`, in generation order.
+    pub(super) fn collect_permutations<T: Copy>(
+        chunks: impl IntoIterator<Item = Chunk<T>>,
+    ) -> Vec<Vec<T>> {
+        chunks
+            .into_iter()
+            .flat_map(|mut chunk| {
+                chunk
+                    .as_mut()
+                    .drain(..)
+                    .map(|permutation| permutation.to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn generate_correct_permutations<T: ToString + PartialEq + Eq + Hash>(
+        values: Vec<T>,
+    ) -> Vec<String> {
+        let correct_permutations = values
+            .iter()
+            .permutations(values.len())
+            .unique()
+            .collect::<Vec<Vec<&T>>>();
+
+        let mut correct_permutations_strings = correct_permutations
+            .iter()
+            .map(|p| {
+                p.iter()
+                    .take(p.len() - 1)
+                    .fold(String::new(), |acc, x| acc + &x.to_string() + ",")
+                    + &p[p.len() - 1].to_string()
+                    + "\n"
+            })
+            .collect::<Vec<String>>();
+        correct_permutations_strings.sort();
+        correct_permutations_strings
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn invalid_input_is_detected(invalid_input: InvalidInput) {
+        assert!(Permutations::try_from(invalid_input.0.as_str()).is_err());
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn valid_input_is_parsed_correctly(valid_input: ValidInput) {
+        let permutations = Permutations::try_from(valid_input.0.as_str()).unwrap();
+        let mut permutation_strings = permutations
+            .into_chunks(1)
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>();
+        permutation_strings.sort();
+
+        let tokens = valid_input
+            .0
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .collect::<Vec<String>>();
+        let mut expected_permutations = generate_correct_permutations(tokens);
+        expected_permutations.sort();
+        assert_eq!(permutation_strings, expected_permutations)
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn total_number_of_permutations_is_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let permutations = Permutations::new(values.0.clone());
+        assert_eq!(
+            permutations.permutations_number(),
+            generate_correct_permutations(values.0).len()
+        )
+    }
+
+    #[test]
+    fn total_number_of_permutations_considers_duplicates() {
+        assert_eq!(
+            Permutations::new([0; 128].to_vec()).permutations_number(),
+            1
+        );
+        let mut values = [0; 30].to_vec();
+        values[0] = 1;
+        assert_eq!(Permutations::new(values).permutations_number(), 30);
+    }
+
+    #[test]
+    fn total_number_of_permutations_overflow_is_detected() {
+        let permutations = Permutations::new((0..30).collect());
+        assert!(permutations.checked_permutations_number().is_none());
+        assert_eq!(permutations.suggested_chunk_size(256), usize::MAX / 256);
+    }
+
+    #[test]
+    fn suggested_chunk_size_is_computed_correctly() {
+        let permutations = Permutations::new((0..10).collect());
+        assert_eq!(permutations.suggested_chunk_size(256), 3628800 / 256);
+        assert_eq!(permutations.suggested_chunk_size(0), 3628800);
+        let duplicates = Permutations::new([0, 0, 0, 0, 0, 0, 0, 0, 1, 2].to_vec());
+        assert_eq!(
+            duplicates.suggested_chunk_size(256),
+            MIN_SUGGESTED_CHUNK_SIZE
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn permutations_of_integers_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let mut permutations = Permutations::new(values.0.clone())
+            .into_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        permutations.sort();
+        assert_eq!(permutations, generate_correct_permutations(values.0))
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn permutations_of_strings_are_computed_correctly(values: RandomStringsWithDuplicates) {
+        let mut permutations = Permutations::new(values.0.iter().map(|v| v.as_str()).collect())
+            .into_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        permutations.sort();
+        assert_eq!(permutations, generate_correct_permutations(values.0))
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn optimized_permutations_of_integers_are_computed_correctly(
+        values: RandomIntegersWithDuplicates,
+    ) {
+        let mut optimized_permutations = Permutations::new(values.0.clone())
+            .into_optimized_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        optimized_permutations.sort();
+        assert_eq!(
+            optimized_permutations,
+            generate_correct_permutations(values.0)
+        )
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn optimized_permutations_of_strings_are_computed_correctly(
+        values: RandomStringsWithDuplicates,
+    ) {
+        let mut optimized_permutations =
+            Permutations::new(values.0.iter().map(|v| v.as_str()).collect())
+                .into_optimized_chunks(1)
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>();
+        optimized_permutations.sort();
+        assert_eq!(
+            optimized_permutations,
+            generate_correct_permutations(values.0)
+        )
+    }
+
+    fn generate_all_orderings(values: Vec<i32>) -> Vec<String> {
+        let mut orderings = values
+            .iter()
+            .permutations(values.len())
+            .map(|p| p.iter().join(",") + "\n")
+            .collect::<Vec<String>>();
+        orderings.sort();
+        orderings
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn permutations_with_duplicates_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let mut values = values.0;
+        values.push(values[0]);
+        let expected = generate_all_orderings(values.clone());
+
+        let mut permutations = Permutations::with_duplicates(values.clone())
+            .into_chunks(7)
+            .map(|c| c.to_string())
+            .collect::<String>()
+            .lines()
+            .map(|line| line.to_string() + "\n")
+            .collect::<Vec<String>>();
+        permutations.sort();
+        assert_eq!(permutations, expected);
+
+        let mut optimized_permutations = Permutations::with_duplicates(values)
+            .into_optimized_chunks(7)
+            .map(|c| c.to_string())
+            .collect::<String>()
+            .lines()
+            .map(|line| line.to_string() + "\n")
+            .collect::<Vec<String>>();
+        optimized_permutations.sort();
+        assert_eq!(optimized_permutations, expected);
+    }
+
+    #[test]
+    fn index_permutations_are_orderings_of_the_input() {
+        let values = vec!["a", "b", "a"];
+        let orderings = |permutations: Permutations<&str>| {
+            let mut orderings = permutations
+                .into_index_chunks(4)
+                .map(|c| c.to_string())
+                .collect::<String>()
+                .lines()
+                .map(String::from)
+                .collect::<Vec<String>>();
+            orderings.sort();
+            orderings
+        };
+        assert_eq!(
+            orderings(Permutations::distinct(values.clone())),
+            vec!["0,1,2", "0,2,1", "1,0,2"]
+        );
+        assert_eq!(orderings(Permutations::with_duplicates(values)).len(), 6);
+    }
+
+    #[test]
+    fn chunks_know_their_position() {
+        let positions = |chunks: Vec<(usize, usize, usize, bool)>| {
+            assert_eq!(
+                chunks,
+                vec![
+                    (0, 0, 4, false),
+                    (1, 5, 9, false),
+                    (2, 10, 14, false),
+                    (3, 15, 19, false),
+                    (4, 20, 23, true)
+                ]
+            )
+        };
+        positions(
+            Permutations::new(vec![1, 2, 3, 4])
+                .into_chunks(5)
+                .map(|c| (c.index(), c.first_rank(), c.last_rank(), c.is_last()))
+                .collect(),
+        );
+        positions(
+            Permutations::new(vec![1, 2, 3, 4])
+                .into_optimized_chunks(5)
+                .map(|c| (c.index(), c.first_rank(), c.last_rank(), c.is_last()))
+                .collect(),
+        );
+        let pruned = Permutations::new(vec![1, 2, 3, 4])
+            .into_pruned_chunks(3, |partial: &[i32]| partial[0] != 1)
+            .map(|c| (c.index(), c.first_rank(), c.is_last()))
+            .collect::<Vec<_>>();
+        assert_eq!(pruned, vec![(0, 0, false), (1, 3, true)]);
+    }
+
+    #[test]
+    fn optimized_chunks_are_decoded_lazily() {
+        let chunk = Permutations::new(vec![7, 8, 7])
+            .into_optimized_chunks(3)
+            .next()
+            .unwrap();
+        let decoded = chunk
+            .iter_decoded()
+            .map(|permutation| permutation.copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, vec![vec![7, 7, 8], vec![7, 8, 7], vec![8, 7, 7]]);
+    }
+
+    #[test]
+    fn chunk_boundaries_do_not_depend_on_the_iterator() {
+        for uniqueness in [Uniqueness::Distinct, Uniqueness::WithDuplicates] {
+            for strategy in [
+                ExpansionStrategy::DepthFirst,
+                ExpansionStrategy::BreadthFirst,
+            ] {
+                let permutations = Permutations {
+                    values: vec![3, 1, 3, 2],
+                    uniqueness,
+                    strategy,
+                    hasher: ValueHasher::default(),
+                };
+                let ranked = permutations
+                    .clone()
+                    .into_ranked_chunks(5)
+                    .map(|c| (c.first_rank(), c.to_string()))
+                    .collect::<Vec<_>>();
+                let normal = permutations
+                    .clone()
+                    .into_chunks(5)
+                    .map(|c| (c.first_rank(), c.to_string()))
+                    .collect::<Vec<_>>();
+                let optimized = permutations
+                    .into_optimized_chunks(5)
+                    .map(|c| (c.first_rank(), c.to_string()))
+                    .collect::<Vec<_>>();
+                assert_eq!(normal, ranked);
+                assert_eq!(optimized, ranked);
+            }
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn breadth_first_permutations_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let expected = generate_correct_permutations(values.0.clone());
+        let mut permutations = Permutations::new(values.0.clone())
+            .with_strategy(ExpansionStrategy::BreadthFirst)
+            .into_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        permutations.sort();
+        assert_eq!(permutations, expected);
+
+        let mut optimized_permutations = Permutations::new(values.0)
+            .with_strategy(ExpansionStrategy::BreadthFirst)
+            .into_optimized_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        optimized_permutations.sort();
+        assert_eq!(optimized_permutations, expected);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn arena_permutations_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let breadth_first = |permutations: Permutations<i32>| {
+            permutations
+                .with_strategy(ExpansionStrategy::BreadthFirst)
+                .into_chunks(4)
+        };
+        for permutations in [
+            Permutations::new(values.0.clone()),
+            Permutations::with_duplicates(values.0.clone()),
+        ] {
+            let expected = breadth_first(permutations.clone()).collect::<Vec<Chunk<i32>>>();
+            let arena = breadth_first(permutations.clone()).with_arena();
+            assert_eq!(arena.collect::<Vec<Chunk<i32>>>(), expected);
+
+            let expected = breadth_first(permutations.clone())
+                .with_branch_order(|left, right| right.cmp(left))
+                .collect::<Vec<Chunk<i32>>>();
+            let arena = breadth_first(permutations.clone())
+                .with_arena()
+                .with_branch_order(|left, right| right.cmp(left));
+            assert_eq!(arena.collect::<Vec<Chunk<i32>>>(), expected);
+        }
+        // the depth-first generation has no jobs.
+        let depth_first = Permutations::new(values.0.clone()).into_chunks(4);
+        assert_eq!(
+            depth_first.with_arena().collect::<Vec<Chunk<i32>>>(),
+            Permutations::new(values.0)
+                .into_chunks(4)
+                .collect::<Vec<Chunk<i32>>>()
+        );
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn hashers_do_not_change_the_permutations(values: RandomIntegersWithDuplicates) {
+        let permutations = Permutations::new(values.0.clone());
+        let expected = permutations
+            .clone()
+            .into_chunks(3)
+            .collect::<Vec<Chunk<i32>>>();
+        let hashers = [
+            ValueHasher::SipHash,
+            #[cfg(feature = "fxhash")]
+            ValueHasher::FxHash,
+        ];
+        for hasher in hashers {
+            let permutations = permutations.clone().with_hasher(hasher);
+            assert_eq!(permutations.hasher(), hasher);
+            let chunks = permutations.clone().into_chunks(3);
+            assert_eq!(chunks.collect::<Vec<Chunk<i32>>>(), expected);
+            let total = permutations.permutations_number();
+            let text = permutations
+                .clone()
+                .into_ranked_chunks(3)
+                .map(|c| c.to_string());
+            assert_eq!(
+                text.collect::<String>(),
+                expected.iter().map(|c| c.to_string()).collect::<String>()
+            );
+            let indices = permutations.clone().into_index_chunks(3).flatten();
+            assert!(indices.eq(Permutations::new(values.0.clone())
+                .into_index_chunks(3)
+                .flatten()));
+            let compiled = permutations.compile();
+            let last = compiled.nth_permutation(total - 1).unwrap();
+            assert_eq!(compiled.rank_of(&last), Some(total - 1));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The arena must be set before the generation starts")]
+    fn arena_is_set_before_the_generation() {
+        let mut chunks = Permutations::new(vec![1, 2, 3])
+            .with_strategy(ExpansionStrategy::BreadthFirst)
+            .into_chunks(1);
+        chunks.next();
+        chunks.with_arena();
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn ranked_permutations_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let mut values = values.0;
+        values.push(values[0]);
+        let ranked = Permutations::new(values.clone())
+            .into_ranked_chunks(7)
+            .map(|c| c.to_string())
+            .collect::<String>()
+            .lines()
+            .map(|line| line.to_string() + "\n")
+            .collect::<Vec<String>>();
+        let mut sorted = ranked.clone();
+        sorted.sort();
+        assert_eq!(sorted, generate_correct_permutations(values.clone()));
+
+        let permutations = Permutations::new(values);
+        let rank = ranked.len() / 3;
+        assert_eq!(
+            permutations.nth_permutation(rank).unwrap().iter().join(",") + "\n",
+            ranked[rank]
+        );
+        assert_eq!(permutations.nth_permutation(ranked.len()), None);
+        let permutation = permutations.nth_permutation(rank).unwrap();
+        assert_eq!(permutations.rank_of(&permutation), Some(rank));
+    }
+
+    #[test]
+    fn rank_is_the_inverse_of_nth_permutation() {
+        let permutations = Permutations::new(vec![1, 2, 3, 4]);
+        assert_eq!(permutations.rank_of(&[3, 1, 4, 2]), Some(13));
+        assert_eq!(permutations.nth_permutation(13), Some(vec![3, 1, 4, 2]));
+        assert_eq!(permutations.rank_of(&[3, 1, 4]), None);
+        assert_eq!(permutations.rank_of(&[3, 1, 4, 4]), None);
+        let with_duplicates = Permutations::with_duplicates(vec![1, 1, 2]);
+        assert_eq!(with_duplicates.rank_of(&[1, 2, 1]), Some(1));
+        assert_eq!(with_duplicates.nth_permutation(1), Some(vec![1, 2, 1]));
+    }
+
+    #[test]
+    fn compiled_permutations_answer_as_the_ranked_ones() {
+        for permutations in [
+            Permutations::new(vec![2, 1, 2, 3, 1, 2]),
+            Permutations::with_duplicates(vec![1, 1, 2, 3]),
+            Permutations::new(vec![]),
+        ] {
+            let compiled = permutations.compile();
+            let total = permutations.permutations_number();
+            assert_eq!(compiled.permutations_number(), Some(total));
+            for rank in 0..total {
+                let permutation = compiled.nth_permutation(rank).unwrap();
+                assert_eq!(
+                    Some(&permutation),
+                    permutations.nth_permutation(rank).as_ref()
+                );
+                assert_eq!(
+                    compiled.rank_of(&permutation),
+                    permutations.rank_of(&permutation)
+                );
+            }
+            assert_eq!(compiled.nth_permutation(total), None);
+            let sample = compiled.sample(3).collect::<Vec<_>>();
+            assert_eq!(sample.len(), total.min(3));
+            assert_eq!(sample[0], permutations.nth_permutation(0).unwrap());
+            let range = compiled
+                .range(1..total.max(3) - 1, 2)
+                .map(|c| c.to_string())
+                .collect::<String>();
+            let ranked = permutations
+                .clone()
+                .into_ranked_chunks(2)
+                .starting_at(1)
+                .with_limit(total.max(3) - 2)
+                .map(|c| c.to_string())
+                .collect::<String>();
+            assert_eq!(range, ranked);
+        }
+        let compiled = Permutations::new(vec![1, 2, 2]).compile();
+        assert_eq!(compiled.rank_of(&[2, 2, 2]), None);
+        assert_eq!(compiled.rank_of(&[2, 2]), None);
+        assert_eq!(compiled.rank_of(&[2, 4, 1]), None);
+    }
+
+    #[test]
+    fn multisets_are_built_from_counts() {
+        let permutations = Permutations::from_counts(&[(1, 3), (2, 2), (5, 1)]);
+        assert_eq!(permutations.values(), &[1, 1, 1, 2, 2, 5]);
+        assert_eq!(permutations.permutations_number(), 60);
+        assert!(Permutations::<i32>::from_counts(&[(1, 0)])
+            .values()
+            .is_empty());
+    }
+
+    #[test]
+    fn labels_are_written_instead_of_the_values() {
+        let permutations = || Permutations::with_labels(vec![7, 9, 7], vec!["ann", "bob", "amy"]);
+        assert_eq!(permutations().permutations_number(), 3);
+        let expected = "ann,ann,bob\nann,bob,ann\nbob,ann,ann\n";
+        assert_eq!(
+            permutations().into_chunks(3).next().unwrap().to_string(),
+            expected
+        );
+        assert_eq!(
+            permutations()
+                .into_optimized_chunks(3)
+                .next()
+                .unwrap()
+                .to_string(),
+            expected
+        );
+        assert_eq!(permutations().nth_permutation(1).unwrap()[1].label, "bob");
+    }
+
+    #[test]
+    fn chunks_are_indexed_and_split() {
+        let chunk = Permutations::new(vec![1, 2, 3])
+            .into_chunks(6)
+            .next()
+            .unwrap();
+        assert_eq!(chunk.len(), 6);
+        assert_eq!(chunk.get(1), Some(&[1, 3, 2][..]));
+        assert_eq!(chunk.get(6), None);
+        assert_eq!(&chunk[5], &[3, 2, 1]);
+        let (first, second) = chunk.split_at(2);
+        assert_eq!(first.to_string(), "1,2,3\n1,3,2\n");
+        assert_eq!((first.first_rank(), first.is_last()), (0, false));
+        assert_eq!(
+            (second.first_rank(), second.len(), second.is_last()),
+            (2, 4, true)
+        );
+        assert_eq!(second.index(), 0);
+
+        let chunk = Permutations::new(vec![1, 2, 3])
+            .into_optimized_chunks(6)
+            .next()
+            .unwrap();
+        let expected = chunk.to_string();
+        let (first, second) = chunk.split_at(6);
+        assert_eq!(first.to_string(), expected);
+        assert!(second.is_empty() && second.is_last() && !first.is_last());
+        assert_eq!(second.first_rank(), 6);
+    }
+
+    #[test]
+    fn optimized_chunks_are_formatted_as_a_whole() {
+        let formats = [
+            OutputFormat::new(),
+            OutputFormat::new().with_quoting(crate::Quoting::Necessary),
+            OutputFormat::new().with_line_ending(crate::LineEnding::CrLf),
+            OutputFormat::new().with_joined(true),
+        ];
+        for values in [vec!["a", "b,c", "a", "\"d\""], vec![], vec!["1"]] {
+            for format in formats {
+                let permutations = Permutations::new(values.clone());
+                let expected = permutations
+                    .clone()
+                    .into_chunks(5)
+                    .map(|chunk| chunk.formatted(format).to_string())
+                    .collect::<String>();
+                let mut text = String::new();
+                for chunk in permutations.into_optimized_chunks(5) {
+                    chunk.write_text(format, &mut text);
+                    assert_eq!(chunk.formatted(format).to_string(), chunk.to_text(format));
+                    assert_eq!(chunk.to_bytes(format), chunk.to_text(format).into_bytes());
+                }
+                assert_eq!(text, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_are_formatted_with_the_values_of_their_iterator() {
+        let formats = [
+            OutputFormat::new(),
+            OutputFormat::new().with_quoting(crate::Quoting::Necessary),
+            OutputFormat::new().with_joined(true),
+        ];
+        let values = vec!["a", "b,c", "a", "\"d\""];
+        for format in formats {
+            for permutations in [
+                Permutations::new(values.clone()),
+                Permutations::with_duplicates(values.clone()),
+            ] {
+                let chunks = permutations
+                    .clone()
+                    .into_chunks(5)
+                    .chain(permutations.clone().into_ranked_chunks(5));
+                for chunk in chunks {
+                    // the same permutations, formatted value by value.
+                    let mut builder = ChunkBuilder::new();
+                    builder.extend((0..chunk.len()).map(|index| chunk[index].to_vec()));
+                    let expected = builder.build().formatted(format).to_string();
+                    assert_eq!(chunk.formatted(format).to_string(), expected);
+                    assert_eq!(chunk.to_text(format), expected);
+                    assert_eq!(chunk.to_bytes(format), expected.as_bytes());
+                }
+            }
+        }
+
+        // the chunks of different iterators are merged without their values.
+        let mut first = Permutations::new(vec![1, 2]).into_chunks(1);
+        let mut second = Permutations::new(vec![3, 4]).into_ranked_chunks(1);
+        let first = first.next().unwrap();
+        second.next();
+        let merged = first.merge(second.next().unwrap());
+        assert_eq!(merged.to_string(), "1,2\n4,3\n");
+    }
+
+    #[test]
+    fn chunks_are_compared_and_cloned() {
+        let permutations = Permutations::new(vec![1, 2, 2]);
+        assert_eq!(permutations.clone(), Permutations::new(vec![1, 2, 2]));
+        assert_ne!(permutations, Permutations::with_duplicates(vec![1, 2, 2]));
+
+        let chunk = permutations.clone().into_chunks(5).next().unwrap();
+        let mut builder = ChunkBuilder::new().with_last(true);
+        builder.extend(vec![[1, 2, 2], [2, 1, 2], [2, 2, 1]]);
+        assert_eq!(chunk, builder.build());
+        assert_eq!(chunk.clone(), chunk);
+        assert_ne!(chunk, permutations.clone().into_chunks(2).next().unwrap());
+        assert_eq!(
+            format!("{:?}", chunk.split_at(1).0),
+            "Chunk { permutations: [[1, 2, 2]], size: 1, index: 0, first_rank: 0, is_last: false }"
+        );
+
+        let optimized = permutations
+            .clone()
+            .into_optimized_chunks(2)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            optimized,
+            permutations.into_optimized_chunks(2).collect::<Vec<_>>()
+        );
+        assert_ne!(optimized[0], optimized[1]);
+        assert_eq!(optimized[1].clone(), optimized[1]);
+        assert_eq!(
+            format!("{:?}", optimized[1]),
+            "OptimizedChunk { permutations: [[2, 2, 1]], index: 1, first_rank: 2, is_last: true }"
+        );
+    }
+
+    #[test]
+    fn chunks_are_merged_and_built() {
+        let chunk = Permutations::new(vec![1, 2, 3])
+            .into_chunks(6)
+            .next()
+            .unwrap();
+        let expected = chunk.to_string();
+        let (first, second) = chunk.split_at(4);
+        let merged = first.merge(second);
+        assert_eq!(merged.to_string(), expected);
+        assert_eq!(
+            (merged.first_rank(), merged.len(), merged.is_last()),
+            (0, 6, true)
+        );
+
+        let mut builder = ChunkBuilder::new()
+            .with_index(3)
+            .with_first_rank(4)
+            .with_last(true);
+        builder.push(vec![3, 1, 2]);
+        builder.extend(vec![[3, 2, 1]]);
+        assert_eq!(builder.len(), 2);
+        let built = builder.build();
+        assert_eq!(built.to_string(), "3,1,2\n3,2,1\n");
+        assert_eq!(
+            (built.index(), built.last_rank(), built.is_last()),
+            (3, 5, true)
+        );
+        let mut start = ChunkBuilder::new().with_first_rank(2);
+        start.push(vec![2, 1, 3]);
+        start.push(vec![2, 3, 1]);
+        assert_eq!(start.build().merge(built).len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "The merged chunk must follow the chunk")]
+    fn chunks_are_merged_in_order() {
+        let mut chunks = Permutations::new(vec![1, 2, 3]).into_chunks(2);
+        let (first, _, third) = (
+            chunks.next().unwrap(),
+            chunks.next().unwrap(),
+            chunks.next().unwrap(),
+        );
+        first.merge(third);
+    }
+
+    #[test]
+    fn ranges_are_permuted_as_their_collected_integers() {
+        let text = |permutations: Permutations<usize>| {
+            let ranked = permutations
+                .clone()
+                .into_ranked_chunks(5)
+                .map(|c| c.to_string())
+                .collect::<String>();
+            let optimized = permutations
+                .clone()
+                .into_optimized_chunks(5)
+                .map(|c| c.to_string())
+                .collect::<String>();
+            let normal = permutations
+                .into_chunks(5)
+                .map(|c| c.to_string())
+                .collect::<String>();
+            assert_eq!(ranked, optimized);
+            assert_eq!(ranked, normal);
+            ranked
+        };
+        let range = Permutations::from_range(0..4);
+        assert_eq!(range.uniqueness(), Uniqueness::WithDuplicates);
+        assert_eq!(range.permutations_number(), 24);
+        assert_eq!(range.values(), &[0, 1, 2, 3]);
+        assert_eq!(
+            text(range),
+            text(Permutations::new((0..4).collect::<Vec<_>>()))
+        );
+        assert_eq!(
+            text(Permutations::from_range_inclusive(2..=4)),
+            text(Permutations::new(vec![2, 3, 4]))
+        );
+        assert_eq!(Permutations::from_range(3..3).permutations_number(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn random_samples_are_reproducible() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let compiled = Permutations::new(vec![1, 2, 2, 3, 4]).compile();
+        let sample = |seed: u64| {
+            compiled
+                .random_sample(20, &mut StdRng::seed_from_u64(seed))
+                .collect::<Vec<_>>()
+        };
+        let first = sample(7);
+        assert_eq!(first.len(), 20);
+        assert_eq!(first, sample(7));
+        assert_ne!(first, sample(8));
+        assert!(first
+            .iter()
+            .all(|permutation| compiled.rank_of(permutation).is_some()));
+    }
+
+    #[test]
+    fn compiled_permutations_fall_back_when_the_total_overflows() {
+        let values = (0..30).collect::<Vec<u32>>();
+        let permutations = Permutations::new(values.clone());
+        let compiled = permutations.compile();
+        assert_eq!(compiled.permutations_number(), None);
+        let first = compiled.nth_permutation(0).unwrap();
+        assert_eq!(first, values);
+        assert_eq!(compiled.rank_of(&first), Some(0));
+        let permutation = compiled.nth_permutation(123_456_789).unwrap();
+        assert_eq!(
+            permutations.nth_permutation(123_456_789),
+            Some(permutation.clone())
+        );
+        assert_eq!(compiled.rank_of(&permutation), Some(123_456_789));
+    }
+
+    #[test]
+    fn ranked_permutations_are_in_lexicographic_order() {
+        let chunks = Permutations::new(vec![3, 1, 3])
+            .into_ranked_chunks(2)
+            .map(|c| (c.to_string(), c.first_rank(), c.is_last()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![
+                ("3,3,1\n3,1,3\n".to_string(), 0, false),
+                ("1,3,3\n".to_string(), 2, true)
+            ]
+        );
+        let resumed = Permutations::with_duplicates(vec![1, 2, 3])
+            .into_ranked_chunks(10)
+            .starting_at(4)
+            .map(|c| c.to_string())
+            .collect::<String>();
+        assert_eq!(resumed, "3,1,2\n3,2,1\n");
+    }
+
+    #[test]
+    fn ranked_generation_ends_at_the_last_usize_rank() {
+        // 25! does not fit in a usize: the ranks after `usize::MAX` are not generated.
+        let chunks = Permutations::new((1..=25).collect::<Vec<_>>())
+            .into_ranked_chunks(4)
+            .starting_at(usize::MAX)
+            .map(|c| (c.get(0).unwrap()[..4].to_vec(), c.first_rank(), c.is_last()))
+            .collect::<Vec<_>>();
+        // 20! < usize::MAX < 21!: only the last 21 positions are permuted.
+        assert_eq!(chunks, vec![(vec![1, 2, 3, 4], usize::MAX, true)]);
+    }
+
+    #[test]
+    fn uniqueness_changes_the_number_of_permutations() {
+        let values = vec![0, 0, 1];
+        assert_eq!(
+            Permutations::distinct(values.clone()).permutations_number(),
+            3
+        );
+        assert_eq!(
+            Permutations::with_duplicates(values.clone()).permutations_number(),
+            6
+        );
+        assert_eq!(Permutations::new(values).uniqueness(), Uniqueness::Distinct);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn mapped_permutations_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let mut lines = Permutations::new(values.0.clone())
+            .into_chunks_mapped(3, |p| {
+                p.iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+                    + "\n"
+            })
+            .flatten()
+            .collect::<Vec<String>>();
+        lines.sort();
+        assert_eq!(lines, generate_correct_permutations(values.0))
+    }
+
+    /// Score a permutation by the weighted sum of its values: `sum(i*p[i])`.
+    fn weighted_sum(permutation: &[i32]) -> f64 {
+        permutation
+            .iter()
+            .enumerate()
+            .map(|(i, v)| i as f64 * *v as f64)
+            .sum()
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn top_k_permutations_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let values = values.0.iter().map(|v| v % 1000).collect::<Vec<i32>>();
+        let mut expected = Permutations::new(values.clone())
+            .into_chunks(1)
+            .flat_map(|mut c| c.as_mut().pop())
+            .map(|p| weighted_sum(&p))
+            .collect::<Vec<f64>>();
+        expected.sort_by(|a, b| b.total_cmp(a));
+        expected.truncate(3);
+
+        let top_k = Permutations::new(values).top_k(3, weighted_sum);
+        assert_eq!(
+            top_k.iter().map(|(s, _)| *s).collect::<Vec<f64>>(),
+            expected
+        );
+        assert!(top_k.iter().all(|(s, p)| *s == weighted_sum(p)));
+    }
+
+    #[test]
+    fn top_k_with_bound_prunes_the_search() {
+        // the values are not negative: the best completion of a partial permutation
+        // puts the remaining values at the remaining positions in ascending order.
+        let values = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        let upper_bound = |partial: &[i32]| {
+            let mut remaining = (0..8)
+                .filter(|v| !partial.contains(v))
+                .collect::<Vec<i32>>();
+            remaining.sort_unstable();
+            weighted_sum(&[partial, &remaining].concat())
+        };
+        let top_k = Permutations::new(values).top_k_with_bound(2, weighted_sum, upper_bound);
+        assert_eq!(top_k[0], (140.0, vec![0, 1, 2, 3, 4, 5, 6, 7]));
+        // any swap of two adjacent values has the second best score.
+        assert_eq!(top_k[1].0, 139.0);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn pruned_permutations_are_computed_correctly(values: RandomIntegersWithDuplicates) {
+        let first_value = values.0[0];
+        let mut pruned_permutations = Permutations::new(values.0.clone())
+            .into_pruned_chunks(2, |partial| partial[0] == first_value)
+            .map(|c| c.to_string())
+            .collect::<String>()
+            .lines()
+            .map(|p| p.to_string() + "\n")
+            .collect::<Vec<String>>();
+        pruned_permutations.sort();
+
+        let first_value = first_value.to_string() + ",";
+        let expected = generate_correct_permutations(values.0)
+            .into_iter()
+            .filter(|p| !p.starts_with(&first_value))
+            .collect::<Vec<String>>();
+        assert_eq!(pruned_permutations, expected)
+    }
+
+    #[test]
+    fn empty_permutation_is_computed_correctly() {
+        let permutations = Permutations::<i32>::new(vec![]);
+        assert_eq!(permutations.permutations_number(), 1);
+        let chunks = permutations
+            .clone()
+            .into_chunks(2)
+            .map(|chunk| (chunk.to_string(), chunk.is_last()))
+            .collect::<Vec<_>>();
+        let optimized_chunks = permutations
+            .clone()
+            .into_optimized_chunks(2)
+            .map(|chunk| (chunk.to_string(), chunk.is_last()))
+            .collect::<Vec<_>>();
+        let ranked_chunks = permutations
+            .clone()
+            .into_ranked_chunks(2)
+            .map(|chunk| (chunk.to_string(), chunk.is_last()))
+            .collect::<Vec<_>>();
+        let expected = vec![("\n".to_string(), true)];
+        assert_eq!(chunks, expected);
+        assert_eq!(optimized_chunks, expected);
+        assert_eq!(ranked_chunks, expected);
+
+        assert_eq!(permutations.nth_permutation(0), Some(vec![]));
+        assert_eq!(permutations.nth_permutation(1), None);
+        assert_eq!(permutations.rank_of(&[]), Some(0));
+        assert!(permutations
+            .clone()
+            .into_ranked_chunks(2)
+            .starting_at(1)
+            .next()
+            .is_none());
+        assert_eq!(
+            permutations
+                .into_index_chunks(2)
+                .next()
+                .unwrap()
+                .to_string(),
+            "\n"
+        );
+    }
+
+    #[test]
+    fn optimized_permutations_of_128_duplicates_are_computed_correctly() {
+        let permutations = Permutations::new([0; 128].to_vec())
+            .into_optimized_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(permutations.join(""), "0,".repeat(127) + "0\n");
+    }
+
+    #[test]
+    fn optimized_permutations_of_129_values_cannot_be_computed() {
+        let permutations = Permutations::new([0; 129].to_vec());
+        assert!(!permutations.can_be_optimized());
+    }
+
+    #[test]
+    fn long_permutations_are_computed_correctly() {
+        let permutations = Permutations::new([0; 129].to_vec())
+            .into_chunks(1)
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(permutations.join(""), "0,".repeat(128) + "0\n");
+    }
+
+    #[test]
+    fn chunks_by_bytes_fit_the_budget() {
+        let permutations = Permutations::new(vec![1, 22, 333]);
+        // every permutation is written as 9 bytes, like `1,22,333\n`.
+        assert_eq!(
+            permutations.chunk_size_for_bytes(20, OutputFormat::default()),
+            2
+        );
+        assert_eq!(
+            permutations.chunk_size_for_bytes(5, OutputFormat::default()),
+            1
+        );
+        let quoted = OutputFormat::new().with_quoting(crate::Quoting::Always);
+        assert_eq!(
+            Permutations::new(vec!["a\"b", "c"]).chunk_size_for_bytes(11, quoted),
+            1
+        );
+        assert_eq!(quoted.permutation_len(["a\"b", "c"].iter()), 11);
+        let chunks = permutations
+            .into_chunks_by_bytes(20)
+            .map(|chunk| chunk.to_string().len())
+            .collect::<Vec<usize>>();
+        assert_eq!(chunks, vec![18, 18, 18]);
+    }
+
+    #[test]
+    fn time_sliced_chunks_are_not_full() {
+        let permutations = Permutations::new(vec![1, 2, 3]);
+        let chunks = permutations
+            .clone()
+            .into_chunks(4)
+            .with_time_slice(Duration::ZERO)
+            .map(|chunk| (chunk.first_rank(), chunk.len(), chunk.is_last()))
+            .collect::<Vec<(usize, usize, bool)>>();
+        let optimized_chunks = permutations
+            .clone()
+            .into_optimized_chunks(4)
+            .with_time_slice(Duration::ZERO)
+            .map(|chunk| (chunk.first_rank(), chunk.len(), chunk.is_last()))
+            .collect::<Vec<(usize, usize, bool)>>();
+
+        let expected = (0..6)
+            .map(|rank| (rank, 1, rank == 5))
+            .collect::<Vec<(usize, usize, bool)>>();
+        assert_eq!(chunks, expected);
+        assert_eq!(optimized_chunks, expected);
+        let slow_chunks = permutations
+            .into_chunks(4)
+            .with_time_slice(Duration::from_secs(60))
+            .map(|chunk| chunk.len())
+            .collect::<Vec<usize>>();
+        assert_eq!(slow_chunks, vec![4, 2]);
+    }
+
+    #[test]
+    fn beam_search_keeps_the_best_partial_permutations() {
+        // the number of adjacent values differing by one: the best permutations are the sorted runs.
+        let adjacent = |permutation: &[i32]| {
+            permutation
+                .windows(2)
+                .filter(|pair| (pair[0] - pair[1]).abs() == 1)
+                .count() as f64
+        };
+        let beam = |values: Vec<i32>, beam_width: usize| {
+            Permutations::new(values)
+                .into_beam_chunks(beam_width, 2, adjacent)
+                .collect::<Vec<_>>()
+        };
+        let chunks = beam((0..200).rev().collect(), 3);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.first_rank(), chunk.len(), chunk.is_last()))
+                .collect::<Vec<_>>(),
+            vec![(0, 2, false), (2, 1, true)]
+        );
+        let best = chunks[0].to_string();
+        let best = best.lines().next().unwrap();
+        assert_eq!(best.split(',').count(), 200);
+        assert!(best.starts_with("199,198,197,"));
+        // a beam as wide as the search space finds all the permutations.
+        let all = beam(vec![1, 2, 2], 10);
+        assert_eq!(all.iter().map(|chunk| chunk.len()).sum::<usize>(), 3);
+        assert_eq!(all[0].to_string(), "2,1,2\n1,2,2\n");
+        assert_eq!(beam(vec![], 1)[0].to_string(), "\n");
+    }
+
+    #[test]
+    fn branches_are_tried_in_the_given_order() {
+        let weight = |value: &i32| value % 3;
+        for strategy in [
+            ExpansionStrategy::DepthFirst,
+            ExpansionStrategy::BreadthFirst,
+        ] {
+            let ordered = |values: Vec<i32>| {
+                Permutations::new(values)
+                    .with_strategy(strategy)
+                    .into_chunks(4)
+                    .with_branch_order(|left, right| weight(right).cmp(&weight(left)))
+                    .map(|chunk| chunk.to_string())
+                    .collect::<String>()
+            };
+            // the heaviest value first: 2 (weight 2), then 1 (weight 1), then 3 (weight 0).
+            assert_eq!(
+                ordered(vec![1, 2, 3]),
+                "2,1,3\n2,3,1\n1,2,3\n1,3,2\n3,2,1\n3,1,2\n"
+            );
+            assert_eq!(ordered(vec![1, 1, 2]), "2,1,1\n1,2,1\n1,1,2\n");
+            // 4 and 1 have the same weight: they keep the order of first occurrence.
+            assert_eq!(ordered(vec![4, 1]), "4,1\n1,4\n");
+            assert_eq!(ordered(vec![]), "\n");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "before the generation starts")]
+    fn branch_order_cannot_change_during_the_generation() {
+        let mut chunks = Permutations::new(vec![1, 2, 3]).into_chunks(1);
+        chunks.next();
+        let _ = chunks.with_branch_order(|left, right| right.cmp(left));
+    }
+
+    #[test]
+    fn cancelled_generation_can_be_resumed() {
+        let permutations = Permutations::new(vec![1, 2, 3]);
+        let remaining = |rank: usize| {
+            permutations
+                .clone()
+                .into_ranked_chunks(6)
+                .starting_at(rank)
+                .map(|chunk| chunk.to_string())
+                .collect::<String>()
+        };
+
+        let token = CancellationToken::new();
+        let mut chunks = permutations
+            .clone()
+            .into_chunks(2)
+            .with_cancellation(token.clone());
+        assert!(chunks.next().is_some());
+        token.cancel();
+        assert!(chunks.next().is_none());
+        assert_eq!(chunks.next_rank(), 2);
+
+        let token = CancellationToken::new();
+        let mut chunks = permutations
+            .clone()
+            .into_optimized_chunks(2)
+            .with_cancellation(token.clone());
+        assert!(chunks.next().is_some());
+        token.cancel();
+        assert!(chunks.next().is_none());
+        assert_eq!(chunks.next_rank(), 2);
+
+        let token = CancellationToken::new();
+        let mut chunks = permutations
+            .clone()
+            .into_ranked_chunks(4)
+            .with_cancellation(token.clone());
+        assert!(chunks.next().is_some());
+        token.cancel();
+        assert!(chunks.next().is_none());
+        assert_eq!(chunks.next_rank(), 4);
+        assert_eq!(remaining(chunks.next_rank()), "3,1,2\n3,2,1\n");
+    }
+
+    #[test]
+    fn generation_stops_at_the_budget() {
+        let permutations = Permutations::new(vec![1, 2, 3, 4]);
+        let mut chunks = permutations.clone().into_chunks(3).with_limit(5);
+        let lengths = chunks
+            .by_ref()
+            .map(|chunk| (chunk.len(), chunk.is_last()))
+            .collect::<Vec<(usize, bool)>>();
+        assert_eq!(lengths, vec![(3, false), (2, true)]);
+        let coverage = Coverage {
+            permutations: 5,
+            last_rank: Some(4),
+            complete: false,
+        };
+        assert_eq!(chunks.coverage(), coverage);
+
+        let mut chunks = permutations.clone().into_optimized_chunks(3).with_limit(5);
+        assert_eq!(chunks.by_ref().map(|chunk| chunk.len()).sum::<usize>(), 5);
+        assert_eq!(chunks.coverage(), coverage);
+
+        let mut chunks = permutations
+            .clone()
+            .into_ranked_chunks(3)
+            .starting_at(20)
+            .with_limit(5);
+        assert_eq!(chunks.by_ref().map(|chunk| chunk.len()).sum::<usize>(), 4);
+        assert_eq!(
+            chunks.coverage(),
+            Coverage {
+                permutations: 4,
+                last_rank: Some(23),
+                complete: true,
+            }
+        );
+
+        let mut chunks = permutations.into_chunks(3).with_deadline(Duration::ZERO);
+        assert!(chunks.next().is_none());
+        assert_eq!(chunks.coverage().last_rank, None);
+    }
+
+    #[test]
+    fn permutations_longer_than_the_inline_length_are_computed_correctly() {
+        let permutations = Permutations::new((0..=crate::INLINE_LENGTH).collect::<Vec<usize>>());
+        let chunk = permutations.clone().into_chunks(2).next().unwrap();
+        let expected = (0..2)
+            .map(|rank| permutations.nth_permutation(rank).unwrap())
+            .collect::<Vec<Vec<usize>>>();
+
+        assert_eq!(chunk.len(), 2);
+        for (index, permutation) in expected.iter().enumerate() {
+            assert!(permutation
+                .iter()
+                .enumerate()
+                .all(|(position, value)| chunk.value(index, position) == value));
+        }
+    }
+
+    #[test]
+    fn latin_rectangles_have_distinct_columns() {
+        // the number of Latin squares of order 3 and 4.
+        assert_eq!(
+            Permutations::new(vec![1, 2, 3]).latin_rectangles(3).count(),
+            12
+        );
+        assert_eq!(
+            Permutations::new(vec!['a', 'b', 'c', 'd'])
+                .latin_rectangles(4)
+                .count(),
+            576
+        );
+        let rectangles = Permutations::new(vec![1, 2, 3])
+            .latin_rectangles(2)
+            .collect::<Vec<_>>();
+        // each of the 3! first rows has 2 derangements.
+        assert_eq!(rectangles.len(), 12);
+        assert_eq!(rectangles[0], vec![vec![1, 2, 3], vec![2, 3, 1]]);
+        assert_eq!(rectangles[1], vec![vec![1, 2, 3], vec![3, 1, 2]]);
+        assert!(rectangles
+            .iter()
+            .all(|rows| (0..3).all(|column| rows[0][column] != rows[1][column])));
+        assert_eq!(Permutations::new(vec![1, 2]).latin_rectangles(3).count(), 0);
+        assert_eq!(
+            Permutations::new(vec![1, 2])
+                .latin_rectangles(0)
+                .collect::<Vec<_>>(),
+            vec![Vec::<Vec<i32>>::new()]
+        );
+    }
+
+    #[cfg(all(feature = "paranoid", debug_assertions))]
+    #[test]
+    fn paranoid_checks_pass_on_every_backend() {
+        for permutations in [
+            Permutations::new(vec![1, 2, 1, 3, 2]),
+            Permutations::with_duplicates(vec![1, 2, 1, 3]),
+        ] {
+            permutations.clone().into_chunks(7).for_each(drop);
+            permutations
+                .clone()
+                .with_strategy(ExpansionStrategy::BreadthFirst)
+                .into_chunks(5)
+                .for_each(drop);
+            permutations.clone().into_optimized_chunks(3).for_each(drop);
+            permutations
+                .clone()
+                .into_ranked_chunks(4)
+                .starting_at(5)
+                .for_each(drop);
+        }
+    }
+
+    #[cfg(all(feature = "paranoid", debug_assertions))]
+    #[test]
+    #[should_panic(expected = "generated twice")]
+    fn paranoid_duplicates_panic() {
+        let mut paranoid = paranoid::Paranoid::new(&[1, 2, 3], Uniqueness::Distinct);
+        paranoid.record_labels(&[1, 0, 2]);
+        paranoid.record_labels(&[1, 0, 2]);
+    }
+
+    #[cfg(all(feature = "paranoid", debug_assertions))]
+    #[test]
+    #[should_panic(expected = "instead of 3")]
+    fn paranoid_missing_permutations_panic() {
+        let mut paranoid = paranoid::Paranoid::new(&[1, 1, 2], Uniqueness::Distinct);
+        paranoid.record_values(&[1, 2, 1]);
+        paranoid.check_complete();
+    }
+}
@@ -0,0 +1,133 @@
+//! # Job Arena
+//!
+//! `JobArena` stores the job queue of the breadth-first generation of `IntoChunks`
+//! (see `IntoChunks::with_arena`) without an allocation for each job.
+//!
+//! The queue is first-in first-out, so it holds at most two levels of the job tree:
+//! the jobs of the current depth, being expanded, and their children, one value longer.
+//! Every job of a level has the same number of labels and the same permutation length,
+//! so each level is stored in two flat buffers, the remaining frequencies and the partial permutations,
+//! and a job is a fixed-size slice of both.
+//! Once the current level has been expanded, the buffers of the next one become the current ones,
+//! and the old ones are cleared and reused: after the first levels, no job allocates.
+use crate::cancellation::{is_cancelled, CancellationToken};
+use crate::freq::FreqTable;
+use crate::permutations::into_chunks::Permutation;
+
+/// The jobs of a level of the job tree.
+struct Level<T> {
+    /// the remaining frequency of each label, for each job.
+    frequencies: Vec<usize>,
+    /// the partial permutation of each job.
+    permutations: Vec<T>,
+}
+
+impl<T> Level<T> {
+    fn clear(&mut self) {
+        self.frequencies.clear();
+        self.permutations.clear();
+    }
+}
+
+/// The job queue of the breadth-first generation, stored level by level.
+pub(crate) struct JobArena<T> {
+    current: Level<T>,
+    next: Level<T>,
+    /// the number of labels, the length of the frequencies of each job.
+    labels: usize,
+    /// the length of the partial permutations of the current level.
+    depth: usize,
+    /// the number of jobs of the current level.
+    jobs: usize,
+    /// the next job of the current level to expand.
+    cursor: usize,
+    permutation_length: usize,
+}
+
+impl<T: Copy> JobArena<T> {
+    /// Initialize the `JobArena` with the job at the root: the `frequencies` of the labels.
+    pub(crate) fn new(frequencies: &[usize], permutation_length: usize) -> Self {
+        Self {
+            current: Level {
+                frequencies: frequencies.to_vec(),
+                permutations: vec![],
+            },
+            next: Level {
+                frequencies: vec![],
+                permutations: vec![],
+            },
+            labels: frequencies.len(),
+            depth: 0,
+            jobs: 1,
+            cursor: 0,
+            permutation_length,
+        }
+    }
+
+    /// Check if the job at the root has been expanded.
+    pub(crate) fn is_started(&self) -> bool {
+        self.depth > 0 || self.cursor > 0
+    }
+
+    /// Get the frequencies of the job at the root, before the generation starts.
+    pub(crate) fn root_frequencies(&mut self) -> FreqTable<&mut [usize]> {
+        assert!(!self.is_started(), "The root has been expanded");
+        FreqTable::new(&mut self.current.frequencies[..])
+    }
+
+    /// Compute the next permutation, expanding the jobs in order: `values` is the value of each label.
+    /// The children rejected by `prune` are not stored.
+    pub(crate) fn next_permutation_pruned(
+        &mut self,
+        values: &[T],
+        cancellation: &Option<CancellationToken>,
+        mut prune: impl FnMut(&[T]) -> bool,
+    ) -> Option<Permutation<T>> {
+        loop {
+            if self.cursor == self.jobs && !self.next_level() {
+                return None;
+            }
+            if is_cancelled(cancellation) {
+                return None;
+            }
+            let job = self.cursor;
+            self.cursor += 1;
+            let permutation = &self.current.permutations[job * self.depth..(job + 1) * self.depth];
+            if self.depth == self.permutation_length {
+                return Some(permutation.iter().copied().collect());
+            }
+            let frequencies = &self.current.frequencies[job * self.labels..(job + 1) * self.labels];
+            for label in FreqTable::new(frequencies).available() {
+                let start = self.next.permutations.len();
+                self.next.permutations.extend_from_slice(permutation);
+                self.next.permutations.push(values[label]);
+                if prune(&self.next.permutations[start..]) {
+                    self.next.permutations.truncate(start);
+                    continue;
+                }
+                let start = self.next.frequencies.len();
+                self.next.frequencies.extend_from_slice(frequencies);
+                FreqTable::new(&mut self.next.frequencies[start..]).take(label);
+            }
+        }
+    }
+
+    /// Move to the children of the current level, reusing its buffers for their children.
+    /// Return `false` if there are none.
+    fn next_level(&mut self) -> bool {
+        if self.next.frequencies.is_empty() {
+            return false;
+        }
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.next.clear();
+        self.depth += 1;
+        self.jobs = self.current.frequencies.len() / self.labels;
+        self.cursor = 0;
+        true
+    }
+
+    /// Check if there are no more jobs.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cursor == self.jobs && self.next.frequencies.is_empty()
+    }
+}
@@ -0,0 +1,62 @@
+//! # Budget
+//!
+//! `Budget` stops the generation of a chunk iterator once a number of permutations has been generated,
+//! or once a deadline has passed, so that a huge space can be explored on a best-effort basis.
+//!
+//! The generation stops cleanly: the chunk being generated is returned, marked as the last one,
+//! and `Coverage` tells how far the generation got.
+use std::time::{Duration, Instant};
+
+/// The limits of the generation of a chunk iterator.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Budget {
+    /// the maximum number of permutations.
+    limit: Option<usize>,
+    /// the instant after which no permutation is generated.
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub(crate) fn with_limit(mut self, max_permutations: usize) -> Self {
+        self.limit = Some(max_permutations);
+        self
+    }
+    /// Set the deadline to `duration` from now.
+    pub(crate) fn with_deadline(mut self, duration: Duration) -> Self {
+        self.deadline = Some(Instant::now() + duration);
+        self
+    }
+    /// Check if no more permutations can be generated, after `generated` permutations.
+    pub(crate) fn is_spent(&self, generated: usize) -> bool {
+        self.limit.is_some_and(|limit| generated >= limit)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// How far the generation of a chunk iterator got.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    /// the number of permutations generated.
+    pub permutations: usize,
+    /// the rank of the last permutation generated, if any.
+    pub last_rank: Option<usize>,
+    /// whether all the permutations have been generated, rather than stopping at the budget.
+    pub complete: bool,
+}
+
+impl Coverage {
+    /// Summarize the generation of the ranks `[first_rank, next_rank)`.
+    pub(crate) fn new(first_rank: usize, next_rank: usize, complete: bool) -> Self {
+        Self {
+            permutations: next_rank - first_rank,
+            last_rank: if next_rank > first_rank {
+                Some(next_rank - 1)
+            } else {
+                None
+            },
+            complete,
+        }
+    }
+}
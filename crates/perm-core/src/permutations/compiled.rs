@@ -0,0 +1,202 @@
+//! # Compiled Permutations
+//!
+//! `CompiledPermutations` holds the tables needed to answer the random access queries
+//! on the permutations of a multiset, built once and reused across many queries:
+//!
+//! - the decode table: the value of each label, as in `IntoRankedChunks`;
+//! - the labels of each value, to encode a permutation back into labels;
+//! - the frequency table of the labels, and the total number of permutations.
+//!
+//! `Permutations::nth_permutation` and `Permutations::rank_of` rebuild the labels on every call,
+//! and count the permutations starting with each label with a multinomial coefficient, in `O(n * k^2)`
+//! for `k` distinct values. Here the count of the permutations starting with a label is derived
+//! from the count of the current prefix: `count * frequency / remaining`,
+//! so each query costs `O(n * k)`, without hashing the values again.
+//! When the total does not fit in a `usize` the queries fall back to the multinomial coefficients.
+//!
+//! It is meant for the servers and the tools answering many sample, unrank and range queries
+//! over the same values. With the `rand` feature the samples can also be drawn at random,
+//! from a generator passed by the caller, so that a seeded run is reproducible.
+use std::hash::Hash;
+use std::ops::Range;
+
+use crate::encoding::{compress_as, Encoding};
+use crate::hasher::{ValueHasher, ValueMap};
+use crate::math::multinomial;
+use crate::permutations::into_ranked_chunks::{rank, unrank, IntoRankedChunks};
+use crate::permutations::paranoid::Paranoid;
+use crate::permutations::Uniqueness;
+
+/// The precompiled tables of the permutations of some values, for repeated random access queries.
+#[derive(Clone, Debug)]
+pub struct CompiledPermutations<T> {
+    values: Vec<T>,
+    uniqueness: Uniqueness,
+    /// the value of each label.
+    label_to_value: Vec<T>,
+    /// the labels of each value, in increasing order.
+    labels_of_value: ValueMap<T, Vec<usize>>,
+    /// the frequency of each label.
+    frequencies: Vec<usize>,
+    /// the number of permutations, if it fits in a `usize`.
+    total: Option<usize>,
+    hasher: ValueHasher,
+}
+
+impl<T: Copy + Eq + Hash> CompiledPermutations<T> {
+    pub(crate) fn new(values: Vec<T>, uniqueness: Uniqueness, hasher: ValueHasher) -> Self {
+        let Encoding {
+            values: label_to_value,
+            frequencies,
+        } = compress_as(&values, uniqueness);
+        let mut labels_of_value = hasher.map::<T, Vec<usize>>();
+        for (label, value) in label_to_value.iter().enumerate() {
+            labels_of_value.entry(*value).or_default().push(label);
+        }
+        let total = multinomial(frequencies.iter().copied());
+        Self {
+            values,
+            uniqueness,
+            label_to_value,
+            labels_of_value,
+            frequencies,
+            total,
+            hasher,
+        }
+    }
+
+    /// Get the length of each permutation.
+    pub fn length(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Get the number of permutations, or `None` if it does not fit in a `usize`.
+    pub fn permutations_number(&self) -> Option<usize> {
+        self.total
+    }
+
+    /// Compute the permutation of rank `rank`, as `Permutations::nth_permutation`.
+    /// Return `None` if `rank` is not lower than the number of permutations.
+    pub fn nth_permutation(&self, rank: usize) -> Option<Vec<T>> {
+        self.unrank(rank).map(|labels| {
+            labels
+                .iter()
+                .map(|label| self.label_to_value[*label])
+                .collect()
+        })
+    }
+
+    /// Compute the rank of `permutation`, as `Permutations::rank_of`.
+    /// Return `None` if it is not a permutation of the values, or if its rank does not fit in a `usize`.
+    pub fn rank_of(&self, permutation: &[T]) -> Option<usize> {
+        if permutation.len() != self.length() {
+            return None;
+        }
+        let mut frequencies = self.frequencies.clone();
+        let mut labels = Vec::with_capacity(permutation.len());
+        for value in permutation {
+            let label = *self
+                .labels_of_value
+                .get(value)?
+                .iter()
+                .find(|label| frequencies[**label] > 0)?;
+            frequencies[label] -= 1;
+            labels.push(label);
+        }
+        let total = match self.total {
+            Some(total) => total,
+            None => return rank(&self.frequencies, &labels),
+        };
+        let mut frequencies = self.frequencies.clone();
+        // the number of permutations starting with the prefix of the labels ranked so far.
+        let mut count = total;
+        let mut rank = 0;
+        for (position, label) in labels.into_iter().enumerate() {
+            let remaining = self.length() - position;
+            for frequency in &frequencies[..label] {
+                rank += starting_with(count, *frequency, remaining);
+            }
+            count = starting_with(count, frequencies[label], remaining);
+            frequencies[label] -= 1;
+        }
+        Some(rank)
+    }
+
+    /// Compute `samples` permutations evenly spaced by rank, starting from the first one.
+    /// All the permutations are returned if they are fewer than `samples`.
+    pub fn sample(&self, samples: usize) -> impl Iterator<Item = Vec<T>> + '_ {
+        let count = self.total.unwrap_or(usize::MAX);
+        let samples = samples.min(count);
+        (0..samples).filter_map(move |sample| {
+            let rank = (sample as u128 * count as u128 / samples as u128) as usize;
+            self.nth_permutation(rank)
+        })
+    }
+
+    /// Compute `samples` uniformly random permutations, drawn independently by rank with `rng`:
+    /// the same seed gives the same permutations.
+    /// When the number of permutations does not fit in a `usize`, the ranks are drawn below `usize::MAX`.
+    #[cfg(feature = "rand")]
+    pub fn random_sample<'a, R: rand::Rng + ?Sized>(
+        &'a self,
+        samples: usize,
+        rng: &'a mut R,
+    ) -> impl Iterator<Item = Vec<T>> + 'a {
+        let count = self.total.unwrap_or(usize::MAX);
+        (0..samples).filter_map(move |_| self.nth_permutation(rng.gen_range(0..count)))
+    }
+
+    /// Create the iterator over chunks of `size` permutations with the ranks in `ranks`,
+    /// as `Permutations::into_ranked_chunks` started at `ranks.start` and limited to `ranks.len()`,
+    /// without computing the labels again.
+    /// Panics if the chunk size is zero.
+    pub fn range(&self, ranks: Range<usize>, size: usize) -> IntoRankedChunks<T> {
+        if size == 0 {
+            panic!("Chunks size must be at least one")
+        }
+        IntoRankedChunks::with_labels(
+            self.label_to_value.clone(),
+            self.frequencies.clone(),
+            None,
+            size,
+            Paranoid::new(&self.values, self.uniqueness),
+            self.hasher,
+        )
+        .starting_with(ranks.start, self.unrank(ranks.start))
+        .with_limit(ranks.len())
+    }
+
+    /// Compute the labels of the permutation of rank `rank`.
+    fn unrank(&self, mut rank: usize) -> Option<Vec<usize>> {
+        let total = match self.total {
+            Some(total) => total,
+            None => return unrank(&self.frequencies, rank),
+        };
+        if rank >= total {
+            return None;
+        }
+        let mut frequencies = self.frequencies.clone();
+        let mut count = total;
+        let mut labels = Vec::with_capacity(self.length());
+        for remaining in (1..=self.length()).rev() {
+            for (label, frequency) in frequencies.iter_mut().enumerate() {
+                let starting = starting_with(count, *frequency, remaining);
+                if rank < starting {
+                    labels.push(label);
+                    *frequency -= 1;
+                    count = starting;
+                    break;
+                }
+                rank -= starting;
+            }
+        }
+        Some(labels)
+    }
+}
+
+/// Compute the number of permutations starting with a label of frequency `frequency`,
+/// among the `count` permutations of `remaining` labels.
+/// It is exact, and not greater than `count`.
+fn starting_with(count: usize, frequency: usize, remaining: usize) -> usize {
+    (count as u128 * frequency as u128 / remaining as u128) as usize
+}
@@ -0,0 +1,211 @@
+//! # Constraints
+//!
+//! Structural constraints on the permutations, relative to the input order of the values:
+//! the value at position `i` is a fixed point if it is the input value at position `i`,
+//! and the permutation is an involution if it is its own inverse,
+//! i.e. if it moves the input value at `i` to `j` it moves the input value at `j` to `i`.
+//! The permutation is indecomposable (or connected) if no proper prefix holds the input values of the same positions,
+//! i.e. it is not the concatenation of a permutation of the first input values and one of the others.
+//!
+//! Like `ForbiddenPattern`, they prune the job tree of `IntoPrunedChunks`:
+//! a partial permutation is cut as soon as none of the permutations starting with it can satisfy them,
+//! so the permutations are never generated and filtered afterwards.
+use std::hash::Hash;
+
+use crate::freq::values_with_frequency;
+use crate::hasher::{ValueHasher, ValueMap};
+
+/// The permutations that are their own inverse.
+pub(crate) struct Involution<T> {
+    values: Vec<T>,
+    /// the input position of each value.
+    position_of: ValueMap<T, usize>,
+}
+
+impl<T: Copy + Eq + Hash> Involution<T> {
+    /// Initialize the `Involution` of the input `values`, hashed with `hasher`.
+    /// Panics if the values are not distinct: the inverse of a permutation of equal values is ambiguous.
+    pub(crate) fn new(values: &[T], hasher: ValueHasher) -> Self {
+        let mut position_of = hasher.map_with_capacity(values.len());
+        for (position, value) in values.iter().enumerate() {
+            if position_of.insert(*value, position).is_some() {
+                panic!("Involutions are defined only for distinct values")
+            }
+        }
+        Self {
+            values: values.to_vec(),
+            position_of,
+        }
+    }
+
+    /// Check if no involution starts with the `partial` permutation.
+    /// Its prefixes have already been checked, so only its last value is.
+    pub(crate) fn rejects(&self, partial: &[T]) -> bool {
+        let last = match partial.len().checked_sub(1) {
+            Some(last) => last,
+            None => return false,
+        };
+        let target = self.position_of[&partial[last]];
+        if target < last {
+            // the value at `target` must be the input value at `last`.
+            partial[target] != self.values[last]
+        } else {
+            // the input value at `last` must go to `target`, so it cannot be already placed.
+            target > last && partial[..last].contains(&self.values[last])
+        }
+    }
+}
+
+/// The permutations with an exact number of fixed points.
+pub(crate) struct FixedPoints<T> {
+    values: Vec<T>,
+    count: usize,
+    hasher: ValueHasher,
+}
+
+impl<T: Copy + Eq + Hash> FixedPoints<T> {
+    /// Initialize the `FixedPoints` of the input `values`, hashed with `hasher`.
+    pub(crate) fn new(values: &[T], count: usize, hasher: ValueHasher) -> Self {
+        Self {
+            values: values.to_vec(),
+            count,
+            hasher,
+        }
+    }
+
+    /// Check if no permutation starting with the `partial` one has exactly `count` fixed points:
+    /// it already has too many, or too few positions can still be fixed with the remaining values.
+    pub(crate) fn rejects(&self, partial: &[T]) -> bool {
+        let fixed = partial
+            .iter()
+            .zip(&self.values)
+            .filter(|(value, input)| value == input)
+            .count();
+        if fixed > self.count {
+            return true;
+        }
+        let mut remaining = values_with_frequency(&self.values, self.hasher);
+        for value in partial {
+            if let Some(frequency) = remaining.get_mut(value) {
+                *frequency -= 1;
+            }
+        }
+        let mut can_be_fixed = 0;
+        for value in &self.values[partial.len()..] {
+            if let Some(frequency) = remaining.get_mut(value).filter(|frequency| **frequency > 0) {
+                *frequency -= 1;
+                can_be_fixed += 1;
+            }
+        }
+        fixed + can_be_fixed < self.count
+    }
+}
+
+/// The permutations without a proper prefix holding the input values of the same positions.
+pub(crate) struct Indecomposable<T> {
+    values: Vec<T>,
+    hasher: ValueHasher,
+}
+
+impl<T: Copy + Eq + Hash> Indecomposable<T> {
+    /// Initialize the `Indecomposable` of the input `values`, hashed with `hasher`.
+    pub(crate) fn new(values: &[T], hasher: ValueHasher) -> Self {
+        Self {
+            values: values.to_vec(),
+            hasher,
+        }
+    }
+
+    /// Check if the `partial` permutation is a proper prefix holding the first input values:
+    /// all the permutations starting with it are decomposable.
+    /// Its prefixes have already been checked, so only the whole of it is.
+    pub(crate) fn rejects(&self, partial: &[T]) -> bool {
+        if partial.is_empty() || partial.len() >= self.values.len() {
+            return false;
+        }
+        values_with_frequency(partial, self.hasher)
+            == values_with_frequency(&self.values[..partial.len()], self.hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::permutations::tests::collect_permutations;
+    use crate::Permutations;
+
+    #[test]
+    fn involutions_and_fixed_points_are_generated() {
+        let never: fn(&[i32]) -> bool = |_| false;
+        let chunks = |values: Vec<i32>| Permutations::new(values).into_pruned_chunks(5, never);
+        // the telephone numbers: 1, 2, 4, 10, 26, 76.
+        assert_eq!(
+            collect_permutations(chunks(vec![1, 2, 3, 4, 5, 6]).involutions_only()).len(),
+            76
+        );
+        assert_eq!(
+            collect_permutations(chunks(vec![3, 1, 2]).involutions_only()),
+            vec![vec![3, 1, 2], vec![3, 2, 1], vec![1, 3, 2], vec![2, 1, 3]]
+        );
+        // the rencontres numbers of 5 values: 44 derangements, 45 with one fixed point, 20 with two.
+        assert_eq!(
+            collect_permutations(chunks(vec![1, 2, 3, 4, 5]).with_fixed_points(0)).len(),
+            44
+        );
+        assert_eq!(
+            collect_permutations(chunks(vec![1, 2, 3, 4, 5]).with_fixed_points(1)).len(),
+            45
+        );
+        assert_eq!(
+            collect_permutations(chunks(vec![1, 2, 3, 4, 5]).with_fixed_points(2)).len(),
+            20
+        );
+        assert_eq!(
+            collect_permutations(chunks(vec![1, 2, 3, 4, 5]).with_fixed_points(4)).len(),
+            0
+        );
+        // the involutions of 4 values without fixed points are the 3 perfect matchings.
+        assert_eq!(
+            collect_permutations(
+                chunks(vec![1, 2, 3, 4])
+                    .involutions_only()
+                    .with_fixed_points(0)
+            ),
+            vec![vec![2, 1, 4, 3], vec![3, 4, 1, 2], vec![4, 3, 2, 1]]
+        );
+        // the equal values are fixed points in each other's positions.
+        assert_eq!(
+            collect_permutations(chunks(vec![1, 1, 2]).with_fixed_points(1)),
+            vec![vec![1, 2, 1], vec![2, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn indecomposable_permutations_have_no_closed_prefix() {
+        let indecomposable = |values: Vec<i32>| {
+            collect_permutations(
+                Permutations::new(values)
+                    .into_pruned_chunks(4, |_: &[i32]| false)
+                    .indecomposable_only(),
+            )
+        };
+        // 1, 1, 3, 13, 71 indecomposable permutations of 1 to 5 values.
+        assert_eq!(indecomposable(vec![1, 2, 3, 4]).len(), 13);
+        assert_eq!(indecomposable(vec![1, 2, 3, 4, 5]).len(), 71);
+        assert_eq!(
+            indecomposable(vec![1, 2, 3]),
+            vec![vec![2, 3, 1], vec![3, 1, 2], vec![3, 2, 1]]
+        );
+        assert_eq!(indecomposable(vec![1]), vec![vec![1]]);
+        // the prefix `1` holds the first input value, whichever of the equal values it is.
+        assert_eq!(indecomposable(vec![1, 1, 2]), vec![vec![2, 1, 1]]);
+        assert!(indecomposable(vec![1, 2, 1]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "distinct values")]
+    fn involutions_of_equal_values_are_rejected() {
+        let _ = Permutations::new(vec![1, 1, 2])
+            .into_pruned_chunks(3, |_: &[i32]| false)
+            .involutions_only();
+    }
+}
@@ -0,0 +1,134 @@
+//! # Beam Iterator
+//!
+//! `IntoBeamChunks` is an iterator over `Chunk`s of permutations found by a beam search:
+//! the job tree is explored level by level, as by the breadth-first generation,
+//! but only the `beam_width` partial permutations with the highest score are kept at each level.
+//! So the search costs `O(n * beam_width * k)` scores for `k` distinct values, whatever the number of permutations,
+//! and it finds good permutations of inputs far too long to be enumerated, without any guarantee to find the best one.
+//!
+//! The children of the kept partial permutations are scored in a single buffer for each parent,
+//! and only the `beam_width` best are copied to the next level.
+//! Among the children with the same score the first ones in generation order are kept.
+//!
+//! The search runs to the last level when the first chunk is requested: then the permutations of the beam,
+//! at most `beam_width`, are returned by descending score.
+use std::hash::Hash;
+
+use crate::encoding::{compress_as, Encoding};
+use crate::permutations::into_chunks::{Chunk, Permutation};
+use crate::permutations::Uniqueness;
+
+/// A partial permutation kept in the beam.
+struct Candidate<T> {
+    permutation: Permutation<T>,
+    /// the remaining frequency of each label.
+    frequencies: Vec<usize>,
+}
+
+/// Iterator over `Chunk`s of the permutations found by a beam search, by descending score.
+pub struct IntoBeamChunks<T, S> {
+    /// the value of each label.
+    label_to_value: Vec<T>,
+    /// the beam of the current level, sorted by descending score.
+    beam: Vec<Candidate<T>>,
+    beam_width: usize,
+    score: S,
+    size: usize,
+    permutation_length: usize,
+    is_searched: bool,
+    chunks_generated: usize,
+    permutations_generated: usize,
+}
+
+impl<T: Copy + Eq + Hash, S: FnMut(&[T]) -> f64> IntoBeamChunks<T, S> {
+    pub(crate) fn new(
+        values: Vec<T>,
+        beam_width: usize,
+        size: usize,
+        uniqueness: Uniqueness,
+        score: S,
+    ) -> Self {
+        let Encoding {
+            values: label_to_value,
+            frequencies,
+        } = compress_as(&values, uniqueness);
+        let root = Candidate {
+            permutation: Permutation::new(),
+            frequencies,
+        };
+        Self {
+            label_to_value,
+            beam: vec![root],
+            beam_width,
+            score,
+            size,
+            permutation_length: values.len(),
+            is_searched: false,
+            chunks_generated: 0,
+            permutations_generated: 0,
+        }
+    }
+
+    /// Replace the beam with the best `beam_width` children of its partial permutations, until they are full.
+    fn search(&mut self) {
+        for _ in 0..self.permutation_length {
+            // the score, the parent and the label of each child.
+            let mut children = vec![];
+            for (parent, candidate) in self.beam.iter().enumerate() {
+                let mut permutation = candidate.permutation.clone();
+                for (label, frequency) in candidate.frequencies.iter().enumerate() {
+                    if *frequency == 0 {
+                        continue;
+                    }
+                    permutation.push(self.label_to_value[label]);
+                    children.push(((self.score)(&permutation), parent, label));
+                    permutation.pop();
+                }
+            }
+            // the sort is stable: the ties keep the generation order.
+            children.sort_by(|(left, _, _), (right, _, _)| right.total_cmp(left));
+            children.truncate(self.beam_width);
+            self.beam = children
+                .into_iter()
+                .map(|(_, parent, label)| {
+                    let parent = &self.beam[parent];
+                    let mut permutation = parent.permutation.clone();
+                    permutation.push(self.label_to_value[label]);
+                    let mut frequencies = parent.frequencies.clone();
+                    frequencies[label] -= 1;
+                    Candidate {
+                        permutation,
+                        frequencies,
+                    }
+                })
+                .collect();
+        }
+        self.is_searched = true;
+    }
+}
+
+/// The iterator implementation to return the permutations of the beam in chunks.
+/// The ranks of the chunks are the positions by descending score.
+impl<T: Copy + Eq + Hash, S: FnMut(&[T]) -> f64> Iterator for IntoBeamChunks<T, S> {
+    type Item = Chunk<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.is_searched {
+            self.search();
+        }
+        let start = self.permutations_generated;
+        let end = self.beam.len().min(start + self.size);
+        if start == end {
+            return None;
+        }
+        let mut chunk = Chunk::new(self.size);
+        chunk.as_mut().extend(
+            self.beam[start..end]
+                .iter()
+                .map(|candidate| candidate.permutation.clone()),
+        );
+        let index = self.chunks_generated;
+        self.chunks_generated += 1;
+        self.permutations_generated = end;
+        Some(chunk.with_position(index, start, end == self.beam.len()))
+    }
+}
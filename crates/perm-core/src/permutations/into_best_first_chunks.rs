@@ -0,0 +1,210 @@
+//! # Best-First Iterator
+//!
+//! `IntoBestFirstChunks` is an iterator over `Chunk`s of permutations, explored best-first:
+//! the partial permutations are kept in a priority queue keyed by a score of the partial permutation,
+//! instead of the stack of `IntoChunks`, and the one with the highest score is expanded first.
+//!
+//! When the score of a partial permutation is not lower than the score of any of its completions
+//! (e.g. the weight collected so far plus an optimistic estimate of the rest),
+//! the permutations are emitted by descending score: the stream can be cut at any time
+//! with the best permutations found so far, as an anytime optimizer.
+//! With any other score the order is only a heuristic, but every permutation is still generated once.
+//!
+//! Among the partial permutations with the same score the longest one is expanded first, to reach the leaves sooner,
+//! and then the one queued first, so the order is deterministic.
+//! The queue holds every partial permutation generated and not yet expanded:
+//! up to the number of permutations, against the `O(n)` path of the depth-first generation.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+
+use crate::encoding::{compress_as, Encoding};
+use crate::permutations::into_chunks::{Chunk, Permutation};
+use crate::permutations::paranoid::Paranoid;
+use crate::permutations::Uniqueness;
+
+/// A partial permutation waiting to be expanded.
+/// It is ordered by score, then by length, then by the reverse of the order it was queued in.
+struct Node<T> {
+    score: f64,
+    /// the number of nodes queued before this one.
+    sequence: usize,
+    permutation: Permutation<T>,
+    /// the remaining frequency of each label.
+    frequencies: Vec<usize>,
+}
+
+impl<T> PartialEq for Node<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Node<T> {}
+
+impl<T> PartialOrd for Node<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Node<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.permutation.len().cmp(&other.permutation.len()))
+            .then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Iterator over `Chunk`s of permutations, the partial permutation with the highest score expanded first.
+pub struct IntoBestFirstChunks<T, S> {
+    /// the value of each label.
+    label_to_value: Vec<T>,
+    queue: BinaryHeap<Node<T>>,
+    score: S,
+    size: usize,
+    permutation_length: usize,
+    nodes_queued: usize,
+    chunks_generated: usize,
+    permutations_generated: usize,
+    paranoid: Paranoid<T>,
+}
+
+impl<T: Copy + Eq + Hash, S: FnMut(&[T]) -> f64> IntoBestFirstChunks<T, S> {
+    pub(crate) fn new(values: Vec<T>, size: usize, uniqueness: Uniqueness, score: S) -> Self {
+        let Encoding {
+            values: label_to_value,
+            frequencies,
+        } = compress_as(&values, uniqueness);
+        let mut chunks = Self {
+            label_to_value,
+            queue: BinaryHeap::new(),
+            score,
+            size,
+            permutation_length: values.len(),
+            nodes_queued: 0,
+            chunks_generated: 0,
+            permutations_generated: 0,
+            paranoid: Paranoid::new(&values, uniqueness),
+        };
+        chunks.push(Permutation::new(), frequencies);
+        chunks
+    }
+
+    /// Queue a partial permutation with its score.
+    fn push(&mut self, permutation: Permutation<T>, frequencies: Vec<usize>) {
+        let score = (self.score)(&permutation);
+        self.queue.push(Node {
+            score,
+            sequence: self.nodes_queued,
+            permutation,
+            frequencies,
+        });
+        self.nodes_queued += 1;
+    }
+
+    /// Expand the partial permutations with the highest score until one of them is a full permutation.
+    fn next_permutation(&mut self) -> Option<Permutation<T>> {
+        loop {
+            let node = self.queue.pop()?;
+            if node.permutation.len() == self.permutation_length {
+                self.paranoid.record_values(&node.permutation);
+                return Some(node.permutation);
+            }
+            for (label, frequency) in node.frequencies.iter().enumerate() {
+                if *frequency == 0 {
+                    continue;
+                }
+                let mut permutation = node.permutation.clone();
+                permutation.push(self.label_to_value[label]);
+                let mut frequencies = node.frequencies.clone();
+                frequencies[label] -= 1;
+                self.push(permutation, frequencies);
+            }
+        }
+    }
+}
+
+/// The iterator implementation to generate a single chunk of permutations.
+/// The ranks of the chunks are the positions in the best-first order.
+impl<T: Copy + Eq + Hash, S: FnMut(&[T]) -> f64> Iterator for IntoBestFirstChunks<T, S> {
+    type Item = Chunk<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Chunk::new(self.size);
+        while !chunk.is_full() {
+            match self.next_permutation() {
+                Some(permutation) => chunk.as_mut().push(permutation),
+                None => break,
+            }
+        }
+        if chunk.is_empty() {
+            return None;
+        }
+        let (index, first_rank) = (self.chunks_generated, self.permutations_generated);
+        self.chunks_generated += 1;
+        self.permutations_generated += chunk.len();
+        let is_last = self.queue.is_empty();
+        if is_last {
+            self.paranoid.check_complete();
+        }
+        Some(chunk.with_position(index, first_rank, is_last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::permutations::tests::collect_permutations;
+    use crate::Permutations;
+
+    #[test]
+    fn best_first_chunks_are_generated_by_descending_score() {
+        let values = vec![3, 1, 4, 1, 5];
+        // the weighted sum of a partial permutation, completed with the best weights for the remaining values:
+        // not lower than the weighted sum of any of its completions.
+        let weighted_sum = |permutation: &[i32]| {
+            permutation
+                .iter()
+                .zip((1..=5).rev())
+                .map(|(value, weight)| f64::from(value * weight))
+                .sum::<f64>()
+        };
+        let bound = |partial: &[i32]| {
+            let mut remaining = values.clone();
+            for value in partial {
+                let position = remaining.iter().position(|v| v == value).unwrap();
+                remaining.remove(position);
+            }
+            remaining.sort_unstable_by(|a, b| b.cmp(a));
+            let completion = partial
+                .iter()
+                .chain(&remaining)
+                .copied()
+                .collect::<Vec<_>>();
+            weighted_sum(&completion)
+        };
+        let chunks = Permutations::new(values.clone())
+            .into_best_first_chunks(7, bound)
+            .collect::<Vec<_>>();
+        assert_eq!(chunks.last().map(|chunk| chunk.is_last()), Some(true));
+        let permutations = collect_permutations(chunks);
+        assert_eq!(permutations[0], vec![5, 4, 3, 1, 1]);
+        let scores = permutations
+            .iter()
+            .map(|permutation| weighted_sum(permutation))
+            .collect::<Vec<f64>>();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(
+            permutations.iter().sorted().dedup().count(),
+            Permutations::new(values).permutations_number()
+        );
+        assert_eq!(permutations.len(), 60);
+        let empty = Permutations::<i32>::new(vec![])
+            .into_best_first_chunks(2, |_| 0.0)
+            .map(|chunk| chunk.to_string())
+            .collect::<String>();
+        assert_eq!(empty, "\n");
+    }
+}
@@ -0,0 +1,875 @@
+//! # Iterator
+//!
+//! `IntoChunks` is an iterator over `Chunk`of permutations.
+//!
+//! `Chunk` is a sequence of permutations-
+//! It is a `Display` to be written to output.
+//! It is a `AsMut` to be updated with new permutations.
+//!
+//! `Job` is the computational node to create a new permutation.
+//! Expanding a job materializes all its children, each one with a copy of the partial permutation
+//! and of the remaining frequencies: the depth-first queue holds `O(n^2)` of them.
+//! So the depth-first generation does not use jobs: `Path` holds the remaining frequencies
+//! and a single partial permutation, with the label of the value chosen at each position as its cursor.
+//! The children are expanded lazily: the path is extended with the first value available after the cursor,
+//! and backtracked when there is none, so the memory is `O(n)` and the order is the same.
+//! Once the path is a full permutation, the next one is computed in place, as the next permutation
+//! of the labels of the multiset: no partial state is cloned or dropped, only the generated permutations.
+//! The breadth-first generation keeps the queue of jobs, since it holds a whole level of the tree anyway.
+//! With `IntoChunks::with_arena` the queue is stored in a `JobArena` instead,
+//! one level at a time in flat buffers reused by the following levels, so the jobs are not allocated one by one.
+//!
+//! The values are tried at each position in order of first occurrence, unless `IntoChunks::with_branch_order`
+//! sorts them: the labels are given in the new order, so both the path and the jobs follow it unchanged,
+//! e.g. to emit first the permutations starting with the heaviest values.
+//!
+//! With the `smallvec` feature the permutations are stored in a `SmallVec`:
+//! up to `INLINE_LENGTH` values are stored inline,
+//! so that the short permutations, the most common ones, do not need a heap allocation each.
+//! The longer permutations are stored on the heap, without limits on the length.
+//! Without it, `Permutation` is a `Vec`: only the API shared by both should be relied upon.
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::hash::Hash;
+use std::ops::Index;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
+
+use crate::cancellation::{is_cancelled, CancellationToken};
+use crate::format::{Formatted, OutputFormat, PermValue, ToBytes, ValueStrings};
+use crate::freq::{values_with_frequency_in_order, Counts, FreqTable};
+use crate::hasher::ValueHasher;
+use crate::permutations::arena::JobArena;
+use crate::permutations::budget::{Budget, Coverage};
+use crate::permutations::paranoid::Paranoid;
+use crate::permutations::utils::is_expired;
+use crate::permutations::{ChunkView, ExpansionStrategy, Uniqueness};
+
+/// The number of values of a `Permutation` stored inline, with the `smallvec` feature.
+pub const INLINE_LENGTH: usize = 32;
+
+/// A permutation generated by `IntoChunks`,
+/// stored inline if it has at most `INLINE_LENGTH` values.
+#[cfg(feature = "smallvec")]
+pub type Permutation<T> = SmallVec<[T; INLINE_LENGTH]>;
+
+/// A permutation generated by `IntoChunks`.
+#[cfg(not(feature = "smallvec"))]
+pub type Permutation<T> = Vec<T>;
+
+/// The partial permutations still to be expanded.
+enum Frontier<T> {
+    /// the single path of the depth-first generation.
+    Path(Box<Path<T>>),
+    /// the job queue of the breadth-first generation, with the value of each label.
+    Queue(Vec<T>, VecDeque<Job<T>>),
+    /// the job queue of the breadth-first generation stored in an arena, with the value of each label.
+    Arena(Vec<T>, JobArena<T>),
+}
+
+/// Iterator over `Chunks`
+pub struct IntoChunks<T> {
+    frontier: Frontier<T>,
+    size: usize,
+    time_slice: Option<Duration>,
+    cancellation: Option<CancellationToken>,
+    budget: Budget,
+    chunks_generated: usize,
+    permutations_generated: usize,
+    paranoid: Paranoid<T>,
+    value_strings: Arc<ValueStrings<T>>,
+    hasher: ValueHasher,
+}
+
+/// Initialize the iterator at the root of the job tree:
+/// the distinct input values with their frequency, in order of first occurrence.
+/// With `Uniqueness::WithDuplicates` each value is a different choice, even if it is equal to another.
+/// The root of the empty input is ready: it generates the empty permutation, the only one.
+impl<T: Copy + Eq + Hash> IntoChunks<T> {
+    pub(crate) fn new(
+        values: Vec<T>,
+        size: usize,
+        uniqueness: Uniqueness,
+        strategy: ExpansionStrategy,
+        hasher: ValueHasher,
+    ) -> Self {
+        let permutation_length = values.len();
+        let paranoid = Paranoid::new(&values, uniqueness);
+        let (labels, frequencies): (Vec<T>, Counts) = match uniqueness {
+            Uniqueness::Distinct => values_with_frequency_in_order(&values, hasher)
+                .into_iter()
+                .unzip(),
+            Uniqueness::WithDuplicates => values.iter().map(|value| (*value, 1)).unzip(),
+        };
+        let value_strings = Arc::new(ValueStrings::new(labels.clone(), hasher));
+        let frequencies = FreqTable::new(frequencies);
+        let frontier = match strategy {
+            ExpansionStrategy::DepthFirst => {
+                Frontier::Path(Box::new(Path::new(labels, frequencies, permutation_length)))
+            }
+            ExpansionStrategy::BreadthFirst => Frontier::Queue(
+                labels,
+                VecDeque::from(vec![Job::new(frequencies, permutation_length)]),
+            ),
+        };
+        Self {
+            frontier,
+            size,
+            time_slice: None,
+            cancellation: None,
+            budget: Budget::default(),
+            chunks_generated: 0,
+            permutations_generated: 0,
+            paranoid,
+            value_strings,
+            hasher,
+        }
+    }
+
+    /// Return each chunk as soon as `time_slice` has passed since the chunk was started,
+    /// even if it is not full, so that a streaming consumer gets the permutations steadily.
+    /// A chunk has at least one permutation, so the chunks are never empty.
+    /// The chunks still start at the rank after the last permutation of the previous one,
+    /// but they are no longer at the ranks multiple of the size.
+    pub fn with_time_slice(mut self, time_slice: Duration) -> Self {
+        self.time_slice = Some(time_slice);
+        self
+    }
+
+    /// Try the values at each position in the order of `compare`, instead of the order of first occurrence:
+    /// the permutations are generated in lexicographic order of the values sorted by `compare`,
+    /// and the equal values for `compare` keep their order of first occurrence.
+    /// The ranks of the chunks are the positions in this order, so they no longer match
+    /// the ones of `Permutations::into_ranked_chunks`.
+    /// Panics if a permutation has already been generated.
+    pub fn with_branch_order(mut self, compare: impl FnMut(&T, &T) -> Ordering) -> Self {
+        match &mut self.frontier {
+            Frontier::Path(path) if path.labels.is_empty() && !path.is_yielded => {
+                sort_labels(&mut path.values, &mut path.frequencies, compare);
+            }
+            Frontier::Queue(values, job_queue)
+                if job_queue.len() == 1 && job_queue[0].permutation.is_empty() =>
+            {
+                sort_labels(values, &mut job_queue[0].frequencies, compare);
+            }
+            Frontier::Arena(values, arena) if !arena.is_started() => {
+                sort_labels(values, &mut arena.root_frequencies(), compare);
+            }
+            _ => panic!("The branch order must be set before the generation starts"),
+        }
+        self
+    }
+
+    /// Store the job queue of the breadth-first generation in an arena, level by level,
+    /// instead of allocating the frequencies and the partial permutation of each job:
+    /// the buffers of each level are reused by the following ones, so the global allocator
+    /// is no longer hit for each of the (many and short-lived) jobs.
+    /// The permutations and their order do not change.
+    /// The depth-first generation has no jobs, so it is left unchanged.
+    /// Panics if a permutation has already been generated.
+    pub fn with_arena(mut self) -> Self {
+        self.frontier = match self.frontier {
+            Frontier::Queue(values, job_queue)
+                if job_queue.len() == 1 && job_queue[0].permutation.is_empty() =>
+            {
+                let root = &job_queue[0];
+                let arena = JobArena::new(root.frequencies.frequencies(), root.permutation_length);
+                Frontier::Arena(values, arena)
+            }
+            Frontier::Queue(..) => panic!("The arena must be set before the generation starts"),
+            frontier => frontier,
+        };
+        self
+    }
+
+    /// Stop the generation when `cancellation` is cancelled:
+    /// the current chunk is discarded and the iterator returns `None`.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Stop the generation after `max_permutations` permutations:
+    /// the chunk reaching the limit is the last one, and it may not be full.
+    pub fn with_limit(mut self, max_permutations: usize) -> Self {
+        self.budget = self.budget.with_limit(max_permutations);
+        self
+    }
+
+    /// Stop the generation once `duration` has passed from now:
+    /// the chunk being generated at the deadline is the last one, and it may not be full.
+    pub fn with_deadline(mut self, duration: Duration) -> Self {
+        self.budget = self.budget.with_deadline(duration);
+        self
+    }
+
+    /// Summarize how far the generation got.
+    pub fn coverage(&self) -> Coverage {
+        Coverage::new(0, self.permutations_generated, self.is_exhausted())
+    }
+
+    /// Get the rank of the first permutation of the next chunk:
+    /// after a cancellation, the generation can be resumed from it with `IntoRankedChunks::starting_at`.
+    pub fn next_rank(&self) -> usize {
+        self.permutations_generated
+    }
+
+    /// Compute the next permutation, or `None` if there are no more.
+    pub(crate) fn next_permutation(&mut self) -> Option<Permutation<T>> {
+        self.next_permutation_pruned(|_| false)
+    }
+
+    /// Compute the next permutation, skipping the subtrees of the partial permutations rejected by `prune`.
+    pub(crate) fn next_permutation_pruned(
+        &mut self,
+        mut prune: impl FnMut(&[T]) -> bool,
+    ) -> Option<Permutation<T>> {
+        let permutation = match &mut self.frontier {
+            Frontier::Path(path) => path.next_permutation_pruned(&self.cancellation, prune),
+            Frontier::Queue(values, job_queue) => loop {
+                let job = job_queue.pop_front()?;
+                if is_cancelled(&self.cancellation) {
+                    return None;
+                }
+                if job.is_ready() {
+                    break Some(job.permutation());
+                }
+                job_queue.extend(
+                    job.compute_next_jobs(values)
+                        .into_iter()
+                        .filter(|next_job| !prune(&next_job.permutation)),
+                );
+            },
+            Frontier::Arena(values, arena) => {
+                arena.next_permutation_pruned(values, &self.cancellation, prune)
+            }
+        };
+        if let Some(permutation) = &permutation {
+            self.paranoid.record_values(permutation);
+        }
+        permutation
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Get the hasher of the maps keyed by the values.
+    pub(crate) fn hasher(&self) -> ValueHasher {
+        self.hasher
+    }
+
+    /// Get the index and the rank of the first permutation of the next chunk,
+    /// and account for its `len` permutations.
+    pub(crate) fn next_chunk_position(&mut self, len: usize) -> (usize, usize) {
+        let position = (self.chunks_generated, self.permutations_generated);
+        self.chunks_generated += 1;
+        self.permutations_generated += len;
+        position
+    }
+
+    /// Check if there are no more permutations.
+    /// Without pruning, every job in the queue generates at least one permutation,
+    /// so there are no more permutations if and only if the queue is empty.
+    pub(crate) fn is_exhausted(&self) -> bool {
+        match &self.frontier {
+            Frontier::Path(path) => path.is_exhausted,
+            Frontier::Queue(_, job_queue) => job_queue.is_empty(),
+            Frontier::Arena(_, arena) => arena.is_empty(),
+        }
+    }
+}
+
+/// The iterator implementation to generate a single chunk of permutations.
+/// It terminates when the chunk is full, the time slice (if any) has passed,
+/// the budget is spent or there are no more permutations.
+/// It returns `None` if the generation has been cancelled.
+impl<T: Copy + Eq + Hash> Iterator for IntoChunks<T> {
+    type Item = Chunk<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Chunk::new(self.size);
+        let deadline = self
+            .time_slice
+            .map(|time_slice| Instant::now() + time_slice);
+
+        while !chunk.is_full() && (chunk.is_empty() || !is_expired(deadline)) {
+            if self
+                .budget
+                .is_spent(self.permutations_generated + chunk.len())
+            {
+                break;
+            }
+            match self.next_permutation() {
+                Some(permutation) => chunk.as_mut().push(permutation),
+                None => break,
+            }
+        }
+        if chunk.is_empty() || is_cancelled(&self.cancellation) {
+            None
+        } else {
+            let (index, first_rank) = self.next_chunk_position(chunk.len());
+            let is_last = self.is_exhausted() || self.budget.is_spent(self.permutations_generated);
+            if self.is_exhausted() {
+                self.paranoid.check_complete();
+            }
+            Some(
+                chunk
+                    .with_position(index, first_rank, is_last)
+                    .with_value_strings(self.value_strings.clone()),
+            )
+        }
+    }
+}
+
+/// Chunk of permutations.
+#[derive(Clone)]
+pub struct Chunk<T> {
+    permutations: Vec<Permutation<T>>,
+    size: usize,
+    index: usize,
+    first_rank: usize,
+    is_last: bool,
+    /// the strings of the values shared by the chunks of the same iterator, if any.
+    value_strings: Option<Arc<ValueStrings<T>>>,
+}
+
+impl<T> Chunk<T> {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            permutations: vec![],
+            size,
+            index: 0,
+            first_rank: 0,
+            is_last: false,
+            value_strings: None,
+        }
+    }
+    /// Initialize a chunk holding the given `permutations`, e.g. read back from an output,
+    /// at the given position in the generation order.
+    pub fn from_permutations(
+        permutations: Vec<Permutation<T>>,
+        index: usize,
+        first_rank: usize,
+        is_last: bool,
+    ) -> Self {
+        Self {
+            size: permutations.len(),
+            permutations,
+            index,
+            first_rank,
+            is_last,
+            value_strings: None,
+        }
+    }
+    /// Set the position of the chunk in the generation order.
+    pub(crate) fn with_position(mut self, index: usize, first_rank: usize, is_last: bool) -> Self {
+        self.index = index;
+        self.first_rank = first_rank;
+        self.is_last = is_last;
+        self
+    }
+    /// Set the strings of the values of the iterator, holding every value of the chunk.
+    pub(crate) fn with_value_strings(mut self, value_strings: Arc<ValueStrings<T>>) -> Self {
+        self.value_strings = Some(value_strings);
+        self
+    }
+    /// The sequence number of the chunk, starting from zero.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// The rank of the first permutation of the chunk.
+    pub fn first_rank(&self) -> usize {
+        self.first_rank
+    }
+    /// The rank of the last permutation of the chunk.
+    pub fn last_rank(&self) -> usize {
+        self.first_rank + self.permutations.len().saturating_sub(1)
+    }
+    /// Check if this is the final chunk of the iterator.
+    pub fn is_last(&self) -> bool {
+        self.is_last
+    }
+    pub(crate) fn is_full(&self) -> bool {
+        self.permutations.len() == self.size
+    }
+    /// The number of permutations in the chunk.
+    pub fn len(&self) -> usize {
+        self.permutations.len()
+    }
+    /// Check if the chunk has no permutations.
+    pub fn is_empty(&self) -> bool {
+        self.permutations.is_empty()
+    }
+    /// Get the permutation at `index` in the chunk, if any: the one of rank `first_rank() + index`.
+    pub fn get(&self, index: usize) -> Option<&[T]> {
+        self.permutations
+            .get(index)
+            .map(|permutation| &permutation[..])
+    }
+    /// Split the chunk in two at `mid`: the permutations before `mid`, and the ones from `mid` on,
+    /// e.g. to process a large chunk on many threads without chunking the source again.
+    /// Both parts keep the index of the chunk and the ranks of their permutations,
+    /// and only the second one can be the last chunk.
+    /// Panics if `mid` is greater than the length of the chunk.
+    pub fn split_at(mut self, mid: usize) -> (Chunk<T>, Chunk<T>) {
+        assert!(
+            mid <= self.permutations.len(),
+            "The split point is: `{}` but the chunk length is: {}",
+            mid,
+            self.permutations.len()
+        );
+        let second = self.permutations.split_off(mid);
+        let first_rank = self.first_rank;
+        let first = Chunk {
+            value_strings: self.value_strings.clone(),
+            ..Chunk::from_permutations(self.permutations, self.index, first_rank, false)
+        };
+        let second = Chunk {
+            value_strings: self.value_strings,
+            ..Chunk::from_permutations(second, self.index, first_rank + mid, self.is_last)
+        };
+        (first, second)
+    }
+    /// Append the permutations of `other`, the chunk following this one in the generation order:
+    /// the inverse of `split_at`, e.g. to put back together the parts of a chunk processed on many threads.
+    /// The merged chunk keeps the index and the first rank of this chunk, and it is the last one if `other` is.
+    /// Panics if `other` does not start at the rank following the last permutation of this chunk.
+    pub fn merge(mut self, other: Chunk<T>) -> Chunk<T> {
+        assert_eq!(
+            other.first_rank,
+            self.first_rank + self.permutations.len(),
+            "The merged chunk must follow the chunk"
+        );
+        self.permutations.extend(other.permutations);
+        self.size = self.permutations.len();
+        self.is_last = other.is_last;
+        // the strings of the values are kept only if they hold the values of both chunks.
+        let shared = match (&self.value_strings, &other.value_strings) {
+            (Some(first), Some(second)) => Arc::ptr_eq(first, second),
+            _ => false,
+        };
+        if !shared {
+            self.value_strings = None;
+        }
+        self
+    }
+}
+
+/// Assemble a `Chunk` from permutations known in advance, outside the generation:
+/// e.g. read back from an output, reordered or verified by another tool.
+/// The chunk is the first one (index and first rank zero) and not the last one, unless set otherwise.
+pub struct ChunkBuilder<T> {
+    permutations: Vec<Permutation<T>>,
+    index: usize,
+    first_rank: usize,
+    is_last: bool,
+}
+
+impl<T> Default for ChunkBuilder<T> {
+    fn default() -> Self {
+        Self {
+            permutations: vec![],
+            index: 0,
+            first_rank: 0,
+            is_last: false,
+        }
+    }
+}
+
+impl<T> ChunkBuilder<T> {
+    /// Initialize a `ChunkBuilder` without permutations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the sequence number of the chunk.
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = index;
+        self
+    }
+    /// Set the rank of the first permutation of the chunk.
+    pub fn with_first_rank(mut self, first_rank: usize) -> Self {
+        self.first_rank = first_rank;
+        self
+    }
+    /// Set whether the chunk is the last one of the generation.
+    pub fn with_last(mut self, is_last: bool) -> Self {
+        self.is_last = is_last;
+        self
+    }
+    /// Append a permutation, given by its values.
+    pub fn push(&mut self, permutation: impl IntoIterator<Item = T>) {
+        self.permutations.push(permutation.into_iter().collect());
+    }
+    /// The number of permutations appended so far.
+    pub fn len(&self) -> usize {
+        self.permutations.len()
+    }
+    /// Check if no permutation has been appended.
+    pub fn is_empty(&self) -> bool {
+        self.permutations.is_empty()
+    }
+    /// Build the `Chunk` of the permutations appended.
+    pub fn build(self) -> Chunk<T> {
+        Chunk::from_permutations(self.permutations, self.index, self.first_rank, self.is_last)
+    }
+}
+
+impl<T, P: IntoIterator<Item = T>> Extend<P> for ChunkBuilder<T> {
+    fn extend<I: IntoIterator<Item = P>>(&mut self, permutations: I) {
+        permutations
+            .into_iter()
+            .for_each(|permutation| self.push(permutation));
+    }
+}
+
+/// The permutation at an index of the chunk.
+/// Panics if the index is out of bounds.
+/// Two chunks are equal if they have the same permutations at the same position:
+/// the size requested to the iterator is not compared, so a generated chunk equals the one assembled by a `ChunkBuilder`.
+impl<T: PartialEq> PartialEq for Chunk<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.permutations == other.permutations
+            && self.index == other.index
+            && self.first_rank == other.first_rank
+            && self.is_last == other.is_last
+    }
+}
+
+impl<T: Eq> Eq for Chunk<T> {}
+
+/// The strings of the values are not shown: they are an implementation detail of the formatting.
+impl<T: fmt::Debug> fmt::Debug for Chunk<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Chunk")
+            .field("permutations", &self.permutations)
+            .field("size", &self.size)
+            .field("index", &self.index)
+            .field("first_rank", &self.first_rank)
+            .field("is_last", &self.is_last)
+            .finish()
+    }
+}
+
+impl<T> Index<usize> for Chunk<T> {
+    type Output = [T];
+    fn index(&self, index: usize) -> &[T] {
+        &self.permutations[index]
+    }
+}
+
+impl<T> ChunkView for Chunk<T> {
+    type Value = T;
+    fn permutations_count(&self) -> usize {
+        self.permutations.len()
+    }
+    fn permutation_length(&self) -> usize {
+        self.permutations
+            .first()
+            .map_or(0, |permutation| permutation.len())
+    }
+    fn value(&self, index: usize, position: usize) -> &T {
+        &self.permutations[index][position]
+    }
+}
+
+impl<T> AsMut<Vec<Permutation<T>>> for Chunk<T> {
+    fn as_mut(&mut self) -> &mut Vec<Permutation<T>> {
+        &mut self.permutations
+    }
+}
+
+/// `Chunk` is a `Display` because it must be outputted.
+impl<T: PermValue> fmt::Display for Chunk<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.formatted(OutputFormat::default()).fmt(fmt)
+    }
+}
+
+impl<T> Chunk<T> {
+    /// Display the `Chunk` with a given `OutputFormat`.
+    pub fn formatted(&self, format: OutputFormat) -> Formatted<'_, Self> {
+        Formatted {
+            chunk: self,
+            format,
+        }
+    }
+}
+
+impl<T: PermValue> Chunk<T> {
+    /// Format the whole chunk with `format`, appending it to `output`.
+    ///
+    /// The chunks of `IntoChunks` and `IntoRankedChunks` share the strings of the values of their iterator,
+    /// formatted (and quoted) once: each permutation is written by copying the strings of its values.
+    /// The other chunks (e.g. built by a `ChunkBuilder`) format each value of each permutation.
+    pub fn write_text(&self, format: OutputFormat, output: &mut String) {
+        match &self.value_strings {
+            Some(value_strings) => {
+                let length = self
+                    .permutations
+                    .first()
+                    .map_or(0, |permutation| permutation.len());
+                let mut indices = Vec::with_capacity(length * self.permutations.len());
+                for (rank, permutation) in self.permutations.iter().enumerate() {
+                    for value in permutation.iter() {
+                        // the consecutive permutations share a prefix: the previous index is tried first.
+                        let previous = rank.checked_sub(1).map(|_| indices[indices.len() - length]);
+                        let index = value_strings
+                            .index_of(value, previous)
+                            .expect("The value strings hold every value of the chunk");
+                        indices.push(index);
+                    }
+                }
+                value_strings.write_permutations(
+                    format,
+                    (0..self.permutations.len())
+                        .map(|rank| indices[rank * length..(rank + 1) * length].iter().copied()),
+                    output,
+                )
+            }
+            None => write!(output, "{}", self.formatted(format))
+                .expect("Writing to a String cannot fail"),
+        }
+    }
+    /// Format the whole chunk with `format`: see `write_text`.
+    pub fn to_text(&self, format: OutputFormat) -> String {
+        let mut output = String::new();
+        self.write_text(format, &mut output);
+        output
+    }
+    /// Format the whole chunk with `format` into bytes: see `write_text`.
+    /// With the strings of the values of its iterator, `ValueStrings::write_permutations`
+    /// reserves the exact length of the text once.
+    pub fn to_bytes(&self, format: OutputFormat) -> Vec<u8> {
+        self.to_text(format).into_bytes()
+    }
+}
+
+impl<T: PermValue> ToBytes for Chunk<T> {
+    fn to_bytes(&self, format: OutputFormat) -> Vec<u8> {
+        Chunk::to_bytes(self, format)
+    }
+}
+
+impl<T: PermValue> fmt::Display for Formatted<'_, Chunk<T>> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.chunk.value_strings {
+            Some(_) => fmt.write_str(&self.chunk.to_text(self.format)),
+            None => {
+                self.chunk.permutations.iter().try_for_each(|permutation| {
+                    self.format.write_permutation(fmt, permutation.iter())
+                })
+            }
+        }
+    }
+}
+
+/// The partial permutation of the depth-first generation, expanded lazily.
+struct Path<T> {
+    /// the distinct values, by label: in the order they are tried.
+    values: Vec<T>,
+    /// the remaining frequency of each label.
+    frequencies: FreqTable<Counts>,
+    /// the label (the index in `values`) of each value of `permutation`.
+    labels: Vec<usize>,
+    permutation: Permutation<T>,
+    /// the first label to try at the next position.
+    next_label: usize,
+    permutation_length: usize,
+    /// the full permutation has been returned, and it must be advanced.
+    is_yielded: bool,
+    is_exhausted: bool,
+}
+
+impl<T: Copy> Path<T> {
+    /// Initialize the `Path` at the root.
+    fn new(values: Vec<T>, frequencies: FreqTable<Counts>, permutation_length: usize) -> Self {
+        Self {
+            values,
+            frequencies,
+            labels: Vec::with_capacity(permutation_length),
+            permutation: Permutation::with_capacity(permutation_length),
+            next_label: 0,
+            permutation_length,
+            is_yielded: false,
+            is_exhausted: false,
+        }
+    }
+
+    /// Compute the next permutation in lexicographic order of the labels.
+    /// The first one is built extending the path with the first available label,
+    /// and backtracking when there is none or the partial permutation is rejected by `prune`.
+    /// The following ones are computed in place from the previous one,
+    /// falling back to the extension only when `prune` rejects one of the changed prefixes.
+    fn next_permutation_pruned(
+        &mut self,
+        cancellation: &Option<CancellationToken>,
+        mut prune: impl FnMut(&[T]) -> bool,
+    ) -> Option<Permutation<T>> {
+        while !self.is_exhausted {
+            if is_cancelled(cancellation) {
+                return None;
+            }
+            if self.labels.len() == self.permutation_length {
+                if !self.is_yielded {
+                    self.is_yielded = true;
+                    // the last permutation has no ascent.
+                    self.is_exhausted = self.ascent().is_none();
+                    return Some(self.permutation.clone());
+                }
+                self.is_yielded = false;
+                if let Some(changed) = self.advance() {
+                    // the prefixes up to the changed position have already been checked.
+                    if let Some(length) = (changed + 1..=self.permutation_length)
+                        .find(|length| prune(&self.permutation[..*length]))
+                    {
+                        self.truncate(length);
+                        self.backtrack();
+                    }
+                }
+                continue;
+            }
+            match self
+                .frequencies
+                .next_available(self.next_label..self.values.len())
+            {
+                Some(label) => {
+                    self.frequencies.take(label);
+                    self.permutation.push(self.values[label]);
+                    self.labels.push(label);
+                    self.next_label = 0;
+                    if prune(&self.permutation) {
+                        self.backtrack();
+                    }
+                }
+                None => self.backtrack(),
+            }
+        }
+        None
+    }
+
+    /// Replace the full permutation with the next one in lexicographic order of the labels, in place:
+    /// the suffix after the rightmost ascent is the last one for its prefix, so the ascent is advanced
+    /// to the smallest greater label of the suffix, and the suffix is reversed to be the first one.
+    /// The remaining frequencies do not change, since the values are only moved.
+    /// Return the position of the advanced ascent, or `None` if this is the last permutation.
+    fn advance(&mut self) -> Option<usize> {
+        let ascent = self.ascent()?;
+        let labels = &mut self.labels;
+        let successor = (ascent + 1..labels.len())
+            .rev()
+            .find(|index| labels[*index] > labels[ascent])
+            .expect("the suffix has a greater label");
+        labels.swap(ascent, successor);
+        labels[ascent + 1..].reverse();
+        self.permutation.swap(ascent, successor);
+        self.permutation[ascent + 1..].reverse();
+        Some(ascent)
+    }
+
+    /// Find the rightmost position whose label is lower than the following one.
+    fn ascent(&self) -> Option<usize> {
+        (1..self.labels.len())
+            .rev()
+            .find(|index| self.labels[index - 1] < self.labels[*index])
+            .map(|index| index - 1)
+    }
+
+    /// Remove the values of the path after the first `length`, restoring their frequency.
+    fn truncate(&mut self, length: usize) {
+        for label in self.labels.drain(length..) {
+            self.frequencies.put_back(label);
+        }
+        self.permutation.truncate(length);
+    }
+
+    /// Remove the last value of the path, to try the following ones at its position.
+    fn backtrack(&mut self) {
+        match self.labels.pop() {
+            Some(label) => {
+                self.permutation.pop();
+                self.frequencies.put_back(label);
+                self.next_label = label + 1;
+            }
+            None => self.is_exhausted = true,
+        }
+    }
+}
+
+/// Sort the labels of the `values` and of their `frequencies` in the order of `compare`:
+/// the equal values for `compare` keep their order.
+fn sort_labels<T: Copy, C: AsRef<[usize]> + AsMut<[usize]>>(
+    values: &mut Vec<T>,
+    frequencies: &mut FreqTable<C>,
+    mut compare: impl FnMut(&T, &T) -> Ordering,
+) {
+    let mut labels = (0..values.len()).collect::<Vec<usize>>();
+    labels.sort_by(|left, right| compare(&values[*left], &values[*right]));
+    frequencies.reorder(&labels);
+    *values = labels.iter().map(|label| values[*label]).collect();
+}
+
+/// The computational unit.
+struct Job<T> {
+    /// the remaining frequency of each label, tried in order.
+    /// choosing each distinct value once allows to ignore the duplicated permutations.
+    frequencies: FreqTable<Counts>,
+
+    /// the current generate permutation.
+    permutation: Permutation<T>,
+
+    /// the target permutation length.
+    /// this is the same for all jobs.
+    permutation_length: usize,
+}
+
+impl<T: Copy + Eq + Hash> Job<T> {
+    /// Initialize a new `Job` at the root, with the `frequencies` of the labels.
+    fn new(frequencies: FreqTable<Counts>, permutation_length: usize) -> Self {
+        Self {
+            frequencies,
+            permutation: Permutation::new(),
+            permutation_length,
+        }
+    }
+
+    /// Given a parent `Job`, it is possible to generate new jobs,
+    /// with one more value in `permutation`
+    /// and a decreased frequency of its label, for each label with occurrences left.
+    fn compute_next_jobs(self, values: &[T]) -> Vec<Job<T>> {
+        self.frequencies
+            .available()
+            .map(|label| self.with_new_value(values, label))
+            .collect()
+    }
+
+    /// Create a new `Job` given the `label` of a new value to add inside the `permutation`.
+    /// The frequency of the label must be decreased in the new `Job` instance.
+    fn with_new_value(&self, values: &[T], label: usize) -> Self {
+        let mut new_permutation = self.permutation.clone();
+        new_permutation.push(values[label]);
+        Self {
+            frequencies: self.frequencies.taken(label),
+            permutation: new_permutation,
+            permutation_length: self.permutation_length,
+        }
+    }
+
+    /// Get the permutation generated by the `Job`.
+    /// It is a valid permutation of correct length
+    /// only if it has the same length of `permutation_length`.
+    fn permutation(self) -> Permutation<T> {
+        self.permutation
+    }
+
+    /// Check if the `Job` has found a permutation,
+    /// and consequently it cannot generate any children jobs.
+    /// It is a valid permutation of correct length
+    /// only if `is_ready()` is true.
+    fn is_ready(&self) -> bool {
+        self.permutation.len() == self.permutation_length
+    }
+}
@@ -0,0 +1,122 @@
+//! # Mapped Iterator
+//!
+//! `IntoMappedChunks` is an iterator over `MappedChunk`s of transformed permutations.
+//!
+//! Each permutation is transformed inside the generation loop, as soon as it is computed,
+//! so that the chunk carries the transformed values rather than the permutations:
+//! there is no need to format the permutations and parse them back.
+use std::fmt;
+use std::hash::Hash;
+
+use crate::format::{Formatted, OutputFormat, PermValue, ToBytes};
+use crate::permutations::into_chunks::IntoChunks;
+use crate::permutations::ChunkView;
+
+/// Iterator over `MappedChunk`s.
+pub struct IntoMappedChunks<T, F> {
+    permutations: IntoChunks<T>,
+    map: F,
+}
+
+impl<T: Copy + Eq + Hash, U, F: FnMut(Vec<T>) -> U> IntoMappedChunks<T, F> {
+    pub(crate) fn new(permutations: IntoChunks<T>, map: F) -> Self {
+        Self { permutations, map }
+    }
+}
+
+/// The iterator implementation to generate a single chunk of transformed permutations.
+/// It terminates when the chunk is full
+/// or there are no more permutations.
+impl<T: Copy + Eq + Hash, U, F: FnMut(Vec<T>) -> U> Iterator for IntoMappedChunks<T, F> {
+    type Item = MappedChunk<U>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = self.permutations.size();
+        let mut values = Vec::with_capacity(size);
+
+        while values.len() < size {
+            match self.permutations.next_permutation() {
+                Some(permutation) => values.push((self.map)(permutation.into_iter().collect())),
+                None => break,
+            }
+        }
+        if values.is_empty() {
+            None
+        } else {
+            Some(MappedChunk { values })
+        }
+    }
+}
+
+/// Chunk of transformed permutations.
+pub struct MappedChunk<U> {
+    values: Vec<U>,
+}
+
+impl<U> MappedChunk<U> {
+    /// The number of transformed permutations in the chunk.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    /// Check if the chunk has no transformed permutations.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+    /// Iterate over the transformed permutations in generation order.
+    pub fn iter(&self) -> std::slice::Iter<'_, U> {
+        self.values.iter()
+    }
+}
+
+impl<U> MappedChunk<U> {
+    /// Display the `MappedChunk` with a given `OutputFormat`.
+    pub fn formatted(&self, format: OutputFormat) -> Formatted<'_, Self> {
+        Formatted {
+            chunk: self,
+            format,
+        }
+    }
+}
+
+/// A chunk of permutations mapped to other permutations can be written like a `Chunk`.
+impl<T> ChunkView for MappedChunk<Vec<T>> {
+    type Value = T;
+    fn permutations_count(&self) -> usize {
+        self.values.len()
+    }
+    fn permutation_length(&self) -> usize {
+        self.values.first().map_or(0, Vec::len)
+    }
+    fn value(&self, index: usize, position: usize) -> &T {
+        &self.values[index][position]
+    }
+}
+
+impl<T: PermValue> fmt::Display for MappedChunk<Vec<T>> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.formatted(OutputFormat::default()).fmt(fmt)
+    }
+}
+
+impl<T: PermValue> fmt::Display for Formatted<'_, MappedChunk<Vec<T>>> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.chunk
+            .values
+            .iter()
+            .try_for_each(|permutation| self.format.write_permutation(fmt, permutation.iter()))
+    }
+}
+
+impl<T: PermValue> ToBytes for MappedChunk<Vec<T>> {
+    fn to_bytes(&self, format: OutputFormat) -> Vec<u8> {
+        self.formatted(format).to_string().into_bytes()
+    }
+}
+
+impl<U> IntoIterator for MappedChunk<U> {
+    type Item = U;
+    type IntoIter = std::vec::IntoIter<U>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
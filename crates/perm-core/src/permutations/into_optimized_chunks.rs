@@ -0,0 +1,608 @@
+//! # Optimized Iterator
+//!
+//! `IntoOptimizedChunks` is an optimized iterator over `OptimizedChunks`of permutations.
+//!
+//! It is more efficient than `IntoChunks` because it store each computation,
+//! in a fixed array that is stack allocated and efficiently copied from one job,
+//! to the other
+//!
+//! Since each `OptimizedJob` must have a map with the frequency of each value,
+//! to avoid using the heap allocated map, the original input in each `OptimizedJob`
+//! is represented as an array where the index is the id of the original value,
+//! and the frequency is the value stored in the index (the layout of `encoding::Encoding`).
+//!
+//! Each `OptimizedChunks` has the decode table between the index and the original value.
+//! Since we have one `OptimizedChunks` for many permutations, the cost to allocate the table,
+//! is negligible.
+//!
+//! This code can be further improved storing chunks of permutation in a fixed array,
+// rather than on the heap allocated vector.
+//!
+//! `OptimizedChunks` is a sequence of permutations-
+//! It is a `Display` to be written to output.
+//! It is a `AsMut` to be updated with new permutations.
+//!
+//! `OptimizedJob` is the computational node to create a new permutation.
+//!
+//! A job holds two fixed arrays of `PERMUTATION_FIXED_LENGTH` indices (2 KiB),
+//! and expanding it materializes all its children at once:
+//! up to `128` jobs, a quarter of a MiB, for each expansion of a wide input.
+//! So the depth-first generation does not use jobs: `OptimizedPath` holds the frequencies and the indices
+//! of a single partial permutation, and expands it lazily, one value at a time,
+//! trying the next index at the deepest position and backtracking when there is none.
+//! Its memory does not depend on the number of values, and the permutations are generated in the same order.
+//! The breadth-first generation keeps a queue of jobs, since it holds a whole level of the tree anyway.
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cancellation::{is_cancelled, CancellationToken};
+use crate::encoding::{compress_as, Encoding};
+use crate::format::{Formatted, OutputFormat, PermValue, ToBytes, ValueStrings};
+use crate::freq::FreqTable;
+use crate::hasher::ValueHasher;
+use crate::limits::OPTIMIZED_MAX_LEN;
+use crate::permutations::budget::{Budget, Coverage};
+use crate::permutations::paranoid::Paranoid;
+use crate::permutations::utils::is_expired;
+use crate::permutations::{ChunkView, ExpansionStrategy, Uniqueness};
+
+pub(crate) const PERMUTATION_FIXED_LENGTH: usize = OPTIMIZED_MAX_LEN;
+
+type FixedArray = [usize; PERMUTATION_FIXED_LENGTH];
+
+fn zeroed_fixed_array() -> FixedArray {
+    [0; PERMUTATION_FIXED_LENGTH]
+}
+
+/// The partial permutations still to be expanded.
+enum Frontier {
+    /// the single path of the depth-first generation, on the heap like the queue.
+    Path(Box<OptimizedPath>),
+    /// the job queue of the breadth-first generation.
+    Queue(VecDeque<OptimizedJob>),
+}
+
+impl Frontier {
+    /// Compute the next permutation, or `None` if there are no more.
+    fn next_permutation(&mut self) -> Option<FixedArray> {
+        match self {
+            Frontier::Path(path) => path.next_permutation(),
+            Frontier::Queue(job_queue) => {
+                while let Some(job) = job_queue.pop_front() {
+                    if job.is_ready() {
+                        return Some(job.permutation());
+                    }
+                    job_queue.extend(job.compute_next_jobs());
+                }
+                None
+            }
+        }
+    }
+
+    /// Check if there are no more permutations:
+    /// every job in the queue generates at least one permutation.
+    fn is_exhausted(&self) -> bool {
+        match self {
+            Frontier::Path(path) => path.is_exhausted(),
+            Frontier::Queue(job_queue) => job_queue.is_empty(),
+        }
+    }
+}
+
+/// Optimized iterator over `OptimizedChunks`.
+pub struct IntoOptimizedChunks<T> {
+    frontier: Frontier,
+    size: usize,
+    index_to_value: Arc<ValueStrings<T>>,
+    permutation_size: usize,
+    time_slice: Option<Duration>,
+    cancellation: Option<CancellationToken>,
+    budget: Budget,
+    chunks_generated: usize,
+    permutations_generated: usize,
+    paranoid: Paranoid<T>,
+}
+
+// Initialize the iterator with the `job_queue` containing the root `OptimizedJob`.
+/// The root `OptimizedJob` has the compressed form of the original input value..
+/// With `Uniqueness::WithDuplicates` each value has its own index, even if it is equal to another.
+impl<T: Copy + Eq + Hash> IntoOptimizedChunks<T> {
+    pub(crate) fn new(
+        values: Vec<T>,
+        size: usize,
+        uniqueness: Uniqueness,
+        strategy: ExpansionStrategy,
+        hasher: ValueHasher,
+    ) -> Self {
+        let permutation_size = values.len();
+        let paranoid = Paranoid::new(&values, uniqueness);
+        let encoding = compress_as(&values, uniqueness);
+        let compressed_values = FreqTable::new(fixed_frequencies(&encoding));
+        let index_to_value = encoding.values;
+
+        let frontier = match strategy {
+            ExpansionStrategy::DepthFirst => Frontier::Path(Box::new(OptimizedPath::new(
+                compressed_values,
+                index_to_value.len(),
+                permutation_size,
+            ))),
+            ExpansionStrategy::BreadthFirst => {
+                Frontier::Queue(VecDeque::from(vec![OptimizedJob::new(compressed_values)]))
+            }
+        };
+        Self {
+            frontier,
+            size,
+            index_to_value: Arc::new(ValueStrings::new(index_to_value, hasher)),
+            permutation_size,
+            time_slice: None,
+            cancellation: None,
+            budget: Budget::default(),
+            chunks_generated: 0,
+            permutations_generated: 0,
+            paranoid,
+        }
+    }
+}
+
+impl<T> IntoOptimizedChunks<T> {
+    /// Return each chunk as soon as `time_slice` has passed since the chunk was started,
+    /// even if it is not full, as `IntoChunks::with_time_slice`.
+    pub fn with_time_slice(mut self, time_slice: Duration) -> Self {
+        self.time_slice = Some(time_slice);
+        self
+    }
+
+    /// Stop the generation when `cancellation` is cancelled, as `IntoChunks::with_cancellation`.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Stop the generation after `max_permutations` permutations, as `IntoChunks::with_limit`.
+    pub fn with_limit(mut self, max_permutations: usize) -> Self {
+        self.budget = self.budget.with_limit(max_permutations);
+        self
+    }
+
+    /// Stop the generation once `duration` has passed from now, as `IntoChunks::with_deadline`.
+    pub fn with_deadline(mut self, duration: Duration) -> Self {
+        self.budget = self.budget.with_deadline(duration);
+        self
+    }
+
+    /// Summarize how far the generation got.
+    pub fn coverage(&self) -> Coverage {
+        Coverage::new(0, self.permutations_generated, self.frontier.is_exhausted())
+    }
+
+    /// Get the rank of the first permutation of the next chunk,
+    /// to resume the generation after a cancellation.
+    pub fn next_rank(&self) -> usize {
+        self.permutations_generated
+    }
+
+    /// Set the position of the `chunk` in the generation order.
+    /// The chunk is the last one if and only if there are no more permutations or the budget is spent.
+    fn with_position(&mut self, mut chunk: OptimizedChunk<T>) -> OptimizedChunk<T> {
+        chunk.index = self.chunks_generated;
+        chunk.first_rank = self.permutations_generated;
+        self.chunks_generated += 1;
+        self.permutations_generated += chunk.len();
+        chunk.is_last =
+            self.frontier.is_exhausted() || self.budget.is_spent(self.permutations_generated);
+        for permutation in chunk.as_mut().iter() {
+            self.paranoid
+                .record_labels(&permutation[..self.permutation_size]);
+        }
+        if self.frontier.is_exhausted() {
+            self.paranoid.check_complete();
+        }
+        chunk
+    }
+}
+
+/// The iterator implementation to generate a single chunk of permutations.
+/// It terminates when the chunk is full, the time slice (if any) has passed,
+/// the budget is spent or there are no more permutations.
+/// It returns `None` if the generation has been cancelled.
+impl<T: Copy> Iterator for IntoOptimizedChunks<T> {
+    type Item = OptimizedChunk<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = OptimizedChunk::new(
+            self.index_to_value.clone(),
+            self.permutation_size,
+            self.size,
+        );
+        let deadline = self
+            .time_slice
+            .map(|time_slice| Instant::now() + time_slice);
+
+        while !chunk.is_full()
+            && !self
+                .budget
+                .is_spent(self.permutations_generated + chunk.len())
+        {
+            if is_cancelled(&self.cancellation) {
+                return None;
+            }
+            match self.frontier.next_permutation() {
+                Some(permutation) => chunk.as_mut().push(permutation),
+                None => break,
+            }
+            if is_expired(deadline) {
+                break;
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(self.with_position(chunk))
+        }
+    }
+}
+
+/// Copy the frequencies of the `encoding` into a fixed array, the unused indices with frequency zero.
+fn fixed_frequencies<T>(encoding: &Encoding<T>) -> FixedArray {
+    let mut compressed_values = zeroed_fixed_array();
+    compressed_values[..encoding.frequencies.len()].copy_from_slice(&encoding.frequencies);
+    compressed_values
+}
+
+/// Optimized chunks of compressed permutations.
+#[derive(Clone)]
+pub struct OptimizedChunk<T> {
+    /// the vector of compressed permutations
+    permutations_compressed: Vec<FixedArray>,
+    /// the map to decode compressed permutations, with the strings of the values.
+    index_to_value: Arc<ValueStrings<T>>,
+    permutation_size: usize,
+    size: usize,
+    index: usize,
+    first_rank: usize,
+    is_last: bool,
+}
+
+impl<T> OptimizedChunk<T> {
+    fn new(index_to_value: Arc<ValueStrings<T>>, permutation_size: usize, size: usize) -> Self {
+        Self {
+            permutations_compressed: vec![],
+            index_to_value,
+            permutation_size,
+            size,
+            index: 0,
+            first_rank: 0,
+            is_last: false,
+        }
+    }
+    /// The sequence number of the chunk, starting from zero.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// The rank of the first permutation of the chunk.
+    pub fn first_rank(&self) -> usize {
+        self.first_rank
+    }
+    /// The rank of the last permutation of the chunk.
+    pub fn last_rank(&self) -> usize {
+        self.first_rank + self.permutations_compressed.len().saturating_sub(1)
+    }
+    /// Check if this is the final chunk of the iterator.
+    pub fn is_last(&self) -> bool {
+        self.is_last
+    }
+    fn is_full(&self) -> bool {
+        self.permutations_compressed.len() == self.size
+    }
+    /// The number of permutations in the chunk.
+    pub fn len(&self) -> usize {
+        self.permutations_compressed.len()
+    }
+    /// Check if the chunk has no permutations.
+    pub fn is_empty(&self) -> bool {
+        self.permutations_compressed.is_empty()
+    }
+    /// Iterate over the permutations, each one as an iterator over its values.
+    /// The values are decoded lazily, without allocating a `Vec` or a `String` for each permutation.
+    pub fn iter_decoded(&self) -> impl Iterator<Item = impl Iterator<Item = &T> + '_> + '_ {
+        self.permutations_compressed.iter().map(move |permutation| {
+            permutation[..self.permutation_size]
+                .iter()
+                .map(move |index| &self.index_to_value.values()[*index])
+        })
+    }
+}
+
+impl<T: Clone> OptimizedChunk<T> {
+    /// Split the chunk in two at `mid`, as `Chunk::split_at`: both parts have a copy of the decode table.
+    /// Panics if `mid` is greater than the length of the chunk.
+    pub fn split_at(mut self, mid: usize) -> (OptimizedChunk<T>, OptimizedChunk<T>) {
+        assert!(
+            mid <= self.permutations_compressed.len(),
+            "The split point is: `{}` but the chunk length is: {}",
+            mid,
+            self.permutations_compressed.len()
+        );
+        let second = OptimizedChunk {
+            permutations_compressed: self.permutations_compressed.split_off(mid),
+            index_to_value: self.index_to_value.clone(),
+            permutation_size: self.permutation_size,
+            size: self.size - mid,
+            index: self.index,
+            first_rank: self.first_rank + mid,
+            is_last: self.is_last,
+        };
+        self.size = mid;
+        self.is_last = false;
+        (self, second)
+    }
+}
+
+/// The permutations are shown decoded, instead of the arrays of indices.
+impl<T: fmt::Debug> fmt::Debug for OptimizedChunk<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        struct Decoded<'a, T>(&'a OptimizedChunk<T>);
+        impl<T: fmt::Debug> fmt::Debug for Decoded<'_, T> {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.debug_list()
+                    .entries(
+                        self.0
+                            .iter_decoded()
+                            .map(|permutation| permutation.collect::<Vec<&T>>()),
+                    )
+                    .finish()
+            }
+        }
+        fmt.debug_struct("OptimizedChunk")
+            .field("permutations", &Decoded(self))
+            .field("index", &self.index)
+            .field("first_rank", &self.first_rank)
+            .field("is_last", &self.is_last)
+            .finish()
+    }
+}
+
+/// Two chunks are equal if they have the same decoded permutations at the same position,
+/// whatever the decode tables: as for `Chunk`, the size requested to the iterator is not compared.
+impl<T: PartialEq> PartialEq for OptimizedChunk<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+            && self.first_rank == other.first_rank
+            && self.is_last == other.is_last
+            && self.permutation_size == other.permutation_size
+            && self.len() == other.len()
+            && self
+                .iter_decoded()
+                .zip(other.iter_decoded())
+                .all(|(permutation, other)| permutation.eq(other))
+    }
+}
+
+impl<T: Eq> Eq for OptimizedChunk<T> {}
+
+/// The values are decoded with the `index_to_value` mapping.
+impl<T> ChunkView for OptimizedChunk<T> {
+    type Value = T;
+    fn permutations_count(&self) -> usize {
+        self.permutations_compressed.len()
+    }
+    fn permutation_length(&self) -> usize {
+        self.permutation_size
+    }
+    fn value(&self, index: usize, position: usize) -> &T {
+        if position >= self.permutation_size {
+            panic!(
+                "The position is: `{}` but the permutation length is: {}",
+                position, self.permutation_size
+            )
+        }
+        &self.index_to_value.values()[self.permutations_compressed[index][position]]
+    }
+}
+
+impl<T> AsMut<Vec<[usize; 128]>> for OptimizedChunk<T> {
+    fn as_mut(&mut self) -> &mut Vec<[usize; 128]> {
+        &mut self.permutations_compressed
+    }
+}
+
+/// `Chunk` is a `Display` because it must be outputted.
+impl<T: PermValue> fmt::Display for OptimizedChunk<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.formatted(OutputFormat::default()).fmt(fmt)
+    }
+}
+
+impl<T> OptimizedChunk<T> {
+    /// Display the `OptimizedChunk` with a given `OutputFormat`.
+    pub fn formatted(&self, format: OutputFormat) -> Formatted<'_, Self> {
+        Formatted {
+            chunk: self,
+            format,
+        }
+    }
+}
+
+impl<T: PermValue> OptimizedChunk<T> {
+    /// Format the whole chunk with `format`, appending it to `output`.
+    ///
+    /// The compressed permutations are small-integer arrays indexing the distinct values,
+    /// whose strings are formatted (and quoted) once by the iterator, and shared by all its chunks:
+    /// each permutation is written by copying the strings of its values with the separators,
+    /// without formatting any value.
+    pub fn write_text(&self, format: OutputFormat, output: &mut String) {
+        self.index_to_value.write_permutations(
+            format,
+            self.permutations_compressed
+                .iter()
+                .map(|permutation| permutation[..self.permutation_size].iter().copied()),
+            output,
+        )
+    }
+    /// Format the whole chunk with `format`: see `write_text`.
+    pub fn to_text(&self, format: OutputFormat) -> String {
+        let mut output = String::new();
+        self.write_text(format, &mut output);
+        output
+    }
+    /// Format the whole chunk with `format` into bytes: see `write_text`,
+    /// where `ValueStrings::write_permutations` reserves the exact length of the text once.
+    pub fn to_bytes(&self, format: OutputFormat) -> Vec<u8> {
+        self.to_text(format).into_bytes()
+    }
+}
+
+impl<T: PermValue> ToBytes for OptimizedChunk<T> {
+    fn to_bytes(&self, format: OutputFormat) -> Vec<u8> {
+        OptimizedChunk::to_bytes(self, format)
+    }
+}
+
+/// This is where the `index_to_value` mapping to decode a compressed permutation is used.
+impl<T: PermValue> fmt::Display for Formatted<'_, OptimizedChunk<T>> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(&self.chunk.to_text(self.format))
+    }
+}
+
+/// The computational unit.
+#[derive(Copy, Clone)]
+struct OptimizedJob {
+    /// the remaining frequency of each compressed value.
+    compressed_values: FreqTable<FixedArray>,
+    /// the current compressed permutation
+    compressed_permutation: FixedArray,
+    /// this is the current permutation length.
+    /// it is not the target permutation length.
+    /// it is used to find the next index of `compressed_permutation`
+    /// to add a new value
+    permutation_length: usize,
+}
+
+impl OptimizedJob {
+    /// Initialize a new `OptimizedJob`.
+    fn new(compressed_values: FreqTable<FixedArray>) -> Self {
+        Self {
+            compressed_values,
+            compressed_permutation: zeroed_fixed_array(),
+            permutation_length: 0,
+        }
+    }
+
+    /// Given a parent `OptimizedJob`, it is possible to generate new jobs,
+    /// with one more value in `compressed_permutation`
+    /// and a decreased frequency in `compressed_values`.
+    fn compute_next_jobs(self) -> Vec<OptimizedJob> {
+        self.compressed_values
+            .available()
+            .map(|value| self.with_new_value(&value))
+            .collect()
+    }
+
+    /// Create a new `OptimizedJob` given a new `value` to add inside the `compressed_permutation`,
+    /// at index: `permutation_length`.
+    /// The frequency of the `value` must be decreased in the new `OptimizedJob` instance.
+    fn with_new_value(&self, value: &usize) -> Self {
+        let frequencies = self.compressed_values.taken(*value);
+
+        let mut new_permutation = self.compressed_permutation;
+        new_permutation[self.permutation_length] = *value;
+
+        let mut new_job = Self::new(frequencies);
+        new_job.compressed_permutation = new_permutation;
+        new_job.permutation_length = self.permutation_length + 1;
+        new_job
+    }
+    /// Check if the `OptimizedJob` has found a permutation,
+    /// and consequently it cannot generate any children jobs.
+    /// This happens when the frequency of each value is zero,
+    /// and consequently `compressed_values` has all only zeros.
+    fn is_ready(&self) -> bool {
+        self.compressed_values.is_exhausted()
+    }
+
+    /// Get the permutation generated by the `OptimizedJob`.
+    /// It is a valid permutation of correct length
+    /// only if `is_ready()` is true.
+    fn permutation(self) -> FixedArray {
+        self.compressed_permutation
+    }
+}
+
+/// The partial permutation of the depth-first generation, expanded lazily.
+struct OptimizedPath {
+    /// the remaining frequency of each index.
+    frequencies: FreqTable<FixedArray>,
+    /// the indices of the partial permutation, up to `depth`.
+    permutation: FixedArray,
+    depth: usize,
+    /// the first index to try at `depth`.
+    next_index: usize,
+    /// the number of distinct indices.
+    distinct: usize,
+    permutation_length: usize,
+    is_exhausted: bool,
+}
+
+impl OptimizedPath {
+    /// Initialize the `OptimizedPath` at the root, with the frequencies of the `distinct` indices.
+    fn new(frequencies: FreqTable<FixedArray>, distinct: usize, permutation_length: usize) -> Self {
+        Self {
+            frequencies,
+            permutation: zeroed_fixed_array(),
+            depth: 0,
+            next_index: 0,
+            distinct,
+            permutation_length,
+            is_exhausted: false,
+        }
+    }
+
+    /// Compute the next permutation in lexicographic order of the indices,
+    /// extending the path with the first available index and backtracking when there is none.
+    fn next_permutation(&mut self) -> Option<FixedArray> {
+        while !self.is_exhausted {
+            if self.depth == self.permutation_length {
+                let permutation = self.permutation;
+                // the last permutation has decreasing indices.
+                self.is_exhausted = permutation[..self.permutation_length]
+                    .windows(2)
+                    .all(|pair| pair[0] >= pair[1]);
+                self.backtrack();
+                return Some(permutation);
+            }
+            match self
+                .frequencies
+                .next_available(self.next_index..self.distinct)
+            {
+                Some(index) => {
+                    self.frequencies.take(index);
+                    self.permutation[self.depth] = index;
+                    self.depth += 1;
+                    self.next_index = 0;
+                }
+                None => self.backtrack(),
+            }
+        }
+        None
+    }
+
+    /// Remove the deepest index of the path, to try the following ones at its position.
+    fn backtrack(&mut self) {
+        if self.depth == 0 {
+            self.is_exhausted = true;
+            return;
+        }
+        self.depth -= 1;
+        let index = self.permutation[self.depth];
+        self.frequencies.put_back(index);
+        self.next_index = index + 1;
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.is_exhausted
+    }
+}
@@ -0,0 +1,178 @@
+//! # Pruned Iterator
+//!
+//! `IntoPrunedChunks` is an iterator over `Chunk`s of permutations,
+//! where the caller can cut subtrees of the job tree early.
+//!
+//! The `PruneFn` is invoked on the partial permutation of every new `Job`:
+//! if it returns `true`, the `Job` is discarded together with all the permutations starting with its partial permutation.
+//! This turns the generator into an exhaustive-search engine:
+//! for example, a partial route already longer than the best known one can be skipped.
+//!
+//! `IntoPrunedChunks::with_forbidden_pattern` adds structural constraints on top of the `PruneFn`:
+//! the permutations containing a pattern, e.g. `[2, 1, 0]` for the 321-avoiding permutations,
+//! are cut while the job tree is expanded instead of being filtered after their generation.
+//!
+//! `IntoPrunedChunks::involutions_only` and `IntoPrunedChunks::with_fixed_points` restrict the permutations
+//! to the involutions and to an exact number of fixed points, relative to the input order, the same way,
+//! and `IntoPrunedChunks::indecomposable_only` to the permutations without a proper prefix holding the first input values.
+//!
+//! Since the remaining jobs can all be pruned, the iterator looks one permutation ahead
+//! to know if a chunk is the last one.
+use std::hash::Hash;
+
+use crate::permutations::constraints::{FixedPoints, Indecomposable, Involution};
+use crate::permutations::into_chunks::{Chunk, IntoChunks, Permutation};
+use crate::permutations::patterns::ForbiddenPattern;
+
+/// A function deciding if all the permutations starting with a partial permutation must be skipped.
+pub trait PruneFn<T>: FnMut(&[T]) -> bool {}
+
+impl<T, F: FnMut(&[T]) -> bool> PruneFn<T> for F {}
+
+/// Iterator over `Chunk`s of permutations not pruned by a `PruneFn`.
+pub struct IntoPrunedChunks<T, P> {
+    /// the input values, the reference of the structural constraints.
+    values: Vec<T>,
+    permutations: IntoChunks<T>,
+    prune: P,
+    forbidden_patterns: Vec<ForbiddenPattern<T>>,
+    involution: Option<Involution<T>>,
+    fixed_points: Option<FixedPoints<T>>,
+    indecomposable: Option<Indecomposable<T>>,
+    /// the first permutation of the next chunk, already generated.
+    lookahead: Option<Permutation<T>>,
+}
+
+impl<T: Copy + Eq + Hash, P: PruneFn<T>> IntoPrunedChunks<T, P> {
+    pub(crate) fn new(values: Vec<T>, permutations: IntoChunks<T>, prune: P) -> Self {
+        Self {
+            values,
+            permutations,
+            prune,
+            forbidden_patterns: vec![],
+            involution: None,
+            fixed_points: None,
+            indecomposable: None,
+            lookahead: None,
+        }
+    }
+
+    /// Skip the permutations containing the `pattern`, a permutation of `0..k`:
+    /// some `k` of their values, not necessarily adjacent, are in the same relative order as the pattern.
+    /// The values are compared with their `Ord`, and the equal values never match the pattern.
+    /// Panics if the `pattern` is not a non-empty permutation of `0..k`.
+    pub fn with_forbidden_pattern(mut self, pattern: &[usize]) -> Self
+    where
+        T: Ord,
+    {
+        self.forbidden_patterns.push(ForbiddenPattern::new(pattern));
+        self
+    }
+
+    /// Skip the permutations that are not their own inverse, relative to the input order:
+    /// moving the input value at position `i` to `j`, they move the input value at `j` to `i`.
+    /// Panics if the input values are not distinct.
+    pub fn involutions_only(mut self) -> Self {
+        self.involution = Some(Involution::new(&self.values, self.permutations.hasher()));
+        self
+    }
+
+    /// Skip the permutations without exactly `count` fixed points:
+    /// the positions holding the same value as the input.
+    pub fn with_fixed_points(mut self, count: usize) -> Self {
+        self.fixed_points = Some(FixedPoints::new(
+            &self.values,
+            count,
+            self.permutations.hasher(),
+        ));
+        self
+    }
+
+    /// Skip the decomposable permutations: those with a proper prefix holding the input values
+    /// of the same positions, such as `1,2,3` and `2,1,3`, but not `2,3,1`, for the input `1,2,3`.
+    pub fn indecomposable_only(mut self) -> Self {
+        self.indecomposable = Some(Indecomposable::new(
+            &self.values,
+            self.permutations.hasher(),
+        ));
+        self
+    }
+
+    fn next_permutation(&mut self) -> Option<Permutation<T>> {
+        if let Some(permutation) = self.lookahead.take() {
+            return Some(permutation);
+        }
+        let (prune, forbidden_patterns, involution, fixed_points, indecomposable) = (
+            &mut self.prune,
+            &self.forbidden_patterns,
+            &self.involution,
+            &self.fixed_points,
+            &self.indecomposable,
+        );
+        self.permutations.next_permutation_pruned(|partial| {
+            forbidden_patterns
+                .iter()
+                .any(|pattern| pattern.ends_in(partial))
+                || involution
+                    .as_ref()
+                    .is_some_and(|involution| involution.rejects(partial))
+                || fixed_points
+                    .as_ref()
+                    .is_some_and(|fixed_points| fixed_points.rejects(partial))
+                || indecomposable
+                    .as_ref()
+                    .is_some_and(|indecomposable| indecomposable.rejects(partial))
+                || prune(partial)
+        })
+    }
+}
+
+/// The iterator implementation to generate a single chunk of permutations.
+/// It terminates when the chunk is full
+/// or there are no more permutations that are not pruned.
+impl<T: Copy + Eq + Hash, P: PruneFn<T>> Iterator for IntoPrunedChunks<T, P> {
+    type Item = Chunk<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Chunk::new(self.permutations.size());
+
+        while !chunk.is_full() {
+            match self.next_permutation() {
+                Some(permutation) => chunk.as_mut().push(permutation),
+                None => break,
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            self.lookahead = self.next_permutation();
+            let (index, first_rank) = self.permutations.next_chunk_position(chunk.len());
+            Some(chunk.with_position(index, first_rank, self.lookahead.is_none()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::permutations::tests::collect_permutations;
+    use crate::Permutations;
+
+    #[test]
+    fn pruned_permutations_keep_the_generation_order() {
+        // the prune rejects the prefixes changed in the middle of the permutation.
+        let values = vec![1, 2, 2, 3, 1, 3];
+        let has_adjacent_equal_values =
+            |partial: &[i32]| partial.windows(2).any(|pair| pair[0] == pair[1]);
+        let pruned = collect_permutations(Permutations::new(values.clone()).into_pruned_chunks(
+            4,
+            |partial: &[i32]| {
+                partial.len() >= 2 && partial[partial.len() - 2] == partial[partial.len() - 1]
+            },
+        ));
+        let expected = collect_permutations(Permutations::new(values).into_chunks(4))
+            .into_iter()
+            .filter(|permutation| !has_adjacent_equal_values(permutation))
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 30);
+        assert_eq!(pruned, expected);
+    }
+}
@@ -0,0 +1,304 @@
+//! # Ranked Iterator
+//!
+//! `IntoRankedChunks` is an iterator over `Chunk`s of permutations without a job tree.
+//!
+//! Each distinct value is replaced by a label: the order of its first occurrence in the input
+//! (or its position, if the equal values are distinguishable).
+//! The permutations are generated in lexicographic order of the labels:
+//!
+//! - the permutation of a given rank is computed directly, counting the permutations
+//!   starting with each label with the multinomial coefficients of the remaining labels.
+//!   Without duplicates this is the factorial number system.
+//! - the next permutation is computed in place from the current one, in `O(n)`.
+//!
+//! Only the current permutation is kept in memory, so it is a low-memory alternative to the job tree,
+//! and the generation can start from any rank.
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cancellation::{is_cancelled, CancellationToken};
+use crate::encoding::{compress_as, Encoding};
+use crate::format::ValueStrings;
+use crate::hasher::ValueHasher;
+use crate::math::multinomial;
+use crate::permutations::budget::{Budget, Coverage};
+use crate::permutations::into_chunks::Chunk;
+use crate::permutations::paranoid::Paranoid;
+use crate::permutations::Uniqueness;
+
+/// Iterator over `Chunk`s of permutations in lexicographic order of the labels.
+pub struct IntoRankedChunks<T> {
+    /// the value of each label.
+    label_to_value: Vec<T>,
+    /// the frequency of each label in the input.
+    frequencies: Vec<usize>,
+    /// the labels of the next permutation, if any.
+    current: Option<Vec<usize>>,
+    size: usize,
+    /// the rank the generation started from.
+    first_rank: usize,
+    next_rank: usize,
+    cancellation: Option<CancellationToken>,
+    budget: Budget,
+    chunks_generated: usize,
+    paranoid: Paranoid<T>,
+    /// the strings of the value of each label, shared by the chunks.
+    value_strings: Arc<ValueStrings<T>>,
+}
+
+impl<T: Copy + Eq + Hash> IntoRankedChunks<T> {
+    pub(crate) fn new(
+        values: Vec<T>,
+        size: usize,
+        uniqueness: Uniqueness,
+        hasher: ValueHasher,
+    ) -> Self {
+        let paranoid = Paranoid::new(&values, uniqueness);
+        let Encoding {
+            values: label_to_value,
+            frequencies,
+        } = compress_as(&values, uniqueness);
+        let current = unrank(&frequencies, 0);
+        Self::with_labels(label_to_value, frequencies, current, size, paranoid, hasher)
+    }
+
+    /// Initialize the iterator with the labels already computed by `encoding::compress`,
+    /// starting from the `current` labels of rank zero.
+    pub(crate) fn with_labels(
+        label_to_value: Vec<T>,
+        frequencies: Vec<usize>,
+        current: Option<Vec<usize>>,
+        size: usize,
+        paranoid: Paranoid<T>,
+        hasher: ValueHasher,
+    ) -> Self {
+        Self {
+            value_strings: Arc::new(ValueStrings::new(label_to_value.clone(), hasher)),
+            label_to_value,
+            frequencies,
+            current,
+            size,
+            first_rank: 0,
+            next_rank: 0,
+            cancellation: None,
+            budget: Budget::default(),
+            chunks_generated: 0,
+            paranoid,
+        }
+    }
+
+    /// Start the generation from the permutation of rank `rank`.
+    /// The iterator is empty if `rank` is not lower than the number of permutations.
+    pub fn starting_at(self, rank: usize) -> Self {
+        let current = unrank(&self.frequencies, rank);
+        self.starting_with(rank, current)
+    }
+
+    /// Start the generation from the permutation of rank `rank`, whose labels are `current`.
+    pub(crate) fn starting_with(mut self, rank: usize, current: Option<Vec<usize>>) -> Self {
+        self.current = current;
+        self.first_rank = rank;
+        self.next_rank = rank;
+        self.paranoid.starting_at(rank);
+        self
+    }
+
+    /// Stop the generation when `cancellation` is cancelled, as `IntoChunks::with_cancellation`.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Stop the generation after `max_permutations` permutations from the starting rank,
+    /// as `IntoChunks::with_limit`.
+    pub fn with_limit(mut self, max_permutations: usize) -> Self {
+        self.budget = self.budget.with_limit(max_permutations);
+        self
+    }
+
+    /// Stop the generation once `duration` has passed from now, as `IntoChunks::with_deadline`.
+    pub fn with_deadline(mut self, duration: Duration) -> Self {
+        self.budget = self.budget.with_deadline(duration);
+        self
+    }
+
+    /// Summarize how far the generation got from the starting rank.
+    pub fn coverage(&self) -> Coverage {
+        let complete = self.current.is_none() && !is_cancelled(&self.cancellation);
+        Coverage::new(self.first_rank, self.next_rank, complete)
+    }
+
+    /// Get the rank of the first permutation of the next chunk,
+    /// to resume the generation after a cancellation.
+    pub fn next_rank(&self) -> usize {
+        self.next_rank
+    }
+
+    /// Get the permutation of rank `rank`, without changing the iterator.
+    pub fn nth_permutation(&self, rank: usize) -> Option<Vec<T>> {
+        unrank(&self.frequencies, rank).map(|labels| self.values_of(&labels))
+    }
+
+    /// Get the rank of `permutation`, the inverse of `IntoRankedChunks::nth_permutation`.
+    /// Return `None` if it is not a permutation of the values, or if its rank does not fit in a `usize`.
+    /// The equal values are given the lowest labels first,
+    /// so with `Uniqueness::WithDuplicates` the lowest of their ranks is returned.
+    pub fn rank_of(&self, permutation: &[T]) -> Option<usize> {
+        if permutation.len() != self.frequencies.iter().sum::<usize>() {
+            return None;
+        }
+        let mut frequencies = self.frequencies.clone();
+        let mut labels = Vec::with_capacity(permutation.len());
+        for value in permutation {
+            let label = (0..frequencies.len())
+                .find(|label| frequencies[*label] > 0 && self.label_to_value[*label] == *value)?;
+            frequencies[label] -= 1;
+            labels.push(label);
+        }
+        rank(&self.frequencies, &labels)
+    }
+
+    fn values_of<C: FromIterator<T>>(&self, labels: &[usize]) -> C {
+        labels
+            .iter()
+            .map(|label| self.label_to_value[*label])
+            .collect()
+    }
+}
+
+/// The iterator implementation to generate a single chunk of permutations.
+/// It terminates when the chunk is full,
+/// the budget is spent or the last permutation in lexicographic order has been generated.
+/// It returns `None` if the generation has been cancelled.
+impl<T: Copy + Eq + Hash> Iterator for IntoRankedChunks<T> {
+    type Item = Chunk<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Chunk::new(self.size);
+        let first_rank = self.next_rank;
+
+        while !chunk.is_full() {
+            if is_cancelled(&self.cancellation) {
+                // the current chunk is discarded.
+                self.next_rank = first_rank;
+                self.current = None;
+                return None;
+            }
+            if self.budget.is_spent(self.next_rank - self.first_rank) {
+                break;
+            }
+            let mut labels = match self.current.take() {
+                Some(labels) => labels,
+                None => break,
+            };
+            self.paranoid.record_labels(&labels);
+            chunk.as_mut().push(self.values_of(&labels));
+            if next_permutation(&mut labels) {
+                self.current = Some(labels);
+            }
+            match self.next_rank.checked_add(1) {
+                Some(rank) => self.next_rank = rank,
+                // the following ranks do not fit in a usize: the generation ends here.
+                None => self.current = None,
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            let index = self.chunks_generated;
+            self.chunks_generated += 1;
+            let is_last =
+                self.current.is_none() || self.budget.is_spent(self.next_rank - self.first_rank);
+            if self.current.is_none() {
+                self.paranoid.check_complete();
+            }
+            Some(
+                chunk
+                    .with_position(index, first_rank, is_last)
+                    .with_value_strings(self.value_strings.clone()),
+            )
+        }
+    }
+}
+
+/// Compute the labels of the permutation of rank `rank`, in lexicographic order,
+/// of the multiset where the label `i` has frequency `frequencies[i]`.
+/// Return `None` if `rank` is not lower than the number of permutations.
+/// The empty multiset has one permutation, the empty one.
+pub(crate) fn unrank(frequencies: &[usize], mut rank: usize) -> Option<Vec<usize>> {
+    let mut frequencies = frequencies.to_vec();
+    let length = frequencies.iter().sum();
+    if length == 0 {
+        return if rank == 0 { Some(vec![]) } else { None };
+    }
+    let mut labels = Vec::with_capacity(length);
+    for _ in 0..length {
+        let mut found = false;
+        for label in 0..frequencies.len() {
+            if frequencies[label] == 0 {
+                continue;
+            }
+            frequencies[label] -= 1;
+            // the number of permutations starting with `label`;
+            // if it does not fit in a `usize`, it is greater than any rank.
+            match multinomial(frequencies.iter().copied()) {
+                Some(count) if rank >= count => rank -= count,
+                _ => {
+                    labels.push(label);
+                    found = true;
+                    break;
+                }
+            }
+            frequencies[label] += 1;
+        }
+        if !found {
+            return None;
+        }
+    }
+    Some(labels)
+}
+
+/// Compute the rank of the permutation of `labels`, in lexicographic order,
+/// of the multiset where the label `i` has frequency `frequencies[i]`: the inverse of `unrank`.
+/// Return `None` if the `labels` are not a permutation of the multiset, or if the rank does not fit in a `usize`.
+pub(crate) fn rank(frequencies: &[usize], labels: &[usize]) -> Option<usize> {
+    if labels.len() != frequencies.iter().sum::<usize>() {
+        return None;
+    }
+    let mut frequencies = frequencies.to_vec();
+    let mut rank: usize = 0;
+    for label in labels {
+        if frequencies.get(*label).copied().unwrap_or(0) == 0 {
+            return None;
+        }
+        // count the permutations starting with a lower label.
+        for lower in 0..*label {
+            if frequencies[lower] == 0 {
+                continue;
+            }
+            frequencies[lower] -= 1;
+            let count = multinomial(frequencies.iter().copied());
+            frequencies[lower] += 1;
+            rank = rank.checked_add(count?)?;
+        }
+        frequencies[*label] -= 1;
+    }
+    Some(rank)
+}
+
+/// Rearrange the `labels` into the next permutation in lexicographic order.
+/// Return `false` if the `labels` are the last permutation.
+fn next_permutation(labels: &mut [usize]) -> bool {
+    let pivot = match (1..labels.len()).rev().find(|i| labels[i - 1] < labels[*i]) {
+        Some(i) => i - 1,
+        None => return false,
+    };
+    let successor = (pivot + 1..labels.len())
+        .rev()
+        .find(|i| labels[*i] > labels[pivot])
+        .expect("The element after the pivot is greater than the pivot");
+    labels.swap(pivot, successor);
+    labels[pivot + 1..].reverse();
+    true
+}
@@ -0,0 +1,109 @@
+//! # Latin Rectangles
+//!
+//! `LatinRectangles` is an iterator over sets of permutations that disagree with each other in every position:
+//! the rows of a Latin rectangle, where no column repeats a value.
+//! With as many rows as values, the rectangles are the Latin squares (and the solved Sudoku bands, with extra constraints).
+//!
+//! The rows are chosen incrementally, with a backtracking search over a stack of `IntoChunks` job trees:
+//! the tree of each row is pruned by the rows above it, cutting a partial permutation
+//! as soon as its last value is already in the same column of a previous row.
+//! So only the permutations compatible with the rows above are ever generated.
+//!
+//! The rectangles are generated in lexicographic order of their rows,
+//! each row in the generation order of the permutations.
+use std::hash::Hash;
+
+use crate::hasher::ValueHasher;
+use crate::permutations::into_chunks::{IntoChunks, Permutation};
+use crate::permutations::{ExpansionStrategy, Uniqueness};
+
+/// Iterator over the Latin rectangles of a given number of rows.
+pub struct LatinRectangles<T> {
+    values: Vec<T>,
+    uniqueness: Uniqueness,
+    hasher: ValueHasher,
+    rows: usize,
+    /// the job tree of each row chosen so far, and of the next one.
+    levels: Vec<IntoChunks<T>>,
+    /// the rows chosen so far.
+    rectangle: Vec<Permutation<T>>,
+    is_exhausted: bool,
+}
+
+impl<T: Copy + Eq + Hash> LatinRectangles<T> {
+    pub(crate) fn new(
+        values: Vec<T>,
+        uniqueness: Uniqueness,
+        hasher: ValueHasher,
+        rows: usize,
+    ) -> Self {
+        Self {
+            values,
+            uniqueness,
+            hasher,
+            rows,
+            levels: vec![],
+            rectangle: vec![],
+            is_exhausted: false,
+        }
+    }
+
+    /// Initialize the job tree of the next row.
+    fn push_level(&mut self) {
+        self.levels.push(IntoChunks::new(
+            self.values.clone(),
+            1,
+            self.uniqueness,
+            ExpansionStrategy::DepthFirst,
+            self.hasher,
+        ));
+    }
+}
+
+/// The iterator implementation to find the next Latin rectangle.
+/// Each rectangle is returned as its rows, from the first one.
+impl<T: Copy + Eq + Hash> Iterator for LatinRectangles<T> {
+    type Item = Vec<Vec<T>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_exhausted {
+            return None;
+        }
+        if self.rows == 0 {
+            // the empty rectangle is the only one.
+            self.is_exhausted = true;
+            return Some(vec![]);
+        }
+        if self.levels.is_empty() {
+            self.push_level();
+        } else {
+            // backtrack from the last rectangle.
+            self.rectangle.pop();
+        }
+        loop {
+            let rectangle = &self.rectangle;
+            let level = self.levels.last_mut()?;
+            let row = level.next_permutation_pruned(|partial| match partial.len() {
+                0 => false,
+                length => rectangle
+                    .iter()
+                    .any(|row| row[length - 1] == partial[length - 1]),
+            });
+            match row {
+                Some(row) => {
+                    self.rectangle.push(row);
+                    if self.rectangle.len() == self.rows {
+                        return Some(self.rectangle.iter().map(|row| row.to_vec()).collect());
+                    }
+                    self.push_level();
+                }
+                None => {
+                    self.levels.pop();
+                    if self.rectangle.pop().is_none() {
+                        self.is_exhausted = true;
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! # Paranoid
+//!
+//! With the `paranoid` feature, in the debug builds, the iterators check their own output:
+//! each permutation is ranked in the lexicographic order of the labels, as by `IntoRankedChunks`,
+//! and its rank is marked in a bitset, so a permutation generated twice panics at once.
+//! Once the generation is complete the number of permutations is checked
+//! against the multinomial count of the values.
+//!
+//! The labels are the order of the first occurrence of each value, or the positions
+//! with `Uniqueness::WithDuplicates`. The job tree of `IntoChunks` keeps only the values,
+//! so with `Uniqueness::WithDuplicates` it repeats the equal permutations by design
+//! and only its count is checked.
+//!
+//! The checks are slow and the bitset grows with the number of permutations:
+//! they are meant for the tests run while changing the backends.
+//! Otherwise `Paranoid` is empty and its methods do nothing.
+#[cfg(all(feature = "paranoid", debug_assertions))]
+use std::collections::HashMap;
+use std::hash::Hash;
+#[cfg(not(all(feature = "paranoid", debug_assertions)))]
+use std::marker::PhantomData;
+
+#[cfg(all(feature = "paranoid", debug_assertions))]
+use crate::math::multinomial;
+#[cfg(all(feature = "paranoid", debug_assertions))]
+use crate::permutations::into_ranked_chunks::rank;
+use crate::permutations::Uniqueness;
+
+/// The checks of the permutations generated by an iterator.
+#[cfg(all(feature = "paranoid", debug_assertions))]
+pub(crate) struct Paranoid<T> {
+    uniqueness: Uniqueness,
+    /// the label of each value, empty with `Uniqueness::WithDuplicates`.
+    value_to_label: HashMap<T, usize>,
+    /// the frequency of each label.
+    frequencies: Vec<usize>,
+    /// the number of permutations, if it fits in a `usize`: otherwise the ranks are not checked.
+    total: Option<usize>,
+    /// the rank the generation started from.
+    first_rank: usize,
+    /// the ranks generated, from `first_rank`.
+    seen: Vec<u64>,
+    generated: usize,
+}
+
+#[cfg(all(feature = "paranoid", debug_assertions))]
+impl<T: Copy + Eq + Hash> Paranoid<T> {
+    pub(crate) fn new(values: &[T], uniqueness: Uniqueness) -> Self {
+        let mut value_to_label = HashMap::new();
+        let mut frequencies = vec![];
+        for value in values {
+            let label = match uniqueness {
+                Uniqueness::Distinct => *value_to_label.entry(*value).or_insert(frequencies.len()),
+                Uniqueness::WithDuplicates => frequencies.len(),
+            };
+            if label == frequencies.len() {
+                frequencies.push(0);
+            }
+            frequencies[label] += 1;
+        }
+        let total = multinomial(frequencies.iter().copied());
+        Self {
+            uniqueness,
+            value_to_label,
+            frequencies,
+            total,
+            first_rank: 0,
+            seen: vec![],
+            generated: 0,
+        }
+    }
+
+    /// Check a permutation of values generated by the job tree.
+    pub(crate) fn record_values(&mut self, permutation: &[T]) {
+        if self.uniqueness == Uniqueness::WithDuplicates {
+            // the equal permutations are generated by design: only count them.
+            self.generated += 1;
+            return;
+        }
+        let labels = permutation
+            .iter()
+            .map(|value| {
+                *self
+                    .value_to_label
+                    .get(value)
+                    .expect("paranoid: a value of the permutation is not in the input")
+            })
+            .collect::<Vec<usize>>();
+        self.record_labels(&labels);
+    }
+}
+
+#[cfg(all(feature = "paranoid", debug_assertions))]
+impl<T> Paranoid<T> {
+    /// Check the permutations generated from the rank `rank`.
+    pub(crate) fn starting_at(&mut self, rank: usize) {
+        self.first_rank = rank;
+    }
+
+    /// Check a permutation of labels: panics if it was already generated.
+    pub(crate) fn record_labels(&mut self, labels: &[usize]) {
+        self.generated += 1;
+        if self.total.is_none() {
+            return;
+        }
+        let rank = rank(&self.frequencies, labels)
+            .expect("paranoid: the generated permutation is not a permutation of the input");
+        let offset = rank
+            .checked_sub(self.first_rank)
+            .expect("paranoid: a permutation before the starting rank was generated");
+        let (word, bit) = (offset / 64, offset % 64);
+        if word >= self.seen.len() {
+            self.seen.resize(word + 1, 0);
+        }
+        if self.seen[word] & (1 << bit) != 0 {
+            panic!(
+                "paranoid: the permutation of rank {} was generated twice",
+                rank
+            );
+        }
+        self.seen[word] |= 1 << bit;
+    }
+
+    /// Check that the generation, now complete, produced all the permutations from the starting rank.
+    pub(crate) fn check_complete(&self) {
+        if let Some(total) = self.total {
+            let expected = total.saturating_sub(self.first_rank);
+            assert_eq!(
+                self.generated, expected,
+                "paranoid: {} permutations were generated instead of {}",
+                self.generated, expected
+            );
+        }
+    }
+}
+
+/// The checks of the permutations generated by an iterator: disabled.
+#[cfg(not(all(feature = "paranoid", debug_assertions)))]
+pub(crate) struct Paranoid<T>(PhantomData<T>);
+
+#[cfg(not(all(feature = "paranoid", debug_assertions)))]
+impl<T: Copy + Eq + Hash> Paranoid<T> {
+    pub(crate) fn new(_values: &[T], _uniqueness: Uniqueness) -> Self {
+        Self(PhantomData)
+    }
+
+    #[inline]
+    pub(crate) fn record_values(&mut self, _permutation: &[T]) {}
+}
+
+#[cfg(not(all(feature = "paranoid", debug_assertions)))]
+impl<T> Paranoid<T> {
+    #[inline]
+    pub(crate) fn starting_at(&mut self, _rank: usize) {}
+
+    #[inline]
+    pub(crate) fn record_labels(&mut self, _labels: &[usize]) {}
+
+    #[inline]
+    pub(crate) fn check_complete(&self) {}
+}
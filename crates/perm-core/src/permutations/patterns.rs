@@ -0,0 +1,140 @@
+//! # Patterns
+//!
+//! A permutation contains a pattern, a permutation of `0..k`, if some `k` of its values
+//! (not necessarily adjacent) are in the same relative order as the pattern:
+//! `3,1,4,2` contains `[1, 0]` (`3,1`) and avoids `[2, 1, 0]` (no decreasing triple).
+//!
+//! `ForbiddenPattern` prunes the job tree of `IntoPrunedChunks`: a partial permutation is cut
+//! as soon as it contains the pattern, since all the permutations starting with it contain it too.
+//! The prefixes of a partial permutation have already been checked,
+//! so only the occurrences ending at its last value are searched, by backtracking over their positions.
+//! The equal values are not in any relative order, so they never match two positions of the pattern.
+use std::cmp::Ordering;
+use std::iter::once;
+
+/// A pattern the generated permutations must avoid.
+pub(crate) struct ForbiddenPattern<T> {
+    pattern: Vec<usize>,
+    compare: fn(&T, &T) -> Ordering,
+}
+
+impl<T: Ord> ForbiddenPattern<T> {
+    /// Initialize the `ForbiddenPattern`, comparing the values with their `Ord`.
+    /// Panics if the `pattern` is not a non-empty permutation of `0..pattern.len()`.
+    pub(crate) fn new(pattern: &[usize]) -> Self {
+        let mut seen = vec![false; pattern.len()];
+        for position in pattern {
+            match seen.get_mut(*position) {
+                Some(seen) if !*seen => *seen = true,
+                _ => panic!("The pattern is not a permutation"),
+            }
+        }
+        if pattern.is_empty() {
+            panic!("The pattern must not be empty")
+        }
+        Self {
+            pattern: pattern.to_vec(),
+            compare: T::cmp,
+        }
+    }
+}
+
+impl<T> ForbiddenPattern<T> {
+    /// Check if the `partial` permutation has an occurrence of the pattern ending at its last value.
+    pub(crate) fn ends_in(&self, partial: &[T]) -> bool {
+        partial.len() >= self.pattern.len() && self.extends(partial, &mut vec![])
+    }
+
+    /// Check if the positions `chosen` for the first values of the pattern can be extended to an occurrence.
+    fn extends(&self, partial: &[T], chosen: &mut Vec<usize>) -> bool {
+        let next = chosen.len();
+        let last = self.pattern.len() - 1;
+        if next == last {
+            return true;
+        }
+        let start = chosen.last().map_or(0, |index| index + 1);
+        // leave room for the following values of the pattern, before the last one.
+        let end = partial.len() - 1 - (last - next);
+        for index in start..=end {
+            if self.is_consistent(partial, next, index, chosen) {
+                chosen.push(index);
+                if self.extends(partial, chosen) {
+                    return true;
+                }
+                chosen.pop();
+            }
+        }
+        false
+    }
+
+    /// Check if the value at `index` is in the same order as the pattern value at `position`,
+    /// with respect to the values `chosen` and the last one.
+    fn is_consistent(
+        &self,
+        partial: &[T],
+        position: usize,
+        index: usize,
+        chosen: &[usize],
+    ) -> bool {
+        let last = (self.pattern.len() - 1, partial.len() - 1);
+        chosen
+            .iter()
+            .copied()
+            .enumerate()
+            .chain(once(last))
+            .all(|(other_position, other_index)| {
+                (self.compare)(&partial[index], &partial[other_index])
+                    == self.pattern[position].cmp(&self.pattern[other_position])
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::permutations::tests::collect_permutations;
+    use crate::Permutations;
+
+    #[test]
+    fn forbidden_patterns_are_avoided() {
+        let avoiding = |length: usize, patterns: &[&[usize]]| {
+            collect_permutations(
+                patterns.iter().fold(
+                    Permutations::new((1..=length).collect::<Vec<usize>>())
+                        .into_pruned_chunks(7, |_: &[usize]| false),
+                    |chunks, pattern| chunks.with_forbidden_pattern(pattern),
+                ),
+            )
+        };
+        // the 321-avoiding and the 132-avoiding permutations are counted by the Catalan numbers.
+        assert_eq!(avoiding(5, &[&[2, 1, 0]]).len(), 42);
+        assert_eq!(avoiding(6, &[&[0, 2, 1]]).len(), 132);
+        assert_eq!(avoiding(5, &[&[0, 1, 2, 3]]).len(), 103);
+        // avoiding both 21 and 12 leaves no permutation with two values.
+        assert_eq!(avoiding(1, &[&[1, 0], &[0, 1]]), vec![vec![1]]);
+        assert_eq!(avoiding(2, &[&[1, 0], &[0, 1]]).len(), 0);
+        assert_eq!(
+            avoiding(3, &[&[1, 0, 2]]),
+            vec![
+                vec![1, 2, 3],
+                vec![1, 3, 2],
+                vec![2, 3, 1],
+                vec![3, 1, 2],
+                vec![3, 2, 1]
+            ]
+        );
+        let equal_values = Permutations::new(vec![2, 1, 1])
+            .into_pruned_chunks(3, |_: &[i32]| false)
+            .with_forbidden_pattern(&[2, 1, 0])
+            .map(|chunk| chunk.len())
+            .sum::<usize>();
+        assert_eq!(equal_values, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn invalid_forbidden_patterns_are_rejected() {
+        let _ = Permutations::new(vec![1, 2, 3])
+            .into_pruned_chunks(3, |_: &[i32]| false)
+            .with_forbidden_pattern(&[0, 2]);
+    }
+}
@@ -0,0 +1,78 @@
+//! # Top-K
+//!
+//! Search the `k` permutations with the highest score,
+//! without writing all the permutations to output.
+//!
+//! The best permutations found so far are kept in a min-heap of size `k`,
+//! so that the worst of them can be replaced in `O(log(k))`.
+//!
+//! With an upper bound of the score of every completion of a partial permutation,
+//! the search becomes a branch-and-bound:
+//! a subtree is not explored when its upper bound cannot beat the worst of the `k` best permutations.
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+
+use crate::permutations::into_chunks::IntoChunks;
+
+/// A permutation with its score.
+/// It is ordered by score only, with a total order on `f64`.
+struct Scored<T> {
+    score: f64,
+    permutation: Vec<T>,
+}
+
+impl<T> PartialEq for Scored<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Scored<T> {}
+
+impl<T> PartialOrd for Scored<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Scored<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.total_cmp(&other.score)
+    }
+}
+
+/// Find the `k` permutations generated by `permutations` with the highest `score`,
+/// sorted by descending score.
+/// The subtrees whose `upper_bound` is not greater than the `k`-th best score are skipped.
+pub(crate) fn top_k<T: Copy + Eq + Hash>(
+    mut permutations: IntoChunks<T>,
+    k: usize,
+    score: impl Fn(&[T]) -> f64,
+    upper_bound: impl Fn(&[T]) -> f64,
+) -> Vec<(f64, Vec<T>)> {
+    let mut best: BinaryHeap<Reverse<Scored<T>>> = BinaryHeap::with_capacity(k + 1);
+    if k == 0 {
+        return vec![];
+    }
+
+    while let Some(permutation) = permutations.next_permutation_pruned(|partial| {
+        best.len() == k
+            && best
+                .peek()
+                .is_some_and(|worst| upper_bound(partial) <= worst.0.score)
+    }) {
+        let score = score(&permutation);
+        let permutation = permutation.into_iter().collect();
+        if best.len() < k {
+            best.push(Reverse(Scored { score, permutation }));
+        } else if best.peek().is_some_and(|worst| score > worst.0.score) {
+            best.pop();
+            best.push(Reverse(Scored { score, permutation }));
+        }
+    }
+    best.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(scored)| (scored.score, scored.permutation))
+        .collect()
+}
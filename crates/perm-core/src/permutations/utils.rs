@@ -0,0 +1,7 @@
+use std::time::Instant;
+
+/// Check if the `deadline` of a time-sliced chunk has passed.
+/// Without a deadline the chunk is filled to its size.
+pub fn is_expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
@@ -0,0 +1,277 @@
+//! # Search
+//!
+//! Local search over the arrangements of `n` positions, as `PermutationMap`s,
+//! for the optimization problems whose permutations are too many to be enumerated (e.g. the routes of a tour).
+//!
+//! A `Move` changes an arrangement into a neighboring one: swapping two positions,
+//! moving the value of a position to another one (insertion), or reversing a segment (the 2-opt move of the tours).
+//! A move is itself a permutation of the positions, so it is applied by composing its `PermutationMap`.
+//! A `Neighborhood` is the set of the moves of one kind.
+//!
+//! `hill_climb` moves to the best neighbor as long as it lowers the cost,
+//! and with the `rand` feature `Annealing` runs a simulated annealing,
+//! accepting a worse random neighbor with a probability decreasing with the temperature.
+//! Both minimize the cost: a score to maximize is negated.
+use crate::algebra::PermutationMap;
+
+/// A change of an arrangement into a neighboring one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Move {
+    /// Swap the values of two positions.
+    Swap(usize, usize),
+    /// Remove the value at position `from`, and insert it back at position `to`.
+    Insertion { from: usize, to: usize },
+    /// Reverse the values from position `start` to position `end`, both included.
+    Reversal { start: usize, end: usize },
+}
+
+impl Move {
+    /// Build the `PermutationMap` of `length` positions rearranging the values as the move.
+    /// Panics if a position of the move is not lower than `length`.
+    pub fn to_map(&self, length: usize) -> PermutationMap {
+        let mut images = (0..length).collect::<Vec<usize>>();
+        match *self {
+            Move::Swap(first, second) => images.swap(first, second),
+            Move::Insertion { from, to } => {
+                let image = images.remove(from);
+                images.insert(to, image);
+            }
+            Move::Reversal { start, end } => images[start.min(end)..=start.max(end)].reverse(),
+        }
+        PermutationMap::new(images).expect("The images of a move are a permutation")
+    }
+    /// Apply the move to `arrangement`: the result rearranges a sequence as `arrangement` and then as the move.
+    /// Panics if a position of the move is not lower than the length of `arrangement`.
+    pub fn apply(&self, arrangement: &PermutationMap) -> PermutationMap {
+        arrangement.compose(&self.to_map(arrangement.len()))
+    }
+}
+
+/// The moves of one kind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Neighborhood {
+    /// The swaps of two positions: `n * (n - 1) / 2` moves.
+    Swap,
+    /// The insertions of a value at another position, without the ones equal to a swap of adjacent positions
+    /// counted twice: `(n - 1)^2` moves.
+    Insertion,
+    /// The reversals of a segment of at least two positions: `n * (n - 1) / 2` moves.
+    Reversal,
+}
+
+impl Neighborhood {
+    /// List the moves of the neighborhood of an arrangement of `length` positions,
+    /// each leading to a different arrangement.
+    pub fn moves(&self, length: usize) -> Vec<Move> {
+        let pairs = (0..length)
+            .flat_map(move |first| (first + 1..length).map(move |second| (first, second)));
+        match self {
+            Neighborhood::Swap => pairs.map(|(first, second)| Move::Swap(first, second)).collect(),
+            Neighborhood::Insertion => (0..length)
+                .flat_map(|from| (0..length).map(move |to| Move::Insertion { from, to }))
+                // moving a value back by one is the same as moving the previous one forward by one.
+                .filter(|mv| matches!(mv, Move::Insertion { from, to } if from != to && to + 1 != *from))
+                .collect(),
+            Neighborhood::Reversal => pairs
+                .map(|(start, end)| Move::Reversal { start, end })
+                .collect(),
+        }
+    }
+    /// Choose a uniformly random move of the neighborhood of an arrangement of `length` positions.
+    /// Return `None` if there are less than two positions: then there is no move.
+    #[cfg(feature = "rand")]
+    pub fn random_move<R: rand::Rng + ?Sized>(&self, length: usize, rng: &mut R) -> Option<Move> {
+        if length < 2 {
+            return None;
+        }
+        let first = rng.gen_range(0..length);
+        let second = (first + rng.gen_range(1..length)) % length;
+        Some(match self {
+            Neighborhood::Swap => Move::Swap(first, second),
+            Neighborhood::Insertion => Move::Insertion {
+                from: first,
+                to: second,
+            },
+            Neighborhood::Reversal => Move::Reversal {
+                start: first.min(second),
+                end: first.max(second),
+            },
+        })
+    }
+}
+
+/// Move from `start` to the neighbor with the lowest `cost`, until no neighbor lowers it:
+/// a local minimum of the `neighborhood`.
+/// Return the arrangement found, with its cost.
+pub fn hill_climb(
+    start: PermutationMap,
+    neighborhood: Neighborhood,
+    mut cost: impl FnMut(&PermutationMap) -> f64,
+) -> (PermutationMap, f64) {
+    let moves = neighborhood.moves(start.len());
+    let mut current_cost = cost(&start);
+    let mut current = start;
+    loop {
+        let best = moves
+            .iter()
+            .map(|mv| {
+                let neighbor = mv.apply(&current);
+                (cost(&neighbor), neighbor)
+            })
+            .min_by(|(left, _), (right, _)| left.total_cmp(right));
+        match best {
+            Some((neighbor_cost, neighbor)) if neighbor_cost < current_cost => {
+                current = neighbor;
+                current_cost = neighbor_cost;
+            }
+            _ => return (current, current_cost),
+        }
+    }
+}
+
+/// The parameters of a simulated annealing.
+///
+/// At each iteration a random move of the neighborhood is applied to the current arrangement:
+/// the neighbor is accepted if it lowers the cost, otherwise with probability `exp(-increase / temperature)`.
+/// The temperature starts at `initial_temperature` and is multiplied by `cooling` after each iteration.
+#[cfg(feature = "rand")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Annealing {
+    neighborhood: Neighborhood,
+    initial_temperature: f64,
+    cooling: f64,
+    iterations: usize,
+}
+
+#[cfg(feature = "rand")]
+impl Annealing {
+    /// Initialize the annealing with the moves of `neighborhood`,
+    /// an initial temperature of `1.0`, a cooling factor of `0.999` and `10000` iterations.
+    pub fn new(neighborhood: Neighborhood) -> Self {
+        Self {
+            neighborhood,
+            initial_temperature: 1.0,
+            cooling: 0.999,
+            iterations: 10_000,
+        }
+    }
+    /// Set the initial temperature: the higher, the more worse neighbors are accepted at first.
+    pub fn with_initial_temperature(mut self, initial_temperature: f64) -> Self {
+        self.initial_temperature = initial_temperature;
+        self
+    }
+    /// Set the factor multiplying the temperature after each iteration, lower than one.
+    pub fn with_cooling(mut self, cooling: f64) -> Self {
+        self.cooling = cooling;
+        self
+    }
+    /// Set the number of iterations.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    /// Run the annealing from `start`, minimizing `cost`.
+    /// Return the arrangement with the lowest cost found, with its cost.
+    pub fn run<R: rand::Rng + ?Sized>(
+        &self,
+        start: PermutationMap,
+        mut cost: impl FnMut(&PermutationMap) -> f64,
+        rng: &mut R,
+    ) -> (PermutationMap, f64) {
+        let mut current_cost = cost(&start);
+        let mut best = (start.clone(), current_cost);
+        let mut current = start;
+        let mut temperature = self.initial_temperature;
+        for _ in 0..self.iterations {
+            let mv = match self.neighborhood.random_move(current.len(), rng) {
+                Some(mv) => mv,
+                None => break,
+            };
+            let neighbor = mv.apply(&current);
+            let neighbor_cost = cost(&neighbor);
+            let increase = neighbor_cost - current_cost;
+            if increase <= 0.0 || rng.gen::<f64>() < (-increase / temperature).exp() {
+                current = neighbor;
+                current_cost = neighbor_cost;
+                if current_cost < best.1 {
+                    best = (current.clone(), current_cost);
+                }
+            }
+            temperature *= self.cooling;
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The distance of each value of the reversed `0..n` from its sorted position, once rearranged.
+    fn displacement(arrangement: &PermutationMap) -> f64 {
+        let length = arrangement.len();
+        arrangement
+            .as_slice()
+            .iter()
+            .enumerate()
+            .map(|(position, image)| ((length - 1 - image) as f64 - position as f64).abs())
+            .sum()
+    }
+
+    #[test]
+    fn moves_rearrange_the_positions() {
+        let images = |mv: Move| mv.to_map(5).as_slice().to_vec();
+        assert_eq!(images(Move::Swap(1, 3)), vec![0, 3, 2, 1, 4]);
+        assert_eq!(
+            images(Move::Insertion { from: 0, to: 3 }),
+            vec![1, 2, 3, 0, 4]
+        );
+        assert_eq!(
+            images(Move::Reversal { start: 1, end: 3 }),
+            vec![0, 3, 2, 1, 4]
+        );
+        let arrangement = PermutationMap::rotation(5, 1);
+        assert_eq!(
+            Move::Swap(0, 1).apply(&arrangement).as_slice(),
+            &[2, 1, 3, 4, 0]
+        );
+        assert_eq!(Neighborhood::Swap.moves(4).len(), 6);
+        assert_eq!(Neighborhood::Insertion.moves(4).len(), 9);
+        assert_eq!(Neighborhood::Reversal.moves(4).len(), 6);
+        let insertions = Neighborhood::Insertion
+            .moves(4)
+            .iter()
+            .map(|mv| mv.to_map(4))
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(insertions.len(), 9);
+    }
+
+    #[test]
+    fn hill_climb_reaches_a_local_minimum() {
+        let (arrangement, cost) = hill_climb(
+            PermutationMap::identity(8),
+            Neighborhood::Swap,
+            displacement,
+        );
+        assert_eq!(cost, 0.0);
+        assert_eq!(arrangement, PermutationMap::reversal(8));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn annealing_finds_the_minimum() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let (arrangement, cost) = Annealing::new(Neighborhood::Swap)
+            .with_initial_temperature(2.0)
+            .with_iterations(20_000)
+            .run(
+                PermutationMap::identity(8),
+                displacement,
+                &mut StdRng::seed_from_u64(7),
+            );
+        assert_eq!(cost, 0.0);
+        assert_eq!(arrangement, PermutationMap::reversal(8));
+    }
+}
@@ -0,0 +1,150 @@
+//! # Table
+//!
+//! The permutations of `0..N` computed at compile time, for the lookup tables
+//! of the shuffles known in advance (SIMD lane shuffles, game move tables).
+//!
+//! `permutation_table` is a `const fn`, so the table can be stored in a `const` or a `static`:
+//! the permutations are in lexicographic order, like the ones of the runtime iterators,
+//! so the permutation at index `i` is the one of rank `i`.
+//!
+//! The length of the table, `N!`, cannot be computed from `N` in the type on stable Rust,
+//! so it is a second parameter checked against `factorial(N)`:
+//! the `permutation_table!` macro fills it in.
+//! The table of `8` values takes 2.5 MiB: store it in a `static` rather than on the stack.
+//!
+//! For example `const SHUFFLES: [[usize; 4]; factorial(4)] = permutation_table!(4);`.
+use crate::math;
+
+/// The maximum length of the permutations in a table: the table of `8` values has `40320` permutations.
+pub const MAX_TABLE_LENGTH: usize = 8;
+
+/// Compute `n!`, the number of permutations of `n` distinct values: see `math::factorial`.
+///
+/// # Panics
+///
+/// Panics if the result does not fit in a `usize` (with `n` greater than `20` on 64 bits).
+pub const fn factorial(n: usize) -> usize {
+    match math::factorial(n) {
+        Some(result) => result,
+        None => panic!("The factorial does not fit in a usize"),
+    }
+}
+
+/// Compute all the permutations of `0..N` in lexicographic order.
+///
+/// # Panics
+///
+/// Panics (at compile time in a const context) if `N` is greater than `MAX_TABLE_LENGTH`,
+/// or if `M` is not `factorial(N)`.
+pub const fn permutation_table<const N: usize, const M: usize>() -> [[usize; N]; M] {
+    assert!(N <= MAX_TABLE_LENGTH, "the permutation table is too long");
+    assert!(
+        M == factorial(N),
+        "the permutation table must have N! permutations"
+    );
+    let mut table = [[0; N]; M];
+    let mut permutation = [0; N];
+    let mut i = 0;
+    while i < N {
+        permutation[i] = i;
+        i += 1;
+    }
+    let mut rank = 0;
+    while rank < M {
+        table[rank] = permutation;
+        rank += 1;
+        if N < 2 {
+            continue;
+        }
+        // the next permutation: the values after the pivot are decreasing.
+        let mut pivot = N - 1;
+        while pivot > 0 && permutation[pivot - 1] > permutation[pivot] {
+            pivot -= 1;
+        }
+        if pivot == 0 {
+            // the last permutation.
+            continue;
+        }
+        let mut successor = N - 1;
+        while permutation[successor] < permutation[pivot - 1] {
+            successor -= 1;
+        }
+        let value = permutation[pivot - 1];
+        permutation[pivot - 1] = permutation[successor];
+        permutation[successor] = value;
+        let mut start = pivot;
+        let mut end = N - 1;
+        while start < end {
+            let value = permutation[start];
+            permutation[start] = permutation[end];
+            permutation[end] = value;
+            start += 1;
+            end -= 1;
+        }
+    }
+    table
+}
+
+/// Compute the table of the permutations of `0..N` with `permutation_table`,
+/// filling in its length `factorial(N)`.
+#[macro_export]
+macro_rules! permutation_table {
+    ($length:expr) => {
+        $crate::table::permutation_table::<{ $length }, { $crate::table::factorial($length) }>()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Permutations;
+
+    use super::*;
+
+    const TABLE_4: [[usize; 4]; factorial(4)] = permutation_table!(4);
+    static TABLE_8: [[usize; 8]; factorial(8)] = permutation_table!(MAX_TABLE_LENGTH);
+
+    #[test]
+    fn factorials_are_correct() {
+        assert_eq!(factorial(0), 1);
+        assert_eq!(factorial(1), 1);
+        assert_eq!(factorial(5), 120);
+        assert_eq!(factorial(MAX_TABLE_LENGTH), 40320);
+    }
+
+    #[test]
+    fn table_is_in_generation_order() {
+        let generated = Permutations::new((0..4).collect::<Vec<usize>>())
+            .into_chunks(factorial(4))
+            .flat_map(|mut chunk| {
+                chunk
+                    .as_mut()
+                    .iter()
+                    .map(|p| p.to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<Vec<usize>>>();
+        assert_eq!(
+            TABLE_4.iter().map(|p| p.to_vec()).collect::<Vec<_>>(),
+            generated
+        );
+    }
+
+    #[test]
+    fn longest_table_is_computed() {
+        assert_eq!(TABLE_8.len(), 40320);
+        assert_eq!(TABLE_8[0], [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(TABLE_8[40319], [7, 6, 5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn tiny_tables_are_computed() {
+        assert_eq!(permutation_table!(0), [[]; 1]);
+        assert_eq!(permutation_table!(1), [[0]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "N! permutations")]
+    fn wrong_table_length_is_rejected() {
+        permutation_table::<3, 5>();
+    }
+}
@@ -0,0 +1,95 @@
+//! # Test Utilities
+//!
+//! Random `Permutations` for the property tests of the crates depending on `perm`,
+//! with both `quickcheck` and `proptest`.
+//!
+//! The inputs have at least one and at most `MAX_LENGTH` values,
+//! so that all their permutations can be generated in a test.
+//! The ranks are always lower than the number of permutations of their input.
+//!
+//! It is available with the `test-util` feature.
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use proptest::collection::vec;
+use proptest::strategy::{Just, Strategy};
+use quickcheck::{Arbitrary, Gen};
+
+use crate::Permutations;
+
+/// The maximum number of values of the random inputs.
+pub const MAX_LENGTH: usize = 7;
+
+/// Random input values, with at least one and at most `MAX_LENGTH` values.
+impl<T> Arbitrary for Permutations<T>
+where
+    T: Arbitrary + Copy + Eq + Hash + Sync,
+{
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let length = 1 + usize::arbitrary(g) % MAX_LENGTH;
+        Permutations::new((0..length).map(|_| T::arbitrary(g)).collect())
+    }
+}
+
+/// Random input values with a valid rank of one of their permutations.
+#[derive(Clone, Debug)]
+pub struct RankedPermutations<T: Copy> {
+    /// the random input values.
+    pub permutations: Permutations<T>,
+    /// a rank lower than the number of permutations.
+    pub rank: usize,
+}
+
+impl<T> Arbitrary for RankedPermutations<T>
+where
+    T: Arbitrary + Copy + Eq + Hash + Sync,
+{
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let permutations = Permutations::<T>::arbitrary(g);
+        let rank = usize::arbitrary(g) % permutations.permutations_number();
+        Self { permutations, rank }
+    }
+}
+
+/// A `proptest` strategy for `Permutations` of at least one and at most `MAX_LENGTH` values
+/// generated by `value`.
+pub fn permutations<T>(value: impl Strategy<Value = T>) -> impl Strategy<Value = Permutations<T>>
+where
+    T: Copy + Eq + Hash + Debug,
+{
+    vec(value, 1..=MAX_LENGTH).prop_map(Permutations::new)
+}
+
+/// A `proptest` strategy for `Permutations` of at least one and at most `MAX_LENGTH` values
+/// generated by `value`, with a valid rank of one of their permutations.
+pub fn ranked_permutations<T>(
+    value: impl Strategy<Value = T>,
+) -> impl Strategy<Value = (Permutations<T>, usize)>
+where
+    T: Copy + Eq + Hash + Debug,
+{
+    permutations(value).prop_flat_map(|permutations| {
+        let permutations_number = permutations.permutations_number();
+        (Just(permutations), 0..permutations_number)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[quickcheck_macros::quickcheck]
+    fn arbitrary_ranks_are_valid(ranked: RankedPermutations<u8>) {
+        assert!(ranked.permutations.nth_permutation(ranked.rank).is_some());
+    }
+
+    proptest! {
+        #[test]
+        fn strategy_ranks_are_valid((permutations, rank) in ranked_permutations(0..4u8)) {
+            prop_assert!(permutations.length() <= MAX_LENGTH);
+            prop_assert!(permutations.nth_permutation(rank).is_some());
+        }
+    }
+}
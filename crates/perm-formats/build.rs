@@ -0,0 +1,31 @@
+//! Generate the stub of the gRPC service of `src/grpc.rs`, described in `proto/perm.proto`.
+//!
+//! The messages are written by hand with `prost`, and the service is described here
+//! for the manual builder of `tonic-build`, so that the build does not need `protoc`.
+fn main() {
+    #[cfg(feature = "grpc")]
+    generate_grpc_service();
+}
+
+#[cfg(feature = "grpc")]
+fn generate_grpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let generate_permutations = Method::builder()
+        .name("generate_permutations")
+        .route_name("GeneratePermutations")
+        .input_type("crate::grpc::GenerateRequest")
+        .output_type("crate::grpc::Chunk")
+        .codec_path("tonic::codec::ProstCodec")
+        .client_streaming()
+        .server_streaming()
+        .build();
+    let service = Service::builder()
+        .name("PermutationGenerator")
+        .package("perm")
+        .method(generate_permutations)
+        .build();
+    // the generated `connect` needs the prelude of the 2021 edition:
+    // the clients connect their own `Channel` instead.
+    Builder::new().build_transport(false).compile(&[service]);
+}
@@ -0,0 +1,186 @@
+//! # Arrow
+//!
+//! Write the chunks of permutations as Arrow record batches and Parquet files.
+//!
+//! Each permutation is a row, and each position of the permutation is a column:
+//! the column of the `i`-th position is named `p{i}`.
+//! The values are stored as UTF-8 strings formatted with `PermValue`,
+//! so that any displayable chunk can be converted.
+//!
+//! `ToRecordBatch` is a `Transform` run by the workers of the `Pipeline`,
+//! and `ParquetSink` writes the record batches to a single Parquet file.
+//!
+//! It is available with the `arrow` feature.
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, RecordBatch, RecordBatchOptions, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+
+use crate::pipeline::{Sink, Transform};
+use perm_core::format::{DisplayValue, PermValue};
+use perm_core::ChunkView;
+
+/// Get the schema of the permutations of length `permutation_length`:
+/// one non-nullable string column for each position.
+pub fn schema(permutation_length: usize) -> Schema {
+    Schema::new(
+        (0..permutation_length)
+            .map(|position| Field::new(format!("p{}", position), DataType::Utf8, false))
+            .collect::<Vec<Field>>(),
+    )
+}
+
+/// Convert a chunk to a `RecordBatch` with one row for each permutation.
+pub fn to_record_batch<C>(chunk: &C) -> Result<RecordBatch, ArrowError>
+where
+    C: ChunkView,
+    C::Value: PermValue,
+{
+    let columns = (0..chunk.permutation_length())
+        .map(|position| {
+            let column = (0..chunk.permutations_count())
+                .map(|index| Some(DisplayValue(chunk.value(index, position)).to_string()))
+                .collect::<StringArray>();
+            Arc::new(column) as ArrayRef
+        })
+        .collect::<Vec<ArrayRef>>();
+    // the permutations of the empty input have no columns, so the rows are counted explicitly.
+    let options = RecordBatchOptions::new().with_row_count(Some(chunk.permutations_count()));
+    RecordBatch::try_new_with_options(
+        Arc::new(schema(chunk.permutation_length())),
+        columns,
+        &options,
+    )
+}
+
+/// Convert each chunk to a `RecordBatch`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ToRecordBatch;
+
+impl<C> Transform<C> for ToRecordBatch
+where
+    C: ChunkView,
+    C::Value: PermValue,
+{
+    type Output = RecordBatch;
+
+    fn transform(&self, chunk: C) -> Option<RecordBatch> {
+        Some(to_record_batch(&chunk).expect("The columns always match the schema"))
+    }
+}
+
+/// Write the record batches to a Parquet file.
+/// The schema is taken from the first record batch.
+pub struct ParquetSink<W: Write + Send> {
+    /// the destination, until the first record batch is written.
+    output: Option<W>,
+    writer: Option<ArrowWriter<W>>,
+    rows_written: usize,
+}
+
+impl<W: Write + Send> ParquetSink<W> {
+    /// Initialize the `ParquetSink` with the underlying `output`.
+    pub fn new(output: W) -> Self {
+        Self {
+            output: Some(output),
+            writer: None,
+            rows_written: 0,
+        }
+    }
+    /// Get the number of rows (permutations) written so far.
+    pub fn rows_written(&self) -> usize {
+        self.rows_written
+    }
+    /// Get the number of bytes written so far to the underlying output.
+    /// The rows are buffered in row groups, so it is updated when a row group is flushed.
+    pub fn bytes_written(&self) -> usize {
+        self.writer
+            .as_ref()
+            .map_or(0, |writer| writer.bytes_written())
+    }
+
+    fn writer(&mut self, schema: SchemaRef) -> io::Result<&mut ArrowWriter<W>> {
+        if self.writer.is_none() {
+            let output = self
+                .output
+                .take()
+                .ok_or_else(|| other_error("The Parquet file has already been closed"))?;
+            self.writer = Some(ArrowWriter::try_new(output, schema, None).map_err(other_error)?);
+        }
+        Ok(self
+            .writer
+            .as_mut()
+            .expect("The writer has just been created"))
+    }
+}
+
+impl<W: Write + Send> Sink<RecordBatch> for ParquetSink<W> {
+    fn write(&mut self, batch: RecordBatch) -> io::Result<()> {
+        self.writer(batch.schema())?
+            .write(&batch)
+            .map_err(other_error)?;
+        self.rows_written += batch.num_rows();
+        Ok(())
+    }
+
+    /// Flush the buffered rows and write the Parquet footer.
+    /// Nothing is written if no record batch has been received.
+    fn finish(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.finish().map(|_| ()).map_err(other_error),
+            None => Ok(()),
+        }
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        Some(self.bytes_written())
+    }
+}
+
+fn other_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::Pipeline;
+    use perm_core::Permutations;
+
+    use super::*;
+
+    #[test]
+    fn each_position_is_a_column() {
+        let batch = to_record_batch(
+            &Permutations::new(vec![1, 2, 3])
+                .into_optimized_chunks(6)
+                .next()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(batch.num_rows(), 6);
+        assert_eq!(batch.num_columns(), 3);
+        assert_eq!(batch.schema().field(2).name(), "p2");
+    }
+
+    #[test]
+    fn all_permutations_are_written_to_parquet() {
+        let mut output = vec![];
+        let mut sink = ParquetSink::new(&mut output);
+        let written = Pipeline::new()
+            .with_workers(2)
+            .run(
+                Permutations::new(vec!["a", "b", "c", "d"]).into_chunks(5),
+                ToRecordBatch,
+                &mut sink,
+            )
+            .unwrap();
+        assert_eq!(written, 5);
+        assert_eq!(sink.rows_written(), 24);
+        let bytes_written = sink.bytes_written();
+        drop(sink);
+        assert_eq!(bytes_written, output.len());
+        assert!(output.starts_with(b"PAR1") && output.ends_with(b"PAR1"));
+    }
+}
@@ -0,0 +1,208 @@
+//! # Compression
+//!
+//! Compress the formatted chunks on the worker threads of the `Pipeline`,
+//! so that the compression runs in parallel instead of behind a single encoder on the writer thread.
+//!
+//! Each chunk is compressed on its own, as an independent gzip member or zstd frame:
+//! the concatenation of the members (frames) written in generation order is a valid gzip (zstd) stream,
+//! decompressed by the standard tools as the whole output.
+//! The compression ratio is slightly lower than the one of a single stream, since each chunk starts from scratch.
+//!
+//! `Compress` is a `Transform` of the formatted chunks, usually after `Encode`.
+//! The `CompressedChunk`s are written as bytes by a `WriterSink`,
+//! and a `ManifestSink` records the byte range of each frame in the `Manifest`,
+//! so that the frames can be decompressed in parallel.
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+
+use crate::pipeline::{ManifestSink, Sink, Transform};
+use crate::records::Records;
+
+/// The compression format of the chunks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// Each chunk is a gzip member, with levels from `0` to `9`.
+    Gzip,
+    /// Each chunk is a zstd frame, with levels from `1` to `22`.
+    Zstd,
+}
+
+impl Codec {
+    /// Get the default level of the codec: `6` for gzip and `3` for zstd.
+    pub fn default_level(&self) -> u32 {
+        match self {
+            Codec::Gzip => 6,
+            Codec::Zstd => 3,
+        }
+    }
+    /// Compress `bytes` as a single member (frame) with the given `level`.
+    pub fn compress(&self, bytes: &[u8], level: u32) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::new(level));
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Codec::Zstd => zstd::bulk::compress(bytes, level as i32),
+        }
+    }
+}
+
+/// A formatted chunk compressed by `Compress`, with the description of the chunk before the compression.
+#[derive(Clone, Debug)]
+pub struct CompressedChunk {
+    frame: Vec<u8>,
+    permutations: usize,
+    uncompressed_bytes: usize,
+    crc32: u32,
+}
+
+impl CompressedChunk {
+    /// Get the compressed chunk.
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+    /// Get the number of permutations of the chunk.
+    pub fn permutations(&self) -> usize {
+        self.permutations
+    }
+    /// Get the size of the chunk before the compression.
+    pub fn uncompressed_bytes(&self) -> usize {
+        self.uncompressed_bytes
+    }
+}
+
+impl AsRef<[u8]> for CompressedChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.frame
+    }
+}
+
+/// Compress each formatted chunk as an independent member (frame) of a `Codec`.
+#[derive(Copy, Clone, Debug)]
+pub struct Compress {
+    codec: Codec,
+    level: u32,
+}
+
+impl Compress {
+    /// Initialize the `Compress` transform with the default level of the `codec`.
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            level: codec.default_level(),
+        }
+    }
+    /// Set the compression level: the higher, the smaller and the slower.
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level;
+        self
+    }
+    /// Get the codec of the chunks.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+    /// Get the compression level.
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+}
+
+impl<I: AsRef<str>> Transform<I> for Compress {
+    type Output = CompressedChunk;
+
+    fn transform(&self, text: I) -> Option<CompressedChunk> {
+        let text = text.as_ref();
+        Some(CompressedChunk {
+            frame: self
+                .codec
+                .compress(text.as_bytes(), self.level)
+                .expect("Compressing to memory does not fail"),
+            permutations: Records::new(text).count(),
+            uncompressed_bytes: text.len(),
+            crc32: crc32fast::hash(text.as_bytes()),
+        })
+    }
+}
+
+/// Record the chunk before the compression, and the byte range of its frame.
+impl<S: Sink<CompressedChunk>> Sink<CompressedChunk> for ManifestSink<S> {
+    fn write(&mut self, chunk: CompressedChunk) -> io::Result<()> {
+        let entry = self.manifest().next_chunk_of(
+            chunk.permutations,
+            chunk.uncompressed_bytes,
+            chunk.crc32,
+        );
+        let frame_bytes = chunk.frame.len();
+        self.write_entry(chunk, entry, Some(frame_bytes))
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner_mut().finish()
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        self.inner().bytes_measured()
+    }
+
+    fn skipped(&mut self) {
+        self.skip_failed();
+        self.inner_mut().skipped()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+    use perm_core::{OptimizedChunk, Permutations};
+
+    use super::*;
+    use crate::pipeline::{Encode, Pipeline, WriterSink};
+
+    fn compressed(codec: Codec) -> (Vec<u8>, crate::pipeline::Manifest) {
+        let mut sink = ManifestSink::new(WriterSink::new(vec![]));
+        Pipeline::new()
+            .with_workers(3)
+            .run(
+                Permutations::new((0..5).collect::<Vec<i32>>()).into_optimized_chunks(7),
+                Transform::<OptimizedChunk<i32>>::then(Encode::default(), Compress::new(codec)),
+                &mut sink,
+            )
+            .unwrap();
+        let (sink, manifest) = sink.into_parts();
+        (sink.into_inner(), manifest)
+    }
+
+    #[test]
+    fn chunks_are_compressed_as_independent_frames() {
+        let expected = Permutations::new((0..5).collect())
+            .into_optimized_chunks(7)
+            .map(|chunk| chunk.to_string())
+            .collect::<String>();
+        let (output, manifest) = compressed(Codec::Gzip);
+        let mut text = String::new();
+        MultiGzDecoder::new(output.as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, expected);
+        assert!(manifest.verify(text.as_bytes()));
+        assert_eq!(manifest.frames.len(), 120 / 7 + 1);
+        assert_eq!(manifest.chunks.len(), manifest.frames.len());
+
+        let (output, manifest) = compressed(Codec::Zstd);
+        assert_eq!(
+            zstd::decode_all(output.as_slice()).unwrap(),
+            expected.as_bytes()
+        );
+        // each frame is decompressed on its own.
+        let frame = manifest.frames[3];
+        let chunk = &manifest.chunks[3];
+        assert_eq!(
+            zstd::decode_all(&output[frame.byte_offset..frame.byte_offset + frame.bytes]).unwrap(),
+            &expected.as_bytes()[chunk.byte_offset..chunk.byte_offset + chunk.bytes]
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! # Framing
+//!
+//! A binary protocol to pass the chunks to another process, without formatting and parsing them as text.
+//!
+//! Each chunk is written as a frame: the length of the frame in bytes as a little-endian `u64`,
+//! followed by the `bincode` encoding of the chunk header (its index, the rank of its first permutation,
+//! the number of permutations and their length) and of its values, permutation after permutation.
+//!
+//! In a `Pipeline`, the `EncodeFrame` transform encodes the values on the worker threads,
+//! and the `FrameSink` numbers the chunks and writes the frames in generation order.
+//! `ChunkReader` reads the frames back as `Chunk`s: the last chunk is the one followed by the end of the stream.
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::{Sink, Transform};
+use perm_core::{Chunk, ChunkView};
+
+/// The metadata of a chunk, at the start of its frame.
+#[derive(Serialize, Deserialize)]
+struct FrameHeader {
+    index: u64,
+    first_rank: u64,
+    permutations: u64,
+    permutation_length: u64,
+}
+
+/// The values of a chunk encoded by `EncodeFrame`, waiting for their header.
+#[derive(Clone)]
+pub struct EncodedChunk {
+    permutations: usize,
+    permutation_length: usize,
+    values: Vec<u8>,
+}
+
+/// Encode the values of a chunk with `bincode`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EncodeFrame;
+
+impl<C> Transform<C> for EncodeFrame
+where
+    C: ChunkView,
+    C::Value: Serialize,
+{
+    type Output = EncodedChunk;
+
+    fn transform(&self, chunk: C) -> Option<EncodedChunk> {
+        let (permutations, permutation_length) =
+            (chunk.permutations_count(), chunk.permutation_length());
+        let values = (0..permutations)
+            .flat_map(|index| (0..permutation_length).map(move |position| (index, position)))
+            .map(|(index, position)| chunk.value(index, position))
+            .collect::<Vec<&C::Value>>();
+        Some(EncodedChunk {
+            permutations,
+            permutation_length,
+            values: bincode::serialize(&values).expect("The values are serializable"),
+        })
+    }
+}
+
+/// Write the encoded chunks as frames to an `io::Write`.
+pub struct FrameSink<W> {
+    writer: W,
+    chunks_written: usize,
+    permutations_written: usize,
+    bytes_written: usize,
+}
+
+impl<W: Write> FrameSink<W> {
+    /// Initialize the `FrameSink` with the underlying `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            chunks_written: 0,
+            permutations_written: 0,
+            bytes_written: 0,
+        }
+    }
+    /// Get the number of bytes written so far.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+}
+
+impl<W: Write + Send> Sink<EncodedChunk> for FrameSink<W> {
+    fn write(&mut self, chunk: EncodedChunk) -> io::Result<()> {
+        let header = bincode::serialize(&FrameHeader {
+            index: self.chunks_written as u64,
+            first_rank: self.permutations_written as u64,
+            permutations: chunk.permutations as u64,
+            permutation_length: chunk.permutation_length as u64,
+        })
+        .expect("The header is serializable");
+        let length = header.len() + chunk.values.len();
+        self.writer.write_all(&(length as u64).to_le_bytes())?;
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&chunk.values)?;
+        self.chunks_written += 1;
+        self.permutations_written += chunk.permutations;
+        self.bytes_written += LENGTH_BYTES + length;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        Some(self.bytes_written)
+    }
+}
+
+/// The size of the length prefix of a frame.
+const LENGTH_BYTES: usize = 8;
+
+/// Iterator over the chunks of a stream of frames.
+pub struct ChunkReader<R, T> {
+    reader: R,
+    /// The length of the next frame, read ahead to know if the current chunk is the last one.
+    next_length: Option<usize>,
+    started: bool,
+    values: PhantomData<T>,
+}
+
+impl<R: Read, T: DeserializeOwned> ChunkReader<R, T> {
+    /// Initialize the `ChunkReader` with the underlying `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            next_length: None,
+            started: false,
+            values: PhantomData,
+        }
+    }
+
+    /// Read the length of the next frame, or `None` at the end of the stream.
+    /// A stream ending inside the length is truncated, not ended.
+    fn read_length(&mut self) -> io::Result<Option<usize>> {
+        let mut length = [0; LENGTH_BYTES];
+        let mut filled = 0;
+        while filled < LENGTH_BYTES {
+            match self.reader.read(&mut length[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the stream ends inside the length of a frame",
+                    ))
+                }
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        usize::try_from(u64::from_le_bytes(length))
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn read_chunk(&mut self, length: usize) -> io::Result<Chunk<T>> {
+        // the length comes from the stream: the frame grows as its bytes are read,
+        // instead of allocating a corrupt length upfront.
+        let mut frame = vec![];
+        self.reader
+            .by_ref()
+            .take(length as u64)
+            .read_to_end(&mut frame)?;
+        if frame.len() < length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the stream ends inside a frame",
+            ));
+        }
+        let (header, values): (FrameHeader, Vec<T>) = bincode::deserialize(&frame)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let permutation_length = header.permutation_length as usize;
+        if header.permutations.checked_mul(header.permutation_length) != Some(values.len() as u64) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the number of values does not match the frame header",
+            ));
+        }
+        let mut values = values.into_iter();
+        let permutations = (0..header.permutations)
+            .map(|_| values.by_ref().take(permutation_length).collect())
+            .collect();
+        self.next_length = self.read_length()?;
+        Ok(Chunk::from_permutations(
+            permutations,
+            header.index as usize,
+            header.first_rank as usize,
+            self.next_length.is_none(),
+        ))
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for ChunkReader<R, T> {
+    type Item = io::Result<Chunk<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            match self.read_length() {
+                Ok(length) => self.next_length = length,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let length = self.next_length.take()?;
+        Some(self.read_chunk(length))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use perm_core::Permutations;
+
+    use crate::pipeline::Pipeline;
+
+    use super::*;
+
+    #[test]
+    fn frames_are_read_back_as_chunks() {
+        let mut sink = FrameSink::new(vec![]);
+        let written = Pipeline::new()
+            .run(
+                Permutations::new(vec!["a", "b", "c"]).into_chunks(4),
+                EncodeFrame,
+                &mut sink,
+            )
+            .unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(sink.bytes_written(), sink.writer.len());
+
+        let chunks = ChunkReader::<_, String>::new(sink.writer.as_slice())
+            .collect::<io::Result<Vec<Chunk<String>>>>()
+            .unwrap();
+        let positions = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.index(),
+                    chunk.first_rank(),
+                    chunk.len(),
+                    chunk.is_last(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(0, 0, 4, false), (1, 4, 2, true)]);
+        assert_eq!(chunks[1].to_string(), "c,a,b\nc,b,a\n");
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let mut sink = FrameSink::new(vec![]);
+        Pipeline::new()
+            .run(
+                Permutations::new(vec![1, 2, 3]).into_chunks(6),
+                EncodeFrame,
+                &mut sink,
+            )
+            .unwrap();
+        let truncated = &sink.writer[..sink.writer.len() - 1];
+        let mut chunks = ChunkReader::<_, i32>::new(truncated);
+        assert!(chunks.next().unwrap().is_err());
+        // a corrupt length is not allocated.
+        let mut corrupt = (u64::MAX >> 1).to_le_bytes().to_vec();
+        corrupt.extend_from_slice(&[0; 16]);
+        let mut chunks = ChunkReader::<_, i32>::new(corrupt.as_slice());
+        assert!(chunks.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn stream_cut_inside_a_length_is_truncated() {
+        let mut sink = FrameSink::new(vec![]);
+        Pipeline::new()
+            .run(
+                Permutations::new(vec![1, 2, 3]).into_chunks(4),
+                EncodeFrame,
+                &mut sink,
+            )
+            .unwrap();
+        let first_frame = LENGTH_BYTES
+            + u64::from_le_bytes(sink.writer[..LENGTH_BYTES].try_into().unwrap()) as usize;
+        // the first frame is followed by 3 bytes of the length of the second one.
+        let truncated = &sink.writer[..first_frame + 3];
+        let mut chunks = ChunkReader::<_, i32>::new(truncated);
+        let error = chunks.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(ChunkReader::<_, i32>::new(&sink.writer[..first_frame])
+            .next()
+            .unwrap()
+            .unwrap()
+            .is_last());
+    }
+}
@@ -0,0 +1,202 @@
+//! # gRPC
+//!
+//! `PermutationService` streams the chunks over gRPC, as typed messages:
+//! `GeneratePermutations(stream GenerateRequest) -> stream Chunk`, described in `proto/perm.proto`.
+//!
+//! The requests of a stream are generated one at a time, in order,
+//! each one from its `start_rank` and up to its `limit`, with the ranked iterator.
+//! The generation runs on a blocking thread and waits when `QUEUED_CHUNKS` chunks are not yet sent,
+//! so a slow client slows down the generation instead of filling the memory,
+//! and it stops when the client goes away.
+//!
+//! The messages are written by hand with `prost`, and the server and client stubs
+//! (`permutation_generator_server` and `permutation_generator_client`) are generated by `build.rs`.
+//! The client is built on a `tonic::transport::Channel` connected to the server.
+use std::convert::TryFrom;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use perm_core::{ChunkView, Permutations, MIN_SUGGESTED_CHUNK_SIZE};
+
+include!(concat!(env!("OUT_DIR"), "/perm.PermutationGenerator.rs"));
+
+pub use permutation_generator_client::PermutationGeneratorClient;
+pub use permutation_generator_server::{PermutationGenerator, PermutationGeneratorServer};
+
+/// The number of chunks generated ahead of the client.
+const QUEUED_CHUNKS: usize = 4;
+
+/// A request to generate the permutations of some values.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GenerateRequest {
+    /// The values to permute.
+    #[prost(string, repeated, tag = "1")]
+    pub values: Vec<String>,
+    /// The number of permutations of each chunk: `MIN_SUGGESTED_CHUNK_SIZE` if zero.
+    #[prost(uint64, tag = "2")]
+    pub chunk_size: u64,
+    /// The rank of the first permutation.
+    #[prost(uint64, tag = "3")]
+    pub start_rank: u64,
+    /// The maximum number of permutations, or all of them.
+    #[prost(uint64, optional, tag = "4")]
+    pub limit: Option<u64>,
+}
+
+/// A permutation of the values of a `GenerateRequest`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Permutation {
+    #[prost(string, repeated, tag = "1")]
+    pub values: Vec<String>,
+}
+
+/// A chunk of the permutations of a `GenerateRequest`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Chunk {
+    /// The sequence number of the chunk in its request, starting from zero.
+    #[prost(uint64, tag = "1")]
+    pub index: u64,
+    /// The rank of the first permutation of the chunk.
+    #[prost(uint64, tag = "2")]
+    pub first_rank: u64,
+    #[prost(message, repeated, tag = "3")]
+    pub permutations: Vec<Permutation>,
+    /// Check if this is the last chunk of its request.
+    #[prost(bool, tag = "4")]
+    pub is_last: bool,
+}
+
+/// The `PermutationGenerator` service: serve it with `PermutationGeneratorServer::new(PermutationService)`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PermutationService;
+
+#[tonic::async_trait]
+impl PermutationGenerator for PermutationService {
+    type GeneratePermutationsStream = ReceiverStream<Result<Chunk, Status>>;
+
+    async fn generate_permutations(
+        &self,
+        request: Request<Streaming<GenerateRequest>>,
+    ) -> Result<Response<Self::GeneratePermutationsStream>, Status> {
+        let mut requests = request.into_inner();
+        let (sender, receiver) = mpsc::channel(QUEUED_CHUNKS);
+        tokio::spawn(async move {
+            loop {
+                let request = match requests.message().await {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(status) => {
+                        let _ = sender.send(Err(status)).await;
+                        break;
+                    }
+                };
+                let sender = sender.clone();
+                let generated =
+                    tokio::task::spawn_blocking(move || send_chunks(request, &sender)).await;
+                if !matches!(generated, Ok(true)) {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Generate the chunks of the `request` and send them to the client, waiting while `QUEUED_CHUNKS` are queued.
+/// Return `false` if the request is not valid or the client has gone away.
+fn send_chunks(request: GenerateRequest, sender: &mpsc::Sender<Result<Chunk, Status>>) -> bool {
+    if request.values.is_empty() {
+        let _ = sender.blocking_send(Err(Status::invalid_argument("the values are required")));
+        return false;
+    }
+    let size = match request.chunk_size {
+        0 => MIN_SUGGESTED_CHUNK_SIZE,
+        size => usize::try_from(size).unwrap_or(usize::MAX),
+    };
+    let values = request.values.iter().map(String::as_str).collect();
+    let mut chunks = Permutations::new(values)
+        .into_ranked_chunks(size)
+        .starting_at(usize::try_from(request.start_rank).unwrap_or(usize::MAX));
+    if let Some(limit) = request.limit {
+        chunks = chunks.with_limit(usize::try_from(limit).unwrap_or(usize::MAX));
+    }
+    for chunk in chunks {
+        let message = Chunk {
+            index: chunk.index() as u64,
+            first_rank: chunk.first_rank() as u64,
+            permutations: (0..chunk.permutations_count())
+                .map(|index| Permutation {
+                    values: (0..chunk.permutation_length())
+                        .map(|position| chunk.value(index, position).to_string())
+                        .collect(),
+                })
+                .collect(),
+            is_last: chunk.is_last(),
+        };
+        if sender.blocking_send(Ok(message)).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, Server};
+
+    use super::*;
+
+    fn request(values: &[&str], chunk_size: u64, start_rank: u64) -> GenerateRequest {
+        GenerateRequest {
+            values: values.iter().map(|value| value.to_string()).collect(),
+            chunk_size,
+            start_rank,
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_streamed_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(PermutationGeneratorServer::new(PermutationService))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let channel = Channel::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = PermutationGeneratorClient::new(channel);
+
+        let limited = GenerateRequest {
+            limit: Some(1),
+            ..request(&["a", "b"], 0, 1)
+        };
+        let requests = tokio_stream::iter(vec![request(&["1", "2", "3"], 4, 0), limited]);
+        let mut chunks = client
+            .generate_permutations(requests)
+            .await
+            .unwrap()
+            .into_inner();
+        let mut received = vec![];
+        while let Some(chunk) = chunks.message().await.unwrap() {
+            received.push((
+                chunk.index,
+                chunk.first_rank,
+                chunk.permutations.len(),
+                chunk.is_last,
+            ));
+        }
+        assert_eq!(
+            received,
+            vec![(0, 0, 4, false), (1, 4, 2, true), (0, 1, 1, true)]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! # Perm formats
+//!
+//! Write the chunks generated by `perm-core` on many threads, to files, sinks and columnar formats.
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod framing;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod pipeline;
+pub mod raw;
+pub mod reader;
+mod records;
+#[cfg(feature = "stream")]
+pub mod stream;
@@ -0,0 +1,621 @@
+//! # Pipeline
+//!
+//! `Pipeline` overlaps the generation of the chunks with their processing and writing.
+//!
+//! It is composed of three stages: `Source -> Transform -> Sink`.
+//!
+//! - the source is any iterator over chunks, consumed sequentially on the calling thread.
+//! - the `Transform` is applied in parallel by a fixed number of worker threads.
+//!   It can map or filter the chunks, and it is usually terminated by `Encode` to format them
+//!   (or by `EncodeBytes`, for the sinks writing bytes).
+//! - the `Sink` receives the transformed chunks on a single writer thread.
+//!   The writer reorders them, so that the sink receives them in generation order
+//!   and the output does not depend on the scheduling of the workers.
+//!
+//! The number of chunks waiting to be transformed is bounded,
+//! so that a slow sink does not make the generation fill the memory.
+//!
+//! With a `CancellationToken` the pipeline stops taking chunks from the source when it is cancelled:
+//! the workers drop the chunks not yet transformed, and the sink receives the chunks before the first dropped one,
+//! so the output is always a prefix of the generation.
+//!
+//! With `Metrics` the pipeline counts the chunks and the time spent in each stage while it runs,
+//! so that the embedding application can report the throughput.
+//! When the sink fails to write a chunk, the `ErrorPolicy` aborts the run (the default),
+//! retries the chunk with an exponential backoff, or skips it and goes on with the next one.
+//!
+//! With an `Observer` it is notified of each chunk generated and written, and of the completion of the run.
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crossbeam::channel;
+
+pub use manifest::{
+    ChunkEntry, ChunkPart, ContentHasher, FileEntry, FrameEntry, Manifest, ManifestSink,
+};
+pub use metrics::{Metrics, MetricsSnapshot};
+#[cfg(feature = "nats")]
+pub use nats::{NatsSink, PublishEach};
+pub use observer::{Observer, Summary};
+pub use ordered::ParallelOrderedPipeline;
+use perm_core::format::{Formatted, OutputFormat};
+use perm_core::CancellationToken;
+#[cfg(feature = "numa")]
+pub use pinned::PinnedPipeline;
+pub use sharded::{ShardedSink, SplitBy, SHARD_PLACEHOLDER};
+pub use sink::{ErrorPolicy, FlushPolicy, Sink, WriterSink};
+pub use transform::{Encode, EncodeBytes, Then, Transform};
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub use uring::{UringFileSink, DEFAULT_URING_BUFFERS, DEFAULT_URING_BUFFER_SIZE};
+
+mod manifest;
+mod metrics;
+#[cfg(feature = "nats")]
+mod nats;
+mod observer;
+mod ordered;
+#[cfg(feature = "numa")]
+mod pinned;
+mod sharded;
+mod sink;
+mod transform;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+
+/// The number of chunks each worker can have waiting to be transformed.
+const QUEUED_CHUNKS_PER_WORKER: usize = 2;
+
+/// Generate, transform and write chunks of permutations with a pool of worker threads.
+#[derive(Clone)]
+pub struct Pipeline {
+    workers: usize,
+    cancellation: Option<CancellationToken>,
+    metrics: Option<Metrics>,
+    observer: Option<Arc<dyn Observer>>,
+    error_policy: ErrorPolicy,
+}
+
+impl Debug for Pipeline {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Pipeline")
+            .field("workers", &self.workers)
+            .field("cancellation", &self.cancellation)
+            .field("metrics", &self.metrics)
+            .field("observer", &self.observer.is_some())
+            .field("error_policy", &self.error_policy)
+            .finish()
+    }
+}
+
+/// What has been written by `Pipeline::write`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Written {
+    /// the number of chunks written.
+    pub chunks: usize,
+    /// the number of bytes written.
+    pub bytes: usize,
+}
+
+/// The default `Pipeline` has one worker for each available CPU.
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            cancellation: None,
+            metrics: None,
+            observer: None,
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+}
+
+impl Pipeline {
+    /// Initialize a `Pipeline` with one worker for each available CPU.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the number of worker threads transforming the chunks.
+    /// Panics if `workers` is zero.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        if workers == 0 {
+            panic!("Pipeline workers must be at least one")
+        }
+        self.workers = workers;
+        self
+    }
+    /// Get the number of worker threads transforming the chunks.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+    /// Stop the generation when `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+    /// Update `metrics` while the pipeline runs.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    /// Get the `Metrics` updated by the pipeline, if any.
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+    /// Set what to do when the sink fails to write a chunk.
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+    /// Get what is done when the sink fails to write a chunk.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+    /// Notify `observer` of the progress of each run.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Format each chunk with `format` and write it to `writer` in generation order.
+    /// The generation stops at the first error writing a chunk.
+    pub fn write<C, W>(
+        &self,
+        chunks: impl IntoIterator<Item = C>,
+        format: OutputFormat,
+        writer: W,
+    ) -> io::Result<Written>
+    where
+        C: Send,
+        for<'a> Formatted<'a, C>: Display,
+        W: Write + Send,
+    {
+        let mut sink = WriterSink::new(writer);
+        let chunks = self.run(chunks, Encode::new(format), &mut sink)?;
+        Ok(Written {
+            chunks,
+            bytes: sink.bytes_written(),
+        })
+    }
+
+    /// Apply `transform` to each chunk of the `source` on the worker threads,
+    /// and write the transformed chunks to the `sink` in generation order.
+    /// The chunks filtered out by the `transform` are skipped.
+    /// The generation stops at the first error of the `sink` the `ErrorPolicy` gives up on, or when it is cancelled.
+    /// The transformed chunks are cloned to be written again by `ErrorPolicy::Retry`.
+    /// Return the number of chunks written to the `sink`, also reported to the `Observer`, if any.
+    pub fn run<C, T, S>(
+        &self,
+        source: impl IntoIterator<Item = C>,
+        transform: T,
+        sink: &mut S,
+    ) -> io::Result<usize>
+    where
+        C: Send,
+        T: Transform<C>,
+        T::Output: Clone,
+        S: Sink<T::Output>,
+    {
+        let (chunk_sender, chunk_receiver) =
+            channel::bounded::<(usize, C)>(self.workers * QUEUED_CHUNKS_PER_WORKER);
+        let (output_sender, output_receiver) = channel::unbounded();
+        let transform = &transform;
+        let cancellation = &self.cancellation;
+        let metrics = &self.metrics;
+        let observer = &self.observer;
+        let error_policy = self.error_policy;
+        let run_start = Instant::now();
+
+        crossbeam::scope(|scope| {
+            for _ in 0..self.workers {
+                let chunk_receiver = chunk_receiver.clone();
+                let output_sender = output_sender.clone();
+                scope.spawn(move |_| {
+                    for (index, chunk) in chunk_receiver.iter() {
+                        if is_cancelled(cancellation) {
+                            // the writer stops at the first missing index.
+                            break;
+                        }
+                        let start = Instant::now();
+                        let output = transform.transform(chunk);
+                        if let Some(metrics) = metrics {
+                            metrics.record_transformed(start.elapsed());
+                            metrics.record_queue_depth(chunk_receiver.len());
+                        }
+                        if output_sender.send((index, output)).is_err() {
+                            // the sink has failed.
+                            break;
+                        }
+                    }
+                });
+            }
+            // the workers own the remaining endpoints:
+            // the writer terminates when all the workers have terminated.
+            drop(chunk_receiver);
+            drop(output_sender);
+
+            let writer = scope.spawn(move |_| {
+                write_in_order(output_receiver, sink, error_policy, metrics, observer)
+            });
+
+            let mut source = source.into_iter().enumerate();
+            let mut chunks_generated = 0;
+            loop {
+                let start = Instant::now();
+                let chunk = match source.next() {
+                    Some(chunk) => chunk,
+                    None => break,
+                };
+                if let Some(metrics) = metrics {
+                    metrics.record_generated(start.elapsed());
+                }
+                if let Some(observer) = observer {
+                    observer.on_chunk_generated(chunk.0);
+                }
+                chunks_generated += 1;
+                if is_cancelled(cancellation) || chunk_sender.send(chunk).is_err() {
+                    // the workers have stopped because the sink has failed.
+                    break;
+                }
+                if let Some(metrics) = metrics {
+                    metrics.record_queue_depth(chunk_sender.len());
+                }
+            }
+            drop(chunk_sender);
+
+            let written = writer
+                .join()
+                .expect("Error waiting the writer to terminate");
+            // the chunks not taken by the workers have been dropped.
+            if let Some(metrics) = metrics {
+                metrics.record_queue_depth(0);
+            }
+            if let (Ok(chunks_written), Some(observer)) = (&written, observer) {
+                observer.on_complete(&Summary {
+                    chunks_generated,
+                    chunks_written: *chunks_written,
+                    cancelled: is_cancelled(cancellation),
+                    elapsed: run_start.elapsed(),
+                });
+            }
+            written
+        })
+        .expect("Error running the pipeline")
+    }
+}
+
+/// Write the outputs received from the workers to the `sink` in the order of their index.
+/// The outputs received ahead of their turn are buffered until the missing ones arrive.
+/// The outputs skipped by the `error_policy` are not counted as written.
+fn write_in_order<O: Clone, S: Sink<O>>(
+    outputs: channel::Receiver<(usize, Option<O>)>,
+    sink: &mut S,
+    error_policy: ErrorPolicy,
+    metrics: &Option<Metrics>,
+    observer: &Option<Arc<dyn Observer>>,
+) -> io::Result<usize> {
+    let mut next_index = 0;
+    let mut written = 0;
+    let mut pending = BTreeMap::new();
+    let mut bytes_measured = sink.bytes_measured().unwrap_or(0);
+    for (index, output) in outputs {
+        pending.insert(index, output);
+        while let Some(output) = pending.remove(&next_index) {
+            if let Some(output) = output {
+                let start = Instant::now();
+                if write_with_policy(sink, output, error_policy)? {
+                    written += 1;
+                    let chunk_bytes = sink.bytes_measured().map(|bytes| {
+                        let chunk_bytes = bytes.saturating_sub(bytes_measured);
+                        bytes_measured = bytes;
+                        chunk_bytes
+                    });
+                    if let Some(metrics) = metrics {
+                        metrics.record_emitted(start.elapsed(), chunk_bytes.unwrap_or(0));
+                    }
+                    if let Some(observer) = observer {
+                        observer.on_chunk_written(next_index, chunk_bytes);
+                    }
+                }
+            }
+            next_index += 1;
+        }
+    }
+    sink.finish()?;
+    Ok(written)
+}
+
+/// Write the `output` to the `sink`, handling the failures with the `error_policy`.
+/// Return whether the output has been written, or skipped.
+fn write_with_policy<O: Clone, S: Sink<O>>(
+    sink: &mut S,
+    output: O,
+    error_policy: ErrorPolicy,
+) -> io::Result<bool> {
+    if let ErrorPolicy::Retry { retries, backoff } = error_policy {
+        for retry in 0..retries {
+            if sink.write(output.clone()).is_ok() {
+                return Ok(true);
+            }
+            thread::sleep(backoff.saturating_mul(2u32.saturating_pow(retry)));
+        }
+    }
+    match sink.write(output) {
+        Ok(()) => Ok(true),
+        Err(_) if error_policy == ErrorPolicy::SkipChunk => {
+            sink.skipped();
+            Ok(false)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Check if the optional `cancellation` token has been cancelled.
+fn is_cancelled(cancellation: &Option<CancellationToken>) -> bool {
+    cancellation
+        .as_ref()
+        .is_some_and(CancellationToken::is_cancelled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use perm_core::{OptimizedChunk, Permutations};
+
+    use super::*;
+
+    #[test]
+    fn chunks_are_written_in_generation_order() {
+        let expected = Permutations::new((0..6).collect())
+            .into_optimized_chunks(7)
+            .map(|c| c.to_string())
+            .collect::<String>();
+
+        let mut output = vec![];
+        let written = Pipeline::new()
+            .with_workers(3)
+            .write(
+                Permutations::new((0..6).collect()).into_optimized_chunks(7),
+                OutputFormat::default(),
+                &mut output,
+            )
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        assert_eq!(written.chunks, 720 / 7 + 1);
+        assert_eq!(written.bytes, expected.len());
+    }
+
+    #[test]
+    fn chunks_are_encoded_to_bytes() {
+        let format = OutputFormat::new().with_quoting(perm_core::Quoting::Necessary);
+        let expected = Permutations::new(vec!["a", "b c", "a"])
+            .into_chunks(2)
+            .map(|chunk| chunk.formatted(format).to_string())
+            .collect::<String>();
+        let mut sink = WriterSink::new(vec![]);
+        let written = Pipeline::new()
+            .with_workers(2)
+            .run(
+                Permutations::new(vec!["a", "b c", "a"]).into_chunks(2),
+                EncodeBytes::new(format),
+                &mut sink,
+            )
+            .unwrap();
+
+        assert_eq!(written, 2);
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(), expected);
+    }
+
+    #[test]
+    fn filtered_chunks_are_skipped() {
+        let keep_short_chunks = |chunk: OptimizedChunk<i32>| {
+            if chunk.len() < 7 {
+                Some(chunk)
+            } else {
+                None
+            }
+        };
+        let mut sink = WriterSink::new(vec![]);
+        let written = Pipeline::new()
+            .with_workers(2)
+            .run(
+                Permutations::new((0..6).collect()).into_optimized_chunks(7),
+                keep_short_chunks.then(Encode::default()),
+                &mut sink,
+            )
+            .unwrap();
+
+        assert_eq!(written, 1);
+        assert_eq!(
+            String::from_utf8(sink.into_inner())
+                .unwrap()
+                .lines()
+                .count(),
+            720 % 7
+        );
+    }
+
+    #[test]
+    fn cancelled_pipeline_writes_a_prefix() {
+        let expected = Permutations::new((0..6).collect())
+            .into_optimized_chunks(7)
+            .map(|c| c.to_string())
+            .collect::<String>();
+        let token = CancellationToken::new();
+        let chunks = Permutations::new((0..6).collect())
+            .into_optimized_chunks(7)
+            .enumerate()
+            .map(|(index, chunk)| {
+                if index == 3 {
+                    token.cancel();
+                }
+                chunk
+            });
+
+        let mut output = vec![];
+        let written = Pipeline::new()
+            .with_workers(2)
+            .with_cancellation(token.clone())
+            .write(chunks, OutputFormat::default(), &mut output)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(written.chunks <= 3);
+        assert_eq!(output.lines().count(), written.chunks * 7);
+        assert!(expected.starts_with(&output));
+    }
+
+    #[test]
+    fn metrics_are_updated() {
+        let metrics = Metrics::new();
+        let mut output = vec![];
+        let written = Pipeline::new()
+            .with_workers(3)
+            .with_metrics(metrics.clone())
+            .write(
+                metrics.counting(Permutations::new((0..6).collect()).into_optimized_chunks(7)),
+                OutputFormat::default(),
+                &mut output,
+            )
+            .unwrap();
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.permutations_generated, 720);
+        assert_eq!(snapshot.chunks_generated, written.chunks);
+        assert_eq!(snapshot.chunks_emitted, written.chunks);
+        assert_eq!(snapshot.bytes_written, output.len());
+        assert_eq!(snapshot.queue_depth, 0);
+    }
+
+    /// Record the notifications of a run.
+    #[derive(Default)]
+    struct Recorder {
+        generated: Mutex<Vec<usize>>,
+        written: Mutex<Vec<(usize, Option<usize>)>>,
+        summaries: Mutex<Vec<Summary>>,
+    }
+
+    impl Observer for Recorder {
+        fn on_chunk_generated(&self, index: usize) {
+            self.generated.lock().unwrap().push(index);
+        }
+        fn on_chunk_written(&self, index: usize, bytes: Option<usize>) {
+            self.written.lock().unwrap().push((index, bytes));
+        }
+        fn on_complete(&self, summary: &Summary) {
+            self.summaries.lock().unwrap().push(*summary);
+        }
+    }
+
+    #[test]
+    fn observer_is_notified_of_each_chunk() {
+        let recorder = Arc::new(Recorder::default());
+        let keep_even_chunks = |(index, chunk): (usize, OptimizedChunk<i32>)| {
+            if index % 2 == 0 {
+                Some(chunk)
+            } else {
+                None
+            }
+        };
+        let mut sink = WriterSink::new(vec![]);
+        let written = Pipeline::new()
+            .with_workers(2)
+            .with_observer(recorder.clone())
+            .run(
+                Permutations::new((0..4).collect())
+                    .into_optimized_chunks(5)
+                    .enumerate(),
+                keep_even_chunks.then(Encode::default()),
+                &mut sink,
+            )
+            .unwrap();
+
+        assert_eq!(*recorder.generated.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            *recorder.written.lock().unwrap(),
+            vec![(0, Some(40)), (2, Some(40)), (4, Some(32))]
+        );
+        let summaries = recorder.summaries.lock().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].chunks_generated, 5);
+        assert_eq!(summaries[0].chunks_written, written);
+        assert!(!summaries[0].cancelled);
+    }
+
+    /// Fail to write the chunks starting with `prefix`, `failures` times.
+    struct Flaky {
+        inner: WriterSink<Vec<u8>>,
+        prefix: &'static str,
+        failures: usize,
+    }
+
+    impl Sink<String> for Flaky {
+        fn write(&mut self, item: String) -> io::Result<()> {
+            if item.starts_with(self.prefix) && self.failures > 0 {
+                self.failures -= 1;
+                return Err(io::Error::other("flaky sink"));
+            }
+            self.inner.write(item)
+        }
+        fn finish(&mut self) -> io::Result<()> {
+            Sink::<String>::finish(&mut self.inner)
+        }
+        fn bytes_measured(&self) -> Option<usize> {
+            Sink::<String>::bytes_measured(&self.inner)
+        }
+    }
+
+    #[test]
+    fn failed_writes_follow_the_error_policy() {
+        // the second chunk of 5 permutations starts with the permutation of rank 5.
+        let flaky = |failures| Flaky {
+            inner: WriterSink::new(vec![]),
+            prefix: "0,3,2,1\n",
+            failures,
+        };
+        let run = |error_policy, sink: &mut ManifestSink<Flaky>| {
+            Pipeline::new()
+                .with_workers(2)
+                .with_error_policy(error_policy)
+                .run(
+                    Permutations::new((0..4).collect()).into_optimized_chunks(5),
+                    Encode::default(),
+                    sink,
+                )
+        };
+        let retry = |retries| ErrorPolicy::Retry {
+            retries,
+            backoff: Duration::from_millis(1),
+        };
+
+        let mut sink = ManifestSink::new(flaky(1));
+        assert!(run(ErrorPolicy::Abort, &mut sink).is_err());
+        let mut sink = ManifestSink::new(flaky(3));
+        assert!(run(retry(2), &mut sink).is_err());
+
+        let mut sink = ManifestSink::new(flaky(2));
+        assert_eq!(run(retry(2), &mut sink).unwrap(), 5);
+        assert_eq!(sink.manifest().permutations(), 24);
+
+        let mut sink = ManifestSink::new(flaky(usize::MAX));
+        assert_eq!(run(ErrorPolicy::SkipChunk, &mut sink).unwrap(), 4);
+        let (flaky, manifest) = sink.into_parts();
+        let output = flaky.inner.into_inner();
+        assert_eq!(manifest.failed_ranks, vec![5..10]);
+        assert_eq!(manifest.chunks[1].first_rank, 10);
+        assert!(manifest.verify(&output));
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 19);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_workers_are_rejected() {
+        Pipeline::new().with_workers(0);
+    }
+}
@@ -0,0 +1,471 @@
+//! # Manifest
+//!
+//! `Manifest` describes the output of a run, to verify its integrity
+//! and to access it randomly without scanning it.
+//!
+//! For each chunk it records the rank of its first permutation, the number of permutations,
+//! its byte range in the output and its CRC-32 checksum.
+//! When the output is sharded across many files, it also records the size and the checksum of each file,
+//! and for each chunk the files and the byte ranges in them it has been written to (`ChunkPart`).
+//!
+//! The manifest can also record the `ContentHasher` hash of the input values and of the options of the run,
+//! so that a later run with the same hash can tell that the output has already been generated.
+//!
+//! `ManifestSink` wraps another `Sink` and fills the `Manifest` with the formatted chunks it receives.
+//! When the chunks are compressed one by one (`compression::Compress`), the byte ranges of the chunks
+//! are the ones of the decompressed output, and each `FrameEntry` records the byte range of the compressed chunk,
+//! so that the frames can be decompressed in parallel.
+//! The chunks skipped by `ErrorPolicy::SkipChunk` are recorded by the ranks of their permutations,
+//! so that they can be generated again later with `CompiledPermutations::range`.
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pipeline::Sink;
+use crate::records::Records;
+
+/// The description of the chunks and files of an output.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// the chunks, in generation order.
+    pub chunks: Vec<ChunkEntry>,
+    /// the files of a sharded output, sorted by path.
+    pub files: Vec<FileEntry>,
+    /// the `ContentHasher` hash of the input and of the options of the run, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_hash: Option<String>,
+    /// the ranks of the permutations of the chunks that could not be written, in generation order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_ranks: Vec<Range<usize>>,
+    /// the compressed chunks, in generation order, if the chunks are compressed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<FrameEntry>,
+}
+
+/// The description of a single chunk.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    /// the position of the chunk in generation order.
+    pub index: usize,
+    /// the rank of the first permutation of the chunk.
+    pub first_rank: usize,
+    /// the number of permutations of the chunk.
+    pub permutations: usize,
+    /// the offset of the chunk in the whole output, as if it was written to a single file.
+    pub byte_offset: usize,
+    /// the size of the formatted chunk.
+    pub bytes: usize,
+    /// the CRC-32 checksum of the formatted chunk.
+    pub crc32: u32,
+    /// the files and the byte ranges in them the chunk has been written to, in order, if the output is sharded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<ChunkPart>,
+}
+
+/// The part of a chunk written to a single file of a sharded output.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkPart {
+    /// the path of the file.
+    pub path: PathBuf,
+    /// the offset of the part in the file.
+    pub byte_offset: usize,
+    /// the size of the part.
+    pub bytes: usize,
+}
+
+/// The description of a single compressed chunk: a gzip member or a zstd frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameEntry {
+    /// the index of the chunk compressed.
+    pub index: usize,
+    /// the offset of the frame in the whole compressed output.
+    pub byte_offset: usize,
+    /// the size of the frame.
+    pub bytes: usize,
+}
+
+/// The description of a single output file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// the path of the file.
+    pub path: PathBuf,
+    /// the size of the file.
+    pub bytes: usize,
+    /// the CRC-32 checksum of the whole file.
+    pub crc32: u32,
+}
+
+impl Manifest {
+    /// Initialize an empty `Manifest`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Append the entry of the next formatted chunk.
+    pub fn push_chunk(&mut self, text: &str) -> &ChunkEntry {
+        let entry = self.next_chunk(text);
+        self.chunks.push(entry);
+        self.chunks.last().expect("The chunk has just been pushed")
+    }
+    /// Build the entry of the next formatted chunk, without appending it:
+    /// it follows the ranks of the chunks written and failed, and the bytes of the chunks written.
+    fn next_chunk(&self, text: &str) -> ChunkEntry {
+        self.next_chunk_of(
+            Records::new(text).count(),
+            text.len(),
+            crc32fast::hash(text.as_bytes()),
+        )
+    }
+    /// Build the entry of the next chunk from its number of permutations, its size and its checksum.
+    pub(crate) fn next_chunk_of(
+        &self,
+        permutations: usize,
+        bytes: usize,
+        crc32: u32,
+    ) -> ChunkEntry {
+        let (mut first_rank, byte_offset) = self.chunks.last().map_or((0, 0), |last| {
+            (
+                last.first_rank + last.permutations,
+                last.byte_offset + last.bytes,
+            )
+        });
+        if let Some(failed) = self.failed_ranks.last() {
+            first_rank = first_rank.max(failed.end);
+        }
+        ChunkEntry {
+            index: self.chunks.len() + self.failed_ranks.len(),
+            first_rank,
+            permutations,
+            byte_offset,
+            bytes,
+            crc32,
+            parts: vec![],
+        }
+    }
+    /// Get the total number of permutations of the chunks.
+    pub fn permutations(&self) -> usize {
+        self.chunks
+            .last()
+            .map_or(0, |last| last.first_rank + last.permutations)
+    }
+    /// Get the chunk containing the permutation of rank `rank`.
+    pub fn chunk_of_rank(&self, rank: usize) -> Option<&ChunkEntry> {
+        let index = self
+            .chunks
+            .partition_point(|chunk| chunk.first_rank + chunk.permutations <= rank);
+        self.chunks
+            .get(index)
+            .filter(|chunk| chunk.first_rank <= rank)
+    }
+    /// Check that `output` has the size and the checksums of the chunks.
+    /// Only meaningful for an output that is not sharded.
+    pub fn verify(&self, output: &[u8]) -> bool {
+        output.len()
+            == self
+                .chunks
+                .last()
+                .map_or(0, |last| last.byte_offset + last.bytes)
+            && self.chunks.iter().all(|chunk| {
+                crc32fast::hash(&output[chunk.byte_offset..chunk.byte_offset + chunk.bytes])
+                    == chunk.crc32
+            })
+    }
+    /// Check that the files of a sharded output have the sizes and the checksums of the `files`,
+    /// and that the parts of each chunk in them have its checksum.
+    pub fn verify_files(&self) -> io::Result<bool> {
+        let mut contents = HashMap::new();
+        for file in &self.files {
+            let content = fs::read(&file.path)?;
+            if content.len() != file.bytes || crc32fast::hash(&content) != file.crc32 {
+                return Ok(false);
+            }
+            contents.insert(&file.path, content);
+        }
+        for chunk in self.chunks.iter().filter(|chunk| !chunk.parts.is_empty()) {
+            let mut hasher = crc32fast::Hasher::new();
+            for part in &chunk.parts {
+                let content = match contents.get(&part.path) {
+                    Some(content) => content,
+                    None => return Ok(false),
+                };
+                match content.get(part.byte_offset..part.byte_offset + part.bytes) {
+                    Some(bytes) => hasher.update(bytes),
+                    None => return Ok(false),
+                }
+            }
+            if hasher.finalize() != chunk.crc32 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    /// Write the `Manifest` as pretty printed JSON.
+    pub fn to_writer(&self, writer: impl Write) -> io::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+    /// Read a `Manifest` written by `Manifest::to_writer`.
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// The offset basis of the 64-bit FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// The prime of the 64-bit FNV-1a hash.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Compute a hash of the input values and of the options of a run.
+///
+/// Unlike the `Hash` of the standard library it is stable across processes, versions and platforms
+/// (64-bit FNV-1a over length-prefixed fields), so it can be stored in a `Manifest` and compared later.
+/// The values are hashed in input order: it decides the order of the permutations and their ranks.
+#[derive(Clone, Debug)]
+pub struct ContentHasher {
+    state: u64,
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl ContentHasher {
+    /// Initialize the `ContentHasher` with nothing hashed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Hash the input `values`, in their order.
+    pub fn write_values<V: AsRef<str>>(&mut self, values: &[V]) {
+        self.write_field(b"values");
+        self.write_length(values.len());
+        for value in values {
+            self.write_field(value.as_ref().as_bytes());
+        }
+    }
+    /// Hash an option of the run, by its `name` and its `value`.
+    pub fn write_option(&mut self, name: &str, value: &str) {
+        self.write_field(name.as_bytes());
+        self.write_field(value.as_bytes());
+    }
+    /// Get the hash as 16 hexadecimal digits.
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.state)
+    }
+
+    /// Hash the `bytes` preceded by their length, so that the fields cannot be confused.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write_length(bytes.len());
+        self.write_bytes(bytes);
+    }
+    fn write_length(&mut self, length: usize) {
+        self.write_bytes(&(length as u64).to_le_bytes());
+    }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state = (self.state ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Record the formatted chunks in a `Manifest`, and pass them to the `inner` sink.
+/// A chunk is recorded once the `inner` sink has written it.
+pub struct ManifestSink<S> {
+    inner: S,
+    manifest: Manifest,
+    /// the entry of the last chunk the `inner` sink failed to write.
+    failed: Option<ChunkEntry>,
+}
+
+impl<S> ManifestSink<S> {
+    /// Initialize the `ManifestSink` with an empty `Manifest`.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            manifest: Manifest::new(),
+            failed: None,
+        }
+    }
+    /// Get the `Manifest` of the chunks written so far.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+    /// Get the `inner` sink.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+    /// Get the `inner` sink and the `Manifest`.
+    pub fn into_parts(self) -> (S, Manifest) {
+        (self.inner, self.manifest)
+    }
+
+    /// Get the `inner` sink, to be written.
+    #[cfg(feature = "compression")]
+    pub(crate) fn inner_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+    /// Record the ranks of the last chunk the `inner` sink failed to write, as it is skipped.
+    pub(crate) fn skip_failed(&mut self) {
+        if let Some(entry) = self.failed.take() {
+            self.manifest
+                .failed_ranks
+                .push(entry.first_rank..entry.first_rank + entry.permutations);
+        }
+    }
+    /// Write `item` to the `inner` sink, and record its chunk `entry` once it is written,
+    /// with its frame of `frame_bytes` if it is compressed.
+    pub(crate) fn write_entry<I>(
+        &mut self,
+        item: I,
+        mut entry: ChunkEntry,
+        frame_bytes: Option<usize>,
+    ) -> io::Result<()>
+    where
+        S: Sink<I>,
+    {
+        match self.inner.write(item) {
+            Ok(()) => {
+                self.failed = None;
+                entry.parts = self.inner.take_parts();
+                if let Some(bytes) = frame_bytes {
+                    let byte_offset = self
+                        .manifest
+                        .frames
+                        .last()
+                        .map_or(0, |last| last.byte_offset + last.bytes);
+                    self.manifest.frames.push(FrameEntry {
+                        index: entry.index,
+                        byte_offset,
+                        bytes,
+                    });
+                }
+                self.manifest.chunks.push(entry);
+                Ok(())
+            }
+            Err(error) => {
+                self.failed = Some(entry);
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<I: AsRef<str>, S: Sink<I>> Sink<I> for ManifestSink<S> {
+    fn write(&mut self, item: I) -> io::Result<()> {
+        let entry = self.manifest.next_chunk(item.as_ref());
+        self.write_entry(item, entry, None)
+    }
+
+    fn skipped(&mut self) {
+        self.skip_failed();
+        self.inner.skipped()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        self.inner.bytes_measured()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::{Encode, Pipeline, WriterSink};
+    use perm_core::Permutations;
+
+    use super::*;
+
+    fn write_with_manifest() -> (Vec<u8>, Manifest) {
+        let mut sink = ManifestSink::new(WriterSink::new(vec![]));
+        Pipeline::new()
+            .with_workers(2)
+            .run(
+                Permutations::new(vec![1, 2, 3, 4]).into_optimized_chunks(5),
+                Encode::default(),
+                &mut sink,
+            )
+            .unwrap();
+        let (inner, manifest) = sink.into_parts();
+        (inner.into_inner(), manifest)
+    }
+
+    #[test]
+    fn chunks_are_recorded_with_rank_and_byte_ranges() {
+        let (output, manifest) = write_with_manifest();
+        assert_eq!(manifest.chunks.len(), 5);
+        assert_eq!(manifest.permutations(), 24);
+        assert_eq!(manifest.chunk_of_rank(23).unwrap().index, 4);
+        assert_eq!(manifest.chunk_of_rank(10).unwrap().first_rank, 10);
+        assert!(manifest.chunk_of_rank(24).is_none());
+        let chunk = &manifest.chunks[1];
+        let text = std::str::from_utf8(&output[chunk.byte_offset..chunk.byte_offset + chunk.bytes])
+            .unwrap();
+        assert_eq!(text.lines().count(), 5);
+    }
+
+    #[test]
+    fn corrupted_output_is_detected() {
+        let (mut output, manifest) = write_with_manifest();
+        assert!(manifest.verify(&output));
+        output[7] = b'9';
+        assert!(!manifest.verify(&output));
+    }
+
+    #[test]
+    fn corrupted_files_are_detected() {
+        let path = std::env::temp_dir().join("perm-manifest-verify-files.txt");
+        fs::write(&path, "1,2\n").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.files.push(FileEntry {
+            path: path.clone(),
+            bytes: 4,
+            crc32: crc32fast::hash(b"1,2\n"),
+        });
+        assert!(manifest.verify_files().unwrap());
+        fs::write(&path, "2,1\n").unwrap();
+        assert!(!manifest.verify_files().unwrap());
+    }
+
+    #[test]
+    fn content_hash_depends_on_the_order_of_the_values() {
+        let hash = |values: &[&str], quote: &str| {
+            let mut hasher = ContentHasher::new();
+            hasher.write_values(values);
+            hasher.write_option("quote", quote);
+            hasher.finish()
+        };
+        assert_eq!(
+            hash(&["1", "2", "2"], "false"),
+            hash(&["1", "2", "2"], "false")
+        );
+        assert_ne!(
+            hash(&["1", "2", "2"], "false"),
+            hash(&["2", "1", "2"], "false")
+        );
+        assert_ne!(
+            hash(&["1", "2", "2"], "false"),
+            hash(&["1", "1", "2"], "false")
+        );
+        assert_ne!(
+            hash(&["1", "2", "2"], "false"),
+            hash(&["1", "2", "2"], "true")
+        );
+        assert_ne!(hash(&["1,2"], "false"), hash(&["1", ",2"], "false"));
+    }
+
+    #[test]
+    fn manifest_is_read_back() {
+        let (_, mut manifest) = write_with_manifest();
+        manifest.input_hash = Some(ContentHasher::new().finish());
+        let mut json = vec![];
+        manifest.to_writer(&mut json).unwrap();
+        assert_eq!(Manifest::from_reader(json.as_slice()).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,124 @@
+//! # Metrics
+//!
+//! `Metrics` is a set of counters updated by the `Pipeline` while it runs,
+//! to be read by the embedding application from another thread, e.g. to report the throughput periodically.
+//!
+//! The clones of a `Metrics` share the same counters, like the clones of a `CancellationToken`:
+//! the application keeps a clone, passes another one to `Pipeline::with_metrics`,
+//! and takes a `MetricsSnapshot` whenever it needs one.
+//!
+//! The pipeline counts the chunks generated and emitted, the chunks waiting for a worker,
+//! and the time spent in each stage: generating the chunks on the calling thread,
+//! transforming them (summed over the workers) and writing them to the sink.
+//! The bytes are counted by the sinks measuring them (`Sink::bytes_measured`).
+//! The permutations are counted by the source, since the pipeline does not look into the chunks:
+//! `Metrics::counting` wraps an iterator over chunks to count them.
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use perm_core::ChunkView;
+
+/// The counters of a `Pipeline`, shared by the clones.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    permutations_generated: AtomicUsize,
+    chunks_generated: AtomicUsize,
+    chunks_emitted: AtomicUsize,
+    bytes_written: AtomicUsize,
+    queue_depth: AtomicUsize,
+    generation_nanos: AtomicU64,
+    transform_nanos: AtomicU64,
+    write_nanos: AtomicU64,
+}
+
+/// The values of the `Metrics` at a given time.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// the number of permutations counted by `Metrics::counting`.
+    pub permutations_generated: usize,
+    /// the number of chunks taken from the source.
+    pub chunks_generated: usize,
+    /// the number of chunks written to the sink.
+    pub chunks_emitted: usize,
+    /// the number of bytes written by the sink, if it measures them.
+    pub bytes_written: usize,
+    /// the number of chunks waiting for a worker.
+    pub queue_depth: usize,
+    /// the time spent generating the chunks.
+    pub generation: Duration,
+    /// the time spent transforming the chunks, summed over the workers.
+    pub transform: Duration,
+    /// the time spent writing the chunks to the sink.
+    pub write: Duration,
+}
+
+impl Metrics {
+    /// Initialize the `Metrics` with all the counters at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Read the current value of the counters.
+    /// The counters are read one at a time, so they may be slightly out of sync with each other.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let counters = &self.counters;
+        MetricsSnapshot {
+            permutations_generated: counters.permutations_generated.load(Ordering::Relaxed),
+            chunks_generated: counters.chunks_generated.load(Ordering::Relaxed),
+            chunks_emitted: counters.chunks_emitted.load(Ordering::Relaxed),
+            bytes_written: counters.bytes_written.load(Ordering::Relaxed),
+            queue_depth: counters.queue_depth.load(Ordering::Relaxed),
+            generation: Duration::from_nanos(counters.generation_nanos.load(Ordering::Relaxed)),
+            transform: Duration::from_nanos(counters.transform_nanos.load(Ordering::Relaxed)),
+            write: Duration::from_nanos(counters.write_nanos.load(Ordering::Relaxed)),
+        }
+    }
+    /// Count the permutations of each chunk of `chunks`, as they are taken from the iterator.
+    pub fn counting<C: ChunkView>(
+        &self,
+        chunks: impl IntoIterator<Item = C>,
+    ) -> impl Iterator<Item = C> {
+        let metrics = self.clone();
+        chunks.into_iter().inspect(move |chunk| {
+            metrics.record_permutations(chunk.permutations_count());
+        })
+    }
+    /// Count `permutations` more generated permutations.
+    pub fn record_permutations(&self, permutations: usize) {
+        self.counters
+            .permutations_generated
+            .fetch_add(permutations, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_generated(&self, elapsed: Duration) {
+        self.counters
+            .chunks_generated
+            .fetch_add(1, Ordering::Relaxed);
+        add_nanos(&self.counters.generation_nanos, elapsed);
+    }
+    pub(crate) fn record_transformed(&self, elapsed: Duration) {
+        add_nanos(&self.counters.transform_nanos, elapsed);
+    }
+    pub(crate) fn record_emitted(&self, elapsed: Duration, bytes: usize) {
+        self.counters.chunks_emitted.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .bytes_written
+            .fetch_add(bytes, Ordering::Relaxed);
+        add_nanos(&self.counters.write_nanos, elapsed);
+    }
+    pub(crate) fn record_queue_depth(&self, depth: usize) {
+        self.counters.queue_depth.store(depth, Ordering::Relaxed);
+    }
+}
+
+/// Add the `elapsed` time to a counter of nanoseconds.
+fn add_nanos(counter: &AtomicU64, elapsed: Duration) {
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    counter.fetch_add(nanos, Ordering::Relaxed);
+}
@@ -0,0 +1,272 @@
+//! # NATS Sink
+//!
+//! `NatsSink` publishes the formatted chunks to a NATS subject, as they are generated,
+//! so that the permutations can be consumed by many downstream processes in real time.
+//!
+//! It speaks the plain text NATS client protocol over TCP: it only publishes,
+//! and it answers the `PING` of the server to keep the connection alive.
+//! Each message is either a whole chunk or a single permutation, according to `PublishEach`.
+//! On `finish` a `PING` is sent and the `PONG` is awaited,
+//! so that all the messages have been processed by the server.
+//!
+//! It is available with the `nats` feature.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use crate::pipeline::Sink;
+use crate::records::Records;
+
+/// The default maximum size of a message, if the server does not tell it.
+const DEFAULT_MAX_PAYLOAD: usize = 1024 * 1024;
+
+/// What is published as a single message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PublishEach {
+    /// Publish each formatted chunk.
+    Chunk,
+    /// Publish each formatted permutation, without the line break.
+    Permutation,
+}
+
+/// Publish the formatted chunks to a NATS subject.
+pub struct NatsSink {
+    reader: BufReader<TcpStream>,
+    writer: io::BufWriter<TcpStream>,
+    subject: String,
+    publish_each: PublishEach,
+    max_payload: usize,
+    messages_published: usize,
+    bytes_published: usize,
+}
+
+impl NatsSink {
+    /// Connect to the NATS server at `address` (`host:port`, optionally prefixed by `nats://`),
+    /// to publish to `subject`.
+    pub fn connect(
+        address: &str,
+        subject: impl Into<String>,
+        publish_each: PublishEach,
+    ) -> io::Result<Self> {
+        let subject = subject.into();
+        if subject.is_empty() || subject.contains(char::is_whitespace) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("The NATS subject: `{}` is not valid", subject),
+            ));
+        }
+        let stream = TcpStream::connect(address.trim_start_matches("nats://"))?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let info = read_line(&mut reader)?;
+        let max_payload = match info.strip_prefix("INFO ") {
+            Some(info) => serde_json::from_str::<serde_json::Value>(info)?
+                .get("max_payload")
+                .and_then(serde_json::Value::as_u64)
+                .map_or(DEFAULT_MAX_PAYLOAD, |max_payload| max_payload as usize),
+            None => return Err(protocol_error(&info)),
+        };
+        let mut writer = io::BufWriter::new(stream);
+        writer
+            .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"perm\"}\r\n")?;
+        Ok(Self {
+            reader,
+            writer,
+            subject,
+            publish_each,
+            max_payload,
+            messages_published: 0,
+            bytes_published: 0,
+        })
+    }
+    /// Get the number of messages published so far.
+    pub fn messages_published(&self) -> usize {
+        self.messages_published
+    }
+    /// Get the number of payload bytes published so far.
+    pub fn bytes_published(&self) -> usize {
+        self.bytes_published
+    }
+
+    fn publish(&mut self, payload: &str) -> io::Result<()> {
+        if payload.len() > self.max_payload {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The message of {} bytes exceeds the maximum payload of the server: {} bytes",
+                    payload.len(),
+                    self.max_payload
+                ),
+            ));
+        }
+        write!(self.writer, "PUB {} {}\r\n", self.subject, payload.len())?;
+        self.writer.write_all(payload.as_bytes())?;
+        self.writer.write_all(b"\r\n")?;
+        self.messages_published += 1;
+        self.bytes_published += payload.len();
+        Ok(())
+    }
+
+    /// Answer the messages the server has sent since the last call, without blocking.
+    fn poll_server(&mut self) -> io::Result<()> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let available = self.reader.fill_buf().map(|buffer| !buffer.is_empty());
+        self.reader.get_ref().set_nonblocking(false)?;
+        match available {
+            Ok(true) => self.handle(None),
+            Ok(false) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The NATS server has closed the connection",
+            )),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Handle the messages of the server until `expected` is received,
+    /// or until the buffered ones are consumed if `expected` is `None`.
+    fn handle(&mut self, expected: Option<&str>) -> io::Result<()> {
+        loop {
+            if expected.is_none() && self.reader.buffer().is_empty() {
+                return Ok(());
+            }
+            let line = read_line(&mut self.reader)?;
+            if Some(line.as_str()) == expected {
+                return Ok(());
+            }
+            if line == "PING" {
+                self.writer.write_all(b"PONG\r\n")?;
+                self.writer.flush()?;
+            } else if line.starts_with("-ERR") {
+                return Err(protocol_error(&line));
+            }
+            // `INFO`, `+OK` and `PONG` need no answer.
+        }
+    }
+}
+
+impl<I: AsRef<str>> Sink<I> for NatsSink {
+    fn write(&mut self, item: I) -> io::Result<()> {
+        self.poll_server()?;
+        let text = item.as_ref();
+        match self.publish_each {
+            PublishEach::Chunk => self.publish(text),
+            PublishEach::Permutation => Records::new(text)
+                .try_for_each(|record| self.publish(record.trim_end_matches(['\n', '\r']))),
+        }
+    }
+
+    /// Flush the messages and wait for the server to process them.
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.write_all(b"PING\r\n")?;
+        self.writer.flush()?;
+        self.handle(Some("PONG"))
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        Some(self.bytes_published())
+    }
+}
+
+/// Read a single line of the protocol, without the line break.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader
+        .by_ref()
+        .take(DEFAULT_MAX_PAYLOAD as u64)
+        .read_line(&mut line)?
+        == 0
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "The NATS server has closed the connection",
+        ));
+    }
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
+fn protocol_error(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Unexpected message from the NATS server: `{}`", line),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use crate::pipeline::{Encode, Pipeline};
+    use perm_core::Permutations;
+
+    use super::*;
+
+    /// Accept a single client and return the published payloads, when the client sends `PING`.
+    fn fake_server() -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writer
+                .write_all(b"INFO {\"max_payload\":1048576}\r\n")
+                .unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut payloads = vec![];
+            loop {
+                let line = read_line(&mut reader).unwrap();
+                if line == "PING" {
+                    writer.write_all(b"PONG\r\n").unwrap();
+                    return payloads;
+                }
+                if let Some(publish) = line.strip_prefix("PUB ") {
+                    let size = publish
+                        .split(' ')
+                        .next_back()
+                        .unwrap()
+                        .parse::<usize>()
+                        .unwrap();
+                    let mut payload = vec![0; size + 2];
+                    reader.read_exact(&mut payload).unwrap();
+                    payload.truncate(size);
+                    payloads.push(String::from_utf8(payload).unwrap());
+                }
+            }
+        });
+        (address, server)
+    }
+
+    fn publish(publish_each: PublishEach) -> (usize, Vec<String>) {
+        let (address, server) = fake_server();
+        let mut sink = NatsSink::connect(&address, "perm.out", publish_each).unwrap();
+        Pipeline::new()
+            .with_workers(2)
+            .run(
+                Permutations::new(vec![1, 2, 3]).into_optimized_chunks(4),
+                Encode::default(),
+                &mut sink,
+            )
+            .unwrap();
+        (sink.messages_published(), server.join().unwrap())
+    }
+
+    #[test]
+    fn each_chunk_is_a_message() {
+        let (published, payloads) = publish(PublishEach::Chunk);
+        assert_eq!(published, 2);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads.concat().lines().count(), 6);
+    }
+
+    #[test]
+    fn each_permutation_is_a_message() {
+        let (published, payloads) = publish(PublishEach::Permutation);
+        assert_eq!(published, 6);
+        assert!(payloads.iter().all(|payload| payload.len() == 5));
+    }
+
+    #[test]
+    fn invalid_subject_is_rejected() {
+        assert!(NatsSink::connect("127.0.0.1:1", "perm out", PublishEach::Chunk).is_err());
+    }
+}
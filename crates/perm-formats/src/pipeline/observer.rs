@@ -0,0 +1,48 @@
+//! # Observer
+//!
+//! `Observer` is notified by the `Pipeline` of the progress of a run,
+//! so that the embedding application can update a UI, push metrics or trigger the downstream jobs
+//! without forking the orchestration code.
+//!
+//! The chunks are identified by their index in the source, from `0`.
+//! `on_chunk_generated` is called on the calling thread, as soon as a chunk is taken from the source,
+//! and `on_chunk_written` on the writer thread, in generation order, after the sink has received the chunk:
+//! so the methods can be called concurrently, and they should return quickly, since they delay the stage calling them.
+//! The chunks filtered out by the `Transform` or dropped by a cancellation are generated but never written.
+//!
+//! `on_complete` is called once, after the sink has finished, only if the run has succeeded:
+//! a failed run returns its error to the caller instead.
+use std::time::Duration;
+
+/// The receiver of the progress of a `Pipeline`.
+/// Every method does nothing by default: an observer implements only the ones it needs.
+pub trait Observer: Send + Sync {
+    /// Called when the chunk of `index` has been taken from the source.
+    fn on_chunk_generated(&self, index: usize) {
+        let _ = index;
+    }
+
+    /// Called when the chunk of `index` has been written to the sink,
+    /// with the number of bytes it took, if the sink measures them.
+    fn on_chunk_written(&self, index: usize, bytes: Option<usize>) {
+        let _ = (index, bytes);
+    }
+
+    /// Called once when the run has completed successfully.
+    fn on_complete(&self, summary: &Summary) {
+        let _ = summary;
+    }
+}
+
+/// What a successful run of the `Pipeline` has done.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    /// the number of chunks taken from the source.
+    pub chunks_generated: usize,
+    /// the number of chunks written to the sink.
+    pub chunks_written: usize,
+    /// whether the run has been stopped by its `CancellationToken`.
+    pub cancelled: bool,
+    /// the wall time of the run.
+    pub elapsed: Duration,
+}
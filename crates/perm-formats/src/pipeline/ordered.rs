@@ -0,0 +1,309 @@
+//! # Parallel Ordered Pipeline
+//!
+//! `ParallelOrderedPipeline` generates the permutations on many threads,
+//! and still writes them in generation order.
+//!
+//! The permutations are split by their first value:
+//! the permutations starting with the same value have consecutive ranks,
+//! so each prefix is a range of ranks generated independently by `IntoRankedChunks`,
+//! starting with `starting_at` and stopping with `with_limit`.
+//! Each prefix is formatted on a worker thread into its own buffer,
+//! or into its own file with `ParallelOrderedPipeline::with_spill_directory`,
+//! and the prefixes are concatenated in rank order as soon as all the previous ones are complete.
+//!
+//! Unlike `Pipeline`, the generation itself is parallel, so the speedup is nearly linear
+//! when there are at least as many prefixes as workers,
+//! but a whole prefix is kept before being written.
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::thread;
+
+use crossbeam::channel;
+
+use crate::pipeline::Written;
+use perm_core::format::{OutputFormat, PermValue};
+use perm_core::{Chunk, Permutations, Uniqueness, MIN_SUGGESTED_CHUNK_SIZE};
+
+/// Generate the permutations sharing the first value on their own thread,
+/// and write them in generation order.
+#[derive(Clone, Debug)]
+pub struct ParallelOrderedPipeline {
+    workers: usize,
+    spill_directory: Option<PathBuf>,
+}
+
+/// The default `ParallelOrderedPipeline` has one worker for each available CPU,
+/// and keeps the prefixes in memory.
+impl Default for ParallelOrderedPipeline {
+    fn default() -> Self {
+        Self {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            spill_directory: None,
+        }
+    }
+}
+
+/// The permutations of a prefix, formatted by a worker.
+enum Part {
+    Buffer(Vec<u8>),
+    File(PathBuf),
+}
+
+impl ParallelOrderedPipeline {
+    /// Initialize a `ParallelOrderedPipeline` with one worker for each available CPU.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the number of worker threads generating the prefixes.
+    /// Panics if `workers` is zero.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        if workers == 0 {
+            panic!("Pipeline workers must be at least one")
+        }
+        self.workers = workers;
+        self
+    }
+    /// Get the number of worker threads generating the prefixes.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+    /// Write each prefix to its own file in `directory` rather than in memory.
+    /// The files are removed once they have been concatenated to the output.
+    pub fn with_spill_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.spill_directory = Some(directory.into());
+        self
+    }
+
+    /// Generate the `permutations`, format them with `format` and write them to `writer` in generation order.
+    /// The generation stops at the first error.
+    /// It fails with `io::ErrorKind::InvalidInput` if the number of permutations does not fit in a `usize`.
+    pub fn write<T, W>(
+        &self,
+        permutations: Permutations<T>,
+        format: OutputFormat,
+        mut writer: W,
+    ) -> io::Result<Written>
+    where
+        T: Copy + Eq + Hash + Send + Sync + PermValue,
+        W: Write,
+    {
+        let prefixes = prefixes(&permutations)?;
+        let (prefix_sender, prefix_receiver) = channel::unbounded();
+        prefixes
+            .into_iter()
+            .enumerate()
+            .for_each(|prefix| prefix_sender.send(prefix).expect("The receiver is alive"));
+        drop(prefix_sender);
+        let (part_sender, part_receiver) = channel::unbounded();
+        let permutations = &permutations;
+
+        crossbeam::scope(|scope| {
+            for _ in 0..self.workers {
+                let prefix_receiver = prefix_receiver.clone();
+                let part_sender = part_sender.clone();
+                scope.spawn(move |_| {
+                    for (index, ranks) in prefix_receiver {
+                        let part = self.generate_prefix(permutations, index, ranks, format);
+                        if part_sender.send((index, part)).is_err() {
+                            // the writer has failed.
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(part_sender);
+            self.concatenate(part_receiver, &mut writer)
+        })
+        .expect("Error running the pipeline")
+    }
+
+    /// Format the permutations with the ranks `ranks`, starting with the same value.
+    /// Return the formatted permutations with the number of chunks.
+    fn generate_prefix<T>(
+        &self,
+        permutations: &Permutations<T>,
+        index: usize,
+        ranks: Range<usize>,
+        format: OutputFormat,
+    ) -> io::Result<(Part, usize)>
+    where
+        T: Copy + Eq + Hash + PermValue,
+    {
+        let chunks = permutations
+            .clone()
+            .into_ranked_chunks(MIN_SUGGESTED_CHUNK_SIZE)
+            .starting_at(ranks.start)
+            .with_limit(ranks.len());
+        match &self.spill_directory {
+            None => {
+                let mut buffer = vec![];
+                let written = write_chunks(chunks, format, &mut buffer)?;
+                Ok((Part::Buffer(buffer), written))
+            }
+            Some(directory) => {
+                let path = directory.join(format!("prefix-{}.part", index));
+                let mut file = BufWriter::new(File::create(&path)?);
+                let written = write_chunks(chunks, format, &mut file)?;
+                file.flush()?;
+                Ok((Part::File(path), written))
+            }
+        }
+    }
+
+    /// Write the parts received from the workers to the `writer` in the order of their index.
+    /// The parts received ahead of their turn are kept until the missing ones arrive.
+    fn concatenate(
+        &self,
+        parts: channel::Receiver<(usize, io::Result<(Part, usize)>)>,
+        writer: &mut impl Write,
+    ) -> io::Result<Written> {
+        let mut written = Written::default();
+        let mut next_index = 0;
+        let mut pending = BTreeMap::new();
+        for (index, part) in parts {
+            pending.insert(index, part?);
+            while let Some((part, chunks)) = pending.remove(&next_index) {
+                written.bytes += match part {
+                    Part::Buffer(buffer) => {
+                        writer.write_all(&buffer)?;
+                        buffer.len()
+                    }
+                    Part::File(path) => {
+                        let bytes = io::copy(&mut File::open(&path)?, writer)?;
+                        fs::remove_file(path)?;
+                        bytes as usize
+                    }
+                };
+                written.chunks += chunks;
+                next_index += 1;
+            }
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+/// Compute the ranks of the permutations starting with each value, in generation order.
+fn prefixes<T: Copy + Eq + Hash>(permutations: &Permutations<T>) -> io::Result<Vec<Range<usize>>> {
+    let too_many = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the number of permutations does not fit in a usize",
+        )
+    };
+    let total = permutations
+        .checked_permutations_number()
+        .ok_or_else(too_many)?;
+    if permutations.values().is_empty() {
+        // the only permutation, the empty one, has no first value.
+        #[allow(clippy::single_range_in_vec_init)]
+        return Ok(vec![0..total]);
+    }
+    let mut prefixes = vec![];
+    let mut first_rank = 0;
+    while first_rank < total {
+        let first = permutations
+            .nth_permutation(first_rank)
+            .expect("The rank is lower than the number of permutations")[0];
+        // removing the last occurrence keeps the order of the first occurrences of the other values.
+        let mut rest = permutations.values().to_vec();
+        let last = rest
+            .iter()
+            .rposition(|value| *value == first)
+            .expect("The first value is one of the values");
+        rest.remove(last);
+        let rest = match permutations.uniqueness() {
+            Uniqueness::Distinct => Permutations::distinct(rest),
+            Uniqueness::WithDuplicates => Permutations::with_duplicates(rest),
+        };
+        let count = rest.checked_permutations_number().ok_or_else(too_many)?;
+        prefixes.push(first_rank..first_rank + count);
+        first_rank += count;
+    }
+    Ok(prefixes)
+}
+
+/// Write the formatted `chunks` to the `writer`, and return their number.
+fn write_chunks<T: PermValue>(
+    chunks: impl Iterator<Item = Chunk<T>>,
+    format: OutputFormat,
+    writer: &mut impl Write,
+) -> io::Result<usize> {
+    let mut written = 0;
+    let mut text = String::new();
+    for chunk in chunks {
+        text.clear();
+        chunk.write_text(format, &mut text);
+        writer.write_all(text.as_bytes())?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequential(permutations: Permutations<i32>) -> String {
+        permutations
+            .into_chunks(5)
+            .map(|chunk| chunk.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn permutations_are_written_in_generation_order() {
+        for permutations in [
+            Permutations::new(vec![3, 1, 3, 2, 1]),
+            Permutations::with_duplicates(vec![2, 1, 2, 3]),
+            Permutations::new(vec![7]),
+        ] {
+            let mut output = vec![];
+            let written = ParallelOrderedPipeline::new()
+                .with_workers(3)
+                .write(permutations.clone(), OutputFormat::default(), &mut output)
+                .unwrap();
+
+            let expected = sequential(permutations);
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+            assert_eq!(written.bytes, expected.len());
+        }
+    }
+
+    #[test]
+    fn prefixes_can_be_spilled_to_files() {
+        let directory = std::env::temp_dir().join("perm-ordered-spill");
+        fs::create_dir_all(&directory).unwrap();
+        let permutations = Permutations::new(vec![1, 2, 3, 4, 5]);
+        let mut output = vec![];
+        let written = ParallelOrderedPipeline::new()
+            .with_workers(2)
+            .with_spill_directory(&directory)
+            .write(permutations.clone(), OutputFormat::default(), &mut output)
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), sequential(permutations));
+        // each prefix of 24 permutations is written in two chunks.
+        assert_eq!(written.chunks, 10);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn empty_input_is_a_single_prefix() {
+        // the empty permutation is written as an empty line.
+        let permutations = Permutations::new(Vec::<i32>::new());
+        let mut output = vec![];
+        let written = ParallelOrderedPipeline::new()
+            .with_workers(2)
+            .write(permutations.clone(), OutputFormat::default(), &mut output)
+            .unwrap();
+        assert_eq!(sequential(permutations), "\n");
+        assert_eq!(String::from_utf8(output).unwrap(), "\n");
+        assert_eq!(written.chunks, 1);
+    }
+}
@@ -0,0 +1,287 @@
+//! # Pinned Pipeline
+//!
+//! `PinnedPipeline` is a thread-per-core architecture for the machines with many cores (and NUMA nodes),
+//! where the single writer of `Pipeline` and the shared `stdout` become the bottleneck.
+//!
+//! The ranks of the permutations are split in as many contiguous ranges as threads.
+//! Each thread is pinned to its own core, generates its range with `IntoRankedChunks`
+//! (starting with `starting_at` and stopping with `with_limit`) and formats it into its own file:
+//! nothing is shared between the threads, so there is no lock and no cross-node traffic.
+//!
+//! The file names are given by a pattern where `{}` is replaced by the thread number, from `0`,
+//! so the files sorted by thread number hold the permutations in generation order.
+//! `PinnedPipeline::write` also concatenates them to a writer at the end, and removes them.
+//!
+//! The pinning is best effort: when the cores cannot be listed, or a thread cannot be pinned,
+//! the thread runs unpinned. With more threads than cores, the cores are assigned round-robin.
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::thread;
+
+use core_affinity::CoreId;
+
+use crate::pipeline::{FileEntry, Written, SHARD_PLACEHOLDER};
+use perm_core::format::{OutputFormat, PermValue};
+use perm_core::{Permutations, MIN_SUGGESTED_CHUNK_SIZE};
+
+/// Generate the permutations on one thread pinned to each core, each writing its own file.
+#[derive(Clone, Debug)]
+pub struct PinnedPipeline {
+    threads: usize,
+    chunk_size: usize,
+}
+
+/// The default `PinnedPipeline` has one thread for each core the process can run on.
+impl Default for PinnedPipeline {
+    fn default() -> Self {
+        Self {
+            threads: core_affinity::get_core_ids().map_or_else(
+                || thread::available_parallelism().map_or(1, |n| n.get()),
+                |cores| cores.len().max(1),
+            ),
+            chunk_size: MIN_SUGGESTED_CHUNK_SIZE,
+        }
+    }
+}
+
+impl PinnedPipeline {
+    /// Initialize a `PinnedPipeline` with one thread for each core the process can run on.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the number of threads, each pinned to its own core when there are enough cores.
+    /// Panics if `threads` is zero.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        if threads == 0 {
+            panic!("Pipeline workers must be at least one")
+        }
+        self.threads = threads;
+        self
+    }
+    /// Get the number of threads.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    /// Set the number of permutations each thread formats at a time.
+    /// Panics if `chunk_size` is zero.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        if chunk_size == 0 {
+            panic!("The chunk size must be at least one")
+        }
+        self.chunk_size = chunk_size;
+        self
+    }
+    /// Get the number of permutations each thread formats at a time.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Generate the `permutations` formatted with `format` to the files of `pattern`, one for each thread.
+    /// Return the files in generation order: a thread with no permutations leaves an empty file.
+    /// It fails with `io::ErrorKind::InvalidInput` if the pattern does not contain `SHARD_PLACEHOLDER`,
+    /// or if the number of permutations does not fit in a `usize`.
+    pub fn write_files<T>(
+        &self,
+        permutations: &Permutations<T>,
+        format: OutputFormat,
+        pattern: &str,
+    ) -> io::Result<Vec<FileEntry>>
+    where
+        T: Copy + Eq + Hash + Send + Sync + PermValue,
+    {
+        self.write_parts(permutations, format, pattern)
+            .map(|parts| parts.into_iter().map(|(file, _)| file).collect())
+    }
+
+    /// Generate the `permutations` formatted with `format` to the files of `pattern`, as `write_files`,
+    /// then concatenate the files to `writer` in generation order, removing them.
+    pub fn write<T, W>(
+        &self,
+        permutations: &Permutations<T>,
+        format: OutputFormat,
+        pattern: &str,
+        mut writer: W,
+    ) -> io::Result<Written>
+    where
+        T: Copy + Eq + Hash + Send + Sync + PermValue,
+        W: Write,
+    {
+        let mut written = Written::default();
+        for (file, chunks) in self.write_parts(permutations, format, pattern)? {
+            written.bytes += io::copy(&mut File::open(&file.path)?, &mut writer)? as usize;
+            written.chunks += chunks;
+            fs::remove_file(file.path)?;
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Write the file of each thread, and return it with the number of chunks written.
+    fn write_parts<T>(
+        &self,
+        permutations: &Permutations<T>,
+        format: OutputFormat,
+        pattern: &str,
+    ) -> io::Result<Vec<(FileEntry, usize)>>
+    where
+        T: Copy + Eq + Hash + Send + Sync + PermValue,
+    {
+        if !pattern.contains(SHARD_PLACEHOLDER) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The output pattern: `{}` does not contain `{}`",
+                    pattern, SHARD_PLACEHOLDER
+                ),
+            ));
+        }
+        let total = permutations.checked_permutations_number().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the number of permutations does not fit in a usize",
+            )
+        })?;
+        let cores = core_affinity::get_core_ids().unwrap_or_default();
+
+        crossbeam::scope(|scope| {
+            let threads = (0..self.threads)
+                .map(|thread| {
+                    let path =
+                        PathBuf::from(pattern.replace(SHARD_PLACEHOLDER, &thread.to_string()));
+                    let ranks = thread_ranks(total, self.threads, thread);
+                    let core = cores.get(thread % cores.len().max(1)).copied();
+                    scope.spawn(move |_| self.write_part(permutations, format, ranks, path, core))
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().expect("Error waiting a thread to terminate"))
+                .collect()
+        })
+        .expect("Error running the pipeline")
+    }
+
+    /// Format the permutations with the ranks `ranks` to the file at `path`, on the thread pinned to `core`.
+    fn write_part<T>(
+        &self,
+        permutations: &Permutations<T>,
+        format: OutputFormat,
+        ranks: Range<usize>,
+        path: PathBuf,
+        core: Option<CoreId>,
+    ) -> io::Result<(FileEntry, usize)>
+    where
+        T: Copy + Eq + Hash + PermValue,
+    {
+        if let Some(core) = core {
+            // an unpinned thread is only slower.
+            core_affinity::set_for_current(core);
+        }
+        let mut file = BufWriter::new(File::create(&path)?);
+        let mut checksum = crc32fast::Hasher::new();
+        let mut bytes = 0;
+        let mut chunks = 0;
+        let mut text = String::new();
+        if !ranks.is_empty() {
+            for chunk in permutations
+                .clone()
+                .into_ranked_chunks(self.chunk_size)
+                .starting_at(ranks.start)
+                .with_limit(ranks.len())
+            {
+                text.clear();
+                chunk.write_text(format, &mut text);
+                checksum.update(text.as_bytes());
+                file.write_all(text.as_bytes())?;
+                bytes += text.len();
+                chunks += 1;
+            }
+        }
+        file.flush()?;
+        Ok((
+            FileEntry {
+                path,
+                bytes,
+                crc32: checksum.finalize(),
+            },
+            chunks,
+        ))
+    }
+}
+
+/// The ranks of the `thread` out of `threads`: the first `total % threads` threads have one more rank.
+fn thread_ranks(total: usize, threads: usize, thread: usize) -> Range<usize> {
+    let start = |thread: usize| total / threads * thread + thread.min(total % threads);
+    start(thread)..start(thread + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequential(permutations: &Permutations<i32>) -> String {
+        permutations
+            .clone()
+            .into_chunks(5)
+            .map(|chunk| chunk.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn threads_write_their_ranks_in_order() {
+        let directory = std::env::temp_dir().join("perm-pinned");
+        fs::create_dir_all(&directory).unwrap();
+        let pattern = directory.join("core-{}.txt");
+        let pattern = pattern.to_str().unwrap();
+        let permutations = Permutations::new(vec![1, 2, 2, 3, 4]);
+        let pipeline = PinnedPipeline::new().with_threads(7).with_chunk_size(4);
+
+        let files = pipeline
+            .write_files(&permutations, OutputFormat::default(), pattern)
+            .unwrap();
+        assert_eq!(files.len(), 7);
+        let output = files
+            .iter()
+            .map(|file| fs::read_to_string(&file.path).unwrap())
+            .collect::<String>();
+        assert_eq!(output, sequential(&permutations));
+        // 60 permutations are split in 4 ranges of 9 and 3 of 8.
+        let lines = files
+            .iter()
+            .map(|file| fs::read_to_string(&file.path).unwrap().lines().count())
+            .collect::<Vec<usize>>();
+        assert_eq!(lines, vec![9, 9, 9, 9, 8, 8, 8]);
+        assert!(files
+            .iter()
+            .all(|file| file.crc32 == crc32fast::hash(&fs::read(&file.path).unwrap())));
+
+        let mut output = vec![];
+        let written = pipeline
+            .write(&permutations, OutputFormat::default(), pattern, &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            sequential(&permutations)
+        );
+        assert_eq!(written.chunks, 4 * 3 + 3 * 2);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn more_threads_than_permutations_leave_empty_files() {
+        assert_eq!(thread_ranks(2, 3, 0), 0..1);
+        assert_eq!(thread_ranks(2, 3, 1), 1..2);
+        assert_eq!(thread_ranks(2, 3, 2), 2..2);
+        assert!(PinnedPipeline::new()
+            .write_files(
+                &Permutations::new(vec![1]),
+                OutputFormat::default(),
+                "out.txt"
+            )
+            .is_err());
+    }
+}
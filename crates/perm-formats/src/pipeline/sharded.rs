@@ -0,0 +1,363 @@
+//! # Sharded Sink
+//!
+//! `ShardedSink` writes the formatted chunks across many files, as they are generated.
+//!
+//! The file names are given by a pattern where `{}` is replaced by the shard key:
+//!
+//! - `SplitBy::Chunk`: the chunks are written round-robin to `shards` files, keyed by the shard index.
+//! - `SplitBy::Prefix`: each permutation is written to the file keyed by its first value.
+//!   Since the job tree is explored depth-first, the permutations with the same first value are contiguous,
+//!   so only one file is open at a time.
+//! - `SplitBy::Size`: the chunks are written to a file until it reaches `max_bytes`,
+//!   then a new file is started, keyed by its sequence number.
+//!
+//! A permutation is never split across two files, but a chunk can be:
+//! the files and the byte ranges each chunk has been written to are given by `Sink::take_parts`.
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::pipeline::{ChunkPart, FileEntry, Sink};
+use crate::records::{first_value, Records};
+
+/// The placeholder replaced by the shard key in the file name pattern.
+pub const SHARD_PLACEHOLDER: &str = "{}";
+
+/// How the output is split across the files.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitBy {
+    /// Write the chunks round-robin to a fixed number of files.
+    Chunk { shards: usize },
+    /// Write each permutation to the file of its first value.
+    Prefix,
+    /// Start a new file when the current one reaches a size in bytes.
+    Size { max_bytes: usize },
+}
+
+/// Write the formatted chunks to many files.
+pub struct ShardedSink {
+    pattern: String,
+    split_by: SplitBy,
+    /// the open files, by shard key.
+    files: HashMap<String, BufWriter<File>>,
+    /// the files created by this sink, that must be appended to when reopened.
+    created: HashSet<String>,
+    /// the size and the running checksum of each file, by shard key.
+    checksums: HashMap<String, (usize, crc32fast::Hasher)>,
+    /// the files and the byte ranges the last chunk has been written to.
+    parts: Vec<ChunkPart>,
+    chunks_written: usize,
+    current_file_bytes: usize,
+    current_file_index: usize,
+    bytes_written: usize,
+}
+
+impl ShardedSink {
+    /// Initialize the `ShardedSink` with the file name `pattern`.
+    /// Fails if the pattern does not contain `SHARD_PLACEHOLDER`, or the number of shards is zero.
+    pub fn new(pattern: impl Into<String>, split_by: SplitBy) -> io::Result<Self> {
+        let pattern = pattern.into();
+        if !pattern.contains(SHARD_PLACEHOLDER) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The output pattern: `{}` does not contain `{}`",
+                    pattern, SHARD_PLACEHOLDER
+                ),
+            ));
+        }
+        if split_by == (SplitBy::Chunk { shards: 0 }) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The shards must be at least one",
+            ));
+        }
+        Ok(Self {
+            pattern,
+            split_by,
+            files: HashMap::new(),
+            created: HashSet::new(),
+            checksums: HashMap::new(),
+            parts: vec![],
+            chunks_written: 0,
+            current_file_bytes: 0,
+            current_file_index: 0,
+            bytes_written: 0,
+        })
+    }
+    /// Get the number of bytes written so far across all the files.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+    /// Get the paths of the files written so far.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = self
+            .created
+            .iter()
+            .map(|key| self.path(key))
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+        paths
+    }
+
+    /// Get the size and the checksum of the files written so far, sorted by path.
+    pub fn file_entries(&self) -> Vec<FileEntry> {
+        let mut entries = self
+            .checksums
+            .iter()
+            .map(|(key, (bytes, hasher))| FileEntry {
+                path: self.path(key),
+                bytes: *bytes,
+                crc32: hasher.clone().finalize(),
+            })
+            .collect::<Vec<FileEntry>>();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        PathBuf::from(self.pattern.replace(SHARD_PLACEHOLDER, key))
+    }
+
+    /// Write `text` to the file of the shard `key`.
+    /// The file is truncated the first time it is opened by this sink.
+    fn write_to(&mut self, key: &str, text: &str) -> io::Result<()> {
+        if !self.files.contains_key(key) {
+            let first_time = self.created.insert(key.to_string());
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(first_time)
+                .append(!first_time)
+                .open(self.path(key))?;
+            self.files.insert(key.to_string(), BufWriter::new(file));
+        }
+        self.files
+            .get_mut(key)
+            .expect("The file has just been opened")
+            .write_all(text.as_bytes())?;
+        let path = self.path(key);
+        let (bytes, hasher) = self.checksums.entry(key.to_string()).or_default();
+        match self.parts.last_mut() {
+            Some(part) if part.path == path => part.bytes += text.len(),
+            _ => self.parts.push(ChunkPart {
+                path,
+                byte_offset: *bytes,
+                bytes: text.len(),
+            }),
+        }
+        *bytes += text.len();
+        hasher.update(text.as_bytes());
+        self.bytes_written += text.len();
+        Ok(())
+    }
+
+    /// Flush and close all the open files.
+    fn close_files(&mut self) -> io::Result<()> {
+        self.files
+            .drain()
+            .try_for_each(|(_, mut file)| file.flush())
+    }
+}
+
+impl<I: AsRef<str>> Sink<I> for ShardedSink {
+    fn write(&mut self, item: I) -> io::Result<()> {
+        let text = item.as_ref();
+        self.parts.clear();
+        match self.split_by {
+            SplitBy::Chunk { shards } => {
+                let key = (self.chunks_written % shards).to_string();
+                self.write_to(&key, text)?;
+            }
+            SplitBy::Prefix => {
+                for record in Records::new(text) {
+                    let key = file_name_safe(&first_value(record));
+                    if !self.files.contains_key(&key) {
+                        // the previous prefix is completed.
+                        self.close_files()?;
+                    }
+                    self.write_to(&key, record)?;
+                }
+            }
+            SplitBy::Size { max_bytes } => {
+                for record in Records::new(text) {
+                    if self.current_file_bytes > 0
+                        && self.current_file_bytes + record.len() > max_bytes
+                    {
+                        self.close_files()?;
+                        self.current_file_index += 1;
+                        self.current_file_bytes = 0;
+                    }
+                    let key = self.current_file_index.to_string();
+                    self.write_to(&key, record)?;
+                    self.current_file_bytes += record.len();
+                }
+            }
+        }
+        self.chunks_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.close_files()
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        Some(self.bytes_written)
+    }
+
+    fn take_parts(&mut self) -> Vec<ChunkPart> {
+        std::mem::take(&mut self.parts)
+    }
+}
+
+/// Replace the characters of a value that are not safe in a file name.
+fn file_name_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '+' || c == '.' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::pipeline::{Encode, ManifestSink, Pipeline};
+    use perm_core::Permutations;
+
+    use super::*;
+
+    fn write_sharded(name: &str, split_by: SplitBy) -> (Vec<String>, String) {
+        let directory = std::env::temp_dir().join(format!("perm-sharded-{}", name));
+        fs::create_dir_all(&directory).unwrap();
+        let pattern = directory.join("out-{}.txt").to_str().unwrap().to_string();
+        let mut sink = ShardedSink::new(pattern, split_by).unwrap();
+        Pipeline::new()
+            .with_workers(2)
+            .run(
+                Permutations::new(vec![1, 2, 3, 4]).into_optimized_chunks(5),
+                Encode::default(),
+                &mut sink,
+            )
+            .unwrap();
+        let names = sink
+            .paths()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+        let contents = sink
+            .paths()
+            .iter()
+            .map(|p| fs::read_to_string(p).unwrap())
+            .collect();
+        fs::remove_dir_all(directory).unwrap();
+        (names, contents)
+    }
+
+    fn sorted_lines(text: &str) -> Vec<&str> {
+        let mut lines = text.lines().collect::<Vec<&str>>();
+        lines.sort_unstable();
+        lines
+    }
+
+    #[test]
+    fn chunks_are_split_round_robin() {
+        let (names, contents) = write_sharded("chunk", SplitBy::Chunk { shards: 3 });
+        assert_eq!(names, vec!["out-0.txt", "out-1.txt", "out-2.txt"]);
+        assert_eq!(sorted_lines(&contents).len(), 24);
+    }
+
+    #[test]
+    fn permutations_are_split_by_prefix() {
+        let (names, contents) = write_sharded("prefix", SplitBy::Prefix);
+        assert_eq!(
+            names,
+            vec!["out-1.txt", "out-2.txt", "out-3.txt", "out-4.txt"]
+        );
+        assert_eq!(sorted_lines(&contents).len(), 24);
+    }
+
+    #[test]
+    fn permutations_are_split_by_size() {
+        // each permutation is 8 bytes long.
+        let (names, contents) = write_sharded("size", SplitBy::Size { max_bytes: 60 });
+        assert_eq!(names.len(), 4);
+        assert_eq!(sorted_lines(&contents).len(), 24);
+    }
+
+    #[test]
+    fn file_checksums_match_the_contents() {
+        let directory = std::env::temp_dir().join("perm-sharded-checksums");
+        fs::create_dir_all(&directory).unwrap();
+        let pattern = directory.join("out-{}.txt").to_str().unwrap().to_string();
+        let mut sink = ShardedSink::new(pattern, SplitBy::Chunk { shards: 2 }).unwrap();
+        Pipeline::new()
+            .run(
+                Permutations::new(vec![1, 2, 3, 4]).into_optimized_chunks(5),
+                Encode::default(),
+                &mut sink,
+            )
+            .unwrap();
+        let entries = sink.file_entries();
+        assert_eq!(entries.len(), 2);
+        for entry in entries {
+            let contents = fs::read(&entry.path).unwrap();
+            assert_eq!(entry.bytes, contents.len());
+            assert_eq!(entry.crc32, crc32fast::hash(&contents));
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn chunks_are_located_in_their_files() {
+        // each permutation is 8 bytes long: the chunks of 5 permutations span two files of 24 bytes.
+        for (name, split_by) in [
+            ("parts-chunk", SplitBy::Chunk { shards: 2 }),
+            ("parts-size", SplitBy::Size { max_bytes: 24 }),
+        ] {
+            let directory = std::env::temp_dir().join(format!("perm-sharded-{}", name));
+            fs::create_dir_all(&directory).unwrap();
+            let pattern = directory.join("out-{}.txt").to_str().unwrap().to_string();
+            let mut sink = ManifestSink::new(ShardedSink::new(pattern, split_by).unwrap());
+            Pipeline::new()
+                .run(
+                    Permutations::new(vec![1, 2, 3, 4]).into_optimized_chunks(5),
+                    Encode::default(),
+                    &mut sink,
+                )
+                .unwrap();
+            let (sink, mut manifest) = sink.into_parts();
+            manifest.files = sink.file_entries();
+            for chunk in &manifest.chunks {
+                let mut text = vec![];
+                for part in &chunk.parts {
+                    let contents = fs::read(&part.path).unwrap();
+                    text.extend_from_slice(
+                        &contents[part.byte_offset..part.byte_offset + part.bytes],
+                    );
+                }
+                assert_eq!(text.len(), chunk.bytes);
+                assert_eq!(crc32fast::hash(&text), chunk.crc32);
+            }
+            let spanning = manifest.chunks.iter().any(|chunk| chunk.parts.len() > 1);
+            assert_eq!(spanning, name == "parts-size");
+            assert!(manifest.verify_files().unwrap());
+            manifest.chunks[1].parts[0].byte_offset += 1;
+            assert!(!manifest.verify_files().unwrap());
+            fs::remove_dir_all(directory).unwrap();
+        }
+    }
+
+    #[test]
+    fn pattern_without_placeholder_is_rejected() {
+        assert!(ShardedSink::new("out.txt", SplitBy::Prefix).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! # Sink
+//!
+//! `Sink` is the last stage of the `Pipeline`.
+//!
+//! It receives the transformed chunks on a single thread, in generation order.
+//!
+//! A file or a network sink can fail transiently:
+//! the `ErrorPolicy` of the `Pipeline` tells whether a failed write aborts the run, is retried, or is skipped.
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::pipeline::ChunkPart;
+
+/// The destination of the transformed chunks.
+pub trait Sink<I>: Send {
+    /// Consume a single transformed chunk.
+    fn write(&mut self, item: I) -> io::Result<()>;
+
+    /// Called once after the last chunk has been written.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Get the number of bytes written so far, if the sink measures them.
+    /// It is reported in the `Metrics` of the `Pipeline`.
+    fn bytes_measured(&self) -> Option<usize> {
+        None
+    }
+
+    /// Called when the item of the last failed `write` is given up by `ErrorPolicy::SkipChunk`,
+    /// before the next item is written.
+    fn skipped(&mut self) {}
+
+    /// Take the files and the byte ranges the last item has been written to, if the sink writes to many files.
+    /// They are recorded in the `Manifest` by `ManifestSink`.
+    fn take_parts(&mut self) -> Vec<ChunkPart> {
+        vec![]
+    }
+}
+
+/// What the `Pipeline` does when the sink fails to write a chunk.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the run and return the error.
+    #[default]
+    Abort,
+    /// Write the chunk again up to `retries` times, waiting `backoff` before the first retry
+    /// and doubling the wait before each of the next ones.
+    /// Stop the run and return the last error if the chunk cannot be written.
+    Retry {
+        /// the number of writes after the failed one.
+        retries: u32,
+        /// the wait before the first retry.
+        backoff: Duration,
+    },
+    /// Give up the chunk, telling the sink with `Sink::skipped`, and go on with the next one.
+    SkipChunk,
+}
+
+/// When a `WriterSink` writes its buffer and flushes the underlying writer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Flush only after the last chunk: the buffer is written when it is full.
+    #[default]
+    AtEnd,
+    /// Flush after each chunk, for the consumers reading the chunks as they are written.
+    PerChunk,
+    /// Flush the complete lines after each chunk, keeping an incomplete last line in the buffer,
+    /// for the interactive consumers reading line by line.
+    PerLine,
+}
+
+/// Write bytes to an `io::Write`.
+///
+/// Without a buffer each chunk is written directly to the writer.
+/// With `WriterSink::with_buffer_size` the chunks are accumulated in a buffer,
+/// and written when it is full or the `FlushPolicy` requires it.
+/// The bytes still in the buffer are written by `Sink::finish`.
+pub struct WriterSink<W> {
+    writer: W,
+    buffer: Vec<u8>,
+    buffer_size: usize,
+    flush_policy: FlushPolicy,
+    bytes_written: usize,
+}
+
+impl<W: Write> WriterSink<W> {
+    /// Initialize the `WriterSink` with the underlying `writer`, without a buffer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: vec![],
+            buffer_size: 0,
+            flush_policy: FlushPolicy::default(),
+            bytes_written: 0,
+        }
+    }
+    /// Accumulate the chunks in a buffer of `buffer_size` bytes before writing them.
+    /// A chunk larger than the buffer is written directly.
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer = Vec::with_capacity(buffer_size);
+        self.buffer_size = buffer_size;
+        self
+    }
+    /// Set when the buffer is written and the writer flushed.
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+    /// Get the number of bytes written so far, including the ones still in the buffer.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+    /// Get the underlying writer.
+    /// The bytes still in the buffer are lost, if `Sink::finish` has not been called.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write the first `length` bytes of the buffer to the writer.
+    fn write_buffer(&mut self, length: usize) -> io::Result<()> {
+        self.writer.write_all(&self.buffer[..length])?;
+        self.buffer.drain(..length);
+        Ok(())
+    }
+}
+
+impl<I: AsRef<[u8]>, W: Write + Send> Sink<I> for WriterSink<W> {
+    fn write(&mut self, item: I) -> io::Result<()> {
+        let bytes = item.as_ref();
+        if self.buffer.len() + bytes.len() > self.buffer_size {
+            self.write_buffer(self.buffer.len())?;
+        }
+        if bytes.len() > self.buffer_size {
+            self.writer.write_all(bytes)?;
+        } else {
+            self.buffer.extend_from_slice(bytes);
+        }
+        self.bytes_written += bytes.len();
+        match self.flush_policy {
+            FlushPolicy::AtEnd => Ok(()),
+            FlushPolicy::PerChunk => {
+                self.write_buffer(self.buffer.len())?;
+                self.writer.flush()
+            }
+            FlushPolicy::PerLine => {
+                if let Some(last_line_break) = self.buffer.iter().rposition(|byte| *byte == b'\n') {
+                    self.write_buffer(last_line_break + 1)?;
+                }
+                self.writer.flush()
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.write_buffer(self.buffer.len())?;
+        self.writer.flush()
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        Some(self.bytes_written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Record the writes and the flushes of a `WriterSink`.
+    #[derive(Default)]
+    struct Recorder {
+        writes: Vec<String>,
+        flushes: usize,
+    }
+
+    impl Write for &mut Recorder {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.writes.push(String::from_utf8(bytes.to_vec()).unwrap());
+            Ok(bytes.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn write_chunks(sink: &mut WriterSink<&mut Recorder>, chunks: &[&str]) {
+        for chunk in chunks {
+            sink.write(*chunk).unwrap();
+        }
+        Sink::<&str>::finish(sink).unwrap();
+    }
+
+    #[test]
+    fn chunks_are_buffered_by_policy() {
+        let mut recorder = Recorder::default();
+        let mut sink = WriterSink::new(&mut recorder).with_buffer_size(8);
+        write_chunks(&mut sink, &["1,2\n", "2,1\n", "3,4\n", "4,3,5,6,7\n"]);
+        assert_eq!(sink.bytes_written(), 22);
+        assert_eq!(recorder.writes, vec!["1,2\n2,1\n", "3,4\n", "4,3,5,6,7\n"]);
+        assert_eq!(recorder.flushes, 1);
+
+        let mut recorder = Recorder::default();
+        let mut sink = WriterSink::new(&mut recorder)
+            .with_buffer_size(8)
+            .with_flush_policy(FlushPolicy::PerChunk);
+        write_chunks(&mut sink, &["1,2\n", "2,1\n"]);
+        assert_eq!(recorder.writes, vec!["1,2\n", "2,1\n"]);
+        assert_eq!(recorder.flushes, 3);
+
+        let mut recorder = Recorder::default();
+        let mut sink = WriterSink::new(&mut recorder)
+            .with_buffer_size(8)
+            .with_flush_policy(FlushPolicy::PerLine);
+        write_chunks(&mut sink, &["1,2\n2", ",1\n"]);
+        assert_eq!(recorder.writes, vec!["1,2\n", "2,1\n"]);
+    }
+}
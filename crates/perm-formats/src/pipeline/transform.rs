@@ -0,0 +1,100 @@
+//! # Transform
+//!
+//! `Transform` is the stage between the generation and the `Sink`.
+//!
+//! It is applied to each chunk by the worker threads of the `Pipeline`,
+//! so it must be `Sync`.
+//! It can map a chunk to a new value, or filter it out returning `None`.
+//!
+//! Any `Fn(I) -> Option<O>` closure is a `Transform`,
+//! and transforms can be composed with `Transform::then`.
+use std::fmt::Display;
+
+use perm_core::format::{Formatted, OutputFormat, ToBytes};
+
+/// A stage applied to each chunk before it reaches the `Sink`.
+pub trait Transform<I>: Sync {
+    /// The transformed value.
+    type Output: Send;
+
+    /// Transform the `input`, or filter it out returning `None`.
+    fn transform(&self, input: I) -> Option<Self::Output>;
+
+    /// Apply `next` to the output of this transform.
+    fn then<N: Transform<Self::Output>>(self, next: N) -> Then<Self, N>
+    where
+        Self: Sized,
+    {
+        Then { first: self, next }
+    }
+}
+
+impl<I, O: Send, F: Fn(I) -> Option<O> + Sync> Transform<I> for F {
+    type Output = O;
+
+    fn transform(&self, input: I) -> Option<O> {
+        self(input)
+    }
+}
+
+/// Two transforms applied one after the other.
+pub struct Then<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<I, A: Transform<I>, B: Transform<A::Output>> Transform<I> for Then<A, B> {
+    type Output = B::Output;
+
+    fn transform(&self, input: I) -> Option<Self::Output> {
+        self.first
+            .transform(input)
+            .and_then(|output| self.next.transform(output))
+    }
+}
+
+/// Format a chunk to text with a given `OutputFormat`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Encode {
+    format: OutputFormat,
+}
+
+impl Encode {
+    /// Initialize the `Encode` transform with the `OutputFormat` of the chunks.
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl<C> Transform<C> for Encode
+where
+    for<'a> Formatted<'a, C>: Display,
+{
+    type Output = String;
+
+    fn transform(&self, chunk: C) -> Option<String> {
+        Some(Formatted::new(&chunk, self.format).to_string())
+    }
+}
+
+/// Format a chunk to bytes with a given `OutputFormat`, for the sinks writing bytes:
+/// the chunks formatted as a whole (e.g. `Chunk` and `OptimizedChunk`) skip the `String` of `Encode`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EncodeBytes {
+    format: OutputFormat,
+}
+
+impl EncodeBytes {
+    /// Initialize the `EncodeBytes` transform with the `OutputFormat` of the chunks.
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl<C: ToBytes> Transform<C> for EncodeBytes {
+    type Output = Vec<u8>;
+
+    fn transform(&self, chunk: C) -> Option<Vec<u8>> {
+        Some(chunk.to_bytes(self.format))
+    }
+}
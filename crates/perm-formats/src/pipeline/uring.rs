@@ -0,0 +1,289 @@
+//! # Uring File Sink
+//!
+//! `UringFileSink` writes the formatted chunks to a file with `io_uring` (Linux only),
+//! so that many chunk writes are in flight at the same time from the single writer thread of the `Pipeline`,
+//! without a thread for each write and without waiting for each write to complete.
+//!
+//! The sink owns a fixed pool of buffers registered with the kernel once (`IORING_REGISTER_BUFFERS`):
+//! each chunk is copied into a free buffer and written with `IORING_OP_WRITE_FIXED` at its own offset,
+//! so the writes can complete in any order. A chunk larger than a buffer takes many of them.
+//! When all the buffers are in flight, the sink waits for the first completion to reuse its buffer.
+//!
+//! A write fails after the chunk it belongs to has been accepted:
+//! the error is returned by the next `write`, or by `finish`, and by every call after it.
+//! So a failed write cannot be retried by the `ErrorPolicy` of the `Pipeline`, and it always aborts the run.
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::pipeline::Sink;
+
+/// The default number of buffers, that is of the writes in flight.
+pub const DEFAULT_URING_BUFFERS: usize = 8;
+
+/// The default size in bytes of each buffer.
+pub const DEFAULT_URING_BUFFER_SIZE: usize = 1 << 20;
+
+/// A write in flight: the bytes of its buffer from `written` to `len`, at `offset` in the file.
+#[derive(Copy, Clone, Debug)]
+struct Pending {
+    offset: u64,
+    len: usize,
+    written: usize,
+}
+
+/// Write bytes to a file with `io_uring`, through a pool of registered buffers.
+pub struct UringFileSink {
+    // the ring is declared before the buffers, and it is waited on when dropped:
+    // the buffers outlive every write reading them.
+    ring: IoUring,
+    file: File,
+    buffers: Vec<Vec<u8>>,
+    /// the write in flight of each buffer.
+    pending: Vec<Option<Pending>>,
+    /// the offset of the next write.
+    offset: u64,
+    bytes_written: usize,
+    /// the first failed write, returned by every following call.
+    error: Option<io::ErrorKind>,
+}
+
+impl UringFileSink {
+    /// Create (or truncate) the file at `path`, with `DEFAULT_URING_BUFFERS` buffers of `DEFAULT_URING_BUFFER_SIZE` bytes.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_buffers(path, DEFAULT_URING_BUFFERS, DEFAULT_URING_BUFFER_SIZE)
+    }
+    /// Create (or truncate) the file at `path`, with `buffers` buffers of `buffer_size` bytes:
+    /// at most `buffers` writes are in flight.
+    /// It fails if the kernel does not support `io_uring`, or it cannot register the buffers
+    /// (e.g. they are over the `RLIMIT_MEMLOCK` limit).
+    /// Panics if `buffers` or `buffer_size` is zero, or if `buffers` is greater than `u16::MAX`.
+    pub fn with_buffers(
+        path: impl AsRef<Path>,
+        buffers: usize,
+        buffer_size: usize,
+    ) -> io::Result<Self> {
+        assert!(
+            buffers > 0 && buffers <= usize::from(u16::MAX) && buffer_size > 0,
+            "The buffers are: {} of {} bytes",
+            buffers,
+            buffer_size
+        );
+        let ring = IoUring::new(buffers.next_power_of_two() as u32)?;
+        let mut buffers = vec![vec![0u8; buffer_size]; buffers];
+        let iovecs = buffers
+            .iter_mut()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_mut_ptr().cast(),
+                iov_len: buffer.len(),
+            })
+            .collect::<Vec<libc::iovec>>();
+        // the buffers are never resized, and they are dropped after the ring.
+        unsafe { ring.submitter().register_buffers(&iovecs)? };
+        Ok(Self {
+            ring,
+            file: File::create(path)?,
+            pending: vec![None; buffers.len()],
+            buffers,
+            offset: 0,
+            bytes_written: 0,
+            error: None,
+        })
+    }
+    /// Get the number of bytes accepted so far, including the ones still in flight.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+
+    /// Submit the write of the bytes of `buffer` still to be written.
+    fn submit(&mut self, buffer: usize) -> io::Result<()> {
+        let pending = self.pending[buffer].expect("The buffer is in flight");
+        let entry = opcode::WriteFixed::new(
+            types::Fd(self.file.as_raw_fd()),
+            self.buffers[buffer][pending.written..].as_ptr(),
+            (pending.len - pending.written) as u32,
+            buffer as u16,
+        )
+        .offset(pending.offset + pending.written as u64)
+        .build()
+        .user_data(buffer as u64);
+        // there is a submission entry for each buffer, so the queue cannot be full.
+        unsafe {
+            self.ring
+                .submission()
+                .push(&entry)
+                .expect("The submission queue has an entry for each buffer")
+        };
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// Handle the completed writes, waiting for at least `wait` of them.
+    /// A short write is submitted again for its remaining bytes.
+    /// Every completion is handled, and the first failed write is returned.
+    fn complete(&mut self, wait: usize) -> io::Result<()> {
+        if wait > 0 {
+            self.ring.submit_and_wait(wait)?;
+        }
+        let completed = self
+            .ring
+            .completion()
+            .map(|entry| (entry.user_data() as usize, entry.result()))
+            .collect::<Vec<(usize, i32)>>();
+        let mut result = Ok(());
+        for (buffer, written) in completed {
+            let pending = self.pending[buffer]
+                .as_mut()
+                .expect("A completed buffer is in flight");
+            let failed = match written {
+                written if written < 0 => Some(io::Error::from_raw_os_error(-written)),
+                0 => Some(io::ErrorKind::WriteZero.into()),
+                written => {
+                    pending.written += written as usize;
+                    if pending.written < pending.len {
+                        // a failed submission stays in the queue, submitted by the next wait.
+                        self.submit(buffer).err()
+                    } else {
+                        self.pending[buffer] = None;
+                        None
+                    }
+                }
+            };
+            if let Some(error) = failed {
+                if written <= 0 {
+                    self.pending[buffer] = None;
+                }
+                result = result.and(Err(error));
+            }
+        }
+        result
+    }
+
+    /// Get a buffer not in flight, waiting for a write to complete if there is none.
+    fn free_buffer(&mut self) -> io::Result<usize> {
+        loop {
+            if let Some(buffer) = self.pending.iter().position(Option::is_none) {
+                return Ok(buffer);
+            }
+            self.complete(1)?;
+        }
+    }
+
+    /// Wait for all the writes in flight to complete.
+    fn complete_all(&mut self) -> io::Result<()> {
+        while self.pending.iter().any(Option::is_some) {
+            self.complete(1)?;
+        }
+        Ok(())
+    }
+
+    /// Fail if a previous write has failed.
+    fn check_failed(&self) -> io::Result<()> {
+        match self.error {
+            Some(kind) => Err(io::Error::new(kind, "a previous write has failed")),
+            None => Ok(()),
+        }
+    }
+
+    /// Remember the first failed write, so that it is returned by every following call.
+    fn remember_failed(&mut self, result: io::Result<()>) -> io::Result<()> {
+        if let Err(error) = &result {
+            self.error = Some(error.kind());
+        }
+        result
+    }
+}
+
+impl<I: AsRef<[u8]>> Sink<I> for UringFileSink {
+    fn write(&mut self, item: I) -> io::Result<()> {
+        self.check_failed()?;
+        let bytes = item.as_ref();
+        let mut result = Ok(());
+        for part in bytes.chunks(self.buffers[0].len()) {
+            result = self.free_buffer().and_then(|buffer| {
+                self.buffers[buffer][..part.len()].copy_from_slice(part);
+                self.pending[buffer] = Some(Pending {
+                    offset: self.offset,
+                    len: part.len(),
+                    written: 0,
+                });
+                self.offset += part.len() as u64;
+                self.submit(buffer)
+            });
+            if result.is_err() {
+                break;
+            }
+        }
+        let result = result.and_then(|()| self.complete(0));
+        self.remember_failed(result)?;
+        self.bytes_written += bytes.len();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        let result = self.complete_all();
+        self.remember_failed(result)
+    }
+
+    fn bytes_measured(&self) -> Option<usize> {
+        Some(self.bytes_written)
+    }
+}
+
+/// The kernel reads the buffers until the writes complete.
+impl Drop for UringFileSink {
+    fn drop(&mut self) {
+        while self.pending.iter().any(Option::is_some) {
+            if self.ring.submit_and_wait(1).is_err() {
+                // the ring cannot be waited on: leak the buffers rather than free them in flight.
+                std::mem::forget(std::mem::take(&mut self.buffers));
+                return;
+            }
+            // the failed writes have already been returned, or they are lost with the sink.
+            let _ = self.complete(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use perm_core::format::OutputFormat;
+    use perm_core::Permutations;
+
+    use super::*;
+    use crate::pipeline::{Encode, Pipeline};
+
+    #[test]
+    fn chunks_are_written_with_registered_buffers() {
+        let path = std::env::temp_dir().join("perm-uring.txt");
+        // the buffers are smaller than the chunks, so each chunk takes many writes in flight.
+        let mut sink = match UringFileSink::with_buffers(&path, 4, 16) {
+            Ok(sink) => sink,
+            // e.g. the kernel is too old, or `io_uring` is disabled by a seccomp profile.
+            Err(_) => return,
+        };
+        let written = Pipeline::new()
+            .with_workers(3)
+            .run(
+                Permutations::new((0..5).collect::<Vec<i32>>()).into_optimized_chunks(7),
+                Encode::new(OutputFormat::default()),
+                &mut sink,
+            )
+            .unwrap();
+        drop(sink);
+
+        let expected = Permutations::new((0..5).collect::<Vec<i32>>())
+            .into_optimized_chunks(7)
+            .map(|chunk| chunk.to_string())
+            .collect::<String>();
+        assert_eq!(written, 120 / 7 + 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+//! # Raw
+//!
+//! Write the permutations of a byte string (`Permutations::from_bytes`) as raw byte strings,
+//! for the tools that read byte-exact candidates (password crackers, key-space searches).
+//!
+//! The bytes of each permutation are written as they are, without separators or quoting.
+//! All the permutations have the same length, so without a terminator the output is a sequence
+//! of fixed-length records; with `EncodeRaw::with_terminator` each permutation is followed by
+//! the terminator byte (e.g. a line break), which is only unambiguous if the input does not contain it.
+use perm_core::ChunkView;
+
+use crate::pipeline::Transform;
+
+/// Concatenate the bytes of the permutations of a chunk.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EncodeRaw {
+    terminator: Option<u8>,
+}
+
+impl EncodeRaw {
+    /// Initialize the `EncodeRaw` transform: the permutations are written as fixed-length records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Write the `terminator` byte after each permutation.
+    pub fn with_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = Some(terminator);
+        self
+    }
+    /// Compute the length in bytes of a permutation of `length` bytes, including the terminator.
+    pub fn record_len(&self, length: usize) -> usize {
+        length + usize::from(self.terminator.is_some())
+    }
+}
+
+impl<C: ChunkView<Value = u8>> Transform<C> for EncodeRaw {
+    type Output = Vec<u8>;
+
+    fn transform(&self, chunk: C) -> Option<Vec<u8>> {
+        let length = chunk.permutation_length();
+        let mut bytes = Vec::with_capacity(chunk.permutations_count() * self.record_len(length));
+        for index in 0..chunk.permutations_count() {
+            bytes.extend((0..length).map(|position| *chunk.value(index, position)));
+            bytes.extend(self.terminator);
+        }
+        Some(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use perm_core::Permutations;
+
+    use crate::pipeline::{Pipeline, WriterSink};
+
+    use super::*;
+
+    #[test]
+    fn permutations_are_written_as_bytes() {
+        let mut sink = WriterSink::new(vec![]);
+        Pipeline::new()
+            .run(
+                Permutations::from_bytes(b"a\0b").into_chunks(4),
+                EncodeRaw::new(),
+                &mut sink,
+            )
+            .unwrap();
+        assert_eq!(sink.into_inner(), b"a\0bab\0\0ab\0baba\0b\0a".to_vec());
+
+        let mut sink = WriterSink::new(vec![]);
+        Pipeline::new()
+            .run(
+                Permutations::from_bytes(&[0xff, 0x00]).into_ranked_chunks(1),
+                EncodeRaw::new().with_terminator(b'\n'),
+                &mut sink,
+            )
+            .unwrap();
+        assert_eq!(
+            sink.into_inner(),
+            vec![0xff, 0x00, b'\n', 0x00, 0xff, b'\n']
+        );
+    }
+}
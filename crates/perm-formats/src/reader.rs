@@ -0,0 +1,222 @@
+//! # Reader
+//!
+//! Parse the output of a previous run back into `Chunk`s, to build tools
+//! (verification, indexing, statistics) on the same types as the generation.
+//!
+//! `TextReader` reads the text output, one permutation for each line, with or without the RFC 4180 quotes,
+//! and parses each value with `FromStr`. An empty line is the empty permutation,
+//! the only one of the empty input, as `index::build_index` counts it.
+//! The binary output of `--format framed` is read by `framing::ChunkReader`, which keeps the chunks of the frames.
+//!
+//! The text output does not keep the chunks, so the reader groups the permutations
+//! in chunks of `size`, numbered from the first one read.
+//! The reader is streaming: it holds one chunk and the permutation after it,
+//! read ahead to know if the chunk is the last one.
+use std::io::{self, BufRead};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::records;
+use perm_core::{Chunk, Permutation};
+
+/// Read the permutations of an output one at a time.
+trait ReadPermutation<T> {
+    /// Read the next permutation, or `None` at the end of the output.
+    fn read_permutation(&mut self) -> io::Result<Option<Vec<T>>>;
+}
+
+/// Group the permutations read in chunks of `size`.
+struct Chunker<P, T> {
+    permutations: P,
+    size: usize,
+    chunks_read: usize,
+    permutations_read: usize,
+    /// The permutation after the current chunk, read ahead to know if the chunk is the last one.
+    /// An error reading it is returned after the chunk.
+    next: Option<io::Result<Vec<T>>>,
+    started: bool,
+}
+
+impl<P: ReadPermutation<T>, T> Chunker<P, T> {
+    fn new(permutations: P, size: usize) -> Self {
+        assert!(size > 0, "The size of the chunks must be positive");
+        Self {
+            permutations,
+            size,
+            chunks_read: 0,
+            permutations_read: 0,
+            next: None,
+            started: false,
+        }
+    }
+}
+
+impl<P: ReadPermutation<T>, T> Iterator for Chunker<P, T> {
+    type Item = io::Result<Chunk<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            self.next = self.permutations.read_permutation().transpose();
+        }
+        let mut permutations: Vec<Permutation<T>> = Vec::with_capacity(self.size);
+        while permutations.len() < self.size {
+            match self.next.take() {
+                None => break,
+                Some(Ok(permutation)) => permutations.push(permutation.into_iter().collect()),
+                Some(Err(e)) if permutations.is_empty() => return Some(Err(e)),
+                Some(Err(e)) => {
+                    self.next = Some(Err(e));
+                    break;
+                }
+            }
+            self.next = self.permutations.read_permutation().transpose();
+        }
+        if permutations.is_empty() {
+            return None;
+        }
+        let (index, first_rank) = (self.chunks_read, self.permutations_read);
+        self.chunks_read += 1;
+        self.permutations_read += permutations.len();
+        Some(Ok(Chunk::from_permutations(
+            permutations,
+            index,
+            first_rank,
+            self.next.is_none(),
+        )))
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The permutations of a text output: a record ends with a line break outside double quotes.
+struct TextRecords<R, T> {
+    reader: R,
+    line: usize,
+    values: PhantomData<T>,
+}
+
+impl<R: BufRead, T: FromStr> ReadPermutation<T> for TextRecords<R, T> {
+    fn read_permutation(&mut self) -> io::Result<Option<Vec<T>>> {
+        let mut record = String::new();
+        loop {
+            if self.reader.read_line(&mut record)? == 0 {
+                break;
+            }
+            self.line += 1;
+            // an odd number of quotes leaves a quoted value open: the line break is part of it.
+            if record.matches('"').count().is_multiple_of(2) {
+                break;
+            }
+        }
+        if record.is_empty() {
+            return Ok(None);
+        }
+        if record.trim_end_matches(['\n', '\r']).is_empty() {
+            // the empty permutation.
+            return Ok(Some(vec![]));
+        }
+        let values = records::values(&record)
+            .ok_or_else(|| invalid_data(format!("line {}: the quotes are not valid", self.line)))?;
+        values
+            .into_iter()
+            .map(|value| {
+                value.parse().map_err(|_| {
+                    invalid_data(format!(
+                        "line {}: the value `{}` is not valid",
+                        self.line, value
+                    ))
+                })
+            })
+            .collect::<io::Result<Vec<T>>>()
+            .map(Some)
+    }
+}
+
+/// Iterator over the chunks of a text output, parsing the values with `FromStr`.
+pub struct TextReader<R, T>(Chunker<TextRecords<R, T>, T>);
+
+impl<R: BufRead, T: FromStr> TextReader<R, T> {
+    /// Initialize the `TextReader` with the underlying `reader`, grouping the permutations in chunks of `size`.
+    /// Panics if `size` is zero.
+    pub fn new(reader: R, size: usize) -> Self {
+        let records = TextRecords {
+            reader,
+            line: 0,
+            values: PhantomData,
+        };
+        Self(Chunker::new(records, size))
+    }
+}
+
+impl<R: BufRead, T: FromStr> Iterator for TextReader<R, T> {
+    type Item = io::Result<Chunk<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use perm_core::format::Formatted;
+    use perm_core::{ChunkView, OutputFormat, Permutations, Quoting};
+
+    use super::*;
+
+    fn positions<T>(chunks: &[Chunk<T>]) -> Vec<(usize, usize, usize, bool)> {
+        chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.index(),
+                    chunk.first_rank(),
+                    chunk.len(),
+                    chunk.is_last(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quoted_text_is_read_back() {
+        let format = OutputFormat::new().with_quoting(Quoting::Necessary);
+        let text = Permutations::new(vec!["a\nb", "c,\"", "d"])
+            .into_chunks(6)
+            .map(|chunk| Formatted::new(&chunk, format).to_string())
+            .collect::<String>();
+        let chunks = TextReader::<_, String>::new(text.as_bytes(), 4)
+            .collect::<io::Result<Vec<Chunk<String>>>>()
+            .unwrap();
+        assert_eq!(positions(&chunks), vec![(0, 0, 4, false), (1, 4, 2, true)]);
+        assert_eq!(*chunks[1].value(1, 0), "d");
+        assert_eq!(*chunks[1].value(1, 1), "c,\"");
+        assert_eq!(*chunks[1].value(1, 2), "a\nb");
+    }
+
+    #[test]
+    fn text_values_are_parsed() {
+        let chunks = TextReader::<_, i32>::new("1,2\r\n2,1\r\n".as_bytes(), 2)
+            .collect::<io::Result<Vec<Chunk<i32>>>>()
+            .unwrap();
+        assert_eq!(positions(&chunks), vec![(0, 0, 2, true)]);
+        assert_eq!(chunks[0].to_string(), "1,2\n2,1\n");
+
+        let mut invalid = TextReader::<_, i32>::new("1,2\n2,a\n".as_bytes(), 1);
+        assert!(invalid.next().unwrap().is_ok());
+        assert!(invalid.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn empty_line_is_the_empty_permutation() {
+        // the output of the empty input.
+        let chunks = TextReader::<_, i32>::new("\n".as_bytes(), 2)
+            .collect::<io::Result<Vec<Chunk<i32>>>>()
+            .unwrap();
+        assert_eq!(positions(&chunks), vec![(0, 0, 1, true)]);
+        assert!(chunks[0].get(0).unwrap().is_empty());
+        assert!(TextReader::<_, i32>::new("".as_bytes(), 2).next().is_none());
+    }
+}
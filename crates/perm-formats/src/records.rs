@@ -0,0 +1,134 @@
+//! # Records
+//!
+//! Split the formatted permutations back into records,
+//! to count them, to route them by their first value and to parse them back.
+use std::borrow::Cow;
+
+const SEPARATOR: char = ',';
+const QUOTE: char = '"';
+
+/// Iterator over the formatted permutations (records) of a text.
+/// A record ends with a line break outside double quotes, and it includes the line break.
+pub(crate) struct Records<'a> {
+    text: &'a str,
+}
+
+impl<'a> Records<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self { text }
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let mut quoted = false;
+        let end = self
+            .text
+            .char_indices()
+            .find(|(_, c)| {
+                if *c == QUOTE {
+                    quoted = !quoted;
+                }
+                *c == '\n' && !quoted
+            })
+            .map_or(self.text.len(), |(index, _)| index + 1);
+        let (record, rest) = self.text.split_at(end);
+        self.text = rest;
+        Some(record)
+    }
+}
+
+/// Get the first value of a formatted permutation, without the quotes.
+pub(crate) fn first_value(record: &str) -> Cow<'_, str> {
+    let record = record.trim_end_matches(['\n', '\r']);
+    if let Some(quoted) = record.strip_prefix(QUOTE) {
+        let mut value = String::new();
+        let mut characters = quoted.chars().peekable();
+        while let Some(c) = characters.next() {
+            if c == QUOTE {
+                if characters.peek() != Some(&QUOTE) {
+                    break;
+                }
+                characters.next();
+            }
+            value.push(c);
+        }
+        Cow::Owned(value)
+    } else {
+        Cow::Borrowed(record.split(SEPARATOR).next().unwrap_or_default())
+    }
+}
+
+/// Split a formatted permutation into its values, without the quotes.
+/// Return `None` if a quoted value is not closed, or not followed by a separator.
+pub(crate) fn values(record: &str) -> Option<Vec<String>> {
+    let record = record.trim_end_matches(['\n', '\r']);
+    let mut values = vec![];
+    let mut characters = record.chars().peekable();
+    loop {
+        let mut value = String::new();
+        if characters.peek() == Some(&QUOTE) {
+            characters.next();
+            loop {
+                match characters.next()? {
+                    QUOTE if characters.peek() == Some(&QUOTE) => {
+                        characters.next();
+                        value.push(QUOTE);
+                    }
+                    QUOTE => break,
+                    c => value.push(c),
+                }
+            }
+            match characters.next() {
+                None => {
+                    values.push(value);
+                    return Some(values);
+                }
+                Some(SEPARATOR) => values.push(value),
+                Some(_) => return None,
+            }
+        } else {
+            loop {
+                match characters.next() {
+                    None => {
+                        values.push(value);
+                        return Some(values);
+                    }
+                    Some(SEPARATOR) => break,
+                    Some(c) => value.push(c),
+                }
+            }
+            values.push(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_records_are_split_correctly() {
+        let text = "\"a\nb\",c\n\"d,\"\"\",e\nf,g\n";
+        let records = Records::new(text).collect::<Vec<&str>>();
+        assert_eq!(records, vec!["\"a\nb\",c\n", "\"d,\"\"\",e\n", "f,g\n"]);
+        let first_values = records.into_iter().map(first_value).collect::<Vec<_>>();
+        assert_eq!(first_values, vec!["a\nb", "d,\"", "f"]);
+    }
+
+    #[test]
+    fn quoted_records_are_split_into_values() {
+        assert_eq!(
+            values("\"a\nb\",c,\"d,\"\"\"\n").unwrap(),
+            vec!["a\nb", "c", "d,\""]
+        );
+        assert_eq!(values("f,,g\r\n").unwrap(), vec!["f", "", "g"]);
+        assert_eq!(values("\"a\"b,c"), None);
+        assert_eq!(values("\"a,b"), None);
+    }
+}
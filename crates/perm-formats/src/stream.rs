@@ -0,0 +1,105 @@
+//! # Stream
+//!
+//! `ChunkStream` adapts any of the blocking chunk iterators to a `futures_core::Stream`,
+//! so that an async application consumes the chunks with the `StreamExt` of `futures` or `tokio-stream`.
+//!
+//! The iterator runs on a blocking thread of the current tokio runtime (`spawn_blocking`),
+//! and the chunks are passed to the stream through a bounded channel:
+//! the generation waits when the consumer is `queued_chunks` chunks behind,
+//! and it stops when the stream is dropped.
+//!
+//! The iterator is moved to another thread, so it must be `'static`:
+//! the chunks of borrowed values (e.g. `Permutations<&str>` of the input text) need owned values first.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+/// The default number of chunks generated ahead of the consumer.
+pub const DEFAULT_QUEUED_CHUNKS: usize = 4;
+
+/// A `Stream` of the chunks of an iterator running on a blocking thread.
+#[derive(Debug)]
+pub struct ChunkStream<C> {
+    receiver: mpsc::Receiver<C>,
+}
+
+impl<C: Send + 'static> ChunkStream<C> {
+    /// Start generating the `chunks` on a blocking thread,
+    /// at most `DEFAULT_QUEUED_CHUNKS` ahead of the consumer.
+    /// Panics if it is not called from a tokio runtime.
+    pub fn new<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = C> + Send + 'static,
+    {
+        Self::with_queued_chunks(chunks, DEFAULT_QUEUED_CHUNKS)
+    }
+    /// Start generating the `chunks` on a blocking thread, at most `queued_chunks` ahead of the consumer.
+    /// Panics if it is not called from a tokio runtime, or if `queued_chunks` is zero.
+    pub fn with_queued_chunks<I>(chunks: I, queued_chunks: usize) -> Self
+    where
+        I: IntoIterator<Item = C> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(queued_chunks);
+        tokio::task::spawn_blocking(move || {
+            for chunk in chunks {
+                if sender.blocking_send(chunk).is_err() {
+                    // the stream has been dropped.
+                    break;
+                }
+            }
+        });
+        Self { receiver }
+    }
+}
+
+impl<C> Stream for ChunkStream<C> {
+    type Item = C;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<C>> {
+        self.receiver.poll_recv(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio_stream::StreamExt;
+
+    use perm_core::Permutations;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn chunks_are_streamed_in_order() {
+        let expected = Permutations::new(vec![1, 2, 2, 3])
+            .into_chunks(5)
+            .map(|chunk| chunk.to_string())
+            .collect::<Vec<String>>();
+        let streamed = ChunkStream::new(Permutations::new(vec![1, 2, 2, 3]).into_chunks(5))
+            .map(|chunk| chunk.to_string())
+            .collect::<Vec<String>>()
+            .await;
+        assert_eq!(streamed, expected);
+    }
+
+    #[tokio::test]
+    async fn dropped_stream_stops_the_generation() {
+        let generated = Arc::new(AtomicUsize::new(0));
+        let counter = generated.clone();
+        let chunks = Permutations::new((0..8).collect::<Vec<u32>>())
+            .into_chunks(1)
+            .inspect(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        let mut stream = ChunkStream::with_queued_chunks(chunks, 1);
+        assert!(stream.next().await.is_some());
+        drop(stream);
+        // the blocking thread notices the dropped stream at its next send.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(generated.load(Ordering::Relaxed) < 10);
+    }
+}
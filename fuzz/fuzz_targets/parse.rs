@@ -0,0 +1,21 @@
+//! Fuzz the parser with arbitrary input lines, as read from `stdin`.
+//!
+//! Run with: `cargo fuzz run parse`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use perm::{ParseOptions, Permutations};
+
+/// The inputs are short enough to generate a chunk of permutations quickly.
+const MAX_LENGTH: usize = 12;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let options = ParseOptions::new().with_max_length(MAX_LENGTH);
+        if let Ok(permutations) = Permutations::parse(text, &options) {
+            assert!(permutations.length() <= MAX_LENGTH);
+            if let Some(chunk) = permutations.into_optimized_chunks(16).next() {
+                assert!(!chunk.to_string().is_empty());
+            }
+        }
+    }
+});
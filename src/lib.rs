@@ -1,10 +1,24 @@
 //! # Perm
 //!
 //! A library for generating permutations.
-pub use permutations::into_chunks::Chunk;
-pub use permutations::into_chunks::IntoChunks;
-pub use permutations::into_optimized_chunks::IntoOptimizedChunks;
-pub use permutations::into_optimized_chunks::OptimizedChunk;
-pub use permutations::Permutations;
+//!
+//! It re-exports `perm-core`, the generation of the permutations,
+//! and with the `pipeline` feature the writers and readers of `perm-formats`.
+pub use perm_core::*;
 
-mod permutations;
+#[cfg(feature = "arrow")]
+pub use perm_formats::arrow;
+#[cfg(feature = "compression")]
+pub use perm_formats::compression;
+#[cfg(feature = "pipeline")]
+pub use perm_formats::framing;
+#[cfg(feature = "grpc")]
+pub use perm_formats::grpc;
+#[cfg(feature = "pipeline")]
+pub use perm_formats::pipeline;
+#[cfg(feature = "pipeline")]
+pub use perm_formats::raw;
+#[cfg(feature = "pipeline")]
+pub use perm_formats::reader;
+#[cfg(feature = "stream")]
+pub use perm_formats::stream;